@@ -0,0 +1,49 @@
+//! Lot full with a waitlist: twelve cars queue for a ten-spot floor. Once two spots are
+//! left the lot runs one-in-one-out, letting a waiting car in for every exit.
+//!
+//! Run with `cargo run --example lot_full_with_waitlist`.
+
+use chrono::{Duration, TimeZone, Utc};
+use parking_lot::{
+    Parkable, ParkingFloor, ParkingLot, Vehicle, VehicleType, admission::AdmissionPolicy,
+    clock::MockClock,
+};
+
+fn main() {
+    let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 3, 4, 8, 0, 0).unwrap());
+    let mut lot = ParkingLot::new("Example Lot".into(), "Main Street".into(), "ex".into())
+        .with_clock(Box::new(clock.clone()));
+    lot.add_floor(ParkingFloor::new(1)).unwrap();
+    lot.set_admission_policy(AdmissionPolicy {
+        one_in_one_out_at: 2,
+    });
+
+    for n in 0..12 {
+        let car = Vehicle::new(VehicleType::Motor, "Corolla".into(), format!("CAR{n:04}"));
+        lot.join_entry_queue(car).unwrap();
+    }
+    let mut parked = Vec::new();
+    while let Some(ticket) = lot.admit_next().unwrap() {
+        parked.push(ticket);
+    }
+    println!(
+        "{} admitted, {} waiting",
+        parked.len(),
+        lot.entry_queue_length().unwrap()
+    );
+
+    while lot.entry_queue_length().unwrap() > 0 {
+        clock.advance(Duration::minutes(10));
+        let leaving = parked.remove(0);
+        lot.unpark_vehicle(leaving.ticket_id).unwrap();
+        let admitted = lot.admit_next().unwrap().expect("an exit admits one car");
+        println!(
+            "{} left, {} let in, {} waiting ({})",
+            leaving.vehicle.license_plate(),
+            admitted.vehicle.license_plate(),
+            lot.entry_queue_length().unwrap(),
+            lot.display_info().unwrap().entry_signage().join("; ")
+        );
+        parked.push(admitted);
+    }
+}
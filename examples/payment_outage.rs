@@ -0,0 +1,64 @@
+//! Payment outage: a gated lot whose card gateway goes down. The barrier stays closed
+//! until the driver's payment goes through.
+//!
+//! Run with `cargo run --example payment_outage`.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use chrono::{Duration, TimeZone, Utc};
+use parking_lot::{
+    Parkable, ParkingFloor, ParkingLot, Vehicle, VehicleType,
+    clock::MockClock,
+    payment::{MockCardProcessor, PaymentMethod, PaymentMethodKind, PaymentProcessor},
+    pricing::FlatHourly,
+};
+
+/// Card gateway that times out while `offline` is set.
+#[derive(Debug, Default, Clone)]
+struct Gateway {
+    cards: Arc<MockCardProcessor>,
+    offline: Arc<AtomicBool>,
+}
+
+impl PaymentProcessor for Gateway {
+    fn charge(&self, method: &PaymentMethod, amount: f32) -> Result<String, String> {
+        if self.offline.load(Ordering::SeqCst) {
+            return Err("gateway timeout".to_string());
+        }
+        self.cards.charge(method, amount)
+    }
+}
+
+fn main() {
+    let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 3, 4, 8, 0, 0).unwrap());
+    let gateway = Gateway::default();
+    let mut lot = ParkingLot::new("Example Lot".into(), "Main Street".into(), "ex".into())
+        .with_clock(Box::new(clock.clone()))
+        .with_pricing_strategy(Box::new(FlatHourly::new(3.0)));
+    lot.add_floor(ParkingFloor::new(1)).unwrap();
+    lot.set_payment_required_before_exit(true);
+    lot.set_payment_processor(PaymentMethodKind::Card, Box::new(gateway.clone()));
+
+    let car = Vehicle::new(VehicleType::Motor, "Corolla".into(), "CAR0001".into());
+    let ticket = lot.park_vehicle(car).unwrap();
+    clock.advance(Duration::hours(2));
+    let card = PaymentMethod::Card("tok_visa".into());
+
+    gateway.offline.store(true, Ordering::SeqCst);
+    match lot.pay_ticket(&ticket.ticket_id, card.clone()) {
+        Ok(_) => unreachable!("the gateway is down"),
+        Err(err) => println!("payment refused: {err}"),
+    }
+    if let Err(err) = lot.unpark_vehicle(ticket.ticket_id.clone()) {
+        println!("barrier stays down: {err}");
+    }
+
+    gateway.offline.store(false, Ordering::SeqCst);
+    let payment = lot.pay_ticket(&ticket.ticket_id, card).unwrap();
+    println!("paid {:.2} ({})", payment.amount, payment.transaction_id);
+    let charge = lot.unpark_vehicle(ticket.ticket_id).unwrap();
+    println!("left owing {:.2}", charge.amount_due());
+}
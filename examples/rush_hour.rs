@@ -0,0 +1,41 @@
+//! Rush hour: 25 cars arrive at a three-floor lot, ten leave after 90 minutes.
+//!
+//! Run with `cargo run --example rush_hour`.
+
+use chrono::{Duration, TimeZone, Utc};
+use parking_lot::{
+    Parkable, ParkingFloor, ParkingLot, Vehicle, VehicleType, clock::MockClock, pricing::FlatHourly,
+};
+
+fn main() {
+    let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 3, 4, 8, 0, 0).unwrap());
+    let mut lot = ParkingLot::new("Example Lot".into(), "Main Street".into(), "ex".into())
+        .with_clock(Box::new(clock.clone()))
+        .with_pricing_strategy(Box::new(FlatHourly::new(3.0)));
+    for floor_id in 1..=3 {
+        lot.add_floor(ParkingFloor::new(floor_id)).unwrap();
+    }
+
+    let tickets: Vec<_> = (0..25)
+        .map(|n| {
+            let car = Vehicle::new(VehicleType::Motor, "Corolla".into(), format!("CAR{n:04}"));
+            lot.park_vehicle(car).unwrap()
+        })
+        .collect();
+    println!(
+        "08:00  {} parked, {} spots free",
+        lot.display_info().unwrap().num_parked_vehicles(),
+        lot.display_info().unwrap().num_empty_spots()
+    );
+
+    clock.advance(Duration::minutes(90));
+    let takings: f32 = tickets
+        .iter()
+        .take(10)
+        .map(|ticket| lot.unpark_vehicle(ticket.ticket_id.clone()).unwrap().total)
+        .sum();
+    println!(
+        "09:30  10 left paying {takings:.2}, {} still parked",
+        lot.display_info().unwrap().num_parked_vehicles()
+    );
+}
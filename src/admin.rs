@@ -0,0 +1,356 @@
+//! Lot management by staff. An `Admin` acting through `LotAdministration` is recorded in
+//! the audit log under their name for every operation; user accounts only park, reserve
+//! and pay. Removing floors, taking spots out of service and force-closing tickets are
+//! only reachable this way. Adding floors, converting spots and setting rates also have
+//! direct `ParkingLot` methods for setting a lot up and for automation such as
+//! `apply_rebalancing`; those aren't audited.
+
+use crate::{
+    ParkingCharge, ParkingFloor, ParkingLot, SpotStatus, SpotType,
+    audit::AuditAction,
+    batch::Effects,
+    error::ParkingError,
+    events::InventoryChange,
+    pricing::{ChargeKind, ChargeLine, PricingStrategy},
+    quota::QuotaWarning,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Admin {
+    name: String,
+    staff_id: String,
+}
+
+impl Admin {
+    pub fn new(name: String, staff_id: String) -> Self {
+        Self { name, staff_id }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn staff_id(&self) -> &str {
+        &self.staff_id
+    }
+
+    /// How the admin is named in charge breakdowns and the audit log.
+    fn actor(&self) -> String {
+        format!("{} ({})", self.name, self.staff_id)
+    }
+}
+
+pub trait LotAdministration {
+    fn add_floor(&self, lot: &mut ParkingLot, floor: ParkingFloor) -> Result<(), ParkingError>;
+    /// Takes an empty floor out of the lot and returns it.
+    fn remove_floor(
+        &self,
+        lot: &mut ParkingLot,
+        floor_id: u32,
+    ) -> Result<ParkingFloor, ParkingError>;
+    /// Withholds a free spot from allocation, e.g. for repairs, until it is returned.
+    fn mark_spot_out_of_service(
+        &self,
+        lot: &ParkingLot,
+        floor_id: u32,
+        spot_id: &str,
+        reason: String,
+    ) -> Result<(), ParkingError>;
+    /// Spots a maintenance window closed stay closed until the window ends.
+    fn return_spot_to_service(
+        &self,
+        lot: &ParkingLot,
+        floor_id: u32,
+        spot_id: &str,
+    ) -> Result<(), ParkingError>;
+    /// Changes a free spot's type, e.g. Regular to Electric, subject to the floor's quota.
+    fn convert_spot(
+        &self,
+        lot: &ParkingLot,
+        floor_id: u32,
+        spot_id: &str,
+        spot_type: SpotType,
+    ) -> Result<Vec<QuotaWarning>, ParkingError>;
+    fn update_rates(&self, lot: &mut ParkingLot, pricing: Box<dyn PricingStrategy>);
+    /// Closes a ticket whose holder can't present it, e.g. a lost ticket. The stay is
+    /// billed as usual but the exit isn't held back for payment.
+    fn force_close_ticket(
+        &self,
+        lot: &ParkingLot,
+        ticket_id: &str,
+    ) -> Result<ParkingCharge, ParkingError>;
+}
+
+impl LotAdministration for Admin {
+    fn add_floor(&self, lot: &mut ParkingLot, floor: ParkingFloor) -> Result<(), ParkingError> {
+        let floor_id = floor.id;
+        lot.add_floor(floor)?;
+        lot.audit(self.actor(), AuditAction::FloorAdded { floor_id });
+        Ok(())
+    }
+
+    fn remove_floor(
+        &self,
+        lot: &mut ParkingLot,
+        floor_id: u32,
+    ) -> Result<ParkingFloor, ParkingError> {
+        let floor = lot.remove_floor(floor_id)?;
+        lot.audit(self.actor(), AuditAction::FloorRemoved { floor_id });
+        Ok(floor)
+    }
+
+    fn mark_spot_out_of_service(
+        &self,
+        lot: &ParkingLot,
+        floor_id: u32,
+        spot_id: &str,
+        reason: String,
+    ) -> Result<(), ParkingError> {
+        lot.set_out_of_service(floor_id, spot_id, Some(reason.clone()))?;
+        lot.audit(
+            self.actor(),
+            AuditAction::SpotOutOfService {
+                floor_id: Some(floor_id),
+                spot_id: spot_id.to_string(),
+                reason,
+            },
+        );
+        Ok(())
+    }
+
+    fn return_spot_to_service(
+        &self,
+        lot: &ParkingLot,
+        floor_id: u32,
+        spot_id: &str,
+    ) -> Result<(), ParkingError> {
+        lot.set_out_of_service(floor_id, spot_id, None)?;
+        lot.audit(
+            self.actor(),
+            AuditAction::SpotReturnedToService {
+                floor_id: Some(floor_id),
+                spot_id: spot_id.to_string(),
+            },
+        );
+        Ok(())
+    }
+
+    fn convert_spot(
+        &self,
+        lot: &ParkingLot,
+        floor_id: u32,
+        spot_id: &str,
+        spot_type: SpotType,
+    ) -> Result<Vec<QuotaWarning>, ParkingError> {
+        let warnings = lot.convert_spot(floor_id, spot_id, spot_type)?;
+        let conversion = lot.spot_conversions().pop().unwrap();
+        lot.audit(
+            self.actor(),
+            AuditAction::SpotConverted {
+                floor_id: Some(conversion.floor_id),
+                spot_id: conversion.spot_id,
+                from: conversion.from,
+                to: conversion.to,
+            },
+        );
+        Ok(warnings)
+    }
+
+    fn update_rates(&self, lot: &mut ParkingLot, pricing: Box<dyn PricingStrategy>) {
+        lot.set_pricing_strategy(pricing);
+        lot.audit(self.actor(), AuditAction::RatesUpdated);
+    }
+
+    fn force_close_ticket(
+        &self,
+        lot: &ParkingLot,
+        ticket_id: &str,
+    ) -> Result<ParkingCharge, ParkingError> {
+        let charge = lot.checkout(
+            ticket_id.to_string(),
+            None,
+            Some(self),
+            &mut Effects::immediate(),
+        )?;
+        let ticket_id = ticket_id.to_string();
+        lot.audit(self.actor(), AuditAction::TicketForceClosed { ticket_id });
+        Ok(charge)
+    }
+}
+
+impl ParkingLot {
+    pub(crate) fn remove_floor(&mut self, floor_id: u32) -> Result<ParkingFloor, ParkingError> {
+        let removed = {
+            let mut floors = self.floors.lock()?;
+            let floor = floors.get(&floor_id).ok_or(ParkingError::FloorNotFound)?;
+            let spots = floor.spots.lock()?;
+            if spots.values().any(|s| s.is_occupied()) {
+                return Err(ParkingError::FloorNotEmpty(floor_id));
+            }
+            if spots.values().any(|s| s.is_reserved() || s.is_leased()) {
+                return Err(ParkingError::SpotUnavailable);
+            }
+            drop(spots);
+            floors.remove(&floor_id).unwrap()
+        };
+        self.closed_floors.lock()?.remove(&floor_id);
+        self.emit_inventory_change(floor_id, InventoryChange::FloorRemoved);
+        Ok(removed)
+    }
+
+    /// Takes the spot out of service with `reason`, or returns it to service for `None`.
+    /// Spots a maintenance window closed aren't returned early.
+    pub(crate) fn set_out_of_service(
+        &self,
+        floor_id: u32,
+        spot_id: &str,
+        reason: Option<String>,
+    ) -> Result<(), ParkingError> {
+        let now = self.now();
+        let floors = self.floors.lock()?;
+        let floor = floors.get(&floor_id).ok_or(ParkingError::FloorNotFound)?;
+        // Holding `floors` keeps a maintenance run from closing the spot meanwhile
+        let held = self.held_for_maintenance(floor_id, spot_id)?;
+        let mut spots = floor.spots.lock()?;
+        let spot = spots.get_mut(spot_id).ok_or(ParkingError::SpotNotFound)?;
+        match reason {
+            Some(reason) => spot.set_maintenance_status(SpotStatus::OutOfService(reason), now),
+            None if held => Err(ParkingError::SpotUnderMaintenance),
+            None if spot.out_of_service_reason().is_some() => {
+                spot.set_maintenance_status(SpotStatus::Free, now)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Breakdown line naming the admin who closed a ticket.
+pub(crate) fn force_close_line(admin: &Admin) -> ChargeLine {
+    ChargeLine::new(format!("Closed by {}", admin.actor()), 0.0).with_kind(ChargeKind::Adjustment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Parkable, Vehicle, VehicleType, clock::MockClock, journal::SpotState,
+        maintenance::MaintenanceWindow, pricing::FlatHourly, standing::RecurrenceRule,
+    };
+    use chrono::{Duration, NaiveTime, TimeZone, Utc, Weekday};
+
+    #[test]
+    fn test_admin_manages_floors_spots_and_lost_tickets() {
+        let admin = Admin::new("Bola".into(), "ST-7".into());
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.set_payment_required_before_exit(true);
+        admin.add_floor(&mut lot, ParkingFloor::new(1)).unwrap();
+        admin.add_floor(&mut lot, ParkingFloor::new(2)).unwrap();
+        admin.remove_floor(&mut lot, 2).unwrap();
+        assert_eq!(lot.display_info().unwrap().num_floors(), 1);
+
+        admin
+            .mark_spot_out_of_service(&lot, 1, "spot_0", "Broken barrier".into())
+            .unwrap();
+        assert_eq!(lot.display_info().unwrap().num_empty_spots(), 9);
+        let floor = lot.get_floor_by_id(1).unwrap().unwrap();
+        assert_eq!(
+            floor.spots.lock().unwrap()["spot_0"].state(),
+            SpotState::OutOfService
+        );
+
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "LOST1".into());
+        let ticket = lot.park_vehicle(car).unwrap();
+        assert_ne!(ticket.spot_id, "spot_0");
+        for ticket in lot.active_tickets.lock().unwrap().values_mut() {
+            ticket.entry_time -= Duration::minutes(61);
+        }
+        assert_eq!(
+            admin.remove_floor(&mut lot, 1).unwrap_err(),
+            ParkingError::FloorNotEmpty(1)
+        );
+        assert!(matches!(
+            lot.unpark_vehicle(ticket.ticket_id.clone()),
+            Err(ParkingError::PaymentRequired)
+        ));
+        let charge = admin.force_close_ticket(&lot, &ticket.ticket_id).unwrap();
+        assert_eq!(
+            charge.breakdown.last().unwrap().description,
+            "Closed by Bola (ST-7)"
+        );
+
+        admin.return_spot_to_service(&lot, 1, "spot_0").unwrap();
+        assert_eq!(lot.display_info().unwrap().num_empty_spots(), 10);
+        assert_eq!(lot.audit_log().len(), 6);
+        assert!(lot.audit_log().iter().all(|e| e.actor == "Bola (ST-7)"));
+    }
+
+    #[test]
+    fn test_out_of_service_keeps_to_its_floor_and_its_maintenance_window() {
+        // A Tuesday, 02:00, during the window
+        let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 1, 2, 2, 0, 0).unwrap());
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()));
+        let admin = Admin::new("Bola".into(), "ST-7".into());
+        admin.add_floor(&mut lot, ParkingFloor::new(1)).unwrap();
+        admin.add_floor(&mut lot, ParkingFloor::new(2)).unwrap();
+        let state = |floor_id: u32| {
+            lot.get_floor_by_id(floor_id)
+                .unwrap()
+                .unwrap()
+                .spots
+                .lock()
+                .unwrap()["spot_0"]
+                .state()
+        };
+
+        admin
+            .mark_spot_out_of_service(&lot, 2, "spot_0", "Pothole".into())
+            .unwrap();
+        assert_eq!(
+            (state(1), state(2)),
+            (SpotState::Free, SpotState::OutOfService)
+        );
+        admin.return_spot_to_service(&lot, 2, "spot_0").unwrap();
+        assert_eq!(
+            admin.mark_spot_out_of_service(&lot, 3, "spot_0", "Pothole".into()),
+            Err(ParkingError::FloorNotFound)
+        );
+
+        let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        lot.add_maintenance_window(MaintenanceWindow::new(
+            "resurface".into(),
+            1,
+            RecurrenceRule::new(vec![Weekday::Tue], at(1, 0), at(4, 0)),
+            "Resurfacing".into(),
+        ))
+        .unwrap();
+        lot.run_maintenance_windows();
+        assert_eq!(
+            admin.return_spot_to_service(&lot, 1, "spot_0"),
+            Err(ParkingError::SpotUnderMaintenance)
+        );
+        clock.advance(Duration::hours(3));
+        lot.run_maintenance_windows();
+        assert_eq!(state(1), SpotState::Free);
+
+        admin.update_rates(&mut lot, Box::new(FlatHourly::new(3.0)));
+        let actions: Vec<AuditAction> = lot.audit_log().into_iter().map(|e| e.action).collect();
+        assert_eq!(
+            actions,
+            [
+                AuditAction::FloorAdded { floor_id: 1 },
+                AuditAction::FloorAdded { floor_id: 2 },
+                AuditAction::SpotOutOfService {
+                    floor_id: Some(2),
+                    spot_id: "spot_0".into(),
+                    reason: "Pothole".into(),
+                },
+                AuditAction::SpotReturnedToService {
+                    floor_id: Some(2),
+                    spot_id: "spot_0".into(),
+                },
+                AuditAction::RatesUpdated,
+            ]
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! Metered admission from the physical entry queue. Once the lot is nearly full it runs
+//! one-in-one-out: every exit lets the next queued vehicle in.
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    Parkable, ParkingLot, ParkingTicket, Vehicle, audit::AuditAction, error::ParkingError,
+    priority::priority_actor,
+};
+
+/// Exits kept for the wait estimate.
+const RECENT_EXITS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdmissionPolicy {
+    /// One-in-one-out applies while this many empty spots or fewer remain.
+    pub one_in_one_out_at: u32,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct EntryQueue {
+    pub(crate) waiting: VecDeque<Vehicle>,
+    /// Exits not yet matched by an admission.
+    pub(crate) exit_credits: u32,
+    pub(crate) recent_exits: VecDeque<DateTime<Utc>>,
+}
+
+impl ParkingLot {
+    pub fn set_admission_policy(&mut self, policy: AdmissionPolicy) {
+        self.admission = Some(policy);
+    }
+
+    /// Adds `vehicle` to the back of the entry queue and returns its position, starting at 1.
+    /// Priority vehicles go ahead of everyone but earlier priority vehicles.
+    pub fn join_entry_queue(&self, vehicle: Vehicle) -> Result<usize, ParkingError> {
+        let mut queue = self.entry_queue.lock()?;
+        let position = if vehicle.priority.is_some() {
+            queue
+                .waiting
+                .iter()
+                .take_while(|v| v.priority.is_some())
+                .count()
+        } else {
+            queue.waiting.len()
+        };
+        queue.waiting.insert(position, vehicle);
+        Ok(position + 1)
+    }
+
+    pub fn entry_queue_length(&self) -> Result<usize, ParkingError> {
+        Ok(self.entry_queue.lock()?.waiting.len())
+    }
+
+    /// Parks the vehicle at the front of the queue if it may enter now. Returns `None`
+    /// when the queue is empty or one-in-one-out is waiting for the next exit, which
+    /// priority vehicles don't wait for. If parking fails the vehicle keeps its place.
+    pub fn admit_next(&self) -> Result<Option<ParkingTicket>, ParkingError> {
+        let metered = self.is_metered(self.display_info()?.num_empty_spots());
+        let (vehicle, bypassed, used_credit) = {
+            let mut queue = self.entry_queue.lock()?;
+            let Some(front) = queue.waiting.front() else {
+                return Ok(None);
+            };
+            let bypassed = metered && front.priority.is_some();
+            // Any admission matches an exit, so a credit earned while the lot had room
+            // isn't spent again once it is metered
+            let used_credit = !bypassed && queue.exit_credits > 0;
+            if metered && !bypassed && !used_credit {
+                return Ok(None);
+            }
+            if used_credit {
+                queue.exit_credits -= 1;
+            }
+            (queue.waiting.pop_front().unwrap(), bypassed, used_credit)
+        };
+
+        match self.park_vehicle(vehicle.clone()) {
+            Ok(ticket) => {
+                if bypassed {
+                    self.audit(priority_actor(&vehicle), AuditAction::QueueBypassed);
+                }
+                Ok(Some(ticket))
+            }
+            Err(err) => {
+                let mut queue = self.entry_queue.lock()?;
+                queue.waiting.push_front(vehicle);
+                if used_credit {
+                    queue.exit_credits += 1;
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// How long the last vehicle in the queue can expect to wait. Zero while admission
+    /// isn't metered; `None` when metered but too few exits have been seen to tell.
+    pub fn estimated_entry_wait(&self) -> Result<Option<Duration>, ParkingError> {
+        Ok(self.display_info()?.estimated_entry_wait())
+    }
+
+    pub(crate) fn entry_wait(&self, queue: &EntryQueue, empty_spots: u32) -> Option<Duration> {
+        if !self.is_metered(empty_spots) || queue.waiting.is_empty() {
+            return Some(Duration::zero());
+        }
+        let (first, last) = (queue.recent_exits.front()?, queue.recent_exits.back()?);
+        let gaps = queue.recent_exits.len() as i32 - 1;
+        if gaps == 0 {
+            return None;
+        }
+        let per_exit = last.signed_duration_since(*first) / gaps;
+        let still_needed = (queue.waiting.len() as u32).saturating_sub(queue.exit_credits);
+        Some(per_exit * still_needed as i32)
+    }
+
+    /// Counts an exit towards the wait estimate and, if vehicles are queued, lets one in.
+    pub(crate) fn record_exit(&self, at: DateTime<Utc>) -> Result<(), ParkingError> {
+        if self.admission.is_none() {
+            return Ok(());
+        }
+        let mut queue = self.entry_queue.lock()?;
+        if queue.recent_exits.len() == RECENT_EXITS {
+            queue.recent_exits.pop_front();
+        }
+        queue.recent_exits.push_back(at);
+        if queue.exit_credits < queue.waiting.len() as u32 {
+            queue.exit_credits += 1;
+        }
+        Ok(())
+    }
+
+    fn is_metered(&self, empty_spots: u32) -> bool {
+        self.admission
+            .is_some_and(|policy| empty_spots <= policy.one_in_one_out_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParkingFloor, VehicleType};
+
+    fn motor(plate: &str) -> Vehicle {
+        Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into())
+    }
+
+    #[test]
+    fn test_one_in_one_out_admits_per_exit() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_admission_policy(AdmissionPolicy {
+            one_in_one_out_at: 100,
+        });
+
+        assert_eq!(lot.join_entry_queue(motor("Q1")).unwrap(), 1);
+        assert_eq!(lot.join_entry_queue(motor("Q2")).unwrap(), 2);
+        assert!(lot.admit_next().unwrap().is_none());
+        assert_eq!(lot.estimated_entry_wait().unwrap(), None);
+
+        for plate in ["X1", "X2"] {
+            let ticket = lot.park_vehicle(motor(plate)).unwrap();
+            lot.unpark_vehicle(ticket.ticket_id).unwrap();
+        }
+        assert!(lot.estimated_entry_wait().unwrap().is_some());
+
+        let admitted = lot.admit_next().unwrap().unwrap();
+        assert_eq!(admitted.vehicle.license_plate(), "Q1");
+        assert_eq!(lot.entry_queue_length().unwrap(), 1);
+        assert_eq!(
+            lot.display_info().unwrap().entry_signage().last().unwrap(),
+            "Queue: 1 waiting"
+        );
+    }
+}
@@ -0,0 +1,280 @@
+//! Strategies deciding which of the free spots a vehicle gets. Every park and reservation
+//! goes through the lot's strategy; the lot only narrows the candidates to spots the
+//! vehicle may use, and to handicapped or charging spots when it has a claim to one.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    ParkingFloor, ParkingLot, ParkingSpot, SpotType, Vehicle, VehicleType, capacity::Occupancy,
+    parking_zone::ParkingZone, tags::SpotTag, units::Dimensions,
+};
+
+/// A free spot the vehicle could take.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpotCandidate {
+    pub floor_id: u32,
+    pub spot_id: String,
+    pub spot_type: SpotType,
+}
+
+pub trait AllocationStrategy: fmt::Debug + Send + Sync {
+    /// Index into `candidates` of the spot to allocate. Candidates are non-empty and
+    /// sorted by floor, then spot id.
+    fn choose(&self, vehicle: &Vehicle, candidates: &[SpotCandidate]) -> usize;
+}
+
+/// Smallest spot that fits, so larger spots stay free for larger vehicles. Ties go to
+/// the lowest floor.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BestFit;
+
+impl BestFit {
+    pub(crate) fn size(spot_type: SpotType) -> u8 {
+        match spot_type {
+            SpotType::Regular | SpotType::Electric => 0,
+            SpotType::Handicapped => 1,
+            SpotType::Large => 2,
+            SpotType::XLarge => 3,
+        }
+    }
+
+    pub(crate) fn smallest(candidates: &[SpotCandidate]) -> Option<usize> {
+        (0..candidates.len()).min_by_key(|&i| Self::size(candidates[i].spot_type))
+    }
+}
+
+impl AllocationStrategy for BestFit {
+    fn choose(&self, _vehicle: &Vehicle, candidates: &[SpotCandidate]) -> usize {
+        Self::smallest(candidates).unwrap()
+    }
+}
+
+/// Lowest floor first, e.g. to keep upper decks closed off-peak.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NearestFloor;
+
+impl AllocationStrategy for NearestFloor {
+    fn choose(&self, _vehicle: &Vehicle, _candidates: &[SpotCandidate]) -> usize {
+        0
+    }
+}
+
+/// Spreads vehicles over the floors in turn to balance load.
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    next: AtomicUsize,
+}
+
+impl AllocationStrategy for RoundRobin {
+    fn choose(&self, _vehicle: &Vehicle, candidates: &[SpotCandidate]) -> usize {
+        let mut floors: Vec<u32> = candidates.iter().map(|c| c.floor_id).collect();
+        floors.dedup();
+        let floor = floors[self.next.fetch_add(1, Ordering::Relaxed) % floors.len()];
+        candidates.iter().position(|c| c.floor_id == floor).unwrap()
+    }
+}
+
+impl ParkingLot {
+    pub fn set_allocation_strategy(&mut self, strategy: Box<dyn AllocationStrategy>) {
+        self.allocation = strategy;
+    }
+
+    /// Picks a spot outside any parking zone for `vehicle` on the floors passing
+    /// `floor_open` and applies `claim` to it. Every floor's spots stay locked from the
+    /// search until `claim` returns, so two vehicles can't be handed the same spot.
+    pub(crate) fn allocate_spot<R>(
+        &self,
+        floors: &HashMap<u32, ParkingFloor>,
+        vehicle: &Vehicle,
+        tags: &[SpotTag],
+        floor_open: impl Fn(u32) -> bool,
+        claim: impl FnOnce(&mut ParkingSpot) -> Option<R>,
+    ) -> Option<(u32, String, R)> {
+        self.allocate_spot_in(None, floors, vehicle, tags, floor_open, claim)
+    }
+
+    /// Like `allocate_spot`, passing over the spots `spot_open` rejects by floor and
+    /// spot id.
+    pub(crate) fn allocate_spot_where<R>(
+        &self,
+        floors: &HashMap<u32, ParkingFloor>,
+        vehicle: &Vehicle,
+        tags: &[SpotTag],
+        floor_open: impl Fn(u32) -> bool,
+        spot_open: impl Fn(u32, &str) -> bool,
+        claim: impl FnOnce(&mut ParkingSpot) -> Option<R>,
+    ) -> Option<(u32, String, R)> {
+        let open = |floor_id, spot_id: &str| floor_open(floor_id) && spot_open(floor_id, spot_id);
+        self.allocate_matching(None, floors, vehicle, tags, open, claim)
+    }
+
+    /// Like `allocate_spot`, but within `zone` and with its allocation strategy when given.
+    /// Spots beyond the lot's occupancy limits are passed over.
+    pub(crate) fn allocate_spot_in<R>(
+        &self,
+        zone: Option<&ParkingZone>,
+        floors: &HashMap<u32, ParkingFloor>,
+        vehicle: &Vehicle,
+        tags: &[SpotTag],
+        floor_open: impl Fn(u32) -> bool,
+        claim: impl FnOnce(&mut ParkingSpot) -> Option<R>,
+    ) -> Option<(u32, String, R)> {
+        let open = |floor_id, _: &str| floor_open(floor_id);
+        self.allocate_matching(zone, floors, vehicle, tags, open, claim)
+    }
+
+    /// Picks from the spots `open` accepts by floor and spot id.
+    fn allocate_matching<R>(
+        &self,
+        zone: Option<&ParkingZone>,
+        floors: &HashMap<u32, ParkingFloor>,
+        vehicle: &Vehicle,
+        tags: &[SpotTag],
+        open: impl Fn(u32, &str) -> bool,
+        claim: impl FnOnce(&mut ParkingSpot) -> Option<R>,
+    ) -> Option<(u32, String, R)> {
+        // Closed floors are locked too, since vehicles on them count towards the limits
+        let mut floor_ids: Vec<u32> = floors.keys().copied().collect();
+        floor_ids.sort_unstable();
+        let mut locked: Vec<_> = floor_ids
+            .iter()
+            .map(|id| (*id, floors[id].spots.lock().unwrap()))
+            .collect();
+
+        let occupancy = Occupancy::count(locked.iter().map(|(id, spots)| (*id, &**spots)));
+        let mut candidates = spot_candidates(
+            locked.iter().map(|(id, spots)| (*id, &**spots)),
+            zone.map(|z| z.zone_id.as_str()),
+            &vehicle.vehicle_type,
+            vehicle.handicapped_permit,
+            vehicle.dimensions.as_ref(),
+            tags,
+            open,
+        );
+        candidates.retain(|c| self.occupancy_limits.admits(c, &occupancy));
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let strategy = zone
+            .and_then(|z| z.allocation.as_deref())
+            .unwrap_or(self.allocation.as_ref());
+        let chosen = candidates.swap_remove(strategy.choose(vehicle, &candidates));
+        let (_, spots) = locked
+            .iter_mut()
+            .find(|(id, _)| *id == chosen.floor_id)
+            .unwrap();
+        let result = claim(spots.get_mut(&chosen.spot_id).unwrap())?;
+        Some((chosen.floor_id, chosen.spot_id, result))
+    }
+}
+
+/// Free spots of parking zone `zone` (or outside every zone) on `floors` that a vehicle
+/// may use and, if its size is known, fits in, sorted by floor then spot id. Spots `open`
+/// rejects by floor and spot id are left out. When some
+/// are handicapped spots for a permit holder or charging spots for an EV, only those are
+/// returned.
+pub(crate) fn spot_candidates<'a>(
+    floors: impl Iterator<Item = (u32, &'a HashMap<String, ParkingSpot>)>,
+    zone: Option<&str>,
+    vehicle_type: &VehicleType,
+    handicapped_permit: bool,
+    dimensions: Option<&Dimensions>,
+    tags: &[SpotTag],
+    open: impl Fn(u32, &str) -> bool,
+) -> Vec<SpotCandidate> {
+    let mut candidates = Vec::new();
+    let mut preferred = Vec::new();
+    for (floor_id, spots) in floors {
+        let mut spot_ids: Vec<&String> = spots.keys().collect();
+        spot_ids.sort();
+        for spot_id in spot_ids {
+            let spot = &spots[spot_id];
+            if !spot.is_available()
+                || !open(floor_id, spot_id)
+                || spot.zone.as_deref() != zone
+                || !spot.admits(vehicle_type, handicapped_permit)
+                || !spot.fits(dimensions)
+                || !spot.has_tags(tags)
+            {
+                continue;
+            }
+            let candidate = SpotCandidate {
+                floor_id,
+                spot_id: spot_id.clone(),
+                spot_type: spot.spot_type,
+            };
+            let claims_spot = match spot.spot_type {
+                SpotType::Handicapped => handicapped_permit,
+                SpotType::Electric => *vehicle_type == VehicleType::Electric,
+                _ => false,
+            };
+            if claims_spot {
+                preferred.push(candidate);
+            } else {
+                candidates.push(candidate);
+            }
+        }
+    }
+    if preferred.is_empty() {
+        candidates
+    } else {
+        preferred
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parkable;
+
+    fn two_floor_lot(strategy: Box<dyn AllocationStrategy>) -> ParkingLot {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.set_allocation_strategy(strategy);
+        for floor_id in [1, 2] {
+            lot.add_floor(ParkingFloor::new(floor_id)).unwrap();
+        }
+        lot
+    }
+
+    fn bike(plate: &str) -> Vehicle {
+        Vehicle::new(VehicleType::Bike, "BMX".into(), plate.into())
+    }
+
+    fn occupied(lot: &ParkingLot, floor_id: u32) -> usize {
+        let floor = lot.get_floor_by_id(floor_id).unwrap().unwrap();
+        let spots = floor.spots.lock().unwrap();
+        spots.values().filter(|s| s.is_occupied()).count()
+    }
+
+    #[test]
+    fn test_best_fit_keeps_large_spots_for_trucks() {
+        let lot = two_floor_lot(Box::new(BestFit));
+        lot.add_spot(2, ParkingSpot::new(true, SpotType::XLarge))
+            .unwrap();
+        for i in 0..20 {
+            lot.park_vehicle(bike(&format!("B{i}"))).unwrap();
+        }
+        let truck = Vehicle::new(VehicleType::Truck, "Volvo".into(), "T1".into());
+        assert!(lot.park_vehicle(truck).is_ok());
+    }
+
+    #[test]
+    fn test_nearest_floor_and_round_robin() {
+        let lot = two_floor_lot(Box::new(NearestFloor));
+        for i in 0..3 {
+            lot.park_vehicle(bike(&format!("B{i}"))).unwrap();
+        }
+        assert_eq!((occupied(&lot, 1), occupied(&lot, 2)), (3, 0));
+
+        let lot = two_floor_lot(Box::new(RoundRobin::default()));
+        for i in 0..4 {
+            lot.park_vehicle(bike(&format!("B{i}"))).unwrap();
+        }
+        assert_eq!((occupied(&lot, 1), occupied(&lot, 2)), (2, 2));
+    }
+}
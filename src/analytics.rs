@@ -0,0 +1,370 @@
+//! Occupancy and revenue analytics. The lot samples its occupancy whenever a vehicle
+//! parks or leaves, and on demand with `snapshot`. Stay lengths and revenue come from the
+//! ticket history. Hours and days are in UTC.
+//!
+//! Park attempts the lot turns away are counted by vehicle type and reason, to size the
+//! demand lost to a full lot or to missing spot types.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use chrono::{DateTime, Duration, NaiveDate, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{ParkingLot, Vehicle, VehicleType, error::ParkingError};
+
+/// Samples kept; older ones are dropped.
+pub const SAMPLE_CAPACITY: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FloorSample {
+    pub floor_id: u32,
+    pub occupied: u32,
+    pub spots: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OccupancySample {
+    pub at: DateTime<Utc>,
+    pub occupied: u32,
+    pub spots: u32,
+    /// Ordered by floor id.
+    pub floors: Vec<FloorSample>,
+}
+
+impl OccupancySample {
+    /// Occupied share of all spots, from 0 to 1.
+    pub fn occupancy_rate(&self) -> f32 {
+        if self.spots == 0 {
+            0.0
+        } else {
+            self.occupied as f32 / self.spots as f32
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct OccupancyLog {
+    pub(crate) samples: VecDeque<OccupancySample>,
+}
+
+impl OccupancyLog {
+    fn push(&mut self, sample: OccupancySample) {
+        if self.samples.len() == SAMPLE_CAPACITY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FloorPeak {
+    pub floor_id: u32,
+    pub occupied: u32,
+    /// First time the floor reached its peak.
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StayStats {
+    pub stays: u32,
+    pub average: Duration,
+}
+
+/// Why `park_vehicle` turned a vehicle away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RejectionReason {
+    /// Every spot that fits the vehicle was taken.
+    Full,
+    /// No spot in the lot fits the vehicle at all.
+    Incompatible,
+    /// The plate is on the blocklist.
+    Blocked,
+    /// Vehicles of its type are refused or at their cap.
+    Restricted,
+    /// Anything else, e.g. the lot was closed or the plate invalid.
+    Other,
+}
+
+impl RejectionReason {
+    pub const ALL: [RejectionReason; 5] = [
+        RejectionReason::Full,
+        RejectionReason::Incompatible,
+        RejectionReason::Blocked,
+        RejectionReason::Restricted,
+        RejectionReason::Other,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RejectionReason::Full => "full",
+            RejectionReason::Incompatible => "incompatible",
+            RejectionReason::Blocked => "blocked",
+            RejectionReason::Restricted => "restricted",
+            RejectionReason::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalyticsReport {
+    /// Mean occupancy rate of the samples taken in each hour of the day.
+    pub occupancy_by_hour: BTreeMap<u32, f32>,
+    pub peak_by_floor: BTreeMap<u32, FloorPeak>,
+    pub stays_by_vehicle_type: HashMap<VehicleType, StayStats>,
+    /// Revenue from stays that ended on each day.
+    pub revenue_by_day: BTreeMap<NaiveDate, f32>,
+    /// Park attempts turned away since the lot started.
+    pub rejections: HashMap<(VehicleType, RejectionReason), u32>,
+}
+
+impl AnalyticsReport {
+    /// One `metric,key,value` row per figure, durations in minutes.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("metric,key,value\n");
+        for (hour, rate) in &self.occupancy_by_hour {
+            csv.push_str(&format!("occupancy_by_hour,{hour:02},{rate:.4}\n"));
+        }
+        for (floor_id, peak) in &self.peak_by_floor {
+            csv.push_str(&format!("peak_by_floor,{floor_id},{}\n", peak.occupied));
+        }
+        for vehicle_type in &VehicleType::ALL {
+            if let Some(stats) = self.stays_by_vehicle_type.get(vehicle_type) {
+                csv.push_str(&format!(
+                    "average_stay_minutes,{vehicle_type:?},{}\n",
+                    stats.average.num_minutes()
+                ));
+            }
+        }
+        for (day, revenue) in &self.revenue_by_day {
+            csv.push_str(&format!("revenue_by_day,{day},{revenue:.2}\n"));
+        }
+        for reason in RejectionReason::ALL {
+            for vehicle_type in &VehicleType::ALL {
+                if let Some(count) = self.rejections.get(&(vehicle_type.clone(), reason)) {
+                    csv.push_str(&format!(
+                        "rejected_{},{vehicle_type:?},{count}\n",
+                        reason.as_str()
+                    ));
+                }
+            }
+        }
+        csv
+    }
+}
+
+impl ParkingLot {
+    /// Samples the lot's occupancy now and returns the sample.
+    pub fn snapshot(&self) -> OccupancySample {
+        let report = self.occupancy_report();
+        let sample = OccupancySample {
+            at: self.now(),
+            occupied: report.occupied,
+            spots: report.spots,
+            floors: report
+                .floors
+                .iter()
+                .map(|floor| FloorSample {
+                    floor_id: floor.floor_id,
+                    occupied: floor.occupied,
+                    spots: floor.spots,
+                })
+                .collect(),
+        };
+        self.occupancy_log.lock().unwrap().push(sample.clone());
+        sample
+    }
+
+    /// Samples taken so far, oldest first.
+    pub fn occupancy_samples(&self) -> Vec<OccupancySample> {
+        self.occupancy_log
+            .lock()
+            .unwrap()
+            .samples
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Every sample per floor as `at,floor_id,occupied,spots` rows.
+    pub fn occupancy_samples_csv(&self) -> String {
+        let mut csv = String::from("at,floor_id,occupied,spots\n");
+        for sample in self.occupancy_log.lock().unwrap().samples.iter() {
+            for floor in &sample.floors {
+                csv.push_str(&format!(
+                    "{},{},{},{}\n",
+                    sample.at.to_rfc3339(),
+                    floor.floor_id,
+                    floor.occupied,
+                    floor.spots
+                ));
+            }
+        }
+        csv
+    }
+
+    pub fn analytics_report(&self) -> Result<AnalyticsReport, ParkingError> {
+        let mut hourly: BTreeMap<u32, (f32, u32)> = BTreeMap::new();
+        let mut peak_by_floor: BTreeMap<u32, FloorPeak> = BTreeMap::new();
+        for sample in self.occupancy_log.lock()?.samples.iter() {
+            let hour = hourly.entry(sample.at.hour()).or_insert((0.0, 0));
+            hour.0 += sample.occupancy_rate();
+            hour.1 += 1;
+            for floor in &sample.floors {
+                let peak = peak_by_floor.entry(floor.floor_id).or_insert(FloorPeak {
+                    floor_id: floor.floor_id,
+                    occupied: floor.occupied,
+                    at: sample.at,
+                });
+                if floor.occupied > peak.occupied {
+                    peak.occupied = floor.occupied;
+                    peak.at = sample.at;
+                }
+            }
+        }
+
+        let mut stays: HashMap<VehicleType, (Duration, u32)> = HashMap::new();
+        let mut revenue_by_day: BTreeMap<NaiveDate, f32> = BTreeMap::new();
+        for completed in self.ticket_history.completed_tickets()? {
+            let ticket = &completed.ticket;
+            let Some(exit_time) = ticket.exit_time else {
+                continue;
+            };
+            let entry = stays
+                .entry(ticket.vehicle.vehicle_type.clone())
+                .or_insert((Duration::zero(), 0));
+            entry.0 += exit_time.signed_duration_since(ticket.entry_time);
+            entry.1 += 1;
+            *revenue_by_day.entry(exit_time.date_naive()).or_insert(0.0) += completed.total;
+        }
+
+        Ok(AnalyticsReport {
+            occupancy_by_hour: hourly
+                .into_iter()
+                .map(|(hour, (sum, count))| (hour, sum / count as f32))
+                .collect(),
+            peak_by_floor,
+            stays_by_vehicle_type: stays
+                .into_iter()
+                .map(|(vehicle_type, (total, count))| {
+                    let stats = StayStats {
+                        stays: count,
+                        average: total / count as i32,
+                    };
+                    (vehicle_type, stats)
+                })
+                .collect(),
+            revenue_by_day,
+            rejections: self.rejections.lock()?.clone(),
+        })
+    }
+
+    /// Counts a failed park attempt. A lot with no free spot for the vehicle counts as
+    /// incompatible rather than full if none of its spots could ever take it.
+    pub(crate) fn record_rejection(&self, vehicle: &Vehicle, error: &ParkingError) {
+        let reason = match error {
+            ParkingError::LotFull | ParkingError::NoSpotAvailable => {
+                let fits = self.floors.lock().unwrap().values().any(|floor| {
+                    floor
+                        .spots
+                        .lock()
+                        .unwrap()
+                        .values()
+                        .any(|spot| spot.accepts(vehicle))
+                });
+                if fits {
+                    RejectionReason::Full
+                } else {
+                    RejectionReason::Incompatible
+                }
+            }
+            ParkingError::ZoneFull => RejectionReason::Full,
+            ParkingError::IncompatibleVehicle => RejectionReason::Incompatible,
+            ParkingError::VehicleBlocked(_) => RejectionReason::Blocked,
+            ParkingError::VehicleTypeRestricted
+            | ParkingError::VehicleTypeCapReached(_)
+            | ParkingError::ExceedsClearance { .. } => RejectionReason::Restricted,
+            _ => RejectionReason::Other,
+        };
+        *self
+            .rejections
+            .lock()
+            .unwrap()
+            .entry((vehicle.vehicle_type.clone(), reason))
+            .or_insert(0) += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, Vehicle, clock::MockClock, pricing::FlatHourly};
+
+    #[test]
+    fn test_samples_on_park_and_unpark_feed_the_report() {
+        let clock = MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_pricing_strategy(Box::new(FlatHourly::new(10.0)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.add_floor(ParkingFloor::new(2)).unwrap();
+
+        let car = |plate: &str| Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into());
+        let first = lot.park_vehicle(car("ANA001")).unwrap();
+        let second = lot.park_vehicle(car("ANA002")).unwrap();
+        clock.advance(Duration::hours(2));
+        lot.unpark_vehicle(first.ticket_id).unwrap();
+        clock.advance(Duration::hours(2));
+        lot.unpark_vehicle(second.ticket_id).unwrap();
+        let last = lot.snapshot();
+        assert_eq!(last.occupied, 0);
+        assert_eq!(lot.occupancy_samples().len(), 5);
+
+        let report = lot.analytics_report().unwrap();
+        let peaks: u32 = report.peak_by_floor.values().map(|p| p.occupied).sum();
+        assert_eq!(peaks, 2);
+        let stays = &report.stays_by_vehicle_type[&VehicleType::Motor];
+        assert_eq!((stays.stays, stays.average), (2, Duration::hours(3)));
+        assert_eq!(report.revenue_by_day.values().sum::<f32>(), 60.0);
+        let hour = lot.now().hour();
+        assert_eq!(report.occupancy_by_hour[&hour], 0.0);
+
+        let csv = report.to_csv();
+        assert!(csv.starts_with("metric,key,value\n"));
+        assert!(csv.contains("average_stay_minutes,Motor,180\n"));
+        assert_eq!(lot.occupancy_samples_csv().lines().count(), 1 + 5 * 2);
+    }
+
+    #[test]
+    fn test_rejected_park_attempts_are_counted_by_reason() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let vehicle = |vehicle_type: VehicleType, plate: String| {
+            Vehicle::new(vehicle_type, "Any".into(), plate)
+        };
+
+        for n in 0..10 {
+            lot.park_vehicle(vehicle(VehicleType::Motor, format!("REJ{n:03}")))
+                .unwrap();
+        }
+        for n in 10..12 {
+            lot.park_vehicle(vehicle(VehicleType::Motor, format!("REJ{n:03}")))
+                .unwrap_err();
+        }
+        lot.park_vehicle(vehicle(VehicleType::Truck, "REJT01".into()))
+            .unwrap_err();
+        lot.block_plate("REJB01", "stolen".into());
+        lot.park_vehicle(vehicle(VehicleType::Bike, "REJB01".into()))
+            .unwrap_err();
+
+        let report = lot.analytics_report().unwrap();
+        let count = |vehicle_type, reason| report.rejections.get(&(vehicle_type, reason)).copied();
+        assert_eq!(count(VehicleType::Motor, RejectionReason::Full), Some(2));
+        assert_eq!(
+            count(VehicleType::Truck, RejectionReason::Incompatible),
+            Some(1)
+        );
+        assert_eq!(count(VehicleType::Bike, RejectionReason::Blocked), Some(1));
+        assert!(report.to_csv().contains("rejected_incompatible,Truck,1\n"));
+    }
+}
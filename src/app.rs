@@ -0,0 +1,311 @@
+//! The backend of a companion mobile app. The app's own backend signs a user in and opens
+//! an `AppSession` for them; the session's token then scopes every call to that user's
+//! vehicles. For each of their parked vehicles the app can show the ticket, where the car
+//! is with directions to it, how long it's been parked and what leaving now would cost,
+//! and can extend the stay or pay for it.
+//!
+//! Sessions hold the user's plates and discount eligibilities as they were when the session
+//! opened; open a new one after the user's account changes.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ParkingLot, ParkingTicket, User,
+    eligibility::Eligibility,
+    error::ParkingError,
+    locator::VehicleLocation,
+    payment::{Payment, PaymentMethod},
+    ticket_id::{IdGenerator, UuidIds},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppSession {
+    /// Random and unguessable; sent by the app with every call.
+    pub token: String,
+    pub user_name: String,
+    pub license_plates: Vec<String>,
+    pub eligibilities: Vec<Eligibility>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// What the app shows for one parked vehicle. Poll it to keep the timer and the estimate
+/// ticking.
+#[derive(Debug, Clone)]
+pub struct AppParkingStatus {
+    pub ticket: ParkingTicket,
+    pub location: VehicleLocation,
+    /// Directions to the spot, one step per line.
+    pub wayfinding: Vec<String>,
+    pub elapsed: Duration,
+    /// What leaving now would still cost, with the user's discount: nothing once paid,
+    /// until the exit grace runs out.
+    pub estimated_charge: f32,
+    /// When the stay runs past its maximum, extensions included; `None` without a cap.
+    pub stay_ends_at: Option<DateTime<Utc>>,
+    pub paid: bool,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct AppSessions {
+    pub(crate) sessions: HashMap<String, AppSession>,
+    ttl: Option<Duration>,
+}
+
+impl ParkingLot {
+    /// How long new app sessions last; 12 hours unless set.
+    pub fn set_app_session_ttl(&mut self, ttl: Duration) {
+        self.app_sessions.get_mut().unwrap().ttl = Some(ttl);
+    }
+
+    /// Opens a session for `user`, who the caller has already authenticated.
+    pub fn open_app_session(&self, user: &User) -> AppSession {
+        let now = self.now();
+        let mut sessions = self.app_sessions.lock().unwrap();
+        sessions
+            .sessions
+            .retain(|_, session| session.expires_at > now);
+        let mut license_plates: Vec<String> = user
+            .vehicles
+            .values()
+            .map(|vehicle| vehicle.license_plate.clone())
+            .collect();
+        license_plates.sort();
+        let session = AppSession {
+            token: UuidIds.next_id(now),
+            user_name: user.name().to_string(),
+            license_plates,
+            eligibilities: user.active_eligibilities(now),
+            expires_at: now + sessions.ttl.unwrap_or(Duration::hours(12)),
+        };
+        sessions
+            .sessions
+            .insert(session.token.clone(), session.clone());
+        session
+    }
+
+    /// Signs the session out. Returns whether it was open.
+    pub fn close_app_session(&self, token: &str) -> bool {
+        self.app_sessions
+            .lock()
+            .unwrap()
+            .sessions
+            .remove(token)
+            .is_some()
+    }
+
+    /// Every vehicle of the session's user that is parked here, by plate.
+    pub fn app_parking_status(&self, token: &str) -> Result<Vec<AppParkingStatus>, ParkingError> {
+        let session = self.app_session(token)?;
+        Ok(session
+            .license_plates
+            .iter()
+            .filter_map(|plate| self.active_ticket_for_plate(plate))
+            .filter_map(|ticket| self.app_status_for(&session, ticket))
+            .collect())
+    }
+
+    /// Pushes back the end of a capped stay by `by`, so it isn't fined as an overstay
+    /// until then.
+    pub fn app_extend_stay(
+        &self,
+        token: &str,
+        ticket_id: &str,
+        by: Duration,
+    ) -> Result<AppParkingStatus, ParkingError> {
+        let session = self.app_session(token)?;
+        let ticket = {
+            let ticket_id = self.canonical_ticket_id(ticket_id);
+            let mut tickets = self.active_tickets.lock()?;
+            let ticket = tickets
+                .get_mut(&ticket_id)
+                .filter(|ticket| {
+                    session
+                        .license_plates
+                        .contains(&ticket.vehicle.license_plate)
+                })
+                .ok_or(ParkingError::InvalidTicket)?;
+            if self
+                .overstay_policy
+                .max_stay_for(ticket.spot_type)
+                .is_none()
+            {
+                return Err(ParkingError::StayNotLimited);
+            }
+            ticket.stay_extension += by;
+            ticket.clone()
+        };
+        self.app_status_for(&session, ticket)
+            .ok_or(ParkingError::InvalidTicket)
+    }
+
+    /// Pays for one of the user's stays ahead of exit, as by `pay_ticket`, with the user's
+    /// discount.
+    pub fn app_pay(
+        &self,
+        token: &str,
+        ticket_id: &str,
+        method: PaymentMethod,
+    ) -> Result<Payment, ParkingError> {
+        let session = self.app_session(token)?;
+        self.active_ticket(ticket_id)
+            .filter(|ticket| {
+                session
+                    .license_plates
+                    .contains(&ticket.vehicle.license_plate)
+            })
+            .ok_or(ParkingError::InvalidTicket)?;
+        let discount = self.discounts.best_discount(&session.eligibilities);
+        self.pay_ticket_with(ticket_id, method, discount)
+    }
+
+    fn app_session(&self, token: &str) -> Result<AppSession, ParkingError> {
+        self.app_sessions
+            .lock()?
+            .sessions
+            .get(token)
+            .filter(|session| session.expires_at > self.now())
+            .cloned()
+            .ok_or(ParkingError::SessionNotFound)
+    }
+
+    fn app_status_for(
+        &self,
+        session: &AppSession,
+        ticket: ParkingTicket,
+    ) -> Option<AppParkingStatus> {
+        let now = self.now();
+        let location = self.locate_vehicle(&ticket.vehicle.license_plate)?;
+        let payments = self.payments_for(&ticket.ticket_id);
+        let discount = match payments.first() {
+            Some(payment) => payment.discount,
+            None => self.discounts.best_discount(&session.eligibilities),
+        };
+        let estimated_charge = self
+            .price_paid_stay(&ticket, &payments, now, discount)
+            .amount_due();
+        let stay_ends_at = self
+            .overstay_policy
+            .max_stay_for(ticket.spot_type)
+            .map(|max_stay| ticket.entry_time + max_stay + ticket.stay_extension);
+        Some(AppParkingStatus {
+            wayfinding: self.wayfinding(&location, &ticket),
+            elapsed: now - ticket.entry_time,
+            estimated_charge,
+            stay_ends_at,
+            paid: !payments.is_empty(),
+            location,
+            ticket,
+        })
+    }
+
+    fn wayfinding(&self, location: &VehicleLocation, ticket: &ParkingTicket) -> Vec<String> {
+        let mut steps = vec![format!("Go to floor {}", location.floor_id)];
+        if let Some(zone_id) = &ticket.zone_id {
+            steps.push(format!("Follow the signs for zone {zone_id}"));
+        }
+        let floors = self.floors.lock().unwrap();
+        let spots = floors
+            .get(&location.floor_id)
+            .map(|f| f.spots.lock().unwrap());
+        let spot = spots
+            .as_ref()
+            .and_then(|spots| spots.get(&location.spot_id));
+        let spot_type = spot.map_or(ticket.spot_type, |spot| Some(spot.spot_type));
+        steps.push(match spot_type {
+            Some(spot_type) => format!("Your car is in {spot_type:?} spot {}", location.spot_id),
+            None => format!("Your car is in spot {}", location.spot_id),
+        });
+        if let Some(spot) = spot {
+            let mut tags: Vec<&str> = spot.tags().iter().map(|tag| tag.as_str()).collect();
+            tags.sort();
+            if !tags.is_empty() {
+                steps.push(format!("The spot is {}", tags.join(", ")));
+            }
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Account, Parkable, ParkingFloor, Vehicle, VehicleType,
+        clock::MockClock,
+        overstay::OverstayPolicy,
+        payment::{CashProcessor, PaymentMethodKind},
+        tags::SpotTag,
+    };
+
+    #[test]
+    fn test_session_sees_and_acts_on_its_own_vehicles_only() {
+        let clock = MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()));
+        lot.add_floor(ParkingFloor::new(2)).unwrap();
+        lot.set_payment_processor(PaymentMethodKind::Cash, Box::new(CashProcessor));
+        lot.set_overstay_policy(OverstayPolicy::default().with_max_stay(Duration::hours(2)));
+        let mut ada = User::new("Ada".into(), "0800".into());
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "APP001".into());
+        ada.register_vehicle(car.clone());
+        let ticket = lot.park_vehicle(car).unwrap();
+        let other = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "APP002".into(),
+            ))
+            .unwrap();
+        lot.tag_spot(
+            ticket.floor_id.unwrap(),
+            &ticket.spot_id,
+            SpotTag::NearElevator,
+        )
+        .unwrap();
+
+        let session = lot.open_app_session(&ada);
+        clock.advance(Duration::minutes(90));
+        let status = lot.app_parking_status(&session.token).unwrap();
+        assert_eq!(status.len(), 1);
+        let status = &status[0];
+        assert_eq!(status.ticket.ticket_id, ticket.ticket_id);
+        assert_eq!(
+            (status.location.floor_id, status.elapsed),
+            (2, Duration::minutes(90))
+        );
+        assert_eq!(status.wayfinding[0], "Go to floor 2");
+        assert_eq!(status.wayfinding[2], "The spot is near-elevator");
+        assert!(status.estimated_charge > 0.0 && !status.paid);
+
+        let extended = lot
+            .app_extend_stay(&session.token, &ticket.ticket_id, Duration::hours(1))
+            .unwrap();
+        assert_eq!(
+            extended.stay_ends_at,
+            Some(ticket.entry_time + Duration::hours(3))
+        );
+        clock.advance(Duration::minutes(60));
+        assert!(
+            lot.find_overstays(lot.now())
+                .iter()
+                .all(|o| o.ticket_id != ticket.ticket_id)
+        );
+
+        assert_eq!(
+            lot.app_pay(&session.token, &other.ticket_id, PaymentMethod::Cash),
+            Err(ParkingError::InvalidTicket)
+        );
+        lot.app_pay(&session.token, &ticket.ticket_id, PaymentMethod::Cash)
+            .unwrap();
+        assert!(lot.app_parking_status(&session.token).unwrap()[0].paid);
+
+        assert!(lot.close_app_session(&session.token));
+        assert_eq!(
+            lot.app_parking_status(&session.token).unwrap_err(),
+            ParkingError::SessionNotFound
+        );
+    }
+}
@@ -0,0 +1,328 @@
+//! Async access to a lot, for embedding in async services (feature `async`).
+//!
+//! `LotHandle` is an actor owning the lot: calls are sent to it over a `tokio::sync::mpsc`
+//! channel and answered on a `tokio::sync::oneshot`, so handlers await the reply instead
+//! of blocking the runtime on the lot's locks. The actor runs on a thread of its own rather
+//! than a runtime task, as lot calls block on locks and on payment processors; the replies
+//! are tokio channels, so any executor can await them. The thread stops once every handle
+//! is dropped, or after `LotHandle::shutdown`.
+
+use std::{
+    future::Future,
+    path::PathBuf,
+    pin::Pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    task::{Context, Poll},
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    Parkable, ParkingCharge, ParkingLot, ParkingTicket, Vehicle,
+    error::ParkingError,
+    payment::{Payment, PaymentMethod},
+    reservation::Reservation,
+    shutdown::ShutdownReport,
+};
+
+type Job = Box<dyn FnOnce(&mut ParkingLot) + Send>;
+type FinishJob = Box<dyn FnOnce(&mut ParkingLot, usize) + Send>;
+
+enum Message {
+    Run(Job),
+    /// Runs after every call queued before it, given how many of them were dropped for
+    /// missing the drain deadline, then stops the lot's thread.
+    Finish(FinishJob),
+}
+
+pub trait AsyncParkable {
+    fn park_vehicle(
+        &self,
+        vehicle: Vehicle,
+    ) -> impl Future<Output = Result<ParkingTicket, ParkingError>> + Send;
+    fn unpark_vehicle(
+        &self,
+        ticket_id: String,
+    ) -> impl Future<Output = Result<ParkingCharge, ParkingError>> + Send;
+    fn pay_ticket(
+        &self,
+        ticket_id: String,
+        method: PaymentMethod,
+    ) -> impl Future<Output = Result<Payment, ParkingError>> + Send;
+    fn reserve_spot(
+        &self,
+        vehicle: Vehicle,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Reservation, ParkingError>> + Send;
+}
+
+/// A cloneable handle to a lot running on its own thread.
+#[derive(Debug, Clone)]
+pub struct LotHandle {
+    jobs: mpsc::UnboundedSender<Message>,
+    /// The lot's own draining flag, so a shutdown refuses parks already queued.
+    draining: Arc<AtomicBool>,
+    /// Calls still queued after this are dropped instead of run.
+    deadline: Arc<Mutex<Option<Instant>>>,
+}
+
+impl LotHandle {
+    /// Moves `lot` onto a new thread and returns a handle to it.
+    pub fn spawn(mut lot: ParkingLot) -> Self {
+        let (jobs, mut queue) = mpsc::unbounded_channel::<Message>();
+        let draining = lot.draining.clone();
+        let deadline = Arc::new(Mutex::new(None::<Instant>));
+        let lot_deadline = deadline.clone();
+        thread::spawn(move || {
+            let mut abandoned = 0;
+            while let Some(message) = queue.blocking_recv() {
+                match message {
+                    Message::Run(job) => {
+                        let expired = lot_deadline
+                            .lock()
+                            .unwrap()
+                            .is_some_and(|deadline| Instant::now() > deadline);
+                        if expired {
+                            // Dropping the job closes its reply
+                            abandoned += 1;
+                        } else {
+                            job(&mut lot);
+                        }
+                    }
+                    Message::Finish(finish) => {
+                        finish(&mut lot, abandoned);
+                        break;
+                    }
+                }
+            }
+        });
+        Self {
+            jobs,
+            draining,
+            deadline,
+        }
+    }
+
+    /// Runs `f` on the lot's thread and resolves to its result. Fails with `LotStopped`
+    /// if the lot's thread has gone, e.g. after a panic in an earlier call.
+    pub fn call<T, F>(&self, f: F) -> Reply<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut ParkingLot) -> T + Send + 'static,
+    {
+        let (sender, reply) = oneshot::channel();
+        // A job that can't be queued is dropped here, closing the reply
+        let _ = self.jobs.send(Message::Run(Box::new(move |lot| {
+            let _ = sender.send(f(lot));
+        })));
+        Reply(reply)
+    }
+
+    /// Shuts the lot down: new parks and reservations are refused at once, calls already
+    /// queued get `timeout` to finish, and the lot is then drained as by
+    /// `ParkingLot::shutdown`. Calls that miss the timeout, and every call after the
+    /// shutdown, fail with `LotStopped`.
+    pub fn shutdown(
+        &self,
+        timeout: Duration,
+        snapshot_path: Option<PathBuf>,
+    ) -> Reply<Result<ShutdownReport, ParkingError>> {
+        self.draining.store(true, Ordering::SeqCst);
+        self.deadline
+            .lock()
+            .unwrap()
+            .get_or_insert(Instant::now() + timeout);
+        let (sender, reply) = oneshot::channel();
+        let _ = self
+            .jobs
+            .send(Message::Finish(Box::new(move |lot, abandoned| {
+                let report = lot.shutdown(snapshot_path.as_deref());
+                let _ = sender.send(report.map(|report| ShutdownReport {
+                    abandoned,
+                    ..report
+                }));
+            })));
+        Reply(reply)
+    }
+}
+
+impl AsyncParkable for LotHandle {
+    fn park_vehicle(
+        &self,
+        vehicle: Vehicle,
+    ) -> impl Future<Output = Result<ParkingTicket, ParkingError>> + Send {
+        let reply = self.call(move |lot| lot.park_vehicle(vehicle));
+        async move { reply.await? }
+    }
+
+    fn unpark_vehicle(
+        &self,
+        ticket_id: String,
+    ) -> impl Future<Output = Result<ParkingCharge, ParkingError>> + Send {
+        let reply = self.call(move |lot| lot.unpark_vehicle(ticket_id));
+        async move { reply.await? }
+    }
+
+    fn pay_ticket(
+        &self,
+        ticket_id: String,
+        method: PaymentMethod,
+    ) -> impl Future<Output = Result<Payment, ParkingError>> + Send {
+        let reply = self.call(move |lot| lot.pay_ticket(&ticket_id, method));
+        async move { reply.await? }
+    }
+
+    fn reserve_spot(
+        &self,
+        vehicle: Vehicle,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Reservation, ParkingError>> + Send {
+        let reply = self.call(move |lot| lot.reserve_spot(vehicle, from, until));
+        async move { reply.await? }
+    }
+}
+
+/// Resolves to what a `LotHandle::call` returned, or `LotStopped` if the call was dropped
+/// unanswered.
+pub struct Reply<T>(oneshot::Receiver<T>);
+
+impl<T> Future for Reply<T> {
+    type Output = Result<T, ParkingError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0)
+            .poll(cx)
+            .map_err(|_| ParkingError::LotStopped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ParkingFloor, VehicleType,
+        payment::{CashProcessor, PaymentMethodKind},
+    };
+    use chrono::Duration as ChronoDuration;
+    use std::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_handle_parks_pays_and_reserves_from_async_code() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_payment_processor(PaymentMethodKind::Cash, Box::new(CashProcessor));
+        let handle = LotHandle::spawn(lot);
+
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "ASYNC1".into());
+        let ticket = handle.park_vehicle(car).await.unwrap();
+        handle
+            .pay_ticket(ticket.ticket_id.clone(), PaymentMethod::Cash)
+            .await
+            .unwrap();
+        let charge = handle
+            .unpark_vehicle(ticket.ticket_id.clone())
+            .await
+            .unwrap();
+        assert_eq!(charge.ticket_id, ticket.ticket_id);
+
+        let guest = Vehicle::new(VehicleType::Motor, "Kia".into(), "ASYNC2".into());
+        let now = handle.call(|lot| lot.now()).await.unwrap();
+        let reservation = handle
+            .reserve_spot(guest, now, now + ChronoDuration::hours(1))
+            .await;
+        assert!(reservation.is_ok());
+
+        // A panic on the lot's thread stops it; later calls fail instead of hanging
+        let panicked = handle.call(|_| -> () { panic!("boom") }).await;
+        assert_eq!(panicked, Err(ParkingError::LotStopped));
+        assert_eq!(
+            handle.unpark_vehicle("TKT_0".into()).await.unwrap_err(),
+            ParkingError::LotStopped
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drains_queued_exits_and_refuses_parks() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let handle = LotHandle::spawn(lot);
+        let car = |plate: &str| Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into());
+        let ticket = handle.park_vehicle(car("DRAIN1")).await.unwrap();
+
+        // Hold the lot's thread so the next calls are still queued at shutdown
+        let (release, held) = mpsc::channel::<()>();
+        let (started, running) = mpsc::channel::<()>();
+        let hold = handle.call(move |_| {
+            started.send(()).unwrap();
+            held.recv().unwrap()
+        });
+        running.recv().unwrap();
+        let park = handle.park_vehicle(car("DRAIN2"));
+        let unpark = handle.unpark_vehicle(ticket.ticket_id);
+        let shutdown = handle.shutdown(Duration::from_secs(60), None);
+        release.send(()).unwrap();
+
+        hold.await.unwrap();
+        assert_eq!(park.await.unwrap_err(), ParkingError::ShuttingDown);
+        assert!(unpark.await.is_ok());
+        let report = shutdown.await.unwrap().unwrap();
+        assert_eq!((report.open_tickets, report.abandoned), (0, 0));
+        assert_eq!(
+            handle.park_vehicle(car("DRAIN3")).await.unwrap_err(),
+            ParkingError::LotStopped
+        );
+
+        // Calls still queued when the timeout runs out are abandoned
+        let handle = LotHandle::spawn(ParkingLot::new("Hub".into(), "Lagos".into(), "2".into()));
+        let (release, held) = mpsc::channel::<()>();
+        let (started, running) = mpsc::channel::<()>();
+        let hold = handle.call(move |_| {
+            started.send(()).unwrap();
+            held.recv().unwrap()
+        });
+        running.recv().unwrap();
+        let late = handle.call(|lot| lot.now());
+        let shutdown = handle.shutdown(Duration::ZERO, None);
+        thread::sleep(Duration::from_millis(5));
+        release.send(()).unwrap();
+        hold.await.unwrap();
+        assert_eq!(late.await, Err(ParkingError::LotStopped));
+        assert_eq!(shutdown.await.unwrap().unwrap().abandoned, 1);
+    }
+
+    #[tokio::test]
+    async fn test_handles_are_shared_across_spawned_tasks() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let handle = LotHandle::spawn(lot);
+
+        let tasks: Vec<_> = (0..8)
+            .map(|n| {
+                let handle = handle.clone();
+                tokio::spawn(async move {
+                    let car = Vehicle::new(VehicleType::Motor, "Kia".into(), format!("TASK{n}"));
+                    handle.park_vehicle(car).await
+                })
+            })
+            .collect();
+        let mut spots = Vec::new();
+        for task in tasks {
+            spots.push(task.await.unwrap().unwrap().spot_id);
+        }
+        spots.sort();
+        spots.dedup();
+        assert_eq!(spots.len(), 8);
+        let parked = handle
+            .call(|lot| lot.display_info().unwrap().num_parked_vehicles())
+            .await;
+        assert_eq!(parked, Ok(8));
+    }
+}
@@ -0,0 +1,74 @@
+//! Audit log of actions that override the lot's normal rules: priority vehicles taking
+//! held spots or skipping capacity limits, and staff changes made through `Admin`.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{ParkingLot, SpotType};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum AuditAction {
+    /// A priority vehicle took a spot held by the reservation or lease `held_by`.
+    SpotPreempted {
+        spot_id: String,
+        held_by: String,
+    },
+    /// A priority vehicle parked on a closed floor.
+    ClosedFloorEntered {
+        floor_id: u32,
+        spot_id: String,
+    },
+    /// A priority vehicle was let in from the entry queue while it was metered.
+    QueueBypassed,
+    FloorAdded {
+        floor_id: u32,
+    },
+    FloorRemoved {
+        floor_id: u32,
+    },
+    /// `floor_id` is unknown for entries saved before spots were audited by floor.
+    SpotOutOfService {
+        floor_id: Option<u32>,
+        spot_id: String,
+        reason: String,
+    },
+    SpotReturnedToService {
+        floor_id: Option<u32>,
+        spot_id: String,
+    },
+    SpotConverted {
+        floor_id: Option<u32>,
+        spot_id: String,
+        from: SpotType,
+        to: SpotType,
+    },
+    /// The lot's pricing strategy was replaced.
+    RatesUpdated,
+    TicketForceClosed {
+        ticket_id: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    /// Who acted: the priority vehicle or the admin.
+    pub actor: String,
+    pub action: AuditAction,
+}
+
+impl ParkingLot {
+    /// Every audited action, oldest first.
+    pub fn audit_log(&self) -> Vec<AuditEntry> {
+        self.audit_log.lock().unwrap().clone()
+    }
+
+    pub(crate) fn audit(&self, actor: String, action: AuditAction) {
+        self.audit_log.lock().unwrap().push(AuditEntry {
+            at: self.now(),
+            actor,
+            action,
+        });
+    }
+}
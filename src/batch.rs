@@ -0,0 +1,434 @@
+//! All-or-nothing batches of park and unpark operations, for valet and shuttle runs that
+//! move many vehicles at once.
+//!
+//! Operations run in order against the live lot. If one fails, the ones already applied
+//! are undone in reverse order and nothing they produced (webhook events, archived
+//! stays, closed-ticket history, experiment stats, receipts, ended charging sessions) is
+//! published; those wait in the batch's own effect buffer until it commits. The one
+//! effect that can't be taken back is a card hold already captured by the payment
+//! processor.
+//!
+//! A batch isn't isolated from the rest of the lot. Other callers' parks and exits run
+//! between its operations, and they see its tickets and spots as each operation applies,
+//! before the batch commits.
+//!
+//! Undoing restores the ticket and the spot an operation used, on its floor, and only
+//! while the batch's vehicle is still the one there. Parking and unparking don't touch
+//! payments or custody sessions, and a charging session only ends on commit, so there's
+//! nothing else to restore. An unpark whose spot was taken by another vehicle in the
+//! meantime can't be undone; it's reported as `RollbackFailed` and its stay stays closed.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    ParkingCharge, ParkingLot, ParkingTicket, Vehicle,
+    error::ParkingError,
+    events::ParkingEvent,
+    experiment::PricingExperiment,
+    history::{CompletedTicket, StayRecord},
+    journal::TransitionCause,
+    notification::Notification,
+};
+
+#[derive(Debug, Clone)]
+pub enum BatchOperation {
+    Park(Vehicle),
+    Unpark(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum BatchItemResult {
+    Parked(Box<ParkingTicket>),
+    Unparked(ParkingCharge),
+    /// The operation that stopped the batch.
+    Failed(ParkingError),
+    /// Applied, then undone because a later operation failed.
+    RolledBack,
+    /// Applied, and left applied because undoing it failed.
+    RollbackFailed(ParkingError),
+    /// Never attempted because an earlier operation failed.
+    Skipped,
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchOutcome {
+    pub committed: bool,
+    pub results: Vec<BatchItemResult>,
+}
+
+/// Side effects that are published only once a batch commits.
+#[derive(Debug, Clone)]
+pub(crate) enum Effect {
+    Event(ParkingEvent),
+    Archive(StayRecord),
+    History(Box<CompletedTicket>),
+    Exit(DateTime<Utc>),
+    /// Ends the ticket's charging session, if still running, at the given time.
+    StopCharging(String, DateTime<Utc>),
+    /// A notice for a vehicle's owner, with its recipient.
+    Notify(String, Notification),
+    ExperimentStay {
+        experiment: Arc<PricingExperiment>,
+        variant: String,
+        charge: f32,
+        minutes: i64,
+    },
+}
+
+enum Undo {
+    Park {
+        ticket: Box<ParkingTicket>,
+        floor_id: Option<u32>,
+    },
+    Unpark {
+        ticket: Box<ParkingTicket>,
+        floor_id: Option<u32>,
+        claimed_until: Option<DateTime<Utc>>,
+    },
+}
+
+/// Where an operation's side effects go: published as they're applied, or held until
+/// the batch running the operation commits.
+#[derive(Debug, Default)]
+pub(crate) struct Effects {
+    deferred: Option<Vec<Effect>>,
+}
+
+impl Effects {
+    pub(crate) fn immediate() -> Self {
+        Self { deferred: None }
+    }
+
+    fn deferred() -> Self {
+        Self {
+            deferred: Some(Vec::new()),
+        }
+    }
+
+    fn into_deferred(self) -> Vec<Effect> {
+        self.deferred.unwrap_or_default()
+    }
+}
+
+impl ParkingLot {
+    /// Publishes `effect`, or holds it back in `effects` if they're deferred.
+    pub(crate) fn apply_effect(
+        &self,
+        effect: Effect,
+        effects: &mut Effects,
+    ) -> Result<(), ParkingError> {
+        match &mut effects.deferred {
+            Some(pending) => {
+                pending.push(effect);
+                Ok(())
+            }
+            None => self.publish_effect(effect),
+        }
+    }
+
+    fn publish_effect(&self, effect: Effect) -> Result<(), ParkingError> {
+        match effect {
+            Effect::Event(event) => self.publish_event(event),
+            Effect::Archive(record) => {
+                if let Some(archive) = &self.archive {
+                    archive.record(record)?;
+                }
+            }
+            Effect::History(entry) => self.ticket_history.record(*entry)?,
+            Effect::Exit(at) => self.record_exit(at)?,
+            Effect::StopCharging(ticket_id, at) => self.stop_charging_at(&ticket_id, at)?,
+            Effect::Notify(recipient, notification) => self.deliver(&recipient, &notification),
+            Effect::ExperimentStay {
+                experiment,
+                variant,
+                charge,
+                minutes,
+            } => experiment.record_stay(&variant, charge, minutes)?,
+        }
+        Ok(())
+    }
+
+    /// Applies `operations` in order, all or nothing. On failure every applied operation
+    /// is rolled back and the outcome reports which one failed.
+    pub fn apply_batch(
+        &self,
+        operations: Vec<BatchOperation>,
+    ) -> Result<BatchOutcome, ParkingError> {
+        let mut effects = Effects::deferred();
+        let mut results = Vec::with_capacity(operations.len());
+        let mut undo_log = Vec::new();
+        let mut failed = false;
+        for operation in operations {
+            if failed {
+                results.push(BatchItemResult::Skipped);
+                continue;
+            }
+            match self.apply_batch_operation(operation, &mut effects) {
+                Ok((result, undo)) => {
+                    results.push(result);
+                    undo_log.push(undo);
+                }
+                Err(e) => {
+                    results.push(BatchItemResult::Failed(e));
+                    failed = true;
+                }
+            }
+        }
+
+        if failed {
+            for (index, undo) in undo_log.into_iter().enumerate().rev() {
+                results[index] = match self.undo_batch_operation(undo) {
+                    Ok(()) => BatchItemResult::RolledBack,
+                    Err(e) => BatchItemResult::RollbackFailed(e),
+                };
+            }
+        } else {
+            for effect in effects.into_deferred() {
+                self.publish_effect(effect)?;
+            }
+        }
+
+        Ok(BatchOutcome {
+            committed: !failed,
+            results,
+        })
+    }
+
+    fn apply_batch_operation(
+        &self,
+        operation: BatchOperation,
+        effects: &mut Effects,
+    ) -> Result<(BatchItemResult, Undo), ParkingError> {
+        match operation {
+            BatchOperation::Park(vehicle) => {
+                let ticket = self.park_with_tags(vehicle, &[], effects)?;
+                let undo = Undo::Park {
+                    ticket: Box::new(ticket.clone()),
+                    floor_id: self.parked_floor(&ticket),
+                };
+                Ok((BatchItemResult::Parked(Box::new(ticket)), undo))
+            }
+            BatchOperation::Unpark(ticket_id) => {
+                let ticket = self
+                    .active_ticket(&ticket_id)
+                    .ok_or(ParkingError::InvalidTicket)?;
+                if ticket.exit_time.is_some() {
+                    return Err(ParkingError::TicketClosed);
+                }
+                let floor_id = self.parked_floor(&ticket);
+                let claimed_until = match floor_id {
+                    Some(floor_id) => self
+                        .with_floor_spot_mut(floor_id, &ticket.spot_id, |spot| {
+                            spot.claimed_until()
+                        })?
+                        .flatten(),
+                    None => None,
+                };
+                let charge = self.checkout(ticket.ticket_id.clone(), None, None, effects)?;
+                let undo = Undo::Unpark {
+                    ticket: Box::new(ticket),
+                    floor_id,
+                    claimed_until,
+                };
+                Ok((BatchItemResult::Unparked(charge), undo))
+            }
+        }
+    }
+
+    /// Floor of the spot `ticket`'s vehicle is parked on.
+    fn parked_floor(&self, ticket: &ParkingTicket) -> Option<u32> {
+        self.locate_vehicle(&ticket.vehicle.license_plate)
+            .filter(|location| location.ticket_id == ticket.ticket_id)
+            .map(|location| location.floor_id)
+    }
+
+    fn undo_batch_operation(&self, undo: Undo) -> Result<(), ParkingError> {
+        let now = self.now();
+        match undo {
+            Undo::Park { ticket, floor_id } => {
+                self.active_tickets.lock()?.remove(&ticket.ticket_id);
+                let plate = &ticket.vehicle.license_plate;
+                if let Some(floor_id) = floor_id {
+                    self.with_floor_spot_mut(floor_id, &ticket.spot_id, |spot| {
+                        // Leave the spot alone if the vehicle has since moved off it
+                        if spot.vehicle().is_some_and(|v| &v.license_plate == plate) {
+                            spot.transition(TransitionCause::BatchRolledBack, now, |spot| {
+                                spot.remove_vehicle()
+                            });
+                        }
+                    })?;
+                }
+                Ok(())
+            }
+            Undo::Unpark {
+                ticket,
+                floor_id,
+                claimed_until,
+            } => {
+                if let Some(floor_id) = floor_id {
+                    self.with_floor_spot_mut(floor_id, &ticket.spot_id, |spot| {
+                        spot.transition(TransitionCause::BatchRolledBack, now, |spot| {
+                            spot.assign_vehicle_until(ticket.vehicle.clone(), claimed_until)
+                        })
+                    })?
+                    .ok_or(ParkingError::SpotNotFound)??;
+                }
+                self.active_tickets
+                    .lock()?
+                    .insert(ticket.ticket_id.clone(), *ticket);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, VehicleType, events::EventKind, history::TicketArchive};
+
+    fn car(plate: &str) -> Vehicle {
+        Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into())
+    }
+
+    #[test]
+    fn test_failed_batch_leaves_lot_untouched() {
+        let archive = TicketArchive::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_ticket_archive(archive.clone());
+        let parked = lot.park_vehicle(car("OLD001")).unwrap();
+
+        let outcome = lot
+            .apply_batch(vec![
+                BatchOperation::Unpark(parked.ticket_id.clone()),
+                BatchOperation::Park(car("NEW001")),
+                BatchOperation::Unpark("TKT_missing".into()),
+                BatchOperation::Park(car("NEW002")),
+            ])
+            .unwrap();
+
+        assert!(!outcome.committed);
+        assert!(matches!(outcome.results[0], BatchItemResult::RolledBack));
+        assert!(matches!(outcome.results[1], BatchItemResult::RolledBack));
+        assert!(matches!(
+            outcome.results[2],
+            BatchItemResult::Failed(ParkingError::InvalidTicket)
+        ));
+        assert!(matches!(outcome.results[3], BatchItemResult::Skipped));
+
+        assert_eq!(lot.display_info().unwrap().num_parked_vehicles(), 1);
+        assert!(archive.stays_for(&["OLD001"]).unwrap().is_empty());
+        assert!(
+            lot.active_tickets.lock().unwrap()[&parked.ticket_id]
+                .exit_time
+                .is_none()
+        );
+
+        let outcome = lot
+            .apply_batch(vec![
+                BatchOperation::Unpark(parked.ticket_id),
+                BatchOperation::Park(car("NEW001")),
+            ])
+            .unwrap();
+        assert!(outcome.committed);
+        assert_eq!(archive.stays_for(&["OLD001"]).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_batch_events_are_published_only_when_it_commits() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let parked = lot.park_vehicle(car("OLD001")).unwrap();
+        let events = lot.events();
+
+        let outcome = lot
+            .apply_batch(vec![
+                BatchOperation::Park(car("NEW001")),
+                BatchOperation::Unpark("TKT_missing".into()),
+            ])
+            .unwrap();
+        assert!(!outcome.committed);
+        lot.park_vehicle(car("SOLO01")).unwrap();
+        let kinds: Vec<EventKind> = events.try_iter().map(|e| e.kind()).collect();
+        assert_eq!(kinds, [EventKind::VehicleParked]);
+
+        let outcome = lot
+            .apply_batch(vec![
+                BatchOperation::Unpark(parked.ticket_id),
+                BatchOperation::Park(car("NEW001")),
+            ])
+            .unwrap();
+        assert!(outcome.committed);
+        let kinds: Vec<EventKind> = events.try_iter().map(|e| e.kind()).collect();
+        assert_eq!(
+            kinds,
+            [EventKind::VehicleUnparked, EventKind::VehicleParked]
+        );
+    }
+
+    #[test]
+    fn test_rollback_leaves_same_spot_id_on_other_floor_alone() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        for floor_id in [1, 2] {
+            lot.add_floor(ParkingFloor::new(floor_id)).unwrap();
+            let floor = lot.get_floor_by_id(floor_id).unwrap().unwrap();
+            floor
+                .spots
+                .lock()
+                .unwrap()
+                .retain(|spot_id, _| spot_id == "spot_3");
+        }
+        let bystander = lot.park_vehicle(car("OLD001")).unwrap();
+        let bystander_floor = lot.locate_vehicle("OLD001").unwrap().floor_id;
+
+        let outcome = lot
+            .apply_batch(vec![
+                BatchOperation::Park(car("NEW001")),
+                BatchOperation::Unpark("TKT_missing".into()),
+            ])
+            .unwrap();
+        assert!(matches!(outcome.results[0], BatchItemResult::RolledBack));
+        let location = lot.locate_vehicle("OLD001").unwrap();
+        assert_eq!(
+            (location.floor_id, location.ticket_id),
+            (bystander_floor, bystander.ticket_id)
+        );
+        assert!(lot.locate_vehicle("NEW001").is_none());
+        assert_eq!(lot.display_info().unwrap().num_empty_spots(), 1);
+    }
+
+    #[test]
+    fn test_rollback_keeps_the_charging_session_running() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.add_spot(1, crate::ParkingSpot::new(true, crate::SpotType::Electric))
+            .unwrap();
+        let ev = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Electric,
+                "Leaf".into(),
+                "EV0001".into(),
+            ))
+            .unwrap();
+        lot.start_charging(&ev.ticket_id).unwrap();
+
+        let outcome = lot
+            .apply_batch(vec![
+                BatchOperation::Unpark(ev.ticket_id.clone()),
+                BatchOperation::Unpark("TKT_missing".into()),
+            ])
+            .unwrap();
+        assert!(matches!(outcome.results[0], BatchItemResult::RolledBack));
+        assert!(lot.charging_session(&ev.ticket_id).unwrap().is_active());
+        lot.record_charging_energy(&ev.ticket_id, 3.0).unwrap();
+
+        let outcome = lot
+            .apply_batch(vec![BatchOperation::Unpark(ev.ticket_id.clone())])
+            .unwrap();
+        assert!(outcome.committed);
+        assert!(!lot.charging_session(&ev.ticket_id).unwrap().is_active());
+    }
+}
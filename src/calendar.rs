@@ -0,0 +1,293 @@
+//! Calendar-style availability queries for booking UIs: how many spots of a type can still
+//! be reserved in each time slot of a date range. Pending reservations, leased spots,
+//! passes valid here, maintenance windows and spots out of service all count against it.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{ParkingError, ParkingLot, SpotType};
+
+/// Most slots one availability calendar will hold.
+pub const MAX_CALENDAR_SLOTS: usize = 10_000;
+
+/// A period during which one spot of `spot_type` is unavailable for booking.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpotHold {
+    pub spot_type: SpotType,
+    pub from: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+}
+
+impl SpotHold {
+    pub fn new(spot_type: SpotType, from: DateTime<Utc>, until: DateTime<Utc>) -> Self {
+        Self {
+            spot_type,
+            from,
+            until,
+        }
+    }
+
+    fn overlaps(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+        self.from < end && start < self.until
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AvailabilitySlot {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub reservable: u32,
+}
+
+impl ParkingLot {
+    pub(crate) fn spot_count(&self, spot_type: SpotType) -> Result<u32, ParkingError> {
+        Ok(self
+            .spot_counts(spot_type)?
+            .iter()
+            .map(|(_, count)| count)
+            .sum())
+    }
+
+    /// Spots of `spot_type` on each open floor, by floor id. Spots out of service are left
+    /// out, unless a maintenance window holds them: those are back once it ends.
+    fn spot_counts(&self, spot_type: SpotType) -> Result<Vec<(u32, u32)>, ParkingError> {
+        let floors = self.floors.lock()?;
+        let closed_floors = self.closed_floors.lock()?;
+        let maintained = self.spots_held_for_maintenance()?;
+        let mut counts = Vec::new();
+        for floor in floors
+            .values()
+            .filter(|floor| !closed_floors.contains(&floor.id))
+        {
+            let count = floor
+                .spots
+                .lock()?
+                .values()
+                .filter(|spot| spot.spot_type == spot_type)
+                .filter(|spot| {
+                    spot.out_of_service_reason().is_none()
+                        || maintained.contains(&(floor.id, spot.id.clone()))
+                })
+                .count() as u32;
+            counts.push((floor.id, count));
+        }
+        Ok(counts)
+    }
+
+    /// Blocks one spot of `hold.spot_type` from booking for the hold's period, e.g. for
+    /// arrangements made outside the system.
+    pub fn add_spot_hold(&self, hold: SpotHold) {
+        self.manual_holds.lock().unwrap().push(hold);
+    }
+
+    /// Drops manual holds that ended before `now`.
+    pub fn prune_spot_holds(&self, now: DateTime<Utc>) {
+        self.manual_holds.lock().unwrap().retain(|h| h.until > now);
+    }
+
+    /// Everything that takes a spot of `spot_type` out of the bookable pool: manual holds,
+    /// pending reservations, leased spots and the passes valid here.
+    fn spot_holds(&self, spot_type: SpotType) -> Result<Vec<SpotHold>, ParkingError> {
+        let mut holds = self.manual_holds.lock()?.clone();
+        holds.extend(self.reservation_holds()?);
+        holds.extend(self.lease_holds()?);
+        holds.retain(|h| h.spot_type == spot_type);
+        holds.extend(self.pass_holds(spot_type)?);
+        Ok(holds)
+    }
+
+    /// Splits `from..until` into `slot`-sized slots and reports how many spots of `spot_type`
+    /// are reservable in each. A hold counts against every slot it touches, and a floor's
+    /// spots are left out of every slot its maintenance windows touch, as booking does.
+    /// A range split into more than `MAX_CALENDAR_SLOTS` slots is refused.
+    pub fn availability_calendar(
+        &self,
+        spot_type: SpotType,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+        slot: Duration,
+    ) -> Result<Vec<AvailabilitySlot>, ParkingError> {
+        if slot <= Duration::zero() {
+            return Err(ParkingError::InvalidCalendarSlot);
+        }
+        if until <= from {
+            return Err(ParkingError::EmptyCalendarRange);
+        }
+        let covered = slot
+            .checked_mul(MAX_CALENDAR_SLOTS as i32)
+            .and_then(|span| from.checked_add_signed(span));
+        if covered.is_some_and(|end| end < until) {
+            return Err(ParkingError::TooManyCalendarSlots(MAX_CALENDAR_SLOTS));
+        }
+
+        let floors = self.spot_counts(spot_type)?;
+        let holds = self.spot_holds(spot_type)?;
+        let mut slots = Vec::new();
+        let mut start = from;
+        while start < until {
+            let end = (start + slot).min(until);
+            let capacity: u32 = floors
+                .iter()
+                .filter(|(floor_id, _)| !self.floor_under_maintenance(*floor_id, start, end))
+                .map(|(_, count)| count)
+                .sum();
+            let held = holds.iter().filter(|h| h.overlaps(start, end)).count() as u32;
+            slots.push(AvailabilitySlot {
+                start,
+                end,
+                reservable: capacity.saturating_sub(held),
+            });
+            start = end;
+        }
+        Ok(slots)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Account, ParkingFloor, User, Vehicle, VehicleType,
+        clock::MockClock,
+        pass::{PassPeriod, PassRegistry},
+    };
+
+    fn reservable(lot: &ParkingLot, from: DateTime<Utc>) -> Vec<u32> {
+        lot.availability_calendar(
+            SpotType::Regular,
+            from,
+            from + Duration::hours(3),
+            Duration::hours(1),
+        )
+        .unwrap()
+        .iter()
+        .map(|slot| slot.reservable)
+        .collect()
+    }
+
+    fn first_spot(lot: &ParkingLot, floor_id: u32) -> String {
+        let floor = lot.get_floor_by_id(floor_id).unwrap().unwrap();
+        let spots = floor.spots.lock().unwrap();
+        spots.keys().min().unwrap().clone()
+    }
+
+    #[test]
+    fn test_holds_reduce_reservable_spots_in_overlapping_slots() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+
+        let from = Utc::now();
+        lot.add_spot_hold(SpotHold::new(
+            SpotType::Regular,
+            from + Duration::minutes(30),
+            from + Duration::minutes(90),
+        ));
+
+        let slots = lot
+            .availability_calendar(
+                SpotType::Regular,
+                from,
+                from + Duration::hours(3),
+                Duration::hours(1),
+            )
+            .unwrap();
+
+        let reservable: Vec<u32> = slots.iter().map(|s| s.reservable).collect();
+        assert_eq!(reservable, vec![9, 9, 10]);
+    }
+
+    #[test]
+    fn test_passes_hold_a_spot_while_valid() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let registry = PassRegistry::default();
+        lot.set_pass_registry(registry.clone());
+        let mut user = User::new("Ada".into(), "123".into());
+        user.register_vehicle(Vehicle::new(
+            VehicleType::Motor,
+            "Kia".into(),
+            "PASS1".into(),
+        ));
+
+        let from = Utc::now();
+        let starts = from + Duration::hours(1);
+        registry
+            .purchase(&mut user, "PASS1", PassPeriod::Weekly, None, starts, 20.0)
+            .unwrap();
+        registry
+            .purchase(
+                &mut user,
+                "PASS1",
+                PassPeriod::Weekly,
+                Some("elsewhere".into()),
+                from,
+                20.0,
+            )
+            .unwrap();
+
+        assert_eq!(reservable(&lot, from), vec![10, 9, 9]);
+    }
+
+    #[test]
+    fn test_leased_spots_are_held_until_the_lease_ends() {
+        let from = Utc::now();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(MockClock::new(from)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let spot_id = first_spot(&lot, 1);
+
+        lot.lease_spots(
+            "CarShare".into(),
+            &[(1, &spot_id)],
+            from + Duration::hours(1),
+            from + Duration::hours(2),
+            15.0,
+        )
+        .unwrap();
+
+        assert_eq!(reservable(&lot, from), vec![9, 9, 10]);
+    }
+
+    #[test]
+    fn test_spots_out_of_service_are_not_reservable() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let spot_id = first_spot(&lot, 1);
+
+        lot.set_out_of_service(1, &spot_id, Some("Broken barrier".into()))
+            .unwrap();
+
+        assert_eq!(lot.spot_count(SpotType::Regular).unwrap(), 9);
+        assert_eq!(reservable(&lot, Utc::now()), vec![9, 9, 9]);
+    }
+
+    #[test]
+    fn test_calendar_refuses_empty_ranges_bad_slots_and_too_many_slots() {
+        let lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        let from = Utc::now();
+        let calendar =
+            |until, slot| lot.availability_calendar(SpotType::Regular, from, until, slot);
+
+        assert_eq!(
+            calendar(from + Duration::hours(1), Duration::zero()),
+            Err(ParkingError::InvalidCalendarSlot)
+        );
+        assert_eq!(
+            calendar(from, Duration::hours(1)),
+            Err(ParkingError::EmptyCalendarRange)
+        );
+        let year = from + Duration::days(365);
+        assert_eq!(
+            calendar(year, Duration::minutes(1)),
+            Err(ParkingError::TooManyCalendarSlots(MAX_CALENDAR_SLOTS))
+        );
+        assert_eq!(
+            calendar(
+                from + Duration::nanoseconds(MAX_CALENDAR_SLOTS as i64 + 1),
+                Duration::nanoseconds(1)
+            ),
+            Err(ParkingError::TooManyCalendarSlots(MAX_CALENDAR_SLOTS))
+        );
+        assert_eq!(calendar(year, Duration::hours(1)).unwrap().len(), 365 * 24);
+    }
+}
@@ -0,0 +1,285 @@
+//! Occupancy caps below the lot's physical size: at most so many vehicles on a floor, or
+//! parked on spots of a given type across the lot. A full lot turns vehicles away at the
+//! entrance, before any spot search.
+//!
+//! Vehicle types can be capped too, e.g. at most 10 trucks for traffic flow, whatever spots
+//! are free. Those caps are checked by the `VehicleTypeCaps` entry policy, and the lot
+//! counts how often each one turns a vehicle away.
+
+use std::collections::HashMap;
+
+use crate::{
+    ParkingLot, ParkingSpot, SpotType, VehicleType,
+    allocation::{SpotCandidate, spot_candidates},
+    units::Dimensions,
+};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OccupancyLimits {
+    floors: HashMap<u32, u32>,
+    spot_types: HashMap<SpotType, u32>,
+    vehicle_types: HashMap<VehicleType, u32>,
+}
+
+impl OccupancyLimits {
+    /// Caps the vehicles parked on floor `floor_id`.
+    pub fn with_floor_capacity(mut self, floor_id: u32, capacity: u32) -> Self {
+        self.floors.insert(floor_id, capacity);
+        self
+    }
+
+    /// Caps the vehicles parked on `spot_type` spots, over all floors.
+    pub fn with_spot_type_capacity(mut self, spot_type: SpotType, capacity: u32) -> Self {
+        self.spot_types.insert(spot_type, capacity);
+        self
+    }
+
+    /// Caps the `vehicle_type` vehicles in the lot at once, on any spot.
+    pub fn with_vehicle_type_cap(mut self, vehicle_type: VehicleType, cap: u32) -> Self {
+        self.vehicle_types.insert(vehicle_type, cap);
+        self
+    }
+
+    pub fn floor_capacity(&self, floor_id: u32) -> Option<u32> {
+        self.floors.get(&floor_id).copied()
+    }
+
+    pub fn spot_type_capacity(&self, spot_type: SpotType) -> Option<u32> {
+        self.spot_types.get(&spot_type).copied()
+    }
+
+    pub fn vehicle_type_cap(&self, vehicle_type: &VehicleType) -> Option<u32> {
+        self.vehicle_types.get(vehicle_type).copied()
+    }
+
+    /// Whether one more vehicle may take `candidate` given the current `occupancy`.
+    pub(crate) fn admits(&self, candidate: &SpotCandidate, occupancy: &Occupancy) -> bool {
+        let below = |cap: Option<u32>, count: Option<&u32>| {
+            cap.is_none_or(|cap| count.copied().unwrap_or(0) < cap)
+        };
+        below(
+            self.floor_capacity(candidate.floor_id),
+            occupancy.floors.get(&candidate.floor_id),
+        ) && below(
+            self.spot_type_capacity(candidate.spot_type),
+            occupancy.spot_types.get(&candidate.spot_type),
+        )
+    }
+}
+
+/// Parked vehicles per floor and per spot type.
+#[derive(Debug, Default)]
+pub(crate) struct Occupancy {
+    floors: HashMap<u32, u32>,
+    spot_types: HashMap<SpotType, u32>,
+}
+
+impl Occupancy {
+    pub(crate) fn count<'a>(
+        floors: impl Iterator<Item = (u32, &'a HashMap<String, ParkingSpot>)>,
+    ) -> Self {
+        let mut occupancy = Self::default();
+        for (floor_id, spots) in floors {
+            for spot in spots.values().filter(|s| s.is_occupied()) {
+                *occupancy.floors.entry(floor_id).or_insert(0) += 1;
+                *occupancy.spot_types.entry(spot.spot_type).or_insert(0) += 1;
+            }
+        }
+        occupancy
+    }
+}
+
+/// How close a vehicle type is to its cap, and how often the cap has turned vehicles away.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VehicleTypeCapUsage {
+    pub vehicle_type: VehicleType,
+    pub cap: u32,
+    pub parked: u32,
+    pub refusals: u32,
+}
+
+impl ParkingLot {
+    pub fn set_occupancy_limits(&mut self, limits: OccupancyLimits) {
+        self.occupancy_limits = limits;
+    }
+
+    pub fn occupancy_limits(&self) -> &OccupancyLimits {
+        &self.occupancy_limits
+    }
+
+    /// Every capped vehicle type, in `VehicleType::ALL` order.
+    pub fn vehicle_type_cap_usage(&self) -> Vec<VehicleTypeCapUsage> {
+        let refusals = self.vehicle_type_cap_refusals.lock().unwrap();
+        VehicleType::ALL
+            .iter()
+            .filter_map(|vehicle_type| {
+                Some(VehicleTypeCapUsage {
+                    vehicle_type: vehicle_type.clone(),
+                    cap: self.occupancy_limits.vehicle_type_cap(vehicle_type)?,
+                    parked: self.parked_of_type(vehicle_type),
+                    refusals: refusals.get(vehicle_type).copied().unwrap_or(0),
+                })
+            })
+            .collect()
+    }
+
+    /// Vehicles of `vehicle_type` holding open tickets.
+    pub(crate) fn parked_of_type(&self, vehicle_type: &VehicleType) -> u32 {
+        self.active_tickets
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|t| &t.vehicle.vehicle_type == vehicle_type)
+            .count() as u32
+    }
+
+    pub(crate) fn record_cap_refusal(&self, vehicle_type: &VehicleType) {
+        *self
+            .vehicle_type_cap_refusals
+            .lock()
+            .unwrap()
+            .entry(vehicle_type.clone())
+            .or_insert(0) += 1;
+    }
+
+    /// Occupied share of all spots, from 0 to 1.
+    pub fn occupancy_ratio(&self) -> f32 {
+        let report = self.occupancy_report();
+        if report.spots == 0 {
+            0.0
+        } else {
+            report.occupied as f32 / report.spots as f32
+        }
+    }
+
+    /// No vehicle of any type can be admitted.
+    pub fn is_full(&self) -> bool {
+        VehicleType::ALL.iter().all(|t| self.is_full_for(t))
+    }
+
+    /// No open floor has a free spot within the occupancy limits for a `vehicle_type`
+    /// vehicle without a handicapped permit.
+    pub fn is_full_for(&self, vehicle_type: &VehicleType) -> bool {
+        !self.has_room_for(vehicle_type, false, None)
+    }
+
+    /// Whether an open floor has a general (unzoned) spot within the limits for the vehicle.
+    pub(crate) fn has_room_for(
+        &self,
+        vehicle_type: &VehicleType,
+        handicapped_permit: bool,
+        dimensions: Option<&Dimensions>,
+    ) -> bool {
+        let now = self.now();
+        let floors = self.floors.lock().unwrap();
+        let closed_floors = self.closed_floors.lock().unwrap();
+        let mut floor_ids: Vec<u32> = floors.keys().copied().collect();
+        floor_ids.sort_unstable();
+        let locked: Vec<_> = floor_ids
+            .iter()
+            .map(|id| (*id, floors[id].spots.lock().unwrap()))
+            .collect();
+        let occupancy = Occupancy::count(locked.iter().map(|(id, spots)| (*id, &**spots)));
+        spot_candidates(
+            locked
+                .iter()
+                .filter(|(id, _)| {
+                    !closed_floors.contains(id) && self.schedule.is_floor_open(*id, now)
+                })
+                .map(|(id, spots)| (*id, &**spots)),
+            None,
+            vehicle_type,
+            handicapped_permit,
+            dimensions,
+            &[],
+            |_, _| true,
+        )
+        .iter()
+        .any(|candidate| self.occupancy_limits.admits(candidate, &occupancy))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, Vehicle, error::ParkingError, events::ParkingEvent};
+
+    #[test]
+    fn test_capacity_limits_turn_vehicles_away_early() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.add_floor(ParkingFloor::new(2)).unwrap();
+        lot.add_spot(2, ParkingSpot::new(true, SpotType::Large))
+            .unwrap();
+        lot.set_occupancy_limits(
+            OccupancyLimits::default()
+                .with_floor_capacity(1, 1)
+                .with_spot_type_capacity(SpotType::Regular, 2),
+        );
+        let events = lot.events();
+
+        let car = |plate: &str| Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into());
+        lot.park_vehicle(car("CAR1")).unwrap();
+        lot.park_vehicle(car("CAR2")).unwrap();
+        // Regular spots are capped, so the third car takes the large spot
+        let third = lot.park_vehicle(car("CAR3")).unwrap();
+        assert_eq!(third.spot_type, Some(SpotType::Large));
+        assert!(lot.is_full_for(&VehicleType::Motor));
+        assert!(lot.is_full());
+
+        assert!(matches!(
+            lot.park_vehicle(car("CAR4")),
+            Err(ParkingError::LotFull)
+        ));
+        assert!(
+            events
+                .try_iter()
+                .any(|e| matches!(e, ParkingEvent::LotFull { .. }))
+        );
+
+        lot.unpark_vehicle(third.ticket_id).unwrap();
+        assert!(!lot.is_full_for(&VehicleType::Truck));
+    }
+
+    #[test]
+    fn test_vehicle_type_caps_refuse_entry_and_count_refusals() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        for _ in 0..3 {
+            lot.add_spot(1, ParkingSpot::new(true, SpotType::Large))
+                .unwrap();
+        }
+        lot.set_occupancy_limits(
+            OccupancyLimits::default().with_vehicle_type_cap(VehicleType::Truck, 2),
+        );
+
+        let truck = |plate: &str| Vehicle::new(VehicleType::Truck, "Volvo".into(), plate.into());
+        let first = lot.park_vehicle(truck("TRK1")).unwrap();
+        lot.park_vehicle(truck("TRK2")).unwrap();
+        assert!(lot.can_admit(&Vehicle::new(
+            VehicleType::Motor,
+            "Kia".into(),
+            "CAR1".into()
+        )));
+        assert!(!lot.can_admit(&truck("TRK3")));
+        for _ in 0..2 {
+            assert_eq!(
+                lot.park_vehicle(truck("TRK3")).unwrap_err(),
+                ParkingError::VehicleTypeCapReached(VehicleType::Truck)
+            );
+        }
+
+        let usage = lot.vehicle_type_cap_usage();
+        assert_eq!(
+            usage,
+            vec![VehicleTypeCapUsage {
+                vehicle_type: VehicleType::Truck,
+                cap: 2,
+                parked: 2,
+                refusals: 2,
+            }]
+        );
+        lot.unpark_vehicle(first.ticket_id).unwrap();
+        lot.park_vehicle(truck("TRK3")).unwrap();
+    }
+}
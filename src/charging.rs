@@ -0,0 +1,213 @@
+//! EV charging at electric spots. A session belongs to a ticket; the charger reports the
+//! energy it delivers and the energy is billed per kWh on top of the parking charge.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ParkingLot, SpotType,
+    error::ParkingError,
+    pricing::{ChargeKind, ChargeLine},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChargingSession {
+    pub ticket_id: String,
+    pub spot_id: String,
+    pub started_at: DateTime<Utc>,
+    pub stopped_at: Option<DateTime<Utc>>,
+    pub energy_kwh: f32,
+}
+
+impl ChargingSession {
+    pub fn is_active(&self) -> bool {
+        self.stopped_at.is_none()
+    }
+}
+
+impl ParkingLot {
+    /// Price per kWh delivered. Charging is free until a rate is set.
+    pub fn set_charging_rate(&mut self, per_kwh: f32) {
+        self.charging_rate = per_kwh;
+    }
+
+    /// Starts (or resumes) charging for a vehicle parked on an electric spot.
+    pub fn start_charging(&self, ticket_id: &str) -> Result<ChargingSession, ParkingError> {
+        let ticket_id = self.canonical_ticket_id(ticket_id);
+        let (floor_id, spot_id) = {
+            let tickets = self.active_tickets.lock()?;
+            let ticket = tickets.get(&ticket_id).ok_or(ParkingError::InvalidTicket)?;
+            if ticket.exit_time.is_some() {
+                return Err(ParkingError::TicketClosed);
+            }
+            (ticket.floor_id, ticket.spot_id.clone())
+        };
+        // Spot ids repeat across floors, so only the ticket's own floor counts
+        let spot_type = match floor_id {
+            Some(floor_id) => {
+                self.with_floor_spot_mut(floor_id, &spot_id, |spot| spot.spot_type)?
+            }
+            None => None,
+        };
+        if spot_type != Some(SpotType::Electric) {
+            return Err(ParkingError::ChargingUnavailable);
+        }
+
+        let mut sessions = self.charging_sessions.lock()?;
+        let session = sessions
+            .entry(ticket_id.clone())
+            .or_insert_with(|| ChargingSession {
+                ticket_id,
+                spot_id,
+                started_at: self.now(),
+                stopped_at: None,
+                energy_kwh: 0.0,
+            });
+        session.stopped_at = None;
+        Ok(session.clone())
+    }
+
+    /// Adds a meter reading of `kwh` delivered since the previous one.
+    pub fn record_charging_energy(&self, ticket_id: &str, kwh: f32) -> Result<(), ParkingError> {
+        let ticket_id = self.canonical_ticket_id(ticket_id);
+        let mut sessions = self.charging_sessions.lock()?;
+        let session = sessions
+            .get_mut(&ticket_id)
+            .filter(|s| s.is_active())
+            .ok_or(ParkingError::NoChargingSession)?;
+        session.energy_kwh += kwh.max(0.0);
+        Ok(())
+    }
+
+    pub fn stop_charging(&self, ticket_id: &str) -> Result<ChargingSession, ParkingError> {
+        let ticket_id = self.canonical_ticket_id(ticket_id);
+        let mut sessions = self.charging_sessions.lock()?;
+        let session = sessions
+            .get_mut(&ticket_id)
+            .filter(|s| s.is_active())
+            .ok_or(ParkingError::NoChargingSession)?;
+        session.stopped_at = Some(self.now());
+        Ok(session.clone())
+    }
+
+    pub fn charging_session(&self, ticket_id: &str) -> Option<ChargingSession> {
+        self.charging_sessions
+            .lock()
+            .unwrap()
+            .get(&self.canonical_ticket_id(ticket_id))
+            .cloned()
+    }
+
+    /// Energy line for the ticket's bill, if it has charged at all.
+    pub(crate) fn charging_line(&self, ticket_id: &str) -> Option<ChargeLine> {
+        let sessions = self.charging_sessions.lock().unwrap();
+        let session = sessions.get(ticket_id)?;
+        Some(
+            ChargeLine::new(
+                format!("EV charging ({:.1} kWh)", session.energy_kwh),
+                session.energy_kwh * self.charging_rate,
+            )
+            .with_kind(ChargeKind::Energy),
+        )
+    }
+
+    /// Ends a session still running when its stay is settled.
+    pub(crate) fn stop_charging_at(
+        &self,
+        ticket_id: &str,
+        at: DateTime<Utc>,
+    ) -> Result<(), ParkingError> {
+        if let Some(session) = self.charging_sessions.lock()?.get_mut(ticket_id) {
+            session.stopped_at.get_or_insert(at);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, ParkingSpot, Vehicle, VehicleType};
+
+    #[test]
+    fn test_energy_is_billed_with_the_stay() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.add_spot(1, ParkingSpot::new(true, SpotType::Electric))
+            .unwrap();
+        lot.set_charging_rate(0.5);
+
+        let petrol = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "AAA111".into(),
+            ))
+            .unwrap();
+        assert_eq!(
+            lot.start_charging(&petrol.ticket_id),
+            Err(ParkingError::ChargingUnavailable)
+        );
+
+        let ev = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Electric,
+                "Leaf".into(),
+                "EV0001".into(),
+            ))
+            .unwrap();
+        lot.start_charging(&ev.ticket_id).unwrap();
+        lot.record_charging_energy(&ev.ticket_id, 12.0).unwrap();
+
+        let charge = lot.unpark_vehicle(ev.ticket_id.clone()).unwrap();
+        assert_eq!(charge.total, 6.0);
+        assert_eq!(charge.breakdown.last().unwrap().amount, 6.0);
+        assert!(!lot.charging_session(&ev.ticket_id).unwrap().is_active());
+    }
+
+    #[test]
+    fn test_charging_checks_the_spot_on_the_tickets_floor() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.add_floor(ParkingFloor::new(2)).unwrap();
+        // spot_10 is electric on floor 1 only
+        let spot = |spot_type| {
+            let mut spot = ParkingSpot::new(true, spot_type);
+            spot.id = "spot_10".into();
+            spot
+        };
+        lot.add_spot(1, spot(SpotType::Electric)).unwrap();
+        lot.add_spot(2, spot(SpotType::Regular)).unwrap();
+        lot.close_floor(1).unwrap();
+
+        let park = |plate: String| {
+            lot.park_vehicle(Vehicle::new(VehicleType::Motor, "Kia".into(), plate))
+                .unwrap()
+        };
+        let regular = (0..11)
+            .map(|i| park(format!("FLR{i:03}")))
+            .find(|ticket| ticket.spot_id == "spot_10")
+            .unwrap();
+        assert_eq!(regular.floor_id, Some(2));
+        assert_eq!(
+            lot.start_charging(&regular.ticket_id),
+            Err(ParkingError::ChargingUnavailable)
+        );
+
+        lot.reopen_floor(1).unwrap();
+        let ev = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Electric,
+                "Leaf".into(),
+                "EV0002".into(),
+            ))
+            .unwrap();
+        assert_eq!((ev.floor_id, ev.spot_id.as_str()), (Some(1), "spot_10"));
+        // Ids read off a ticket are taken in any case, as at the other entry points
+        let typed = ev.ticket_id.to_uppercase();
+        lot.start_charging(&typed).unwrap();
+        lot.record_charging_energy(&typed, 4.0).unwrap();
+        let session = lot.stop_charging(&typed).unwrap();
+        assert_eq!((session.ticket_id, session.energy_kwh), (ev.ticket_id, 4.0));
+    }
+}
@@ -0,0 +1,118 @@
+//! Where the lot gets the current time from. Entry and exit times, billing, reservation
+//! and lease windows all read the lot's clock, so tests can swap in a `MockClock` and
+//! move time forward instead of sleeping.
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::ParkingLot;
+
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system's UTC time. The default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time, so a test can keep
+/// one and hand another to the lot.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<DateTime<Utc>>>,
+}
+
+impl MockClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+
+    pub fn set(&self, at: DateTime<Utc>) {
+        *self.now.lock().unwrap() = at;
+    }
+}
+
+impl Default for MockClock {
+    /// Starts at the current system time.
+    fn default() -> Self {
+        Self::new(Utc::now())
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
+
+impl ParkingLot {
+    pub fn with_clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Current time on the lot's clock.
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Parkable, ParkingFloor, Vehicle, VehicleType, error::ParkingError, pricing::FlatHourly,
+    };
+
+    #[test]
+    fn test_billing_follows_the_mock_clock() {
+        let clock = MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_pricing_strategy(Box::new(FlatHourly::new(10.0)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "AAA111".into());
+        let ticket = lot.park_vehicle(car).unwrap();
+        assert_eq!(ticket.entry_time, clock.now());
+
+        clock.advance(Duration::minutes(59));
+        assert_eq!(lot.estimate_charge(&ticket.ticket_id).unwrap().total, 0.0);
+        clock.advance(Duration::hours(2) + Duration::minutes(1));
+        let charge = lot.unpark_vehicle(ticket.ticket_id.clone()).unwrap();
+        assert_eq!(charge.total, 30.0);
+        assert_eq!(charge.duration(), Duration::hours(3));
+
+        // Reservations lapse on the lot's clock as well
+        let guest = Vehicle::new(VehicleType::Motor, "Kia".into(), "GUEST1".into());
+        let now = clock.now();
+        let reservation = lot
+            .reserve_spot(guest, now, now + Duration::hours(1))
+            .unwrap();
+        clock.advance(Duration::hours(2));
+        assert!(matches!(
+            lot.check_in_reservation(&reservation.reservation_id),
+            Err(ParkingError::ReservationExpired)
+        ));
+    }
+}
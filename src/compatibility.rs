@@ -0,0 +1,152 @@
+//! Which vehicle types fit which spot types. A `CompatibilityPolicy` is a table of allowed
+//! (vehicle type, spot type) pairs, written out rule by rule or derived from sizes: a
+//! vehicle fits every spot at least as big as it is.
+//!
+//! The lot hands its policy to every floor and spot, so `assign_vehicle` and spot search
+//! agree on what fits. Handicapped spots additionally need a permit, see
+//! `ParkingSpot::accepts`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{ParkingLot, SpotType, VehicleType};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatibilityPolicy {
+    allowed: HashSet<(VehicleType, SpotType)>,
+}
+
+impl Default for CompatibilityPolicy {
+    /// Cars and bikes fit regular and larger spots, trucks need a large one and electric
+    /// vehicles fit anywhere a car does plus charging spots. Handicapped spots take
+    /// anything but trucks.
+    fn default() -> Self {
+        use SpotType::*;
+        let mut policy = Self::empty();
+        for vehicle_type in [VehicleType::Motor, VehicleType::Bike] {
+            for spot_type in [Regular, Large, XLarge, Handicapped] {
+                policy = policy.allow(vehicle_type.clone(), spot_type);
+            }
+        }
+        for spot_type in [Regular, Large, XLarge, Handicapped, Electric] {
+            policy = policy.allow(VehicleType::Electric, spot_type);
+        }
+        policy
+            .allow(VehicleType::Truck, Large)
+            .allow(VehicleType::Truck, XLarge)
+    }
+}
+
+impl CompatibilityPolicy {
+    /// A policy under which nothing fits anywhere, to build up with `allow`.
+    pub fn empty() -> Self {
+        Self {
+            allowed: HashSet::new(),
+        }
+    }
+
+    /// Each vehicle type fits every spot type whose size is at least its own. Types left
+    /// out of either list fit nowhere.
+    pub fn by_size(vehicles: &[(VehicleType, u32)], spots: &[(SpotType, u32)]) -> Self {
+        let spot_sizes: HashMap<SpotType, u32> = spots.iter().copied().collect();
+        let mut policy = Self::empty();
+        for (vehicle_type, vehicle_size) in vehicles {
+            for (spot_type, spot_size) in &spot_sizes {
+                if spot_size >= vehicle_size {
+                    policy = policy.allow(vehicle_type.clone(), *spot_type);
+                }
+            }
+        }
+        policy
+    }
+
+    pub fn allow(mut self, vehicle_type: VehicleType, spot_type: SpotType) -> Self {
+        self.allowed.insert((vehicle_type, spot_type));
+        self
+    }
+
+    pub fn forbid(mut self, vehicle_type: VehicleType, spot_type: SpotType) -> Self {
+        self.allowed.remove(&(vehicle_type, spot_type));
+        self
+    }
+
+    pub fn allows(&self, vehicle_type: &VehicleType, spot_type: SpotType) -> bool {
+        self.allowed.contains(&(vehicle_type.clone(), spot_type))
+    }
+}
+
+impl ParkingLot {
+    pub fn with_compatibility_policy(mut self, policy: CompatibilityPolicy) -> Self {
+        self.set_compatibility_policy(policy);
+        self
+    }
+
+    /// Applies `policy` to every floor and spot, including vehicles arriving at spots
+    /// already claimed for them. Vehicles already parked stay where they are.
+    pub fn set_compatibility_policy(&mut self, policy: CompatibilityPolicy) {
+        self.compatibility = Arc::new(policy);
+        for floor in self.floors.lock().unwrap().values() {
+            floor.set_compatibility(&self.compatibility);
+        }
+    }
+
+    pub fn compatibility_policy(&self) -> &CompatibilityPolicy {
+        &self.compatibility
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, ParkingSpot, Vehicle, error::ParkingError};
+
+    #[test]
+    fn test_lot_policy_governs_search_and_assignment() {
+        let default = CompatibilityPolicy::default();
+        for vehicle_type in VehicleType::ALL {
+            for spot_type in SpotType::ALL {
+                let spot = ParkingSpot::new(true, spot_type);
+                assert_eq!(
+                    spot.is_compatible(&vehicle_type),
+                    spot_type != SpotType::Handicapped && default.allows(&vehicle_type, spot_type)
+                );
+            }
+        }
+
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let truck = |plate: &str| Vehicle::new(VehicleType::Truck, "Iveco".into(), plate.into());
+        assert_eq!(
+            lot.park_vehicle(truck("TRK001")).unwrap_err(),
+            ParkingError::LotFull
+        );
+
+        // Trucks are small enough for regular spots here
+        lot.set_compatibility_policy(CompatibilityPolicy::by_size(
+            &[(VehicleType::Motor, 1), (VehicleType::Truck, 2)],
+            &[(SpotType::Regular, 2), (SpotType::Large, 3)],
+        ));
+        assert!(
+            lot.compatibility_policy()
+                .allows(&VehicleType::Truck, SpotType::Large)
+        );
+        assert!(
+            !lot.compatibility_policy()
+                .allows(&VehicleType::Bike, SpotType::Large)
+        );
+        let ticket = lot.park_vehicle(truck("TRK001")).unwrap();
+        assert_eq!(lot.display_info().unwrap().num_parked_vehicles(), 1);
+
+        let floor = lot.get_floor_by_id(1).unwrap().unwrap();
+        let mut spots = floor.spots.lock().unwrap();
+        let spot = spots.get_mut(&ticket.spot_id).unwrap();
+        spot.remove_vehicle();
+        let bike = Vehicle::new(VehicleType::Bike, "Honda".into(), "BKE001".into());
+        assert_eq!(
+            spot.assign_vehicle(bike),
+            Err(ParkingError::IncompatibleVehicle)
+        );
+    }
+}
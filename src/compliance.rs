@@ -0,0 +1,221 @@
+//! Handicapped-spot compliance reporting for ADA-style audits.
+
+use crate::{ParkingLot, SpotType, error::ParkingError};
+
+/// Minimum accessible spots for a facility with `total_spots` spaces, per the ADA
+/// Standards for Accessible Design (section 208.2).
+pub fn ada_required_accessible(total_spots: u32) -> u32 {
+    match total_spots {
+        0 => 0,
+        1..=25 => 1,
+        26..=50 => 2,
+        51..=75 => 3,
+        76..=100 => 4,
+        101..=150 => 5,
+        151..=200 => 6,
+        201..=300 => 7,
+        301..=400 => 8,
+        401..=500 => 9,
+        501..=1000 => total_spots.div_ceil(50),
+        _ => 20 + (total_spots - 1000).div_ceil(100),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloorCompliance {
+    pub floor_id: u32,
+    pub total_spots: u32,
+    pub handicapped_spots: u32,
+    pub occupied_handicapped_spots: u32,
+}
+
+/// A handicapped spot occupied by a vehicle that isn't permitted to use it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComplianceViolation {
+    pub floor_id: u32,
+    pub spot_id: String,
+    pub license_plate: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandicappedComplianceReport {
+    pub floors: Vec<FloorCompliance>,
+    pub required_handicapped_spots: u32,
+    pub violations: Vec<ComplianceViolation>,
+}
+
+impl HandicappedComplianceReport {
+    pub fn total_spots(&self) -> u32 {
+        self.floors.iter().map(|f| f.total_spots).sum()
+    }
+
+    pub fn handicapped_spots(&self) -> u32 {
+        self.floors.iter().map(|f| f.handicapped_spots).sum()
+    }
+
+    pub fn occupied_handicapped_spots(&self) -> u32 {
+        self.floors
+            .iter()
+            .map(|f| f.occupied_handicapped_spots)
+            .sum()
+    }
+
+    /// Share of handicapped spots currently occupied, between 0 and 1.
+    pub fn utilization(&self) -> f32 {
+        match self.handicapped_spots() {
+            0 => 0.0,
+            total => self.occupied_handicapped_spots() as f32 / total as f32,
+        }
+    }
+
+    pub fn meets_quota(&self) -> bool {
+        self.handicapped_spots() >= self.required_handicapped_spots
+    }
+
+    pub fn is_compliant(&self) -> bool {
+        self.meets_quota() && self.violations.is_empty()
+    }
+}
+
+impl ParkingLot {
+    pub fn handicapped_compliance_report(
+        &self,
+    ) -> Result<HandicappedComplianceReport, ParkingError> {
+        let floors = self.floors.lock()?;
+        let mut floor_ids: Vec<u32> = floors.keys().copied().collect();
+        floor_ids.sort_unstable();
+
+        let mut report = HandicappedComplianceReport {
+            floors: Vec::with_capacity(floor_ids.len()),
+            required_handicapped_spots: 0,
+            violations: Vec::new(),
+        };
+
+        for floor_id in floor_ids {
+            let spots = floors[&floor_id].spots.lock()?;
+            let mut floor = FloorCompliance {
+                floor_id,
+                total_spots: spots.len() as u32,
+                handicapped_spots: 0,
+                occupied_handicapped_spots: 0,
+            };
+
+            let mut spot_ids: Vec<&String> = spots.keys().collect();
+            spot_ids.sort();
+            for spot_id in spot_ids {
+                let spot = &spots[spot_id];
+                if !matches!(spot.spot_type, SpotType::Handicapped) {
+                    continue;
+                }
+                floor.handicapped_spots += 1;
+                if let Some(vehicle) = &spot.vehicle {
+                    floor.occupied_handicapped_spots += 1;
+                    if !spot.accepts(vehicle) {
+                        report.violations.push(ComplianceViolation {
+                            floor_id,
+                            spot_id: spot_id.clone(),
+                            license_plate: vehicle.license_plate.clone(),
+                        });
+                    }
+                }
+            }
+            report.floors.push(floor);
+        }
+
+        report.required_handicapped_spots = ada_required_accessible(report.total_spots());
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParkingFloor, ParkingSpot, SpotStatus, Vehicle, VehicleType};
+
+    #[test]
+    fn test_ada_quota_table_boundaries() {
+        assert_eq!(ada_required_accessible(25), 1);
+        assert_eq!(ada_required_accessible(26), 2);
+        assert_eq!(ada_required_accessible(500), 9);
+        assert_eq!(ada_required_accessible(501), 11);
+        assert_eq!(ada_required_accessible(1000), 20);
+        assert_eq!(ada_required_accessible(1001), 21);
+    }
+
+    #[test]
+    fn test_report_counts_utilization_and_lists_unpermitted_vehicles() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        for n in 1..=4 {
+            let mut spot = ParkingSpot::new(true, SpotType::Handicapped);
+            spot.id = format!("hc_{n}");
+            lot.add_spot(1, spot).unwrap();
+        }
+        let car = |plate: &str| Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into());
+        {
+            let floor = lot.get_floor_by_id(1).unwrap().unwrap();
+            let mut spots = floor.spots.lock().unwrap();
+            spots
+                .get_mut("hc_1")
+                .unwrap()
+                .assign_vehicle(car("PRM001").with_handicapped_permit())
+                .unwrap();
+            // Entry refuses these, so put them there as a vehicle left on the spot would be
+            for (spot_id, plate) in [("hc_2", "VIO001"), ("hc_3", "VIO002")] {
+                let spot = spots.get_mut(spot_id).unwrap();
+                spot.vehicle = Some(car(plate));
+                spot.status = SpotStatus::Occupied;
+            }
+        }
+
+        let report = lot.handicapped_compliance_report().unwrap();
+        assert_eq!(
+            report.floors,
+            vec![FloorCompliance {
+                floor_id: 1,
+                total_spots: 14,
+                handicapped_spots: 4,
+                occupied_handicapped_spots: 3,
+            }]
+        );
+        assert_eq!(report.utilization(), 0.75);
+        assert_eq!(report.required_handicapped_spots, 1);
+        assert!(report.meets_quota());
+        assert_eq!(
+            report.violations,
+            vec![
+                ComplianceViolation {
+                    floor_id: 1,
+                    spot_id: "hc_2".into(),
+                    license_plate: "VIO001".into(),
+                },
+                ComplianceViolation {
+                    floor_id: 1,
+                    spot_id: "hc_3".into(),
+                    license_plate: "VIO002".into(),
+                },
+            ]
+        );
+        assert!(!report.is_compliant());
+    }
+
+    #[test]
+    fn test_report_on_a_poisoned_floor_is_lock_poisoned() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let floor = lot.get_floor_by_id(1).unwrap().unwrap();
+        let _ = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let _spots = floor.spots.lock().unwrap();
+                    panic!("poison the floor");
+                })
+                .join()
+        });
+
+        assert_eq!(
+            lot.handicapped_compliance_report(),
+            Err(ParkingError::LockPoisoned)
+        );
+    }
+}
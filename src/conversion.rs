@@ -0,0 +1,164 @@
+//! Spot conversions, e.g. turning a regular spot into an EV or handicapped one. Every
+//! conversion is kept so the lot's inventory history can be traced. Tickets record the
+//! spot type they were parked on, so usage reports keep attributing past stays to the
+//! type the spot had at the time.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ParkingLot, SpotType, error::ParkingError, events::InventoryChange, quota::QuotaWarning,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpotConversion {
+    pub floor_id: u32,
+    pub spot_id: String,
+    pub from: SpotType,
+    pub to: SpotType,
+    pub at: DateTime<Utc>,
+}
+
+/// Closed stays on one spot type and what they were billed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpotTypeUsage {
+    pub spot_type: SpotType,
+    pub stays: u32,
+    pub revenue: f32,
+}
+
+impl ParkingLot {
+    /// Changes the type of a free spot that isn't reserved or leased. The floor's quota is
+    /// checked as for `add_spot`.
+    pub fn convert_spot(
+        &self,
+        floor_id: u32,
+        spot_id: &str,
+        spot_type: SpotType,
+    ) -> Result<Vec<QuotaWarning>, ParkingError> {
+        let (from, warnings) = self.with_floor_mut(floor_id, |floor| {
+            let from = floor
+                .spots
+                .lock()?
+                .get(spot_id)
+                .ok_or(ParkingError::SpotNotFound)?
+                .spot_type;
+            Ok((from, floor.convert_spot(spot_id, spot_type)?))
+        })?;
+        self.spot_conversions.lock()?.push(SpotConversion {
+            floor_id,
+            spot_id: spot_id.to_string(),
+            from,
+            to: spot_type,
+            at: self.now(),
+        });
+        let spot_id = spot_id.to_string();
+        self.emit_inventory_change(
+            floor_id,
+            InventoryChange::SpotConverted { spot_id, spot_type },
+        );
+        Ok(warnings)
+    }
+
+    /// Every conversion so far, oldest first.
+    pub fn spot_conversions(&self) -> Vec<SpotConversion> {
+        self.spot_conversions.lock().unwrap().clone()
+    }
+
+    /// Stays that exited in `[start, end)`, grouped by the spot type they were parked on.
+    pub fn usage_by_spot_type(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<SpotTypeUsage>, ParkingError> {
+        let mut usage: HashMap<SpotType, SpotTypeUsage> = HashMap::new();
+        for entry in self.ticket_history.tickets_between(start, end)? {
+            let Some(spot_type) = entry.ticket.spot_type else {
+                continue;
+            };
+            let row = usage.entry(spot_type).or_insert(SpotTypeUsage {
+                spot_type,
+                stays: 0,
+                revenue: 0.0,
+            });
+            row.stays += 1;
+            row.revenue += entry.total;
+        }
+        Ok(SpotType::ALL
+            .iter()
+            .filter_map(|spot_type| usage.remove(spot_type))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Parkable, ParkingFloor, Vehicle, VehicleType,
+        admin::{Admin, LotAdministration},
+        audit::AuditAction,
+    };
+    use chrono::Duration;
+
+    #[test]
+    fn test_converted_spot_keeps_past_usage_on_old_type() {
+        let admin = Admin::new("Bola".into(), "ST-7".into());
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let spot_ids: Vec<String> = {
+            let floor = lot.get_floor_by_id(1).unwrap().unwrap();
+            let spots = floor.spots.lock().unwrap();
+            spots.keys().cloned().collect()
+        };
+        for spot_id in &spot_ids[1..] {
+            admin
+                .mark_spot_out_of_service(&lot, 1, spot_id, "Resurfacing".into())
+                .unwrap();
+        }
+        let spot_id = spot_ids[0].clone();
+
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "AAA111".into());
+        let ticket = lot.park_vehicle(car).unwrap();
+        assert_eq!(ticket.spot_type, Some(SpotType::Regular));
+        assert!(
+            admin
+                .convert_spot(&lot, 1, &spot_id, SpotType::Electric)
+                .is_err()
+        );
+        lot.unpark_vehicle(ticket.ticket_id).unwrap();
+        admin
+            .convert_spot(&lot, 1, &spot_id, SpotType::Electric)
+            .unwrap();
+
+        let ev = Vehicle::new(VehicleType::Electric, "Leaf".into(), "EV001".into());
+        let ticket = lot.park_vehicle(ev).unwrap();
+        assert_eq!(ticket.spot_type, Some(SpotType::Electric));
+        lot.unpark_vehicle(ticket.ticket_id).unwrap();
+
+        let now = Utc::now();
+        let usage = lot
+            .usage_by_spot_type(now - Duration::hours(1), now + Duration::hours(1))
+            .unwrap();
+        let types: Vec<(SpotType, u32)> = usage.iter().map(|u| (u.spot_type, u.stays)).collect();
+        assert_eq!(types, vec![(SpotType::Regular, 1), (SpotType::Electric, 1)]);
+
+        let conversions = lot.spot_conversions();
+        assert_eq!(conversions.len(), 1);
+        assert_eq!(
+            (conversions[0].from, conversions[0].to),
+            (SpotType::Regular, SpotType::Electric)
+        );
+        assert_eq!(
+            lot.audit_log().last().unwrap().action,
+            AuditAction::SpotConverted {
+                floor_id: Some(1),
+                spot_id,
+                from: SpotType::Regular,
+                to: SpotType::Electric,
+            }
+        );
+    }
+}
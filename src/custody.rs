@@ -0,0 +1,214 @@
+//! Custody of a parked car-share vehicle. The vehicle stays on its spot while
+//! responsibility passes between users (owner, renter pickup, renter dropoff); each
+//! custodian pays for the part of the stay they were responsible for.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{ParkingLot, error::ParkingError};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustodyInterval {
+    pub custodian: String,
+    pub from: DateTime<Utc>,
+    /// `None` while this custodian still holds the vehicle.
+    pub until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustodySession {
+    pub ticket_id: String,
+    /// Oldest first; every interval ends where the next one starts.
+    pub intervals: Vec<CustodyInterval>,
+}
+
+impl CustodySession {
+    pub fn current_custodian(&self) -> &str {
+        &self.intervals.last().unwrap().custodian
+    }
+}
+
+/// One custodian's part of the stay and what they owe for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustodyShare {
+    pub custodian: String,
+    pub from: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub amount: f32,
+}
+
+impl ParkingLot {
+    /// Makes `custodian` responsible for the ticket's stay from entry onwards.
+    pub fn start_custody(
+        &self,
+        ticket_id: &str,
+        custodian: String,
+    ) -> Result<CustodySession, ParkingError> {
+        let entry_time = self.open_ticket_entry(ticket_id)?;
+        let mut sessions = self.custody_sessions.lock()?;
+        if sessions.contains_key(ticket_id) {
+            return Err(ParkingError::InvalidHandoff);
+        }
+        let session = CustodySession {
+            ticket_id: ticket_id.to_string(),
+            intervals: vec![CustodyInterval {
+                custodian,
+                from: entry_time,
+                until: None,
+            }],
+        };
+        sessions.insert(ticket_id.to_string(), session.clone());
+        Ok(session)
+    }
+
+    pub fn hand_off(&self, ticket_id: &str, to: String) -> Result<CustodySession, ParkingError> {
+        self.hand_off_at(ticket_id, to, self.now())
+    }
+
+    /// Passes custody to `to` at `at`, which must lie between the current custodian's
+    /// start and now.
+    pub fn hand_off_at(
+        &self,
+        ticket_id: &str,
+        to: String,
+        at: DateTime<Utc>,
+    ) -> Result<CustodySession, ParkingError> {
+        self.open_ticket_entry(ticket_id)?;
+        let mut sessions = self.custody_sessions.lock()?;
+        let session = sessions
+            .get_mut(ticket_id)
+            .ok_or(ParkingError::NoCustodySession)?;
+        let current = session.intervals.last_mut().unwrap();
+        if at < current.from || at > self.now() {
+            return Err(ParkingError::InvalidHandoff);
+        }
+        current.until = Some(at);
+        session.intervals.push(CustodyInterval {
+            custodian: to,
+            from: at,
+            until: None,
+        });
+        Ok(session.clone())
+    }
+
+    pub fn custody_session(&self, ticket_id: &str) -> Option<CustodySession> {
+        self.custody_sessions
+            .lock()
+            .unwrap()
+            .get(ticket_id)
+            .cloned()
+    }
+
+    /// Splits the ticket's charge between its custodians in proportion to how long each
+    /// held the vehicle. Closed tickets split their final charge; open ones split the
+    /// current estimate.
+    pub fn custody_split(&self, ticket_id: &str) -> Result<Vec<CustodyShare>, ParkingError> {
+        let session = self
+            .custody_session(ticket_id)
+            .ok_or(ParkingError::NoCustodySession)?;
+        let closed = self
+            .ticket_history
+            .completed_tickets()?
+            .into_iter()
+            .find(|entry| entry.ticket.ticket_id == ticket_id);
+        let (entry_time, end, total) = match closed {
+            Some(entry) => (
+                entry.ticket.entry_time,
+                entry.ticket.exit_time.unwrap(),
+                entry.total,
+            ),
+            None => {
+                let now = self.now();
+                let estimate = self.estimate_charge_at(ticket_id, now, None)?;
+                (self.open_ticket_entry(ticket_id)?, now, estimate.total)
+            }
+        };
+
+        let stay = end
+            .signed_duration_since(entry_time)
+            .num_milliseconds()
+            .max(1) as f32;
+        let mut shares: Vec<CustodyShare> = session
+            .intervals
+            .iter()
+            .filter(|interval| interval.from <= end)
+            .map(|interval| {
+                let until = interval.until.map_or(end, |until| until.min(end));
+                let held = until
+                    .signed_duration_since(interval.from)
+                    .num_milliseconds() as f32;
+                CustodyShare {
+                    custodian: interval.custodian.clone(),
+                    from: interval.from,
+                    until,
+                    amount: total * held / stay,
+                }
+            })
+            .collect();
+        // Whatever rounding leaves over goes to the last custodian, so shares sum to total
+        let assigned: f32 = shares.iter().map(|s| s.amount).sum();
+        if let Some(last) = shares.last_mut() {
+            last.amount += total - assigned;
+        }
+        Ok(shares)
+    }
+
+    fn open_ticket_entry(&self, ticket_id: &str) -> Result<DateTime<Utc>, ParkingError> {
+        match self.active_tickets.lock()?.get(ticket_id) {
+            Some(ticket) => Ok(ticket.entry_time),
+            None => Err(self.missing_ticket(ticket_id)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, Vehicle, VehicleType};
+    use chrono::Duration;
+
+    #[test]
+    fn test_charge_is_split_by_custody_time() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let ticket = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "SHARE1".into(),
+            ))
+            .unwrap();
+        let entry_time = Utc::now() - Duration::hours(3);
+        lot.active_tickets
+            .lock()
+            .unwrap()
+            .get_mut(&ticket.ticket_id)
+            .unwrap()
+            .entry_time = entry_time;
+
+        lot.start_custody(&ticket.ticket_id, "owner".into())
+            .unwrap();
+        let handoff = entry_time + Duration::hours(1);
+        lot.hand_off_at(&ticket.ticket_id, "renter".into(), handoff)
+            .unwrap();
+        assert!(matches!(
+            lot.hand_off_at(&ticket.ticket_id, "late".into(), entry_time),
+            Err(ParkingError::InvalidHandoff)
+        ));
+
+        let charge = lot.unpark_vehicle(ticket.ticket_id.clone()).unwrap();
+        let shares = lot.custody_split(&ticket.ticket_id).unwrap();
+        assert_eq!(shares.len(), 2);
+        assert_eq!(
+            (shares[0].custodian.as_str(), shares[0].until),
+            ("owner", handoff)
+        );
+        assert_eq!(charge.total, 30.0);
+        assert!((shares[0].amount - 10.0).abs() < 0.01);
+        assert!((shares[1].amount - 20.0).abs() < 0.01);
+        assert!(matches!(
+            lot.hand_off(&ticket.ticket_id, "someone".into()),
+            Err(ParkingError::TicketClosed)
+        ));
+    }
+}
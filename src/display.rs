@@ -0,0 +1,119 @@
+//! Per-floor display boards, for the signage at each level.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{ParkingFloor, ParkingLot, SpotType};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloorDisplayBoard {
+    floor_id: u32,
+    closed: bool,
+    /// Free spots per type, for every type the floor has at least one spot of.
+    free_by_type: HashMap<SpotType, u32>,
+}
+
+impl FloorDisplayBoard {
+    fn new(floor: &ParkingFloor, closed: bool) -> Self {
+        let mut free_by_type = HashMap::new();
+        for spot in floor.spots.lock().unwrap().values() {
+            *free_by_type.entry(spot.spot_type).or_insert(0) += u32::from(spot.is_available());
+        }
+        Self {
+            floor_id: floor.id,
+            closed,
+            free_by_type,
+        }
+    }
+
+    pub fn floor_id(&self) -> u32 {
+        self.floor_id
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Free spots of `spot_type`; zero on a closed floor, since they can't be taken.
+    pub fn free_spots(&self, spot_type: SpotType) -> u32 {
+        if self.closed {
+            return 0;
+        }
+        self.free_by_type.get(&spot_type).copied().unwrap_or(0)
+    }
+
+    pub fn total_free_spots(&self) -> u32 {
+        SpotType::ALL.iter().map(|t| self.free_spots(*t)).sum()
+    }
+
+    /// One line for the floor sign, e.g. `Regular: 4, Large: FULL, Handicapped: 2`. Only
+    /// spot types the floor has are listed.
+    pub fn signage(&self) -> String {
+        if self.closed {
+            return "CLOSED".to_string();
+        }
+        SpotType::ALL
+            .iter()
+            .filter(|t| self.free_by_type.contains_key(t))
+            .map(|t| match self.free_spots(*t) {
+                0 => format!("{:?}: FULL", t),
+                n => format!("{:?}: {}", t, n),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl ParkingLot {
+    pub fn display_floor(&self, floor_id: u32) -> Option<FloorDisplayBoard> {
+        let floors = self.floors.lock().unwrap();
+        let closed = self.closed_floors.lock().unwrap().contains(&floor_id);
+        floors
+            .get(&floor_id)
+            .map(|floor| FloorDisplayBoard::new(floor, closed))
+    }
+
+    /// A board for every floor, keyed by floor id.
+    pub fn display_floors(&self) -> BTreeMap<u32, FloorDisplayBoard> {
+        let floors = self.floors.lock().unwrap();
+        let closed_floors = self.closed_floors.lock().unwrap();
+        floors
+            .iter()
+            .map(|(id, floor)| {
+                (
+                    *id,
+                    FloorDisplayBoard::new(floor, closed_floors.contains(id)),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingSpot, Vehicle, VehicleType};
+
+    #[test]
+    fn test_floor_boards_break_down_free_spots_by_type() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.add_floor(ParkingFloor::new(2)).unwrap();
+        lot.add_spot(1, ParkingSpot::new(true, SpotType::Large))
+            .unwrap();
+        lot.add_spot(1, ParkingSpot::new(true, SpotType::Handicapped))
+            .unwrap();
+        lot.close_floor(2).unwrap();
+        let truck = Vehicle::new(VehicleType::Truck, "Volvo".into(), "T1".into());
+        lot.park_vehicle(truck).unwrap();
+
+        let board = lot.display_floor(1).unwrap();
+        assert_eq!(board.free_spots(SpotType::Regular), 10);
+        assert_eq!(board.total_free_spots(), 11);
+        assert_eq!(board.signage(), "Regular: 10, Large: FULL, Handicapped: 1");
+
+        let boards = lot.display_floors();
+        assert_eq!(boards.keys().copied().collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(boards[&2].signage(), "CLOSED");
+        assert!(lot.display_floor(3).is_none());
+    }
+}
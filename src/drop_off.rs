@@ -0,0 +1,263 @@
+//! Drop-off and pick-up zones: kerbside bays where vehicles stop briefly without a ticket.
+//! Sensors or plate cameras report arrivals and departures, and a vehicle that stays past
+//! the zone's maximum dwell gets a citation, opened once per visit.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{ParkingLot, error::ParkingError, events::ParkingEvent, plate::LicensePlate};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DropOffZone {
+    pub id: String,
+    pub floor_id: u32,
+    #[serde(rename = "max_dwell_secs", with = "crate::persistence::seconds")]
+    pub max_dwell: Duration,
+    /// Charged on each citation.
+    pub fine: f32,
+}
+
+impl DropOffZone {
+    pub fn new(id: String, floor_id: u32, max_dwell: Duration) -> Self {
+        Self {
+            id,
+            floor_id,
+            max_dwell,
+            fine: 0.0,
+        }
+    }
+
+    pub fn with_fine(mut self, fine: f32) -> Self {
+        self.fine = fine;
+        self
+    }
+}
+
+/// A vehicle that stayed in a drop-off zone past its maximum dwell.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Citation {
+    pub citation_id: String,
+    pub zone_id: String,
+    pub license_plate: String,
+    pub arrived_at: DateTime<Utc>,
+    pub issued_at: DateTime<Utc>,
+    /// Time in the zone when the citation was opened.
+    #[serde(rename = "dwell_secs", with = "crate::persistence::seconds")]
+    pub dwell: Duration,
+    pub fine: f32,
+}
+
+#[derive(Debug)]
+pub(crate) struct Visit {
+    pub(crate) arrived_at: DateTime<Utc>,
+    pub(crate) cited: bool,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct DropOffZones {
+    pub(crate) zones: Vec<DropOffZone>,
+    /// Vehicles in a zone now, by zone id and plate.
+    pub(crate) visits: HashMap<(String, String), Visit>,
+    pub(crate) citations: Vec<Citation>,
+}
+
+impl DropOffZones {
+    fn zone(&self, zone_id: &str) -> Result<&DropOffZone, ParkingError> {
+        self.zones
+            .iter()
+            .find(|zone| zone.id == zone_id)
+            .ok_or(ParkingError::ZoneNotFound)
+    }
+
+    /// Opens a citation for the visit at `key` if it has gone past the zone's dwell.
+    fn cite(&mut self, key: &(String, String), now: DateTime<Utc>) -> Option<Citation> {
+        let zone = self.zone(&key.0).ok()?.clone();
+        let visit = self.visits.get_mut(key)?;
+        let dwell = now - visit.arrived_at;
+        if visit.cited || dwell <= zone.max_dwell {
+            return None;
+        }
+        visit.cited = true;
+        let citation = Citation {
+            citation_id: format!("cit_{}", self.citations.len() + 1),
+            zone_id: zone.id,
+            license_plate: key.1.clone(),
+            arrived_at: visit.arrived_at,
+            issued_at: now,
+            dwell,
+            fine: zone.fine,
+        };
+        self.citations.push(citation.clone());
+        Some(citation)
+    }
+}
+
+impl ParkingLot {
+    pub fn add_drop_off_zone(&self, zone: DropOffZone) -> Result<(), ParkingError> {
+        if !self.floors.lock()?.contains_key(&zone.floor_id) {
+            return Err(ParkingError::FloorNotFound);
+        }
+        let mut drop_off = self.drop_off_zones.lock()?;
+        if drop_off.zone(&zone.id).is_ok() {
+            return Err(ParkingError::DuplicateZone(zone.id));
+        }
+        drop_off.zones.push(zone);
+        Ok(())
+    }
+
+    pub fn drop_off_zones(&self) -> Vec<DropOffZone> {
+        self.drop_off_zones.lock().unwrap().zones.clone()
+    }
+
+    /// A sensor saw `license_plate` pull into the zone. Repeated sightings keep the first
+    /// arrival time.
+    pub fn record_drop_off_arrival(
+        &self,
+        zone_id: &str,
+        license_plate: &str,
+    ) -> Result<(), ParkingError> {
+        let plate = LicensePlate::parse(license_plate)?;
+        let now = self.now();
+        let mut drop_off = self.drop_off_zones.lock()?;
+        drop_off.zone(zone_id)?;
+        drop_off
+            .visits
+            .entry((zone_id.to_string(), plate.into()))
+            .or_insert(Visit {
+                arrived_at: now,
+                cited: false,
+            });
+        Ok(())
+    }
+
+    /// A sensor saw `license_plate` leave the zone. Returns the citation opened if it left
+    /// past the dwell limit before a scan caught it.
+    pub fn record_drop_off_departure(
+        &self,
+        zone_id: &str,
+        license_plate: &str,
+    ) -> Result<Option<Citation>, ParkingError> {
+        let key = (
+            zone_id.to_string(),
+            LicensePlate::parse(license_plate)?.into(),
+        );
+        let citation = {
+            let mut drop_off = self.drop_off_zones.lock()?;
+            drop_off.zone(zone_id)?;
+            let citation = drop_off.cite(&key, self.now());
+            drop_off.visits.remove(&key);
+            citation
+        };
+        if let Some(citation) = &citation {
+            self.announce_citation(citation);
+        }
+        Ok(citation)
+    }
+
+    /// Opens citations for vehicles still in a zone past its dwell limit, oldest arrival
+    /// first. Each visit is cited once, however often it's scanned.
+    pub fn scan_drop_off_zones(&self) -> Vec<Citation> {
+        let now = self.now();
+        let citations: Vec<Citation> = {
+            let mut drop_off = self.drop_off_zones.lock().unwrap();
+            let mut keys: Vec<(DateTime<Utc>, (String, String))> = drop_off
+                .visits
+                .iter()
+                .map(|(key, visit)| (visit.arrived_at, key.clone()))
+                .collect();
+            keys.sort();
+            keys.iter()
+                .filter_map(|(_, key)| drop_off.cite(key, now))
+                .collect()
+        };
+        for citation in &citations {
+            self.announce_citation(citation);
+        }
+        citations
+    }
+
+    /// Every citation opened so far, oldest first.
+    pub fn citations(&self) -> Vec<Citation> {
+        self.drop_off_zones.lock().unwrap().citations.clone()
+    }
+
+    fn announce_citation(&self, citation: &Citation) {
+        self.emit(ParkingEvent::CitationIssued {
+            citation_id: citation.citation_id.clone(),
+            zone_id: citation.zone_id.clone(),
+            license_plate: citation.license_plate.clone(),
+            fine: citation.fine,
+            at: citation.issued_at,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParkingFloor, clock::MockClock};
+
+    #[test]
+    fn test_vehicles_past_the_dwell_limit_are_cited_once() {
+        let clock = MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let zone = DropOffZone::new("kerb_1".into(), 1, Duration::minutes(10)).with_fine(25.0);
+        lot.add_drop_off_zone(zone.clone()).unwrap();
+        assert_eq!(
+            lot.add_drop_off_zone(zone.clone()),
+            Err(ParkingError::DuplicateZone(zone.id.clone()))
+        );
+        let upstairs = DropOffZone::new("kerb_9".into(), 9, Duration::minutes(10));
+        assert_eq!(
+            lot.add_drop_off_zone(upstairs),
+            Err(ParkingError::FloorNotFound)
+        );
+        let events = lot.events();
+
+        lot.record_drop_off_arrival("kerb_1", "quick 1").unwrap();
+        lot.record_drop_off_arrival("kerb_1", "SLOW 1").unwrap();
+        clock.advance(Duration::minutes(5));
+        assert_eq!(
+            lot.record_drop_off_departure("kerb_1", "QUICK 1").unwrap(),
+            None
+        );
+        // A second sighting doesn't restart the clock
+        lot.record_drop_off_arrival("kerb_1", "SLOW 1").unwrap();
+        assert!(lot.scan_drop_off_zones().is_empty());
+
+        clock.advance(Duration::minutes(6));
+        let cited = lot.scan_drop_off_zones();
+        assert_eq!(cited.len(), 1);
+        assert_eq!(cited[0].license_plate, "SLOW 1");
+        assert_eq!(
+            (cited[0].dwell, cited[0].fine),
+            (Duration::minutes(11), 25.0)
+        );
+        assert!(lot.scan_drop_off_zones().is_empty());
+        assert_eq!(
+            lot.record_drop_off_departure("kerb_1", "SLOW 1").unwrap(),
+            None
+        );
+
+        lot.record_drop_off_arrival("kerb_1", "LATE 1").unwrap();
+        clock.advance(Duration::minutes(15));
+        let late = lot.record_drop_off_departure("kerb_1", "LATE 1").unwrap();
+        assert_eq!(late.unwrap().citation_id, "cit_2");
+        assert_eq!(lot.citations().len(), 2);
+        assert_eq!(
+            events
+                .try_iter()
+                .filter(|e| matches!(e, ParkingEvent::CitationIssued { .. }))
+                .count(),
+            2
+        );
+        assert_eq!(
+            lot.record_drop_off_arrival("nowhere", "LATE 1"),
+            Err(ParkingError::ZoneNotFound)
+        );
+    }
+}
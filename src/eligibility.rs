@@ -0,0 +1,149 @@
+//! Verified account attributes (student, senior, military) and the discounts they unlock.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Eligibility {
+    Student,
+    Senior,
+    Military,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verification {
+    pub eligibility: Eligibility,
+    pub granted_by: String,
+    pub granted_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Verification {
+    pub fn is_valid_at(&self, now: DateTime<Utc>) -> bool {
+        self.granted_at <= now && now < self.expires_at
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerificationAction {
+    Granted,
+    Revoked,
+}
+
+/// One entry in an account's verification audit trail.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationAudit {
+    pub eligibility: Eligibility,
+    pub action: VerificationAction,
+    pub actor: String,
+    pub at: DateTime<Utc>,
+}
+
+/// Percentage discount per eligibility, configured per lot.
+#[derive(Debug, Clone, Default)]
+pub struct DiscountSchedule {
+    percentages: HashMap<Eligibility, f32>,
+}
+
+impl DiscountSchedule {
+    pub fn set(&mut self, eligibility: Eligibility, percent: f32) {
+        self.percentages
+            .insert(eligibility, percent.clamp(0.0, 100.0));
+    }
+
+    pub fn remove(&mut self, eligibility: Eligibility) {
+        self.percentages.remove(&eligibility);
+    }
+
+    /// The largest discount any of `eligibilities` qualifies for. Discounts don't stack.
+    pub fn best_discount(&self, eligibilities: &[Eligibility]) -> Option<(Eligibility, f32)> {
+        eligibilities
+            .iter()
+            .filter_map(|e| self.percentages.get(e).map(|pct| (*e, *pct)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Parkable, ParkingFloor, ParkingLot, User, Vehicle, VehicleType, clock::MockClock,
+        pricing::FlatHourly,
+    };
+    use chrono::Duration;
+
+    #[test]
+    fn test_expired_verification_no_longer_qualifies_for_discount() {
+        let mut schedule = DiscountSchedule::default();
+        schedule.set(Eligibility::Student, 20.0);
+        schedule.set(Eligibility::Senior, 30.0);
+
+        let mut user = User::new("Ada".into(), "123".into());
+        let issued = Utc::now();
+        user.grant_verification(
+            Eligibility::Student,
+            "registrar".into(),
+            issued,
+            issued + Duration::days(30),
+        );
+        user.grant_verification(
+            Eligibility::Senior,
+            "clerk".into(),
+            issued,
+            issued + Duration::days(1),
+        );
+
+        let now = Utc::now();
+        let today = user.active_eligibilities(now);
+        let next_week = user.active_eligibilities(now + Duration::days(7));
+
+        assert_eq!(
+            schedule.best_discount(&today),
+            Some((Eligibility::Senior, 30.0))
+        );
+        assert_eq!(
+            schedule.best_discount(&next_week),
+            Some((Eligibility::Student, 20.0))
+        );
+        assert_eq!(user.verification_audit()[1].actor, "clerk");
+    }
+
+    #[test]
+    fn test_verifications_follow_the_lot_clock() {
+        let clock = MockClock::new(Utc::now() - Duration::days(10));
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_pricing_strategy(Box::new(FlatHourly::new(2.0)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_discount(Eligibility::Senior, 50.0);
+        let mut user = User::new("Ada".into(), "123".into());
+        // Granted on a lot clock running well behind the system time
+        user.grant_verification(
+            Eligibility::Senior,
+            "clerk".into(),
+            lot.now(),
+            lot.now() + Duration::days(1),
+        );
+
+        let park = |plate: &str| {
+            lot.park_vehicle(Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into()))
+                .unwrap()
+        };
+        let first = park("ELG001");
+        clock.advance(Duration::hours(2));
+        let charge = lot.unpark_vehicle_for_user(first.ticket_id, &user).unwrap();
+        assert_eq!(charge.total, 2.0);
+
+        user.revoke_verification(Eligibility::Senior, "clerk".into(), lot.now());
+        assert_eq!(user.verification_audit()[1].at, lot.now());
+        let second = park("ELG002");
+        clock.advance(Duration::hours(2));
+        let charge = lot
+            .unpark_vehicle_for_user(second.ticket_id, &user)
+            .unwrap();
+        assert_eq!(charge.total, 4.0);
+    }
+}
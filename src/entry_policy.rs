@@ -0,0 +1,411 @@
+//! Admission checks run before a vehicle is given a spot. Each check is an `EntryPolicy`.
+//! The lot runs them in order and turns the vehicle away at the first one that refuses.
+//! `explain_entry` runs every check and reports what each one decided, so staff can see
+//! exactly why a vehicle was refused.
+
+use std::{collections::HashMap, fmt};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    ParkingLot, Vehicle, VehicleType, error::ParkingError, plate::normalize_plate, units::Length,
+};
+
+/// A vehicle asking to park.
+#[derive(Debug, Clone, Copy)]
+pub struct EntryRequest<'a> {
+    pub vehicle: &'a Vehicle,
+    /// Parking zone asked for, if any. Zones enforce their own capacity.
+    pub zone_id: Option<&'a str>,
+    pub at: DateTime<Utc>,
+}
+
+pub trait EntryPolicy: fmt::Debug + Send + Sync {
+    /// Shown in entry explanations.
+    fn name(&self) -> &str;
+    fn check(&self, lot: &ParkingLot, request: &EntryRequest) -> Result<(), ParkingError>;
+}
+
+/// Refuses entry while the operating schedule has the lot closed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OperatingHours;
+
+impl EntryPolicy for OperatingHours {
+    fn name(&self) -> &str {
+        "operating-hours"
+    }
+
+    fn check(&self, lot: &ParkingLot, request: &EntryRequest) -> Result<(), ParkingError> {
+        if !lot.schedule.is_lot_open(request.at) {
+            return Err(ParkingError::LotClosed);
+        }
+        Ok(())
+    }
+}
+
+/// Refuses entry during an evacuation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Evacuation;
+
+impl EntryPolicy for Evacuation {
+    fn name(&self) -> &str {
+        "evacuation"
+    }
+
+    fn check(&self, lot: &ParkingLot, _request: &EntryRequest) -> Result<(), ParkingError> {
+        if lot.active_evacuation().is_some() {
+            return Err(ParkingError::EvacuationInProgress);
+        }
+        Ok(())
+    }
+}
+
+/// Refuses plates blocked with `ParkingLot::block_plate`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Blocklist;
+
+impl EntryPolicy for Blocklist {
+    fn name(&self) -> &str {
+        "blocklist"
+    }
+
+    fn check(&self, lot: &ParkingLot, request: &EntryRequest) -> Result<(), ParkingError> {
+        match lot
+            .blocked_plates
+            .lock()?
+            .get(request.vehicle.license_plate())
+        {
+            Some(reason) => Err(ParkingError::VehicleBlocked(reason.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Refuses vehicles whose passes for the lot have all run out.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Permits;
+
+impl EntryPolicy for Permits {
+    fn name(&self) -> &str {
+        "permits"
+    }
+
+    fn check(&self, lot: &ParkingLot, request: &EntryRequest) -> Result<(), ParkingError> {
+        lot.pass_for_entry(request.vehicle, request.at).map(|_| ())
+    }
+}
+
+/// Refuses entry when no spot is free within the occupancy limits. Priority vehicles and
+/// zone parking aren't bound by it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Capacity;
+
+impl EntryPolicy for Capacity {
+    fn name(&self) -> &str {
+        "capacity"
+    }
+
+    fn check(&self, lot: &ParkingLot, request: &EntryRequest) -> Result<(), ParkingError> {
+        let vehicle = request.vehicle;
+        if vehicle.priority.is_some()
+            || request.zone_id.is_some()
+            || lot.has_room_for(
+                &vehicle.vehicle_type,
+                vehicle.handicapped_permit,
+                vehicle.dimensions.as_ref(),
+            )
+        {
+            Ok(())
+        } else {
+            Err(ParkingError::LotFull)
+        }
+    }
+}
+
+/// Refuses a vehicle once its type is at the cap set with
+/// `OccupancyLimits::with_vehicle_type_cap`. Priority vehicles aren't bound by it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VehicleTypeCaps;
+
+impl EntryPolicy for VehicleTypeCaps {
+    fn name(&self) -> &str {
+        "vehicle-type-caps"
+    }
+
+    fn check(&self, lot: &ParkingLot, request: &EntryRequest) -> Result<(), ParkingError> {
+        let vehicle = request.vehicle;
+        match lot.occupancy_limits.vehicle_type_cap(&vehicle.vehicle_type) {
+            Some(cap)
+                if vehicle.priority.is_none()
+                    && lot.parked_of_type(&vehicle.vehicle_type) >= cap =>
+            {
+                Err(ParkingError::VehicleTypeCapReached(
+                    vehicle.vehicle_type.clone(),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Refuses the listed vehicle types, e.g. trucks at a lot with low clearance.
+#[derive(Debug, Clone, Default)]
+pub struct VehicleTypeRestriction {
+    refused: Vec<VehicleType>,
+}
+
+impl VehicleTypeRestriction {
+    pub fn new(refused: Vec<VehicleType>) -> Self {
+        Self { refused }
+    }
+}
+
+impl EntryPolicy for VehicleTypeRestriction {
+    fn name(&self) -> &str {
+        "vehicle-type-restriction"
+    }
+
+    fn check(&self, _lot: &ParkingLot, request: &EntryRequest) -> Result<(), ParkingError> {
+        if self.refused.contains(&request.vehicle.vehicle_type) {
+            return Err(ParkingError::VehicleTypeRestricted);
+        }
+        Ok(())
+    }
+}
+
+/// Refuses vehicles taller than the entrance clearance. Vehicles of unknown size are let
+/// through.
+#[derive(Debug, Clone, Copy)]
+pub struct ClearanceGate {
+    clearance: Length,
+}
+
+impl ClearanceGate {
+    pub fn new(clearance: Length) -> Self {
+        Self { clearance }
+    }
+}
+
+impl EntryPolicy for ClearanceGate {
+    fn name(&self) -> &str {
+        "clearance-gate"
+    }
+
+    fn check(&self, _lot: &ParkingLot, request: &EntryRequest) -> Result<(), ParkingError> {
+        match request.vehicle.dimensions {
+            Some(dimensions) if dimensions.height > self.clearance => {
+                Err(ParkingError::ExceedsClearance {
+                    height: dimensions.height,
+                    clearance: self.clearance,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Policies run in order on every entry.
+#[derive(Debug)]
+pub struct EntryPolicies {
+    policies: Vec<Box<dyn EntryPolicy>>,
+}
+
+impl EntryPolicies {
+    /// No checks at all.
+    pub fn empty() -> Self {
+        Self {
+            policies: Vec::new(),
+        }
+    }
+
+    /// Appends `policy` to the pipeline.
+    pub fn with(mut self, policy: Box<dyn EntryPolicy>) -> Self {
+        self.policies.push(policy);
+        self
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.policies.iter().map(|p| p.name()).collect()
+    }
+}
+
+impl Default for EntryPolicies {
+    /// Operating hours, evacuation, blocklist, permits, vehicle type caps, then capacity.
+    fn default() -> Self {
+        Self::empty()
+            .with(Box::new(OperatingHours))
+            .with(Box::new(Evacuation))
+            .with(Box::new(Blocklist))
+            .with(Box::new(Permits))
+            .with(Box::new(VehicleTypeCaps))
+            .with(Box::new(Capacity))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolicyOutcome {
+    pub policy: String,
+    pub result: Result<(), ParkingError>,
+}
+
+/// What every policy decided about one entry, in pipeline order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryDecision {
+    pub outcomes: Vec<PolicyOutcome>,
+}
+
+impl EntryDecision {
+    pub fn is_admitted(&self) -> bool {
+        self.outcomes.iter().all(|o| o.result.is_ok())
+    }
+
+    /// The first policy that refused the vehicle; entry stops there.
+    pub fn rejected_by(&self) -> Option<&PolicyOutcome> {
+        self.outcomes.iter().find(|o| o.result.is_err())
+    }
+}
+
+impl ParkingLot {
+    pub fn set_entry_policies(&mut self, policies: EntryPolicies) {
+        self.entry_policies = policies;
+    }
+
+    pub fn entry_policies(&self) -> &EntryPolicies {
+        &self.entry_policies
+    }
+
+    /// Turns `license_plate` away at the entrance, for `reason`, until unblocked.
+    pub fn block_plate(&self, license_plate: &str, reason: String) {
+        self.blocked_plates
+            .lock()
+            .unwrap()
+            .insert(normalize_plate(license_plate), reason);
+    }
+
+    /// Returns whether the plate was blocked.
+    pub fn unblock_plate(&self, license_plate: &str) -> bool {
+        self.blocked_plates
+            .lock()
+            .unwrap()
+            .remove(&normalize_plate(license_plate))
+            .is_some()
+    }
+
+    pub fn blocked_plates(&self) -> HashMap<String, String> {
+        self.blocked_plates.lock().unwrap().clone()
+    }
+
+    /// Runs every entry policy for `vehicle` without parking it.
+    pub fn explain_entry(&self, vehicle: &Vehicle, zone_id: Option<&str>) -> EntryDecision {
+        let request = EntryRequest {
+            vehicle,
+            zone_id,
+            at: self.now(),
+        };
+        EntryDecision {
+            outcomes: self
+                .entry_policies
+                .policies
+                .iter()
+                .map(|policy| PolicyOutcome {
+                    policy: policy.name().to_string(),
+                    result: policy.check(self, &request),
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether `vehicle` would be let in now, outside any zone.
+    pub fn can_admit(&self, vehicle: &Vehicle) -> bool {
+        self.run_entry_policies(&EntryRequest {
+            vehicle,
+            zone_id: None,
+            at: self.now(),
+        })
+        .is_ok()
+    }
+
+    /// Runs the entry policies for a vehicle at the entrance, counting refusals by a
+    /// vehicle type cap.
+    pub(crate) fn check_entry(&self, request: &EntryRequest) -> Result<(), ParkingError> {
+        let result = self.run_entry_policies(request);
+        if let Err(ParkingError::VehicleTypeCapReached(vehicle_type)) = &result {
+            self.record_cap_refusal(vehicle_type);
+        }
+        result
+    }
+
+    /// Runs the entry policies in order, stopping at the first refusal. A fenced-off
+    /// or draining instance refuses everything, and plates are checked before any policy.
+    fn run_entry_policies(&self, request: &EntryRequest) -> Result<(), ParkingError> {
+        self.check_fence()?;
+        self.check_not_draining()?;
+        self.check_plate(request.vehicle)?;
+        self.entry_policies
+            .policies
+            .iter()
+            .try_for_each(|policy| policy.check(self, request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, ParkingSpot, SpotType};
+
+    #[test]
+    fn test_explain_entry_names_the_refusing_policy() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.add_spot(1, ParkingSpot::new(true, SpotType::Large))
+            .unwrap();
+        lot.set_entry_policies(EntryPolicies::default().with(Box::new(
+            VehicleTypeRestriction::new(vec![VehicleType::Truck]),
+        )));
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "BAD001".into());
+        let truck = Vehicle::new(VehicleType::Truck, "Volvo".into(), "TRK001".into());
+
+        assert!(lot.explain_entry(&car, None).is_admitted());
+        lot.block_plate("BAD001", "Unpaid fines".into());
+        let decision = lot.explain_entry(&car, None);
+        let rejected = decision.rejected_by().unwrap();
+        assert_eq!(rejected.policy, "blocklist");
+        assert_eq!(
+            rejected.result,
+            Err(ParkingError::VehicleBlocked("Unpaid fines".into()))
+        );
+        assert_eq!(decision.outcomes.len(), 7);
+        assert!(matches!(
+            lot.park_vehicle(car.clone()),
+            Err(ParkingError::VehicleBlocked(_))
+        ));
+
+        assert_eq!(
+            lot.explain_entry(&truck, None)
+                .rejected_by()
+                .unwrap()
+                .policy,
+            "vehicle-type-restriction"
+        );
+        assert!(lot.unblock_plate("BAD001"));
+        lot.park_vehicle(car).unwrap();
+    }
+
+    #[test]
+    fn test_blocked_plates_match_in_any_case_and_spacing() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.add_spot(1, ParkingSpot::new(true, SpotType::Large))
+            .unwrap();
+        lot.block_plate("ABC 123", "Unpaid fines".into());
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), " abc 123 ".into());
+        assert_eq!(car.license_plate(), "ABC 123");
+        assert_eq!(
+            lot.park_vehicle(car.clone()).unwrap_err(),
+            ParkingError::VehicleBlocked("Unpaid fines".into())
+        );
+
+        assert!(lot.unblock_plate("abc 123"));
+        lot.park_vehicle(car).unwrap();
+    }
+}
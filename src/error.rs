@@ -0,0 +1,405 @@
+//! Errors returned by parking, payment and reservation operations.
+
+use std::{error::Error, fmt, sync::PoisonError};
+
+use crate::{
+    SpotStatus, VehicleType, quota::QuotaWarning, reservation::ReservationStatus, units::Length,
+    valet::ValetStatus,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParkingError {
+    /// The lot is closed by its operating schedule.
+    LotClosed,
+    /// The operator already runs a lot with this uid.
+    DuplicateLot(String),
+    NoSpotAvailable,
+    SpotNotFound,
+    SpotOccupied,
+    DuplicateSpot(String),
+    FloorNotFound,
+    DuplicateFloor(u32),
+    /// The floor still has parked vehicles.
+    FloorNotEmpty(u32),
+    /// The lot already has as many floors as its inventory limits allow.
+    FloorLimitReached(u32),
+    /// The floor already has as many spots as its inventory limits allow.
+    SpotLimitReached {
+        floor_id: u32,
+        max_spots: u32,
+    },
+    /// The change would break a floor's spot-type quota under `QuotaEnforcement::Reject`.
+    QuotaViolation(QuotaWarning),
+    /// The spot is held by a reservation or lease.
+    SpotUnavailable,
+    /// A maintenance window took the spot out of service; it returns when the window ends.
+    SpotUnderMaintenance,
+    /// The spot's lifecycle doesn't allow the change, e.g. parking on a spot that is
+    /// out of service.
+    InvalidSpotTransition {
+        from: SpotStatus,
+        to: SpotStatus,
+    },
+    /// Arrival was confirmed at a spot not claimed for a vehicle on its way.
+    SpotNotClaimed,
+    IncompatibleVehicle,
+    InvalidTicket,
+    /// A ticket with this id is still open; ids are never reissued.
+    DuplicateTicket(String),
+    /// The ticket's vehicle has already left.
+    TicketClosed,
+    AlreadyPaid,
+    /// The lot requires payment before exit and the ticket hasn't been paid.
+    PaymentRequired,
+    /// The processor declined or failed the payment; carries its message.
+    PaymentFailed(String),
+    /// A payment for the ticket is still being processed.
+    PaymentInProgress,
+    /// No processor is registered for the requested kind of payment.
+    NoPaymentProcessor,
+    PreAuthorizationDisabled,
+    /// The ticket's spot has no charger.
+    ChargingUnavailable,
+    NoChargingSession,
+    NoCustodySession,
+    /// Custody was already started, or a hand-off predates the current custodian's start.
+    InvalidHandoff,
+    PanelNotFound(String),
+    DuplicatePanel(String),
+    ReservationNotFound,
+    ReservationNotPending(ReservationStatus),
+    ReservationExpired,
+    /// Check-in came before the reservation's window, grace period included, began.
+    ReservationNotStarted,
+    InvalidReservationWindow,
+    StandingReservationNotFound,
+    StandingReservationCancelled,
+    InvalidRecurrenceRule,
+    DuplicateMaintenanceWindow(String),
+    /// The window's rule has no days or ends before it starts.
+    InvalidMaintenanceWindow,
+    /// Every parking pass the vehicle holds for this lot has run out.
+    PassExpired,
+    PassNotFound,
+    /// The license plate isn't one of the account's registered vehicles.
+    VehicleNotRegistered,
+    LeaseNotFound,
+    /// The lease window doesn't include the current time.
+    LeaseNotActive,
+    InvalidLeaseWindow,
+    /// No vehicle may enter while the lot is being evacuated.
+    EvacuationInProgress,
+    NoEvacuation,
+    ZoneNotFound,
+    ZoneFull,
+    DuplicateZone(String),
+    /// A zone was given the id of a spot on its floor.
+    ZoneClashesWithSpot(String),
+    NoParkingZoneNotFound,
+    /// A parking zone listed the same spot more than once.
+    ZoneSpotRepeated(String),
+    /// The spot already belongs to another parking zone.
+    SpotAlreadyZoned(String),
+    /// The lot has no room within its occupancy limits for the vehicle.
+    LotFull,
+    /// The plate is on the lot's blocklist; carries the reason.
+    VehicleBlocked(String),
+    /// The lot doesn't admit this type of vehicle.
+    VehicleTypeRestricted,
+    /// The vehicle is too tall for the entrance.
+    ExceedsClearance {
+        height: Length,
+        clearance: Length,
+    },
+    /// As many vehicles of the type are in the lot as its cap allows.
+    VehicleTypeCapReached(VehicleType),
+    ValetTicketNotFound,
+    /// The valet step doesn't follow from the car's current status.
+    InvalidValetState(ValetStatus),
+    /// The valet ticket is assigned to another attendant, or to none.
+    ValetNotAssigned,
+    /// A lock was poisoned by a panic in another thread.
+    LockPoisoned,
+    /// A pricing experiment was set up without any variants.
+    NoExperimentVariants,
+    /// A pricing experiment's variant weights don't add up to 100; carries their sum.
+    InvalidVariantWeights(u32),
+    /// An availability calendar was asked for with a slot that isn't positive.
+    InvalidCalendarSlot,
+    /// An availability calendar was asked for over an empty range.
+    EmptyCalendarRange,
+    /// An availability calendar was asked for with more slots than it will build; carries
+    /// the limit.
+    TooManyCalendarSlots(usize),
+    /// The thread running an async lot handle has stopped.
+    LotStopped,
+    /// A newer instance was promoted; this one may no longer allocate spots.
+    Fenced {
+        epoch: u64,
+        current: u64,
+    },
+    /// A replication message arrived out of sequence.
+    ReplicationGap {
+        expected: u64,
+        received: u64,
+    },
+    /// The standby hasn't received a checkpoint to promote from.
+    StandbyNotReady,
+    /// Not a plate; see `plate::LicensePlate`.
+    InvalidLicensePlate(String),
+    /// Not a number followed by a length unit; see `units::Length::parse`.
+    InvalidLength(String),
+    /// A vehicle with this plate is already parked in the lot.
+    PlateAlreadyParked(String),
+    /// The lot is shutting down and takes no new vehicles or reservations.
+    ShuttingDown,
+    /// Saving or loading lot state failed.
+    Storage(String),
+    /// The fraud detector held the operation; carries the review id.
+    HeldForReview(String),
+    /// The fraud detector blocked the operation; carries its reason.
+    SuspectedFraud(String),
+    FraudReviewNotFound,
+    /// The ticket code presented at a kiosk isn't signed by this lot.
+    ForgedTicket,
+    /// The app session is unknown, closed or expired.
+    SessionNotFound,
+    /// An extension was asked for on a stay without a maximum.
+    StayNotLimited,
+    NoNotifier,
+    /// The vehicle's owner left no contact to notify.
+    NoContact,
+    /// The notifier couldn't deliver the message; carries its message.
+    NotificationFailed(String),
+}
+
+impl fmt::Display for ParkingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParkingError::LotClosed => write!(f, "lot is closed"),
+            ParkingError::DuplicateLot(uid) => write!(f, "lot {uid} is already operated"),
+            ParkingError::NoSpotAvailable => write!(f, "no available spots"),
+            ParkingError::SpotNotFound => write!(f, "spot not found"),
+            ParkingError::SpotOccupied => write!(f, "spot is already occupied"),
+            ParkingError::DuplicateSpot(id) => write!(f, "spot {id} already exists"),
+            ParkingError::FloorNotFound => write!(f, "floor not found"),
+            ParkingError::DuplicateFloor(id) => write!(f, "floor {id} already exists"),
+            ParkingError::FloorNotEmpty(id) => write!(f, "floor {id} still has parked vehicles"),
+            ParkingError::FloorLimitReached(max_floors) => {
+                write!(f, "lot is limited to {max_floors} floors")
+            }
+            ParkingError::SpotLimitReached {
+                floor_id,
+                max_spots,
+            } => write!(f, "floor {floor_id} is limited to {max_spots} spots"),
+            ParkingError::QuotaViolation(w) => write!(
+                f,
+                "change would leave floor {} with {:.0}% {:?} spots (minimum {:.0}%)",
+                w.floor_id,
+                w.actual_ratio * 100.0,
+                w.spot_type,
+                w.required_ratio * 100.0
+            ),
+            ParkingError::SpotUnavailable => write!(f, "spot is held by a reservation or lease"),
+            ParkingError::SpotUnderMaintenance => {
+                write!(f, "spot is closed for a maintenance window")
+            }
+            ParkingError::InvalidSpotTransition { from, to } => {
+                write!(f, "spot can't go from {from:?} to {to:?}")
+            }
+            ParkingError::SpotNotClaimed => write!(f, "spot is not awaiting a vehicle"),
+            ParkingError::IncompatibleVehicle => {
+                write!(f, "vehicle type not compatible with spot type")
+            }
+            ParkingError::InvalidTicket => write!(f, "invalid ticket id"),
+            ParkingError::DuplicateTicket(id) => write!(f, "ticket {id} is already open"),
+            ParkingError::TicketClosed => write!(f, "ticket is already closed"),
+            ParkingError::AlreadyPaid => write!(f, "ticket is already paid"),
+            ParkingError::PaymentRequired => write!(f, "ticket must be paid before exit"),
+            ParkingError::PaymentFailed(reason) => write!(f, "payment failed: {reason}"),
+            ParkingError::PaymentInProgress => write!(f, "ticket is being paid"),
+            ParkingError::NoPaymentProcessor => write!(f, "no payment processor configured"),
+            ParkingError::PreAuthorizationDisabled => {
+                write!(f, "card-on-entry is not enabled for this lot")
+            }
+            ParkingError::ChargingUnavailable => write!(f, "spot has no charger"),
+            ParkingError::NoChargingSession => write!(f, "no active charging session"),
+            ParkingError::NoCustodySession => write!(f, "no custody session for ticket"),
+            ParkingError::InvalidHandoff => write!(f, "invalid custody hand-off"),
+            ParkingError::PanelNotFound(id) => write!(f, "panel {id} not found"),
+            ParkingError::DuplicatePanel(id) => write!(f, "panel {id} already exists"),
+            ParkingError::ReservationNotFound => write!(f, "reservation not found"),
+            ParkingError::ReservationNotPending(status) => {
+                write!(f, "reservation is {status:?}")
+            }
+            ParkingError::ReservationExpired => write!(f, "reservation has expired"),
+            ParkingError::ReservationNotStarted => write!(f, "reservation has not started yet"),
+            ParkingError::InvalidReservationWindow => write!(f, "reservation window is invalid"),
+            ParkingError::StandingReservationNotFound => {
+                write!(f, "standing reservation not found")
+            }
+            ParkingError::StandingReservationCancelled => {
+                write!(f, "standing reservation has been cancelled")
+            }
+            ParkingError::InvalidRecurrenceRule => write!(f, "recurrence rule is invalid"),
+            ParkingError::DuplicateMaintenanceWindow(id) => {
+                write!(f, "maintenance window {id} already exists")
+            }
+            ParkingError::InvalidMaintenanceWindow => {
+                write!(f, "maintenance window has no days or ends before it starts")
+            }
+            ParkingError::PassExpired => write!(f, "parking pass has expired"),
+            ParkingError::PassNotFound => write!(f, "parking pass not found"),
+            ParkingError::VehicleNotRegistered => {
+                write!(f, "vehicle is not registered to this account")
+            }
+            ParkingError::LeaseNotFound => write!(f, "lease not found"),
+            ParkingError::LeaseNotActive => write!(f, "lease is not active"),
+            ParkingError::InvalidLeaseWindow => write!(f, "lease window is invalid"),
+            ParkingError::EvacuationInProgress => write!(f, "lot is being evacuated"),
+            ParkingError::NoEvacuation => write!(f, "no such evacuation"),
+            ParkingError::ZoneNotFound => write!(f, "parking zone not found"),
+            ParkingError::ZoneFull => write!(f, "parking zone is full"),
+            ParkingError::DuplicateZone(id) => write!(f, "zone {id} already exists"),
+            ParkingError::ZoneClashesWithSpot(id) => {
+                write!(f, "zone id {id} clashes with an existing spot")
+            }
+            ParkingError::NoParkingZoneNotFound => write!(f, "no-parking zone not found"),
+            ParkingError::ZoneSpotRepeated(id) => {
+                write!(f, "zone lists spot {id} more than once")
+            }
+            ParkingError::SpotAlreadyZoned(id) => write!(f, "spot {id} is already in a zone"),
+            ParkingError::LotFull => write!(f, "parking lot is full"),
+            ParkingError::VehicleBlocked(reason) => write!(f, "vehicle is blocked: {reason}"),
+            ParkingError::VehicleTypeRestricted => {
+                write!(f, "vehicle type is not admitted at this lot")
+            }
+            ParkingError::ExceedsClearance { height, clearance } => {
+                write!(
+                    f,
+                    "vehicle is {height} tall, over the {clearance} clearance"
+                )
+            }
+            ParkingError::VehicleTypeCapReached(vehicle_type) => {
+                write!(f, "{vehicle_type:?} vehicles are at their cap")
+            }
+            ParkingError::ValetTicketNotFound => write!(f, "valet ticket not found"),
+            ParkingError::InvalidValetState(status) => {
+                write!(f, "valet ticket is {status:?}")
+            }
+            ParkingError::ValetNotAssigned => {
+                write!(f, "valet ticket is not assigned to this attendant")
+            }
+            ParkingError::LockPoisoned => write!(f, "internal lock poisoned"),
+            ParkingError::NoExperimentVariants => {
+                write!(f, "experiment needs at least one variant")
+            }
+            ParkingError::InvalidVariantWeights(total) => {
+                write!(f, "variant weights add up to {total}, not 100")
+            }
+            ParkingError::InvalidCalendarSlot => write!(f, "slot length must be positive"),
+            ParkingError::EmptyCalendarRange => write!(f, "calendar range is empty"),
+            ParkingError::TooManyCalendarSlots(max) => {
+                write!(f, "calendar range spans more than {max} slots")
+            }
+            ParkingError::LotStopped => write!(f, "lot is no longer running"),
+            ParkingError::Fenced { epoch, current } => {
+                write!(
+                    f,
+                    "instance holds epoch {epoch} but epoch {current} is current"
+                )
+            }
+            ParkingError::ReplicationGap { expected, received } => {
+                write!(
+                    f,
+                    "expected replication message {expected}, received {received}"
+                )
+            }
+            ParkingError::StandbyNotReady => write!(f, "standby has no checkpoint yet"),
+            ParkingError::InvalidLicensePlate(plate) => {
+                write!(f, "{plate:?} is not a valid license plate")
+            }
+            ParkingError::InvalidLength(text) => {
+                write!(f, "{text:?} is not a length in m or ft")
+            }
+            ParkingError::PlateAlreadyParked(plate) => write!(f, "{plate} is already parked"),
+            ParkingError::ShuttingDown => write!(f, "lot is shutting down"),
+            ParkingError::Storage(reason) => write!(f, "storage error: {reason}"),
+            ParkingError::HeldForReview(review_id) => {
+                write!(f, "held for fraud review {review_id}")
+            }
+            ParkingError::SuspectedFraud(reason) => {
+                write!(f, "refused as suspected fraud: {reason}")
+            }
+            ParkingError::FraudReviewNotFound => write!(f, "no fraud hold with that id"),
+            ParkingError::ForgedTicket => write!(f, "ticket code is forged or altered"),
+            ParkingError::SessionNotFound => write!(f, "app session not found or expired"),
+            ParkingError::StayNotLimited => write!(f, "stay has no maximum to extend"),
+            ParkingError::NoNotifier => write!(f, "no notifier configured"),
+            ParkingError::NoContact => write!(f, "no contact for the vehicle's owner"),
+            ParkingError::NotificationFailed(reason) => {
+                write!(f, "notification failed: {reason}")
+            }
+        }
+    }
+}
+
+impl Error for ParkingError {}
+
+impl<T> From<PoisonError<T>> for ParkingError {
+    fn from(_: PoisonError<T>) -> Self {
+        ParkingError::LockPoisoned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[test]
+    fn test_errors_display_their_details() {
+        assert_eq!(ParkingError::FloorNotFound.to_string(), "floor not found");
+        assert_eq!(
+            ParkingError::SpotLimitReached {
+                floor_id: 2,
+                max_spots: 40
+            }
+            .to_string(),
+            "floor 2 is limited to 40 spots"
+        );
+        assert_eq!(
+            ParkingError::PaymentFailed("card declined".into()).to_string(),
+            "payment failed: card declined"
+        );
+    }
+
+    #[test]
+    fn test_errors_box_as_std_errors() {
+        let error: Box<dyn Error + Send + Sync> = Box::new(ParkingError::LotFull);
+        assert_eq!(error.to_string(), "parking lot is full");
+        assert!(error.source().is_none());
+        assert_eq!(
+            error.downcast_ref::<ParkingError>(),
+            Some(&ParkingError::LotFull)
+        );
+    }
+
+    #[test]
+    fn test_poisoned_locks_become_lock_poisoned() {
+        fn read(lock: &Mutex<u32>) -> Result<u32, ParkingError> {
+            Ok(*lock.lock()?)
+        }
+
+        let lock = Arc::new(Mutex::new(0));
+        assert_eq!(read(&lock), Ok(0));
+        let poisoner = Arc::clone(&lock);
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poison the lock");
+        })
+        .join();
+        assert_eq!(read(&lock), Err(ParkingError::LockPoisoned));
+    }
+}
@@ -0,0 +1,167 @@
+//! Lot-wide emergency evacuation. While an evacuation runs no vehicle may enter, every
+//! exit is free and no payment is needed to leave. Tickets open at the start are marked
+//! with the evacuation, so the post-incident report can tell who got out during it.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{ParkingLot, ParkingTicket, error::ParkingError};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Evacuation {
+    pub evacuation_id: String,
+    pub reason: String,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+impl Evacuation {
+    pub fn is_active(&self) -> bool {
+        self.ended_at.is_none()
+    }
+}
+
+/// A vehicle that was in the lot when the evacuation began.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvacuatedVehicle {
+    pub ticket_id: String,
+    pub license_plate: String,
+    pub spot_id: String,
+    /// When it left, if it did before the evacuation ended.
+    pub exit_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvacuationReport {
+    pub evacuation: Evacuation,
+    pub departed: Vec<EvacuatedVehicle>,
+    pub remained: Vec<EvacuatedVehicle>,
+}
+
+impl ParkingLot {
+    /// Starts an evacuation and marks every open ticket with it.
+    pub fn start_evacuation(&self, reason: String) -> Result<Evacuation, ParkingError> {
+        let mut evacuations = self.evacuations.lock()?;
+        if evacuations.last().is_some_and(Evacuation::is_active) {
+            return Err(ParkingError::EvacuationInProgress);
+        }
+        let evacuation = Evacuation {
+            evacuation_id: format!("EVC_{}", evacuations.len() + 1),
+            reason,
+            started_at: self.now(),
+            ended_at: None,
+        };
+        for ticket in self.active_tickets.lock()?.values_mut() {
+            ticket.evacuation_id = Some(evacuation.evacuation_id.clone());
+        }
+        evacuations.push(evacuation.clone());
+        Ok(evacuation)
+    }
+
+    /// Ends the running evacuation and returns its report.
+    pub fn end_evacuation(&self) -> Result<EvacuationReport, ParkingError> {
+        let evacuation_id = {
+            let mut evacuations = self.evacuations.lock()?;
+            let evacuation = evacuations
+                .last_mut()
+                .filter(|e| e.is_active())
+                .ok_or(ParkingError::NoEvacuation)?;
+            evacuation.ended_at = Some(self.now());
+            evacuation.evacuation_id.clone()
+        };
+        self.evacuation_report(&evacuation_id)
+    }
+
+    pub fn active_evacuation(&self) -> Option<Evacuation> {
+        self.evacuations
+            .lock()
+            .unwrap()
+            .last()
+            .filter(|e| e.is_active())
+            .cloned()
+    }
+
+    /// Which of the vehicles present at the start left during the evacuation, and which
+    /// stayed. For a running evacuation, departures so far.
+    pub fn evacuation_report(&self, evacuation_id: &str) -> Result<EvacuationReport, ParkingError> {
+        let evacuation = self
+            .evacuations
+            .lock()?
+            .iter()
+            .find(|e| e.evacuation_id == evacuation_id)
+            .cloned()
+            .ok_or(ParkingError::NoEvacuation)?;
+        let ended_at = evacuation.ended_at.unwrap_or_else(Utc::now);
+        let affected =
+            |ticket: &ParkingTicket| ticket.evacuation_id.as_deref() == Some(evacuation_id);
+        let vehicle = |ticket: &ParkingTicket| EvacuatedVehicle {
+            ticket_id: ticket.ticket_id.clone(),
+            license_plate: ticket.vehicle.license_plate.clone(),
+            spot_id: ticket.spot_id.clone(),
+            exit_time: ticket.exit_time.filter(|at| *at <= ended_at),
+        };
+
+        let mut departed = Vec::new();
+        let mut remained: Vec<EvacuatedVehicle> = self
+            .active_tickets
+            .lock()?
+            .values()
+            .filter(|t| affected(t))
+            .map(vehicle)
+            .collect();
+        for entry in self.ticket_history.completed_tickets()? {
+            if affected(&entry.ticket) {
+                let evacuated = vehicle(&entry.ticket);
+                if evacuated.exit_time.is_some() {
+                    departed.push(evacuated);
+                } else {
+                    remained.push(evacuated);
+                }
+            }
+        }
+        departed.sort_by_key(|v| v.exit_time);
+        remained.sort_by(|a, b| a.ticket_id.cmp(&b.ticket_id));
+        Ok(EvacuationReport {
+            evacuation,
+            departed,
+            remained,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, Vehicle, VehicleType};
+
+    #[test]
+    fn test_evacuation_frees_exits_and_reports_departures() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_payment_required_before_exit(true);
+        let motor = |plate: &str| Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into());
+        let leaving = lot.park_vehicle(motor("AAA111")).unwrap();
+        let staying = lot.park_vehicle(motor("BBB222")).unwrap();
+        for ticket in lot.active_tickets.lock().unwrap().values_mut() {
+            ticket.entry_time -= chrono::Duration::minutes(61);
+        }
+
+        let evacuation = lot.start_evacuation("Fire alarm".into()).unwrap();
+        assert!(matches!(
+            lot.park_vehicle(motor("CCC333")),
+            Err(ParkingError::EvacuationInProgress)
+        ));
+        let charge = lot.unpark_vehicle(leaving.ticket_id.clone()).unwrap();
+        assert_eq!(charge.total, 0.0);
+
+        let report = lot.end_evacuation().unwrap();
+        assert_eq!(report.evacuation.evacuation_id, evacuation.evacuation_id);
+        assert_eq!(report.departed.len(), 1);
+        assert_eq!(report.departed[0].ticket_id, leaving.ticket_id);
+        assert_eq!(report.remained[0].ticket_id, staying.ticket_id);
+        assert!(matches!(
+            lot.unpark_vehicle(staying.ticket_id),
+            Err(ParkingError::PaymentRequired)
+        ));
+    }
+}
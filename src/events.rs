@@ -0,0 +1,281 @@
+//! Lot activity events delivered to integrations: webhooks, and in-process subscribers
+//! registered with `ParkingLot::subscribe` or `ParkingLot::events`.
+
+use std::{
+    fmt,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver},
+    },
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{ParkingLot, SpotType, payment::PaymentMethodKind};
+
+static SUBSCRIPTION_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Callback registered with `ParkingLot::subscribe`.
+pub type Subscriber = Box<dyn Fn(ParkingEvent) + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+#[derive(Default)]
+pub(crate) struct Subscribers {
+    entries: Mutex<Vec<(SubscriptionId, Subscriber)>>,
+}
+
+impl fmt::Debug for Subscribers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscribers")
+            .field("count", &self.entries.lock().unwrap().len())
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    VehicleParked,
+    VehicleUnparked,
+    PaymentReceived,
+    SpotReserved,
+    LotFull,
+    FloorFull,
+    InventoryChanged,
+    VehicleOverstayed,
+    VehicleRelocated,
+    CitationIssued,
+    TicketVoided,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::VehicleParked => "vehicle.parked",
+            EventKind::VehicleUnparked => "vehicle.unparked",
+            EventKind::PaymentReceived => "payment.received",
+            EventKind::SpotReserved => "spot.reserved",
+            EventKind::LotFull => "lot.full",
+            EventKind::FloorFull => "floor.full",
+            EventKind::InventoryChanged => "inventory.changed",
+            EventKind::VehicleOverstayed => "vehicle.overstayed",
+            EventKind::VehicleRelocated => "vehicle.relocated",
+            EventKind::CitationIssued => "citation.issued",
+            EventKind::TicketVoided => "ticket.voided",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum InventoryChange {
+    FloorAdded,
+    FloorReplaced,
+    FloorRemoved,
+    SpotAdded {
+        spot_id: String,
+    },
+    SpotRemoved {
+        spot_id: String,
+    },
+    SpotConverted {
+        spot_id: String,
+        spot_type: SpotType,
+    },
+}
+
+/// Serialized as the webhook payload: the event's kind under `event`, then its fields.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event")]
+pub enum ParkingEvent {
+    #[serde(rename = "vehicle.parked")]
+    VehicleParked {
+        ticket_id: String,
+        license_plate: String,
+        spot_id: String,
+        at: DateTime<Utc>,
+    },
+    #[serde(rename = "vehicle.unparked")]
+    VehicleUnparked {
+        ticket_id: String,
+        license_plate: String,
+        total: f32,
+        at: DateTime<Utc>,
+    },
+    #[serde(rename = "payment.received")]
+    PaymentReceived {
+        ticket_id: String,
+        amount: f32,
+        method: PaymentMethodKind,
+        at: DateTime<Utc>,
+    },
+    #[serde(rename = "spot.reserved")]
+    SpotReserved {
+        reservation_id: String,
+        license_plate: String,
+        spot_id: String,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+    },
+    /// The last free spot in the lot was taken.
+    #[serde(rename = "lot.full")]
+    LotFull { at: DateTime<Utc> },
+    /// The last free spot on the floor was taken.
+    #[serde(rename = "floor.full")]
+    FloorFull { floor_id: u32, at: DateTime<Utc> },
+    #[serde(rename = "inventory.changed")]
+    InventoryChanged {
+        floor_id: u32,
+        #[serde(flatten)]
+        change: InventoryChange,
+        at: DateTime<Utc>,
+    },
+    /// An open ticket went past its maximum stay.
+    #[serde(rename = "vehicle.overstayed")]
+    VehicleOverstayed {
+        ticket_id: String,
+        license_plate: String,
+        max_stay_minutes: i64,
+        at: DateTime<Utc>,
+    },
+    /// An attendant moved a parked vehicle to another spot.
+    #[serde(rename = "vehicle.relocated")]
+    VehicleRelocated {
+        ticket_id: String,
+        license_plate: String,
+        from_spot_id: String,
+        spot_id: String,
+        floor_id: u32,
+        at: DateTime<Utc>,
+    },
+    /// A vehicle stayed in a drop-off zone past its maximum dwell.
+    #[serde(rename = "citation.issued")]
+    CitationIssued {
+        citation_id: String,
+        zone_id: String,
+        license_plate: String,
+        fine: f32,
+        at: DateTime<Utc>,
+    },
+    /// A transit claim ran out before the vehicle reached its spot, and its ticket was
+    /// voided.
+    #[serde(rename = "ticket.voided")]
+    TicketVoided {
+        ticket_id: String,
+        license_plate: String,
+        spot_id: String,
+        at: DateTime<Utc>,
+    },
+}
+
+impl ParkingEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            ParkingEvent::VehicleParked { .. } => EventKind::VehicleParked,
+            ParkingEvent::VehicleUnparked { .. } => EventKind::VehicleUnparked,
+            ParkingEvent::PaymentReceived { .. } => EventKind::PaymentReceived,
+            ParkingEvent::SpotReserved { .. } => EventKind::SpotReserved,
+            ParkingEvent::LotFull { .. } => EventKind::LotFull,
+            ParkingEvent::FloorFull { .. } => EventKind::FloorFull,
+            ParkingEvent::InventoryChanged { .. } => EventKind::InventoryChanged,
+            ParkingEvent::VehicleOverstayed { .. } => EventKind::VehicleOverstayed,
+            ParkingEvent::VehicleRelocated { .. } => EventKind::VehicleRelocated,
+            ParkingEvent::CitationIssued { .. } => EventKind::CitationIssued,
+            ParkingEvent::TicketVoided { .. } => EventKind::TicketVoided,
+        }
+    }
+
+    /// JSON body used for webhook payloads.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("events serialize to JSON")
+    }
+}
+
+impl ParkingLot {
+    /// Calls `subscriber` with every event from now on, after webhooks are queued. Events
+    /// from a batch arrive once it commits. Subscribers run on the thread that caused the
+    /// event and must not subscribe or unsubscribe from inside the callback.
+    pub fn subscribe(&self, subscriber: Subscriber) -> SubscriptionId {
+        let id = SubscriptionId(SUBSCRIPTION_COUNTER.fetch_add(1, Ordering::SeqCst));
+        self.subscribers
+            .entries
+            .lock()
+            .unwrap()
+            .push((id, subscriber));
+        id
+    }
+
+    /// Returns whether the subscription existed.
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        let mut subscribers = self.subscribers.entries.lock().unwrap();
+        let before = subscribers.len();
+        subscribers.retain(|(existing, _)| *existing != id);
+        subscribers.len() != before
+    }
+
+    /// Channel-based alternative to `subscribe`: every event from now on is sent to the
+    /// returned receiver. Events sent after the receiver is dropped are discarded.
+    pub fn events(&self) -> Receiver<ParkingEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribe(Box::new(move |event| {
+            let _ = sender.send(event);
+        }));
+        receiver
+    }
+
+    pub(crate) fn notify_subscribers(&self, event: &ParkingEvent) {
+        for (_, subscriber) in self.subscribers.entries.lock().unwrap().iter() {
+            subscriber(event.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, Vehicle, VehicleType};
+    use chrono::Duration;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    #[test]
+    fn test_subscribers_see_reservations_and_capacity_events() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let events = lot.events();
+        let parked = Arc::new(AtomicUsize::new(0));
+        let counter = parked.clone();
+        let subscription = lot.subscribe(Box::new(move |event| {
+            if event.kind() == EventKind::VehicleParked {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        }));
+
+        let car = |plate: String| Vehicle::new(VehicleType::Motor, "Kia".into(), plate);
+        let now = Utc::now();
+        lot.reserve_spot(car("RES".into()), now, now + Duration::hours(1))
+            .unwrap();
+        for n in 0..9 {
+            lot.park_vehicle(car(format!("CAR{n}"))).unwrap();
+        }
+        assert_eq!(parked.load(Ordering::SeqCst), 9);
+        assert!(lot.unsubscribe(subscription));
+        assert!(!lot.unsubscribe(subscription));
+
+        let kinds: Vec<EventKind> = events.try_iter().map(|e| e.kind()).collect();
+        assert_eq!(kinds[0], EventKind::SpotReserved);
+        assert_eq!(
+            &kinds[kinds.len() - 3..],
+            [
+                EventKind::VehicleParked,
+                EventKind::FloorFull,
+                EventKind::LotFull
+            ]
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Pricing A/B experiments: entering vehicles are split across rate variants by percentage,
+//! with sticky assignment per license plate, and revenue/duration is tracked per variant.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    ParkingError, fnv1a_hash,
+    plate::normalize_plate,
+    pricing::{FlatHourly, PricingStrategy},
+};
+
+#[derive(Debug)]
+pub struct PricingVariant {
+    pub name: String,
+    pub strategy: Arc<dyn PricingStrategy>,
+    /// Share of entering vehicles, in percent.
+    pub weight: u32,
+}
+
+impl PricingVariant {
+    /// A variant billed at a flat `hourly_rate`.
+    pub fn new(name: String, hourly_rate: f32, weight: u32) -> Self {
+        Self::with_strategy(name, Box::new(FlatHourly::new(hourly_rate)), weight)
+    }
+
+    pub fn with_strategy(name: String, strategy: Box<dyn PricingStrategy>, weight: u32) -> Self {
+        Self {
+            name,
+            strategy: strategy.into(),
+            weight,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VariantStats {
+    pub name: String,
+    pub completed_stays: u32,
+    pub revenue: f32,
+    pub total_minutes: i64,
+}
+
+impl VariantStats {
+    pub fn average_minutes(&self) -> f32 {
+        if self.completed_stays == 0 {
+            return 0.0;
+        }
+        self.total_minutes as f32 / self.completed_stays as f32
+    }
+}
+
+#[derive(Debug)]
+pub struct PricingExperiment {
+    variants: Vec<PricingVariant>,
+    stats: Mutex<HashMap<String, VariantStats>>,
+}
+
+impl PricingExperiment {
+    /// Variant weights must add up to 100.
+    pub fn new(variants: Vec<PricingVariant>) -> Result<Self, ParkingError> {
+        if variants.is_empty() {
+            return Err(ParkingError::NoExperimentVariants);
+        }
+        let total = variants.iter().map(|v| v.weight).sum::<u32>();
+        if total != 100 {
+            return Err(ParkingError::InvalidVariantWeights(total));
+        }
+        Ok(Self {
+            variants,
+            stats: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn variants(&self) -> &[PricingVariant] {
+        &self.variants
+    }
+
+    pub fn variant(&self, name: &str) -> Option<&PricingVariant> {
+        self.variants.iter().find(|v| v.name == name)
+    }
+
+    /// Returns the variant for `license_plate`. It's picked by a stable hash of the plate,
+    /// so the same vehicle always gets the same variant without anything being stored.
+    pub fn assign(&self, license_plate: &str) -> &PricingVariant {
+        let bucket = (fnv1a_hash(&normalize_plate(license_plate)) % 100) as u32;
+        let mut cumulative = 0;
+        self.variants
+            .iter()
+            .find(|v| {
+                cumulative += v.weight;
+                bucket < cumulative
+            })
+            .unwrap_or(&self.variants[0])
+    }
+
+    pub fn record_stay(
+        &self,
+        variant: &str,
+        charge: f32,
+        minutes: i64,
+    ) -> Result<(), ParkingError> {
+        let mut stats = self.stats.lock()?;
+        let entry = stats
+            .entry(variant.to_string())
+            .or_insert_with(|| VariantStats {
+                name: variant.to_string(),
+                ..Default::default()
+            });
+        entry.completed_stays += 1;
+        entry.revenue += charge;
+        entry.total_minutes += minutes;
+        Ok(())
+    }
+
+    /// Per-variant totals, in variant declaration order.
+    pub fn report(&self) -> Vec<VariantStats> {
+        let stats = self.stats.lock().unwrap();
+        self.variants
+            .iter()
+            .map(|v| {
+                stats.get(&v.name).cloned().unwrap_or_else(|| VariantStats {
+                    name: v.name.clone(),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assignment_is_sticky_and_follows_weights() {
+        let experiment = PricingExperiment::new(vec![
+            PricingVariant::new("control".into(), 10.0, 50),
+            PricingVariant::new("discount".into(), 8.0, 50),
+        ])
+        .unwrap();
+
+        let first = experiment.assign("ABC123").name.clone();
+        assert_eq!(experiment.assign("abc123").name, first);
+        let rerun = PricingExperiment::new(vec![
+            PricingVariant::new("control".into(), 10.0, 50),
+            PricingVariant::new("discount".into(), 8.0, 50),
+        ])
+        .unwrap();
+        assert_eq!(rerun.assign("ABC123").name, first);
+
+        let discount = (0..1000)
+            .filter(|i| experiment.assign(&format!("PLATE{i}")).name == "discount")
+            .count();
+        assert!((400..600).contains(&discount));
+    }
+
+    #[test]
+    fn test_experiments_need_weights_adding_up_to_100() {
+        assert_eq!(
+            PricingExperiment::new(Vec::new()).unwrap_err(),
+            ParkingError::NoExperimentVariants
+        );
+        assert_eq!(
+            PricingExperiment::new(vec![
+                PricingVariant::new("control".into(), 10.0, 60),
+                PricingVariant::new("discount".into(), 8.0, 30),
+            ])
+            .unwrap_err(),
+            ParkingError::InvalidVariantWeights(90)
+        );
+    }
+
+    #[test]
+    fn test_stays_keep_their_variant_after_the_experiment_stops() {
+        use crate::{Parkable, ParkingFloor, ParkingLot, Vehicle, VehicleType, clock::MockClock};
+        use chrono::Duration;
+
+        let clock = MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_pricing_strategy(Box::new(FlatHourly::new(2.0)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_pricing_experiment(Some(
+            PricingExperiment::new(vec![PricingVariant::new("trial".into(), 5.0, 100)]).unwrap(),
+        ));
+        let ticket = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "EXP001".into(),
+            ))
+            .unwrap();
+
+        let stopped = lot.set_pricing_experiment(None).unwrap();
+        clock.advance(Duration::hours(2));
+        assert_eq!(lot.unpark_vehicle(ticket.ticket_id).unwrap().total, 10.0);
+        let report = stopped.report();
+        assert_eq!((report[0].completed_stays, report[0].revenue), (1, 10.0));
+        assert!(lot.variant_report().is_empty());
+    }
+}
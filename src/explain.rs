@@ -0,0 +1,156 @@
+//! Dry-run explanations of what an open ticket would cost, for justifying disputed
+//! charges: the rates the stay is priced at, then every pricing step in order with the
+//! running total after it. Nothing is closed or charged.
+
+use std::fmt;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{ParkingLot, error::ParkingError, pricing::ChargeKind};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExplanationStep {
+    pub kind: ChargeKind,
+    pub description: String,
+    pub amount: f32,
+    /// Total after this step.
+    pub running_total: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChargeExplanation {
+    pub ticket_id: String,
+    pub entry_time: DateTime<Utc>,
+    /// End of the billed stay: now, or when the ticket was paid.
+    pub billed_until: DateTime<Utc>,
+    /// Completed hours, the unit the rates bill in.
+    pub billed_hours: i64,
+    /// The pricing strategy the stay is priced with.
+    pub rate_plan: String,
+    /// Whether the rates were locked in at entry rather than taken from current pricing.
+    pub rates_locked: bool,
+    pub steps: Vec<ExplanationStep>,
+    pub total: f32,
+}
+
+impl ChargeExplanation {
+    pub fn duration(&self) -> Duration {
+        self.billed_until.signed_duration_since(self.entry_time)
+    }
+
+    /// Sum of the steps of one kind, e.g. every discount.
+    pub fn subtotal(&self, kind: ChargeKind) -> f32 {
+        self.steps
+            .iter()
+            .filter(|s| s.kind == kind)
+            .map(|s| s.amount)
+            .sum()
+    }
+}
+
+impl fmt::Display for ChargeExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Ticket {}: {} min, {} h billed",
+            self.ticket_id,
+            self.duration().num_minutes(),
+            self.billed_hours
+        )?;
+        let locked = if self.rates_locked {
+            "locked at entry"
+        } else {
+            "current"
+        };
+        writeln!(f, "Rates ({locked}): {}", self.rate_plan)?;
+        for step in &self.steps {
+            writeln!(
+                f,
+                "  {:?}: {} {:+.2} = {:.2}",
+                step.kind, step.description, step.amount, step.running_total
+            )?;
+        }
+        write!(f, "Total: ${:.2}", self.total)
+    }
+}
+
+impl ParkingLot {
+    /// Explains what the open ticket would cost if the vehicle left now.
+    pub fn explain_charge(&self, ticket_id: &str) -> Result<ChargeExplanation, ParkingError> {
+        let charge = self.estimate_charge(ticket_id)?;
+        let ticket = self
+            .active_tickets
+            .lock()?
+            .get(ticket_id)
+            .cloned()
+            .ok_or(ParkingError::InvalidTicket)?;
+        let rate_plan = ticket
+            .rate_plan
+            .clone()
+            .unwrap_or_else(|| self.rate_plan_for(&ticket));
+
+        let mut running_total = 0.0;
+        let steps = charge
+            .breakdown
+            .iter()
+            .map(|line| {
+                running_total += line.amount;
+                ExplanationStep {
+                    kind: line.kind,
+                    description: line.description.clone(),
+                    amount: line.amount,
+                    running_total,
+                }
+            })
+            .collect();
+        Ok(ChargeExplanation {
+            ticket_id: charge.ticket_id.clone(),
+            entry_time: charge.entry_time,
+            billed_until: charge.billed_until,
+            billed_hours: charge.duration().num_hours().max(0),
+            rate_plan: format!("{rate_plan:?}"),
+            rates_locked: ticket.rate_plan.is_some(),
+            steps,
+            total: charge.total,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Parkable, ParkingFloor, Vehicle, VehicleType,
+        clock::MockClock,
+        pricing::{DailyCap, FlatHourly},
+    };
+
+    #[test]
+    fn test_explain_charge_walks_through_each_step() {
+        let clock = MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_pricing_strategy(Box::new(DailyCap::new(
+                Box::new(FlatHourly::new(5.0)),
+                40.0,
+            )));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "WHY001".into());
+        let ticket = lot.park_vehicle(car).unwrap();
+        clock.advance(Duration::hours(10) + Duration::minutes(20));
+        let explanation = lot.explain_charge(&ticket.ticket_id).unwrap();
+
+        assert_eq!(explanation.billed_hours, 10);
+        assert!(explanation.rates_locked);
+        let kinds: Vec<ChargeKind> = explanation.steps.iter().map(|s| s.kind).collect();
+        assert_eq!(kinds, vec![ChargeKind::Rate, ChargeKind::Cap]);
+        assert_eq!(explanation.steps[0].running_total, 50.0);
+        assert_eq!(explanation.subtotal(ChargeKind::Cap), -10.0);
+        assert_eq!(explanation.total, 40.0);
+        assert!(explanation.to_string().ends_with("Total: $40.00"));
+
+        // Explaining is a dry run
+        assert_eq!(lot.unpark_vehicle(ticket.ticket_id).unwrap().total, 40.0);
+    }
+}
@@ -0,0 +1,186 @@
+//! Availability forecasts for pre-booking. A forecast combines the reservation calendar
+//! with the occupancy the lot has seen at the same hours of day, and suggests a
+//! reservation price that rises with the demand expected for the window.
+//!
+//! Historical occupancy comes from the lot's occupancy samples, see `analytics`, and is
+//! lot-wide: it's applied to every spot type alike. Hours without samples count as empty.
+
+use chrono::{DateTime, Duration, Timelike, Utc};
+
+use crate::{ParkingLot, SpotType, VehicleType, error::ParkingError};
+
+/// How suggested reservation prices follow forecast demand.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReservationPricing {
+    /// Demand, from 0 to 1, up to which reservations cost the regular rate.
+    pub threshold: f32,
+    /// Multiplier on the regular rate when every spot is expected to be taken.
+    pub max_multiplier: f32,
+}
+
+impl Default for ReservationPricing {
+    fn default() -> Self {
+        Self {
+            threshold: 0.6,
+            max_multiplier: 2.0,
+        }
+    }
+}
+
+impl ReservationPricing {
+    /// Rises linearly from 1 at `threshold` to `max_multiplier` at full demand.
+    pub fn multiplier(&self, demand: f32) -> f32 {
+        if demand <= self.threshold || self.threshold >= 1.0 {
+            return 1.0;
+        }
+        let share = ((demand - self.threshold) / (1.0 - self.threshold)).min(1.0);
+        1.0 + (self.max_multiplier - 1.0) * share
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForecastSlot {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Spots not held by reservations or manual holds.
+    pub reservable: u32,
+    /// Mean occupancy rate seen at this hour of day.
+    pub expected_occupancy: f32,
+    /// Spots expected to be free: the reservable ones, less those drive-ins are expected
+    /// to take.
+    pub expected_available: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReservationForecast {
+    pub spot_type: SpotType,
+    pub spots: u32,
+    /// Hourly, the last slot cut short at the end of the window.
+    pub slots: Vec<ForecastSlot>,
+    /// Spots expected to be free for the whole window.
+    pub expected_available: u32,
+    /// Share of spots expected to be taken in the busiest slot, from 0 to 1.
+    pub demand: f32,
+    /// The regular price of the window.
+    pub base_price: f32,
+    pub suggested_price: f32,
+}
+
+impl ParkingLot {
+    pub fn set_reservation_pricing(&mut self, pricing: ReservationPricing) {
+        self.reservation_pricing = pricing;
+    }
+
+    /// Forecasts how many spots of `spot_type` will be free over `from..until` and
+    /// suggests what to charge a `vehicle_type` for reserving one.
+    pub fn forecast_reservation(
+        &self,
+        spot_type: SpotType,
+        vehicle_type: &VehicleType,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<ReservationForecast, ParkingError> {
+        if until <= from {
+            return Err(ParkingError::InvalidReservationWindow);
+        }
+        let spots = self.spot_count(spot_type)?;
+        let calendar = self.availability_calendar(spot_type, from, until, Duration::hours(1))?;
+        let by_hour = self.analytics_report()?.occupancy_by_hour;
+
+        let slots: Vec<ForecastSlot> = calendar
+            .into_iter()
+            .map(|slot| {
+                let expected_occupancy = by_hour.get(&slot.start.hour()).copied().unwrap_or(0.0);
+                let walk_ins = (spots as f32 * expected_occupancy).round() as u32;
+                ForecastSlot {
+                    start: slot.start,
+                    end: slot.end,
+                    reservable: slot.reservable,
+                    expected_occupancy,
+                    expected_available: slot.reservable.min(spots.saturating_sub(walk_ins)),
+                }
+            })
+            .collect();
+        let expected_available = slots
+            .iter()
+            .map(|slot| slot.expected_available)
+            .min()
+            .unwrap_or(0);
+        let demand = if spots == 0 {
+            1.0
+        } else {
+            1.0 - expected_available as f32 / spots as f32
+        };
+        let base_price = self.pricing.quote(until - from, vehicle_type).total();
+        Ok(ReservationForecast {
+            spot_type,
+            spots,
+            slots,
+            expected_available,
+            demand,
+            base_price,
+            suggested_price: base_price * self.reservation_pricing.multiplier(demand),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, Vehicle, clock::MockClock, pricing::FlatHourly};
+
+    #[test]
+    fn test_busy_hours_raise_the_suggested_price() {
+        let clock = MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_pricing_strategy(Box::new(FlatHourly::new(10.0)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let regular = lot.spot_count(SpotType::Regular).unwrap();
+
+        // Yesterday this hour, every regular spot filled up
+        let car = |n: u32| Vehicle::new(VehicleType::Motor, "Kia".into(), format!("FC{n:03}"));
+        let tickets: Vec<_> = (0..regular)
+            .map(|n| lot.park_vehicle(car(n)).unwrap())
+            .collect();
+        // Sampled a while longer at full occupancy
+        for _ in 0..10 {
+            lot.snapshot();
+        }
+        let busy = lot.now();
+        clock.advance(Duration::hours(1));
+        for ticket in tickets {
+            lot.unpark_vehicle(ticket.ticket_id).unwrap();
+        }
+
+        let tomorrow = busy + Duration::days(1);
+        let quiet = lot
+            .forecast_reservation(
+                SpotType::Regular,
+                &VehicleType::Motor,
+                tomorrow + Duration::hours(3),
+                tomorrow + Duration::hours(5),
+            )
+            .unwrap();
+        assert_eq!(quiet.slots.len(), 2);
+        assert_eq!(quiet.expected_available, regular);
+        assert_eq!(quiet.suggested_price, quiet.base_price);
+
+        let peak = lot
+            .forecast_reservation(
+                SpotType::Regular,
+                &VehicleType::Motor,
+                tomorrow,
+                tomorrow + Duration::hours(2),
+            )
+            .unwrap();
+        // The ramp up averages 0.55, so the hour averages (0.55 + 1) / 2
+        assert!((peak.slots[0].expected_occupancy - 0.775).abs() < 1e-4);
+        assert_eq!(peak.expected_available, 2);
+        assert!((peak.suggested_price - peak.base_price * 1.5).abs() < 1e-3);
+        assert_eq!(
+            lot.forecast_reservation(SpotType::Regular, &VehicleType::Motor, tomorrow, tomorrow),
+            Err(ParkingError::InvalidReservationWindow)
+        );
+    }
+}
@@ -0,0 +1,363 @@
+//! Fraud screening of payments and exits. A lot with a `FraudDetector` asks it about each
+//! ticket payment and exit, and the detector lets the operation through, flags it, holds
+//! it until staff clear it, or blocks it. Everything but a plain allow is recorded as a
+//! `FraudReview` for staff to look at.
+//!
+//! Validations here are passes: a stay validated with a pass isn't billed by the hour, so
+//! one pass showing up on many stays in a short time suggests it's being shared.
+//! Staff closing a ticket and exits during an evacuation aren't screened.
+
+use std::fmt;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{ParkingLot, ParkingTicket, error::ParkingError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FraudOperation {
+    Payment,
+    Exit,
+}
+
+/// What a detector knows about an operation.
+#[derive(Debug, Clone)]
+pub struct FraudCheck<'a> {
+    pub operation: FraudOperation,
+    pub ticket: &'a ParkingTicket,
+    pub at: DateTime<Utc>,
+    /// What the lot is about to charge.
+    pub amount: f32,
+    /// The whole stay so far at the ticket's own rates (the plan locked in at entry, or
+    /// its zone's or experiment variant's), before discounts, passes and payments made
+    /// ahead of exit.
+    pub expected: f32,
+    /// When a vehicle with the same plate last left the lot, if ever.
+    pub previous_exit: Option<DateTime<Utc>>,
+    /// Exit times of earlier stays validated with the ticket's pass.
+    pub validation_uses: Vec<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "decision", content = "reason")]
+pub enum FraudDecision {
+    Allow,
+    /// Let the operation through, recording why it looked unusual.
+    Flag(String),
+    /// Refuse the operation until staff clear the review.
+    Hold(String),
+    Block(String),
+}
+
+pub trait FraudDetector: fmt::Debug + Send + Sync {
+    fn assess(&self, check: &FraudCheck) -> FraudDecision;
+}
+
+/// The built-in checks: a plate leaving again after it already left during this stay is
+/// blocked, a pass used too often is held, and a charge far below the expected price is
+/// flagged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FraudRules {
+    /// Stays one pass may validate within `validation_window`, counting the current one.
+    pub max_validation_uses: u32,
+    pub validation_window: Duration,
+    /// Share of the expected price below which a charge is flagged.
+    pub min_charge_ratio: f32,
+}
+
+impl Default for FraudRules {
+    fn default() -> Self {
+        Self {
+            max_validation_uses: 4,
+            validation_window: Duration::days(1),
+            min_charge_ratio: 0.25,
+        }
+    }
+}
+
+impl FraudDetector for FraudRules {
+    fn assess(&self, check: &FraudCheck) -> FraudDecision {
+        let plate = &check.ticket.vehicle.license_plate;
+        if check.operation == FraudOperation::Exit
+            && check
+                .previous_exit
+                .is_some_and(|exit| exit >= check.ticket.entry_time)
+        {
+            return FraudDecision::Block(format!("{plate} already left during this stay"));
+        }
+        if let Some(pass_id) = &check.ticket.pass_id {
+            let since = check.at - self.validation_window;
+            let uses = 1 + check
+                .validation_uses
+                .iter()
+                .filter(|&&used| used >= since)
+                .count() as u32;
+            if uses > self.max_validation_uses {
+                return FraudDecision::Hold(format!(
+                    "pass {pass_id} validated {uses} stays within {} hours",
+                    self.validation_window.num_hours()
+                ));
+            }
+        } else if check.expected > 0.0 && check.amount < check.expected * self.min_charge_ratio {
+            return FraudDecision::Flag(format!(
+                "charging {:.2} where {:.2} was expected",
+                check.amount, check.expected
+            ));
+        }
+        FraudDecision::Allow
+    }
+}
+
+/// A decision other than allow, kept for staff to review.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FraudReview {
+    pub review_id: String,
+    pub ticket_id: String,
+    pub license_plate: String,
+    pub operation: FraudOperation,
+    #[serde(flatten)]
+    pub decision: FraudDecision,
+    pub at: DateTime<Utc>,
+    /// Set when staff clear a hold; the operation then goes through unscreened.
+    pub cleared_by: Option<String>,
+}
+
+impl ParkingLot {
+    pub fn with_fraud_detector(mut self, detector: Box<dyn FraudDetector>) -> Self {
+        self.fraud_detector = Some(detector);
+        self
+    }
+
+    /// Every review opened so far, oldest first.
+    pub fn fraud_reviews(&self) -> Vec<FraudReview> {
+        self.fraud_reviews.lock().unwrap().clone()
+    }
+
+    /// Lets the held operation through on its next attempt.
+    pub fn clear_fraud_hold(&self, review_id: &str, staff_id: &str) -> Result<(), ParkingError> {
+        let mut reviews = self.fraud_reviews.lock()?;
+        let review = reviews
+            .iter_mut()
+            .find(|review| review.review_id == review_id)
+            .filter(|review| matches!(review.decision, FraudDecision::Hold(_)))
+            .ok_or(ParkingError::FraudReviewNotFound)?;
+        review.cleared_by = Some(staff_id.to_string());
+        Ok(())
+    }
+
+    /// Asks the fraud detector about `operation` on `ticket`, failing if it holds or
+    /// blocks it. Must be called with no lock held, as the detector may call back into
+    /// the lot.
+    pub(crate) fn screen_for_fraud(
+        &self,
+        operation: FraudOperation,
+        ticket: &ParkingTicket,
+        amount: f32,
+    ) -> Result<(), ParkingError> {
+        let Some(detector) = &self.fraud_detector else {
+            return Ok(());
+        };
+        let cleared = self.fraud_reviews.lock()?.iter().any(|review| {
+            review.ticket_id == ticket.ticket_id
+                && review.operation == operation
+                && review.cleared_by.is_some()
+        });
+        if cleared {
+            return Ok(());
+        }
+
+        let now = self.now();
+        let previous_exit = self
+            .ticket_history
+            .exit_times(|closed| closed.vehicle.license_plate == ticket.vehicle.license_plate)?
+            .into_iter()
+            .max();
+        let validation_uses = match &ticket.pass_id {
+            Some(pass_id) => self
+                .ticket_history
+                .exit_times(|closed| closed.pass_id.as_ref() == Some(pass_id))?,
+            None => Vec::new(),
+        };
+        let expected = ticket
+            .rate_plan
+            .clone()
+            .unwrap_or_else(|| self.rate_plan_for(ticket))
+            .quote(now - ticket.entry_time, &ticket.vehicle.vehicle_type)
+            .total();
+        let decision = detector.assess(&FraudCheck {
+            operation,
+            ticket,
+            at: now,
+            amount,
+            expected,
+            previous_exit,
+            validation_uses,
+        });
+        if decision == FraudDecision::Allow {
+            return Ok(());
+        }
+
+        let mut reviews = self.fraud_reviews.lock()?;
+        let review_id = format!("fraud_{}", reviews.len() + 1);
+        reviews.push(FraudReview {
+            review_id: review_id.clone(),
+            ticket_id: ticket.ticket_id.clone(),
+            license_plate: ticket.vehicle.license_plate.clone(),
+            operation,
+            decision: decision.clone(),
+            at: now,
+            cleared_by: None,
+        });
+        match decision {
+            FraudDecision::Hold(_) => Err(ParkingError::HeldForReview(review_id)),
+            FraudDecision::Block(reason) => Err(ParkingError::SuspectedFraud(reason)),
+            FraudDecision::Allow | FraudDecision::Flag(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, Vehicle, VehicleType, payment::PaymentMethod};
+
+    /// Holds every exit and blocks every payment.
+    #[derive(Debug)]
+    struct Strict;
+
+    impl FraudDetector for Strict {
+        fn assess(&self, check: &FraudCheck) -> FraudDecision {
+            match check.operation {
+                FraudOperation::Exit => FraudDecision::Hold("manual check".into()),
+                FraudOperation::Payment => FraudDecision::Block("no payments".into()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_detector_decisions_gate_operations_and_are_recorded() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_fraud_detector(Box::new(Strict));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "FRD001".into());
+        let ticket = lot.park_vehicle(car).unwrap();
+
+        assert_eq!(
+            lot.pay_ticket(&ticket.ticket_id, PaymentMethod::Cash),
+            Err(ParkingError::SuspectedFraud("no payments".into()))
+        );
+        assert_eq!(
+            lot.unpark_vehicle(ticket.ticket_id.clone()).unwrap_err(),
+            ParkingError::HeldForReview("fraud_2".into())
+        );
+        assert!(lot.active_ticket(&ticket.ticket_id).is_some());
+        assert_eq!(
+            lot.clear_fraud_hold("fraud_1", "staff_7"),
+            Err(ParkingError::FraudReviewNotFound)
+        );
+        lot.clear_fraud_hold("fraud_2", "staff_7").unwrap();
+        lot.unpark_vehicle(ticket.ticket_id.clone()).unwrap();
+
+        let reviews = lot.fraud_reviews();
+        assert_eq!(reviews.len(), 2);
+        assert_eq!(reviews[1].operation, FraudOperation::Exit);
+        assert_eq!(reviews[1].cleared_by.as_deref(), Some("staff_7"));
+    }
+
+    #[test]
+    fn test_rules_flag_hold_and_block_unusual_patterns() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "FRD002".into());
+        let ticket = lot.park_vehicle(car).unwrap();
+        let rules = FraudRules::default();
+        let at = ticket.entry_time + Duration::hours(3);
+        let mut check = FraudCheck {
+            operation: FraudOperation::Exit,
+            ticket: &ticket,
+            at,
+            amount: 5.0,
+            expected: 30.0,
+            previous_exit: None,
+            validation_uses: Vec::new(),
+        };
+        assert!(matches!(rules.assess(&check), FraudDecision::Flag(_)));
+        check.previous_exit = Some(at - Duration::hours(1));
+        assert!(matches!(rules.assess(&check), FraudDecision::Block(_)));
+
+        let mut validated = ticket.clone();
+        validated.pass_id = Some("pass_1".into());
+        let mut check = FraudCheck {
+            ticket: &validated,
+            previous_exit: None,
+            ..check
+        };
+        // Passes aren't billed by the hour, so the low charge is expected
+        assert_eq!(rules.assess(&check), FraudDecision::Allow);
+        check.validation_uses = (1..=4).map(|h| at - Duration::hours(h)).collect();
+        assert!(matches!(rules.assess(&check), FraudDecision::Hold(_)));
+        check.validation_uses[0] = at - Duration::days(2);
+        assert_eq!(rules.assess(&check), FraudDecision::Allow);
+    }
+
+    /// Looks the ticket up in the lot it screens for.
+    #[derive(Debug, Default, Clone)]
+    struct CallsBack {
+        lot: std::sync::Arc<std::sync::OnceLock<std::sync::Weak<ParkingLot>>>,
+    }
+
+    impl FraudDetector for CallsBack {
+        fn assess(&self, check: &FraudCheck) -> FraudDecision {
+            let lot = self.lot.get().and_then(|lot| lot.upgrade()).unwrap();
+            match lot.active_ticket(&check.ticket.ticket_id) {
+                Some(_) => FraudDecision::Allow,
+                None => FraudDecision::Block("unknown ticket".into()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_detector_may_call_back_into_the_lot() {
+        let detector = CallsBack::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_fraud_detector(Box::new(detector.clone()));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_payment_processor(
+            crate::payment::PaymentMethodKind::Cash,
+            Box::new(crate::payment::CashProcessor),
+        );
+        let lot = std::sync::Arc::new(lot);
+        detector.lot.set(std::sync::Arc::downgrade(&lot)).unwrap();
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "FRD003".into());
+        let ticket = lot.park_vehicle(car).unwrap();
+
+        lot.pay_ticket(&ticket.ticket_id, PaymentMethod::Cash)
+            .unwrap();
+        lot.unpark_vehicle(ticket.ticket_id).unwrap();
+        assert!(lot.fraud_reviews().is_empty());
+    }
+
+    #[test]
+    fn test_charges_are_expected_at_the_rates_locked_in_at_entry() {
+        let clock = crate::clock::MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_pricing_strategy(Box::new(crate::pricing::FlatHourly::new(2.0)))
+            .with_fraud_detector(Box::new(FraudRules::default()));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_payment_processor(
+            crate::payment::PaymentMethodKind::Cash,
+            Box::new(crate::payment::CashProcessor),
+        );
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "FRD004".into());
+        let ticket = lot.park_vehicle(car).unwrap();
+        lot.set_pricing_strategy(Box::new(crate::pricing::FlatHourly::new(20.0)));
+        clock.advance(Duration::hours(3));
+
+        let payment = lot
+            .pay_ticket(&ticket.ticket_id, PaymentMethod::Cash)
+            .unwrap();
+        assert_eq!(payment.amount, 6.0);
+        assert!(lot.fraud_reviews().is_empty());
+    }
+}
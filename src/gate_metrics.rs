@@ -0,0 +1,200 @@
+//! Performance metrics for staffing: vehicles handled, handling time and failures per
+//! gate (entrance or exit panel) and per attendant. An attendant is credited with what
+//! their gate handles while they're assigned to it. Only entries at an entrance and exits
+//! at an exit are counted; payments taken ahead of exit are not.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{ParkingLot, error::ParkingError};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GateMetrics {
+    /// Vehicles let in or out.
+    pub processed: u32,
+    /// Attempts that failed, e.g. no spot free or payment required.
+    pub errors: u32,
+    /// Time spent on the processed vehicles.
+    #[serde(rename = "handling_ms", with = "crate::persistence::millis")]
+    pub handling_time: Duration,
+    pub first_at: Option<DateTime<Utc>>,
+    pub last_at: Option<DateTime<Utc>>,
+}
+
+impl GateMetrics {
+    fn record(&mut self, at: DateTime<Utc>, elapsed: Duration, succeeded: bool) {
+        if succeeded {
+            self.processed += 1;
+            self.handling_time += elapsed;
+        } else {
+            self.errors += 1;
+        }
+        self.first_at.get_or_insert(at);
+        self.last_at = Some(at);
+    }
+
+    /// Share of attempts that failed, from 0 to 1.
+    pub fn error_rate(&self) -> f32 {
+        let attempts = self.processed + self.errors;
+        if attempts == 0 {
+            0.0
+        } else {
+            self.errors as f32 / attempts as f32
+        }
+    }
+
+    pub fn average_handling_time(&self) -> Option<Duration> {
+        (self.processed > 0).then(|| self.handling_time / self.processed)
+    }
+
+    /// Throughput between the first and last attempt; `None` until they're apart.
+    pub fn vehicles_per_hour(&self) -> Option<f32> {
+        let span = self.last_at? - self.first_at?;
+        (span > chrono::Duration::zero())
+            .then(|| self.processed as f32 * 3600.0 / span.num_seconds().max(1) as f32)
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct GateMetricsBook {
+    pub(crate) gates: HashMap<String, GateMetrics>,
+    pub(crate) attendants: HashMap<String, GateMetrics>,
+    /// Attendant on duty at each staffed panel.
+    pub(crate) on_duty: HashMap<String, String>,
+}
+
+/// Metrics for every gate and attendant, each ordered by id or name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaffingReport {
+    pub gates: Vec<(String, GateMetrics)>,
+    pub attendants: Vec<(String, GateMetrics)>,
+}
+
+impl ParkingLot {
+    /// Puts `attendant` on duty at the panel, or with `None` leaves it unstaffed.
+    pub fn assign_attendant(
+        &self,
+        panel_id: &str,
+        attendant: Option<String>,
+    ) -> Result<(), ParkingError> {
+        if !self.entrance_panels.lock()?.contains_key(panel_id)
+            && !self.exit_panels.lock()?.contains_key(panel_id)
+        {
+            return Err(ParkingError::PanelNotFound(panel_id.to_string()));
+        }
+        let mut book = self.gate_metrics.lock()?;
+        match attendant {
+            Some(attendant) => book.on_duty.insert(panel_id.to_string(), attendant),
+            None => book.on_duty.remove(panel_id),
+        };
+        Ok(())
+    }
+
+    pub fn attendant_on_duty(&self, panel_id: &str) -> Option<String> {
+        self.gate_metrics
+            .lock()
+            .unwrap()
+            .on_duty
+            .get(panel_id)
+            .cloned()
+    }
+
+    pub fn gate_metrics(&self, panel_id: &str) -> GateMetrics {
+        let book = self.gate_metrics.lock().unwrap();
+        book.gates.get(panel_id).cloned().unwrap_or_default()
+    }
+
+    pub fn attendant_metrics(&self, attendant: &str) -> GateMetrics {
+        let book = self.gate_metrics.lock().unwrap();
+        book.attendants.get(attendant).cloned().unwrap_or_default()
+    }
+
+    pub fn staffing_report(&self) -> StaffingReport {
+        let book = self.gate_metrics.lock().unwrap();
+        let sorted = |metrics: &HashMap<String, GateMetrics>| {
+            let mut rows: Vec<(String, GateMetrics)> = metrics
+                .iter()
+                .map(|(id, m)| (id.clone(), m.clone()))
+                .collect();
+            rows.sort_by(|a, b| a.0.cmp(&b.0));
+            rows
+        };
+        StaffingReport {
+            gates: sorted(&book.gates),
+            attendants: sorted(&book.attendants),
+        }
+    }
+
+    /// Records a vehicle handled at `panel_id` since `started`, crediting the attendant
+    /// on duty there.
+    pub(crate) fn record_gate(&self, panel_id: &str, started: Instant, succeeded: bool) {
+        let elapsed = started.elapsed();
+        let at = self.now();
+        let mut book = self.gate_metrics.lock().unwrap();
+        book.gates
+            .entry(panel_id.to_string())
+            .or_default()
+            .record(at, elapsed, succeeded);
+        if let Some(attendant) = book.on_duty.get(panel_id).cloned() {
+            book.attendants
+                .entry(attendant)
+                .or_default()
+                .record(at, elapsed, succeeded);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ParkingFloor, Vehicle, VehicleType,
+        panel::{EntrancePanel, ExitPanel},
+    };
+
+    #[test]
+    fn test_gates_and_attendants_track_throughput_and_errors() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_payment_required_before_exit(true);
+        lot.add_entrance_panel(EntrancePanel::new("north".into(), "North gate".into()))
+            .unwrap();
+        lot.add_exit_panel(ExitPanel::new("south".into(), "South gate".into()))
+            .unwrap();
+        assert!(lot.assign_attendant("west", Some("Tunde".into())).is_err());
+        lot.assign_attendant("north", Some("Tunde".into())).unwrap();
+        lot.assign_attendant("south", Some("Ngozi".into())).unwrap();
+
+        let car = |plate: &str| Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into());
+        let ticket = lot.park_at_entrance("north", car("AAA111")).unwrap();
+        lot.park_at_entrance("north", car("BBB222")).unwrap();
+        for ticket in lot.active_tickets.lock().unwrap().values_mut() {
+            ticket.entry_time -= chrono::Duration::minutes(61);
+        }
+        assert!(lot.unpark_at_exit("south", &ticket.ticket_id).is_err());
+
+        let north = lot.gate_metrics("north");
+        assert_eq!((north.processed, north.errors), (2, 0));
+        assert!(north.average_handling_time().is_some());
+        let south = lot.gate_metrics("south");
+        assert_eq!((south.processed, south.errors), (0, 1));
+        assert_eq!(south.error_rate(), 1.0);
+        assert_eq!(south.average_handling_time(), None);
+
+        assert_eq!(lot.attendant_metrics("Tunde").processed, 2);
+        assert_eq!(lot.attendant_metrics("Ngozi").errors, 1);
+        lot.assign_attendant("north", None).unwrap();
+        lot.park_at_entrance("north", car("CCC333")).unwrap();
+        assert_eq!(lot.attendant_metrics("Tunde").processed, 2);
+
+        let report = lot.staffing_report();
+        let gates: Vec<&str> = report.gates.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(gates, vec!["north", "south"]);
+        assert_eq!(report.attendants.len(), 2);
+    }
+}
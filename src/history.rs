@@ -0,0 +1,341 @@
+//! Archive of completed stays, shared between lots so a user's history covers every lot
+//! they have parked at, and the per-lot history of closed tickets.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Datelike, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{ParkingLot, ParkingTicket, User, error::ParkingError, plate::normalize_plate};
+
+/// Closed tickets kept per lot; older ones are dropped. A lot's stays outlive this in a
+/// `TicketArchive`.
+pub const HISTORY_CAPACITY: usize = 100_000;
+
+/// One completed stay, recorded when the vehicle leaves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StayRecord {
+    pub ticket_id: String,
+    pub lot_uid: String,
+    pub lot_name: String,
+    pub license_plate: String,
+    pub entry_time: DateTime<Utc>,
+    pub exit_time: DateTime<Utc>,
+    pub charge: f32,
+}
+
+impl StayRecord {
+    pub fn duration(&self) -> Duration {
+        self.exit_time.signed_duration_since(self.entry_time)
+    }
+}
+
+/// Clones share the same records, so one archive can be attached to several lots.
+#[derive(Debug, Clone, Default)]
+pub struct TicketArchive {
+    records: Arc<Mutex<Vec<StayRecord>>>,
+}
+
+impl TicketArchive {
+    pub fn record(&self, record: StayRecord) -> Result<(), ParkingError> {
+        self.records.lock()?.push(record);
+        Ok(())
+    }
+
+    /// Stays of the given vehicles, oldest first.
+    pub fn stays_for(&self, license_plates: &[&str]) -> Result<Vec<StayRecord>, ParkingError> {
+        let license_plates: Vec<String> =
+            license_plates.iter().map(|p| normalize_plate(p)).collect();
+        let mut stays: Vec<StayRecord> = self
+            .records
+            .lock()?
+            .iter()
+            .filter(|r| license_plates.contains(&r.license_plate))
+            .cloned()
+            .collect();
+        stays.sort_by_key(|r| r.entry_time);
+        Ok(stays)
+    }
+}
+
+/// A closed ticket together with the amount it was billed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletedTicket {
+    pub ticket: ParkingTicket,
+    pub total: f32,
+}
+
+/// The last `HISTORY_CAPACITY` tickets closed at one lot, in the order the vehicles
+/// left. Range queries match on exit time and treat `start` as inclusive and `end` as
+/// exclusive.
+#[derive(Debug, Default)]
+pub struct TicketHistory {
+    closed: Mutex<ClosedTickets>,
+}
+
+#[derive(Debug, Default)]
+struct ClosedTickets {
+    entries: VecDeque<CompletedTicket>,
+    ticket_ids: HashSet<String>,
+}
+
+impl ClosedTickets {
+    fn push(&mut self, entry: CompletedTicket) {
+        if self.entries.len() == HISTORY_CAPACITY
+            && let Some(dropped) = self.entries.pop_front()
+        {
+            self.ticket_ids.remove(&dropped.ticket.ticket_id);
+        }
+        self.ticket_ids.insert(entry.ticket.ticket_id.clone());
+        self.entries.push_back(entry);
+    }
+}
+
+impl TicketHistory {
+    pub(crate) fn record(&self, entry: CompletedTicket) -> Result<(), ParkingError> {
+        self.closed.lock()?.push(entry);
+        Ok(())
+    }
+
+    /// Whether `ticket_id` is among the tickets still kept.
+    pub(crate) fn contains(&self, ticket_id: &str) -> Result<bool, ParkingError> {
+        Ok(self.closed.lock()?.ticket_ids.contains(ticket_id))
+    }
+
+    pub fn completed_tickets(&self) -> Result<Vec<CompletedTicket>, ParkingError> {
+        Ok(self.closed.lock()?.entries.iter().cloned().collect())
+    }
+
+    pub fn tickets_for_license_plate(
+        &self,
+        license_plate: &str,
+    ) -> Result<Vec<CompletedTicket>, ParkingError> {
+        let license_plate = normalize_plate(license_plate);
+        self.filtered(|e| e.ticket.vehicle.license_plate == license_plate)
+    }
+
+    pub fn tickets_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<CompletedTicket>, ParkingError> {
+        self.filtered(|e| e.ticket.exit_time.is_some_and(|t| t >= start && t < end))
+    }
+
+    pub fn revenue_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<f32, ParkingError> {
+        Ok(self
+            .tickets_between(start, end)?
+            .iter()
+            .map(|e| e.total)
+            .sum())
+    }
+
+    /// Exit times of the closed tickets `keep` matches, without cloning them.
+    pub(crate) fn exit_times(
+        &self,
+        keep: impl Fn(&ParkingTicket) -> bool,
+    ) -> Result<Vec<DateTime<Utc>>, ParkingError> {
+        Ok(self
+            .closed
+            .lock()?
+            .entries
+            .iter()
+            .filter(|e| keep(&e.ticket))
+            .filter_map(|e| e.ticket.exit_time)
+            .collect())
+    }
+
+    fn filtered(
+        &self,
+        keep: impl Fn(&CompletedTicket) -> bool,
+    ) -> Result<Vec<CompletedTicket>, ParkingError> {
+        Ok(self
+            .closed
+            .lock()?
+            .entries
+            .iter()
+            .filter(|e| keep(e))
+            .cloned()
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonthlySpending {
+    pub year: i32,
+    pub month: u32,
+    pub stays: u32,
+    pub total_minutes: i64,
+    pub total_spent: f32,
+}
+
+impl User {
+    /// Completed stays of this user's registered vehicles, oldest first. Stays of
+    /// vehicles that have since been removed from the account are not included.
+    pub fn parking_history(
+        &self,
+        archive: &TicketArchive,
+    ) -> Result<Vec<StayRecord>, ParkingError> {
+        let plates: Vec<&str> = self.vehicles.values().map(|v| v.license_plate()).collect();
+        archive.stays_for(&plates)
+    }
+
+    /// Spending per calendar month (by exit date, UTC), oldest month first.
+    pub fn spending_summary(
+        &self,
+        archive: &TicketArchive,
+    ) -> Result<Vec<MonthlySpending>, ParkingError> {
+        let mut summary: Vec<MonthlySpending> = Vec::new();
+        for stay in self.parking_history(archive)? {
+            let (year, month) = (stay.exit_time.year(), stay.exit_time.month());
+            let index = match summary
+                .iter()
+                .position(|m| m.year == year && m.month == month)
+            {
+                Some(index) => index,
+                None => {
+                    summary.push(MonthlySpending {
+                        year,
+                        month,
+                        stays: 0,
+                        total_minutes: 0,
+                        total_spent: 0.0,
+                    });
+                    summary.len() - 1
+                }
+            };
+            let entry = &mut summary[index];
+            entry.stays += 1;
+            entry.total_minutes += stay.duration().num_minutes();
+            entry.total_spent += stay.charge;
+        }
+        summary.sort_by_key(|m| (m.year, m.month));
+        Ok(summary)
+    }
+}
+
+impl ParkingLot {
+    /// Records every completed stay at this lot in `archive`.
+    pub fn set_ticket_archive(&mut self, archive: TicketArchive) {
+        self.archive = Some(archive);
+    }
+
+    /// Tickets closed at this lot.
+    pub fn ticket_history(&self) -> &TicketHistory {
+        &self.ticket_history
+    }
+
+    /// Why no open ticket has `ticket_id`: `TicketClosed` if the vehicle has left,
+    /// otherwise `InvalidTicket`. A ticket dropped from the history counts as invalid.
+    pub(crate) fn missing_ticket(&self, ticket_id: &str) -> ParkingError {
+        match self.ticket_history.contains(ticket_id) {
+            Ok(true) => ParkingError::TicketClosed,
+            Ok(false) => ParkingError::InvalidTicket,
+            Err(err) => err,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Account, Parkable, ParkingFloor, Vehicle, VehicleType};
+
+    #[test]
+    fn test_history_spans_lots_and_sums_by_month() {
+        let archive = TicketArchive::default();
+        let mut lots = Vec::new();
+        for uid in ["1", "2"] {
+            let mut lot = ParkingLot::new(format!("Lot {uid}"), "Lagos".into(), uid.into());
+            lot.add_floor(ParkingFloor::new(1)).unwrap();
+            lot.set_ticket_archive(archive.clone());
+            lots.push(lot);
+        }
+
+        let vehicle = Vehicle::new(VehicleType::Motor, "Kia".into(), "AAA111".into());
+        let mut user = User::new("Ada".into(), "555".into());
+        user.register_vehicle(vehicle.clone());
+        for lot in &lots {
+            let ticket = lot.park_vehicle(vehicle.clone()).unwrap();
+            lot.unpark_vehicle(ticket.ticket_id).unwrap();
+        }
+        let stranger = Vehicle::new(VehicleType::Motor, "Kia".into(), "ZZZ999".into());
+        let ticket = lots[0].park_vehicle(stranger).unwrap();
+        lots[0].unpark_vehicle(ticket.ticket_id).unwrap();
+
+        let history = user.parking_history(&archive).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].lot_uid, "2");
+
+        let summary = user.spending_summary(&archive).unwrap();
+        assert_eq!(summary.len(), 1);
+        assert_eq!(summary[0].stays, 2);
+    }
+
+    #[test]
+    fn test_closed_tickets_move_to_lot_history() {
+        let mut lot = ParkingLot::new("Lot".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let start = Utc::now();
+        for plate in ["AAA111", "BBB222", "AAA111"] {
+            let vehicle = Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into());
+            let ticket = lot.park_vehicle(vehicle).unwrap();
+            lot.unpark_vehicle(ticket.ticket_id).unwrap();
+        }
+        let end = Utc::now() + Duration::seconds(1);
+
+        let history = lot.ticket_history();
+        assert_eq!(history.completed_tickets().unwrap().len(), 3);
+        assert_eq!(
+            history.tickets_for_license_plate("AAA111").unwrap().len(),
+            2
+        );
+        assert_eq!(history.tickets_between(start, end).unwrap().len(), 3);
+        assert!(
+            history
+                .tickets_between(end, end + Duration::hours(1))
+                .unwrap()
+                .is_empty()
+        );
+        let billed: f32 = history
+            .completed_tickets()
+            .unwrap()
+            .iter()
+            .map(|e| e.total)
+            .sum();
+        assert_eq!(history.revenue_between(start, end).unwrap(), billed);
+        assert!(lot.active_tickets.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_history_keeps_only_the_latest_tickets() {
+        let history = TicketHistory::default();
+        let vehicle = Vehicle::new(VehicleType::Motor, "Kia".into(), "AAA111".into());
+        let ticket = ParkingTicket::new(String::new(), vehicle, "spot_0".into(), Utc::now());
+        for n in 0..=HISTORY_CAPACITY {
+            let mut ticket = ticket.clone();
+            ticket.ticket_id = format!("TKT_{n}");
+            history
+                .record(CompletedTicket { ticket, total: 1.0 })
+                .unwrap();
+        }
+
+        let kept = history.completed_tickets().unwrap();
+        assert_eq!(kept.len(), HISTORY_CAPACITY);
+        assert_eq!(kept[0].ticket.ticket_id, "TKT_1");
+        assert!(!history.contains("TKT_0").unwrap());
+        assert!(history.contains("TKT_1").unwrap());
+        assert!(
+            history
+                .contains(&format!("TKT_{HISTORY_CAPACITY}"))
+                .unwrap()
+        );
+    }
+}
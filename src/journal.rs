@@ -0,0 +1,224 @@
+//! Per-spot journal of state transitions, for troubleshooting a spot that shows the
+//! wrong state and for fine-grained utilization figures. Each spot keeps its most recent
+//! transitions in memory; older ones are dropped.
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{ParkingLot, ParkingSpot, error::ParkingError};
+
+/// Transitions kept per spot.
+pub const JOURNAL_CAPACITY: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SpotState {
+    Free,
+    Reserved,
+    Leased,
+    OutOfService,
+    /// Handed out at the gate, vehicle not yet arrived.
+    Claimed,
+    Occupied,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransitionCause {
+    Parked,
+    ArrivalConfirmed,
+    Unparked,
+    ClaimExpired,
+    Reserved,
+    ReservationCancelled,
+    ReservationExpired,
+    CheckedIn,
+    Leased,
+    LeaseEnded,
+    /// A priority vehicle took the spot from its reservation or lease.
+    Preempted,
+    TakenOutOfService,
+    ReturnedToService,
+    BatchRolledBack,
+    /// An attendant moved the parked vehicle from or to the spot.
+    Relocated,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpotTransition {
+    pub from: SpotState,
+    pub to: SpotState,
+    pub at: DateTime<Utc>,
+    pub cause: TransitionCause,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SpotJournal {
+    pub(crate) entries: VecDeque<SpotTransition>,
+}
+
+impl SpotJournal {
+    fn push(&mut self, transition: SpotTransition) {
+        if self.entries.len() == JOURNAL_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(transition);
+    }
+}
+
+impl ParkingSpot {
+    pub fn state(&self) -> SpotState {
+        if self.is_reserved() {
+            SpotState::Reserved
+        } else if self.out_of_service_reason().is_some() {
+            SpotState::OutOfService
+        } else if !self.is_occupied() && self.is_leased() {
+            SpotState::Leased
+        } else if !self.is_occupied() {
+            SpotState::Free
+        } else if self.is_claimed() {
+            SpotState::Claimed
+        } else {
+            SpotState::Occupied
+        }
+    }
+
+    /// Recent transitions, oldest first.
+    pub fn transitions(&self) -> Vec<SpotTransition> {
+        self.journal.entries.iter().cloned().collect()
+    }
+
+    /// Runs `change` and journals the resulting transition at `at`, the lot's time, if
+    /// the state changed.
+    pub(crate) fn transition<R>(
+        &mut self,
+        cause: TransitionCause,
+        at: DateTime<Utc>,
+        change: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        let from = self.state();
+        let result = change(self);
+        let to = self.state();
+        if from != to {
+            self.journal.push(SpotTransition {
+                from,
+                to,
+                at,
+                cause,
+            });
+        }
+        result
+    }
+}
+
+impl ParkingLot {
+    pub fn spot_transitions(
+        &self,
+        floor_id: u32,
+        spot_id: &str,
+    ) -> Result<Vec<SpotTransition>, ParkingError> {
+        self.with_floor_spot_mut(floor_id, spot_id, |spot| spot.transitions())?
+            .ok_or(ParkingError::SpotNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Parkable, ParkingFloor, SpotType, Vehicle, VehicleType,
+        admin::{Admin, LotAdministration},
+        clock::MockClock,
+    };
+
+    #[test]
+    fn test_journal_follows_claim_arrival_and_exit() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.set_transit_hold(Some(chrono::Duration::minutes(5)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let ticket = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "AAA111".into(),
+            ))
+            .unwrap();
+        lot.confirm_arrival(1, &ticket.spot_id).unwrap();
+        lot.unpark_vehicle(ticket.ticket_id).unwrap();
+
+        let steps: Vec<_> = lot
+            .spot_transitions(1, &ticket.spot_id)
+            .unwrap()
+            .iter()
+            .map(|t| (t.from, t.to, t.cause))
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                (SpotState::Free, SpotState::Claimed, TransitionCause::Parked),
+                (
+                    SpotState::Claimed,
+                    SpotState::Occupied,
+                    TransitionCause::ArrivalConfirmed
+                ),
+                (
+                    SpotState::Occupied,
+                    SpotState::Free,
+                    TransitionCause::Unparked
+                ),
+            ]
+        );
+
+        let mut spot = ParkingSpot::new(true, SpotType::Regular);
+        let now = lot.now();
+        for i in 0..JOURNAL_CAPACITY {
+            let vehicle = Vehicle::new(VehicleType::Motor, "Kia".into(), format!("P{i}"));
+            spot.transition(TransitionCause::Parked, now, |s| s.assign_vehicle(vehicle))
+                .unwrap();
+            spot.transition(TransitionCause::Unparked, now, |s| s.remove_vehicle());
+        }
+        assert_eq!(spot.transitions().len(), JOURNAL_CAPACITY);
+    }
+
+    #[test]
+    fn test_out_of_service_changes_are_journaled_on_the_lot_clock() {
+        let clock = MockClock::new(Utc::now() - chrono::Duration::days(2));
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let admin = Admin::new("Bola".into(), "ST-7".into());
+
+        let taken_out = lot.now();
+        admin
+            .mark_spot_out_of_service(&lot, 1, "spot_4", "Pothole".into())
+            .unwrap();
+        clock.advance(chrono::Duration::hours(3));
+        admin.return_spot_to_service(&lot, 1, "spot_4").unwrap();
+
+        let steps: Vec<_> = lot
+            .spot_transitions(1, "spot_4")
+            .unwrap()
+            .iter()
+            .map(|t| (t.to, t.cause, t.at))
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                (
+                    SpotState::OutOfService,
+                    TransitionCause::TakenOutOfService,
+                    taken_out
+                ),
+                (
+                    SpotState::Free,
+                    TransitionCause::ReturnedToService,
+                    lot.now()
+                ),
+            ]
+        );
+        assert_eq!(
+            lot.spot_transitions(2, "spot_4"),
+            Err(ParkingError::SpotNotFound)
+        );
+    }
+}
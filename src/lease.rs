@@ -0,0 +1,402 @@
+//! Blocks of spots leased to third parties such as car-share companies or delivery
+//! fleets. A leased spot is withheld from public allocation from the moment the lease is
+//! signed until `release_expired_leases` runs after it ends. The lessee is billed per spot
+//! and day, so stays on leased spots cost nothing at the exit.
+
+use std::sync::atomic::Ordering;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    LEASE_COUNTER, ParkingLot, ParkingTicket, Vehicle, batch::Effects, calendar::SpotHold,
+    error::ParkingError, journal::TransitionCause,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpotLease {
+    pub lease_id: String,
+    pub lessee: String,
+    /// Floor and spot id of each leased spot; spot ids repeat across floors. Leases saved
+    /// before floors were recorded load without any and are given theirs by the lot.
+    #[serde(default, with = "leased_spots")]
+    pub spots: Vec<(u32, String)>,
+    pub from: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub daily_rate: f32,
+}
+
+/// Leased spots are saved as `{"floor_id": 1, "spot_id": "spot_4"}`.
+mod leased_spots {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct LeasedSpot {
+        floor_id: u32,
+        spot_id: String,
+    }
+
+    pub(super) fn serialize<S: Serializer>(
+        spots: &[(u32, String)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(spots.iter().map(|(floor_id, spot_id)| LeasedSpot {
+            floor_id: *floor_id,
+            spot_id: spot_id.clone(),
+        }))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(u32, String)>, D::Error> {
+        let spots = Vec::<LeasedSpot>::deserialize(deserializer)?;
+        Ok(spots
+            .into_iter()
+            .map(|spot| (spot.floor_id, spot.spot_id))
+            .collect())
+    }
+}
+
+impl SpotLease {
+    pub fn is_active_at(&self, at: DateTime<Utc>) -> bool {
+        self.from <= at && at < self.until
+    }
+
+    /// Started days in the lease window.
+    pub fn billable_days(&self) -> i64 {
+        let window = self.until.signed_duration_since(self.from);
+        let days = window.num_days();
+        if window > Duration::days(days) {
+            days + 1
+        } else {
+            days
+        }
+    }
+
+    pub fn amount_due(&self) -> f32 {
+        self.daily_rate * self.spots.len() as f32 * self.billable_days() as f32
+    }
+}
+
+/// What the lessee owes for one lease, with the number of stays it covered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaseStatement {
+    pub lease_id: String,
+    pub lessee: String,
+    pub spot_count: u32,
+    pub days: i64,
+    pub stays: u32,
+    pub amount: f32,
+}
+
+impl ParkingLot {
+    fn generate_lease_id(&self) -> String {
+        format!("LSE_{}", LEASE_COUNTER.fetch_add(1, Ordering::SeqCst))
+    }
+
+    /// Leases `spots`, given as floor and spot id, to `lessee` for the `from..until`
+    /// window. Every spot must be empty and not held by a reservation or another lease;
+    /// otherwise nothing is leased.
+    pub fn lease_spots(
+        &self,
+        lessee: String,
+        spots: &[(u32, &str)],
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+        daily_rate: f32,
+    ) -> Result<SpotLease, ParkingError> {
+        let now = self.now();
+        if until <= from || until <= now || spots.is_empty() {
+            return Err(ParkingError::InvalidLeaseWindow);
+        }
+
+        let floors = self.floors.lock()?;
+        for (floor_id, spot_id) in spots {
+            let floor = floors.get(floor_id).ok_or(ParkingError::FloorNotFound)?;
+            let floor_spots = floor.spots.lock()?;
+            let spot = floor_spots
+                .get(*spot_id)
+                .ok_or(ParkingError::SpotNotFound)?;
+            if spot.is_occupied() {
+                return Err(ParkingError::SpotOccupied);
+            } else if !spot.is_available() {
+                return Err(ParkingError::SpotUnavailable);
+            }
+        }
+
+        let lease = SpotLease {
+            lease_id: self.generate_lease_id(),
+            lessee,
+            spots: spots
+                .iter()
+                .map(|(floor_id, spot_id)| (*floor_id, spot_id.to_string()))
+                .collect(),
+            from,
+            until,
+            daily_rate,
+        };
+        for (floor_id, spot_id) in spots {
+            floors[floor_id]
+                .spots
+                .lock()?
+                .get_mut(*spot_id)
+                .unwrap()
+                .transition(TransitionCause::Leased, now, |spot| {
+                    spot.leased_by = Some(lease.lease_id.clone())
+                });
+        }
+        drop(floors);
+
+        self.leases
+            .lock()?
+            .insert(lease.lease_id.clone(), lease.clone());
+        Ok(lease)
+    }
+
+    pub fn lease(&self, lease_id: &str) -> Option<SpotLease> {
+        self.leases.lock().unwrap().get(lease_id).cloned()
+    }
+
+    /// Leases held by `lessee`, oldest first.
+    pub fn leases_for(&self, lessee: &str) -> Vec<SpotLease> {
+        let mut leases: Vec<SpotLease> = self
+            .leases
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|lease| lease.lessee == lessee)
+            .cloned()
+            .collect();
+        leases.sort_by_key(|lease| lease.from);
+        leases
+    }
+
+    /// Parks one of the lessee's vehicles on a free spot of the lease.
+    pub fn park_leased(
+        &self,
+        lease_id: &str,
+        vehicle: Vehicle,
+    ) -> Result<ParkingTicket, ParkingError> {
+        let now = self.now();
+        let lease = self.lease(lease_id).ok_or(ParkingError::LeaseNotFound)?;
+        if !lease.is_active_at(now) {
+            return Err(ParkingError::LeaseNotActive);
+        }
+        if self.active_evacuation().is_some() {
+            return Err(ParkingError::EvacuationInProgress);
+        }
+        let _plate = self.reserve_plate(&vehicle)?;
+
+        let mut parked_spot_id = None;
+        {
+            let floors = self.floors.lock()?;
+            for (floor_id, spot_id) in &lease.spots {
+                let Some(floor) = floors.get(floor_id) else {
+                    continue;
+                };
+                let mut spots = floor.spots.lock()?;
+                let Some(spot) = spots.get_mut(spot_id).filter(|spot| {
+                    !spot.is_occupied()
+                        && spot.leased_by.as_deref() == Some(lease_id)
+                        && spot.accepts(&vehicle)
+                }) else {
+                    continue;
+                };
+                let parked = spot.transition(TransitionCause::Parked, now, |spot| {
+                    spot.assign_vehicle(vehicle.clone())
+                });
+                if parked.is_ok() {
+                    parked_spot_id = Some(spot_id.clone());
+                    break;
+                }
+            }
+        }
+        let spot_id = parked_spot_id.ok_or(ParkingError::NoSpotAvailable)?;
+
+        self.issue_ticket_with(
+            vehicle,
+            spot_id,
+            |ticket| ticket.lease_id = Some(lease.lease_id.clone()),
+            &mut Effects::immediate(),
+        )
+    }
+
+    /// The lease a stay is billed to: the one whose spot the vehicle parked on, while it
+    /// was active at entry.
+    pub(crate) fn lease_covering(&self, ticket: &ParkingTicket) -> Option<SpotLease> {
+        let lease = self.lease(ticket.lease_id.as_deref()?)?;
+        lease.is_active_at(ticket.entry_time).then_some(lease)
+    }
+
+    pub fn lease_statement(&self, lease_id: &str) -> Result<LeaseStatement, ParkingError> {
+        let lease = self.lease(lease_id).ok_or(ParkingError::LeaseNotFound)?;
+        let covers = |ticket: &ParkingTicket| {
+            ticket.lease_id.as_deref() == Some(lease_id) && lease.is_active_at(ticket.entry_time)
+        };
+        let open = self
+            .active_tickets
+            .lock()?
+            .values()
+            .filter(|ticket| covers(ticket))
+            .count();
+        let closed = self
+            .ticket_history
+            .completed_tickets()?
+            .iter()
+            .filter(|entry| covers(&entry.ticket))
+            .count();
+        Ok(LeaseStatement {
+            lease_id: lease.lease_id.clone(),
+            lessee: lease.lessee.clone(),
+            spot_count: lease.spots.len() as u32,
+            days: lease.billable_days(),
+            stays: (open + closed) as u32,
+            amount: lease.amount_due(),
+        })
+    }
+
+    /// A hold on each spot still withheld by a lease, until the lease ends.
+    pub(crate) fn lease_holds(&self) -> Result<Vec<SpotHold>, ParkingError> {
+        let leases = self.leases.lock()?.clone();
+        let floors = self.floors.lock()?;
+        let mut holds = Vec::new();
+        for floor in floors.values() {
+            for spot in floor.spots.lock()?.values() {
+                if let Some(lease) = spot.leased_by.as_ref().and_then(|id| leases.get(id)) {
+                    holds.push(SpotHold::new(
+                        spot.spot_type,
+                        DateTime::<Utc>::MIN_UTC,
+                        lease.until,
+                    ));
+                }
+            }
+        }
+        Ok(holds)
+    }
+
+    /// Returns the spots of leases that ended before `now` to public use. Returns the
+    /// ended lease ids.
+    pub fn release_expired_leases(&self, now: DateTime<Utc>) -> Vec<String> {
+        let mut ended: Vec<String> = self
+            .leases
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|lease| lease.until <= now)
+            .map(|lease| lease.lease_id.clone())
+            .collect();
+        ended.sort();
+
+        let floors = self.floors.lock().unwrap();
+        let mut released = Vec::new();
+        for floor in floors.values() {
+            for spot in floor.spots.lock().unwrap().values_mut() {
+                if let Some(lease_id) = spot.leased_by.clone().filter(|id| ended.contains(id)) {
+                    spot.transition(TransitionCause::LeaseEnded, now, |spot| {
+                        spot.leased_by = None
+                    });
+                    released.push(lease_id);
+                }
+            }
+        }
+        ended.retain(|id| released.contains(id));
+        ended
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, VehicleType, clock::MockClock};
+
+    #[test]
+    fn test_leased_spots_are_private_and_billed_to_lessee() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let from = Utc::now() - Duration::hours(1);
+        let lease = lot
+            .lease_spots(
+                "ShareCo".into(),
+                &[(1, "spot_0"), (1, "spot_1")],
+                from,
+                from + Duration::hours(36),
+                20.0,
+            )
+            .unwrap();
+        assert_eq!(lot.display_info().unwrap().num_empty_spots(), 8);
+        assert!(matches!(
+            lot.lease_spots(
+                "Other".into(),
+                &[(1, "spot_1")],
+                from,
+                from + Duration::days(1),
+                5.0
+            ),
+            Err(ParkingError::SpotUnavailable)
+        ));
+
+        let fleet_car = Vehicle::new(VehicleType::Motor, "Kia".into(), "FLEET1".into());
+        let ticket = lot.park_leased(&lease.lease_id, fleet_car).unwrap();
+        assert_eq!(ticket.lease_id.as_deref(), Some(lease.lease_id.as_str()));
+        assert!(lease.spots.contains(&(1, ticket.spot_id.clone())));
+        let public = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "PUB1".into(),
+            ))
+            .unwrap();
+        assert!(!lease.spots.contains(&(1, public.spot_id)));
+
+        let charge = lot.unpark_vehicle(ticket.ticket_id).unwrap();
+        assert_eq!(charge.total, 0.0);
+        let statement = lot.lease_statement(&lease.lease_id).unwrap();
+        assert_eq!((statement.days, statement.stays), (2, 1));
+        assert_eq!(statement.amount, 80.0);
+
+        assert_eq!(
+            lot.release_expired_leases(from + Duration::days(2)),
+            vec![lease.lease_id]
+        );
+        assert_eq!(lot.display_info().unwrap().num_empty_spots(), 9);
+    }
+
+    #[test]
+    fn test_lease_covers_only_its_own_floor() {
+        let clock = MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.add_floor(ParkingFloor::new(2)).unwrap();
+        let from = lot.now() - Duration::hours(1);
+        let lease = lot
+            .lease_spots(
+                "Fleet".into(),
+                &[(1, "spot_3")],
+                from,
+                from + Duration::days(1),
+                20.0,
+            )
+            .unwrap();
+        assert_eq!(
+            lot.lease_spots("Other".into(), &[(9, "spot_3")], from, lease.until, 5.0),
+            Err(ParkingError::FloorNotFound)
+        );
+        lot.close_floor(1).unwrap();
+
+        // Fill floor 2 until a walk-in lands on its own spot_3
+        let public = (0..10)
+            .map(|i| {
+                let plate = format!("PUB{i}");
+                let car = Vehicle::new(VehicleType::Motor, "Kia".into(), plate);
+                lot.park_vehicle(car).unwrap()
+            })
+            .find(|ticket| ticket.spot_id == "spot_3")
+            .unwrap();
+        assert_eq!(public.lease_id, None);
+        clock.advance(Duration::hours(2));
+        let charge = lot.unpark_vehicle(public.ticket_id).unwrap();
+        assert!(charge.total > 0.0);
+        assert_eq!(lot.lease_statement(&lease.lease_id).unwrap().stays, 0);
+    }
+}
@@ -1,32 +1,189 @@
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
+    collections::{HashMap, HashSet, hash_map::Entry},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
 };
 
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+pub mod admin;
+pub mod admission;
+pub mod allocation;
+pub mod analytics;
+pub mod app;
+#[cfg(feature = "async")]
+pub mod async_lot;
+pub mod audit;
+pub mod batch;
+pub mod calendar;
+pub mod capacity;
+pub mod charging;
+pub mod clock;
+pub mod compatibility;
+pub mod compliance;
+pub mod conversion;
+pub mod custody;
+pub mod display;
+pub mod drop_off;
+pub mod eligibility;
+pub mod entry_policy;
+pub mod error;
+pub mod evacuation;
+pub mod events;
+pub mod experiment;
+pub mod explain;
+pub mod forecast;
+pub mod fraud;
+pub mod gate_metrics;
+pub mod history;
+pub mod journal;
+pub mod lease;
+pub mod locator;
+pub mod maintenance;
+pub mod notification;
+pub mod operator;
+pub mod overstay;
+pub mod panel;
+pub mod parking_zone;
+pub mod pass;
+pub mod payment;
+#[cfg(feature = "pdf")]
+pub mod pdf;
+pub mod persistence;
+pub mod plate;
+pub mod pricing;
+pub mod priority;
+pub mod quota;
+pub mod rebalancing;
+pub mod relocation;
+pub mod replication;
+pub mod reservation;
+pub mod schedule;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod shutdown;
+pub mod signing;
+pub mod standing;
+pub mod tags;
+pub mod ticket_id;
+pub mod ticket_signing;
+pub mod timeline;
+pub mod units;
+pub mod valet;
+pub mod view;
+pub mod webhook;
+pub mod zones;
+
+use admin::Admin;
+use admission::{AdmissionPolicy, EntryQueue};
+use allocation::{AllocationStrategy, BestFit, spot_candidates};
+use analytics::{OccupancyLog, RejectionReason};
+use app::AppSessions;
+use audit::AuditEntry;
+use batch::{Effect, Effects};
+use calendar::SpotHold;
+use capacity::OccupancyLimits;
+use charging::ChargingSession;
+use clock::{Clock, SystemClock};
+use compatibility::CompatibilityPolicy;
+use conversion::SpotConversion;
+use custody::CustodySession;
+use drop_off::DropOffZones;
+use eligibility::{
+    DiscountSchedule, Eligibility, Verification, VerificationAction, VerificationAudit,
+};
+use entry_policy::{EntryPolicies, EntryRequest};
+use error::ParkingError;
+use evacuation::Evacuation;
+use events::{InventoryChange, ParkingEvent, Subscribers};
+use experiment::{PricingExperiment, VariantStats};
+use forecast::ReservationPricing;
+use fraud::{FraudDetector, FraudOperation, FraudReview};
+use gate_metrics::GateMetricsBook;
+use history::{CompletedTicket, StayRecord, TicketArchive, TicketHistory};
+use journal::{SpotJournal, TransitionCause};
+use lease::SpotLease;
+use maintenance::MaintenanceSchedule;
+use notification::{Contact, Notification, Notifier, Template, TemplateKind, TemplateSet};
+use overstay::OverstayPolicy;
+use panel::{EntrancePanel, ExitPanel};
+use parking_zone::ParkingZone;
+use pass::PassRegistry;
+use payment::{
+    CashRounding, Payment, PaymentMethodKind, PaymentProcessor, PreAuthorizationPolicy, to_cents,
+};
+use plate::LicensePlate;
+use pricing::{ChargeKind, ChargeLine, FlatHourly, PricingStrategy, Surge, Surged};
+use priority::PriorityClass;
+use quota::{QuotaEnforcement, QuotaWarning, SpotQuota};
+use relocation::Relocation;
+use replication::FenceLease;
+use reservation::Reservation;
+use schedule::{ClosurePeriod, OperatingSchedule};
+use standing::StandingReservation;
+use tags::SpotTag;
+use ticket_id::{IdGenerator, UuidIds};
+use units::{Dimensions, LengthUnit};
+use valet::ValetDesk;
+use view::ViewSlot;
+use webhook::WebhookDispatcher;
+use zones::{NoParkingZone, ZoneIncident};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum SpotType {
     Large,
     Regular,
     XLarge,
     Handicapped,
+    /// Has a charger; only electric vehicles may use it.
+    Electric,
 }
 
-#[derive(Debug, Clone)]
+impl SpotType {
+    pub const ALL: [SpotType; 5] = [
+        SpotType::Regular,
+        SpotType::Large,
+        SpotType::XLarge,
+        SpotType::Handicapped,
+        SpotType::Electric,
+    ];
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum VehicleType {
     Motor,
     Truck,
     Bike,
+    Electric,
 }
 
-#[derive(Debug, Clone)]
+impl VehicleType {
+    pub const ALL: [VehicleType; 4] = [
+        VehicleType::Motor,
+        VehicleType::Truck,
+        VehicleType::Bike,
+        VehicleType::Electric,
+    ];
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum PaymentStatus {
     Succeeded,
     Failed,
     Pending,
 }
 
+/// Sequence numbers behind generated ids. Restoring a saved lot moves them past the
+/// restored ids so new ones don't collide.
+pub(crate) static TICKET_COUNTER: AtomicU64 = AtomicU64::new(0);
+pub(crate) static SPOT_COUNTER: AtomicU64 = AtomicU64::new(0);
+pub(crate) static RESERVATION_COUNTER: AtomicU64 = AtomicU64::new(0);
+pub(crate) static LEASE_COUNTER: AtomicU64 = AtomicU64::new(0);
+pub(crate) static VALET_COUNTER: AtomicU64 = AtomicU64::new(0);
+
 // === PARKING LOT ===
 
 #[derive(Debug)]
@@ -36,6 +193,91 @@ pub struct ParkingLot {
     uid: String,
     floors: Arc<Mutex<HashMap<u32, ParkingFloor>>>,
     active_tickets: Arc<Mutex<HashMap<String, ParkingTicket>>>,
+    templates: TemplateSet,
+    notifier: Option<Box<dyn Notifier>>,
+    /// Where to send notices about each plate's stays.
+    contacts: Mutex<HashMap<String, Contact>>,
+    ticket_ids: Box<dyn IdGenerator>,
+    webhooks: WebhookDispatcher,
+    pricing_experiment: Option<Arc<PricingExperiment>>,
+    discounts: DiscountSchedule,
+    manual_holds: Mutex<Vec<SpotHold>>,
+    reservation_pricing: ReservationPricing,
+    compatibility: Arc<CompatibilityPolicy>,
+    length_unit: LengthUnit,
+    transit_hold: Option<chrono::Duration>,
+    schedule: OperatingSchedule,
+    closed_floors: Mutex<HashSet<u32>>,
+    pricing: Arc<dyn PricingStrategy>,
+    allocation: Box<dyn AllocationStrategy>,
+    no_parking_zones: Mutex<Vec<NoParkingZone>>,
+    parking_zones: Mutex<HashMap<String, Arc<ParkingZone>>>,
+    zone_incidents: Mutex<Vec<ZoneIncident>>,
+    drop_off_zones: Mutex<DropOffZones>,
+    maintenance: Mutex<MaintenanceSchedule>,
+    app_sessions: Mutex<AppSessions>,
+    payment_processors: HashMap<PaymentMethodKind, Box<dyn PaymentProcessor>>,
+    pre_authorization: Option<PreAuthorizationPolicy>,
+    /// Payments taken ahead of exit, oldest first per ticket.
+    payments: Mutex<HashMap<String, Vec<Payment>>>,
+    cash_rounding: Option<CashRounding>,
+    payment_required_before_exit: bool,
+    /// How long a driver who paid ahead of exit has to leave before more time is billed.
+    exit_grace: chrono::Duration,
+    /// Tickets whose payment is with the processor.
+    paying: Mutex<HashSet<String>>,
+    /// Plates of vehicles being given a spot; locked after `active_tickets`.
+    entering_plates: Mutex<HashSet<LicensePlate>>,
+    fraud_detector: Option<Box<dyn FraudDetector>>,
+    fraud_reviews: Mutex<Vec<FraudReview>>,
+    ticket_signing_key: Option<String>,
+    view: Arc<ViewSlot>,
+    entrance_panels: Mutex<HashMap<String, EntrancePanel>>,
+    exit_panels: Mutex<HashMap<String, ExitPanel>>,
+    gate_metrics: Mutex<GateMetricsBook>,
+    reservations: Mutex<HashMap<String, Reservation>>,
+    /// How early before its window a reservation holds its spot and may be checked in.
+    reservation_grace: chrono::Duration,
+    standing_reservations: Mutex<HashMap<String, StandingReservation>>,
+    valet: Mutex<ValetDesk>,
+    leases: Mutex<HashMap<String, SpotLease>>,
+    passes: PassRegistry,
+    limits: InventoryLimits,
+    occupancy_limits: OccupancyLimits,
+    /// Entries turned away by each vehicle type cap.
+    vehicle_type_cap_refusals: Mutex<HashMap<VehicleType, u32>>,
+    rejections: Mutex<HashMap<(VehicleType, RejectionReason), u32>>,
+    entry_policies: EntryPolicies,
+    /// Plates turned away at the entrance, with the reason.
+    blocked_plates: Mutex<HashMap<String, String>>,
+    overstay_policy: OverstayPolicy,
+    /// Tickets already announced as overstaying.
+    reported_overstays: Mutex<HashSet<String>>,
+    /// Epoch this instance holds when it runs under a replication fence.
+    fence: Option<FenceLease>,
+    /// Set once shutdown begins; shared with the `LotHandle` running the lot.
+    draining: Arc<AtomicBool>,
+    archive: Option<TicketArchive>,
+    ticket_history: TicketHistory,
+    occupancy_log: Mutex<OccupancyLog>,
+    admission: Option<AdmissionPolicy>,
+    entry_queue: Mutex<EntryQueue>,
+    charging_rate: f32,
+    charging_sessions: Mutex<HashMap<String, ChargingSession>>,
+    custody_sessions: Mutex<HashMap<String, CustodySession>>,
+    /// Every evacuation so far; the last one is running if it hasn't ended.
+    evacuations: Mutex<Vec<Evacuation>>,
+    audit_log: Mutex<Vec<AuditEntry>>,
+    spot_conversions: Mutex<Vec<SpotConversion>>,
+    subscribers: Subscribers,
+    clock: Box<dyn Clock>,
+}
+
+/// Upper bounds on lot inventory, enforced when floors and spots are added.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InventoryLimits {
+    pub max_floors: Option<u32>,
+    pub max_spots_per_floor: Option<u32>,
 }
 
 pub struct ParkingLotDisplayBoard {
@@ -43,34 +285,155 @@ pub struct ParkingLotDisplayBoard {
     num_floors: u32,
     num_empty_spots: u32,
     num_parked_vehicles: u32,
+    num_claimed_spots: u32,
+    num_reserved_spots: u32,
+    num_out_of_service_spots: u32,
+    num_closed_floors: u32,
+    available_by_vehicle_type: HashMap<VehicleType, u32>,
+    entry_queue_length: u32,
+    estimated_entry_wait: Option<chrono::Duration>,
 }
 
-#[derive(Debug, Clone)]
+/// Vehicles still on a closed floor. The floor is drained once this is empty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloorDrainStatus {
+    pub floor_id: u32,
+    pub occupied_spot_ids: Vec<String>,
+}
+
+impl FloorDrainStatus {
+    pub fn is_drained(&self) -> bool {
+        self.occupied_spot_ids.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParkingTicket {
     pub ticket_id: String,
     pub vehicle: Vehicle,
     pub spot_id: String,
+    /// Floor of `spot_id`, as spot ids repeat across floors. Unknown for closed tickets
+    /// saved before floors were recorded.
+    pub floor_id: Option<u32>,
+    /// Type of the spot when the vehicle parked, kept for reports if the spot is converted.
+    pub spot_type: Option<SpotType>,
     pub entry_time: DateTime<Utc>,
     pub exit_time: Option<DateTime<Utc>>,
     pub payment_status: PaymentStatus,
+    /// Pricing experiment variant assigned at entry, if an experiment was running.
+    pub pricing_variant: Option<String>,
+    /// The experiment `pricing_variant` belongs to. Not saved; a reloaded ticket looks its
+    /// variant up in the lot's current experiment.
+    #[serde(skip)]
+    pub(crate) experiment: Option<Arc<PricingExperiment>>,
+    /// Card hold placed at entry for ticketless (pay-by-plate) stays.
+    pub pre_authorization: Option<String>,
+    /// Entrance and exit panels the vehicle passed through, when parked via panels.
+    pub entrance_id: Option<String>,
+    pub exit_id: Option<String>,
+    /// Evacuation that began while the vehicle was parked.
+    pub evacuation_id: Option<String>,
+    /// Pass the stay is billed to, when the vehicle entered on a valid one.
+    pub pass_id: Option<String>,
+    /// Lease the stay is billed to, when the vehicle parked on a leased spot.
+    pub lease_id: Option<String>,
+    /// Parking zone the vehicle was parked in, priced by the zone's own strategy.
+    pub zone_id: Option<String>,
+    /// Rates in force when the vehicle entered; later pricing changes don't apply to the
+    /// stay. Priced at the current rates when unset.
+    #[serde(default, with = "pricing::saved")]
+    pub rate_plan: Option<Arc<dyn PricingStrategy>>,
+    /// Demand surge included in `rate_plan`, if the lot was busy at entry.
+    pub surge: Option<Surge>,
+    /// Moves by attendants, oldest first. `spot_id` is where the vehicle is now.
+    #[serde(default)]
+    pub relocations: Vec<Relocation>,
+    /// Time added to the maximum stay from the companion app.
+    #[serde(default, rename = "stay_extension_secs", with = "persistence::seconds")]
+    pub stay_extension: chrono::Duration,
 }
 
 impl ParkingTicket {
-    pub fn new(ticket_id: String, vehicle: Vehicle, spot_id: String) -> Self {
+    /// A ticket for `vehicle` parking on `spot_id` from `entry_time`.
+    pub fn new(
+        ticket_id: String,
+        vehicle: Vehicle,
+        spot_id: String,
+        entry_time: DateTime<Utc>,
+    ) -> Self {
         Self {
             ticket_id,
             vehicle,
             spot_id,
-            entry_time: Utc::now(),
+            floor_id: None,
+            spot_type: None,
+            entry_time,
             exit_time: None,
             payment_status: PaymentStatus::Pending,
+            pricing_variant: None,
+            experiment: None,
+            pre_authorization: None,
+            entrance_id: None,
+            exit_id: None,
+            evacuation_id: None,
+            pass_id: None,
+            lease_id: None,
+            zone_id: None,
+            rate_plan: None,
+            surge: None,
+            relocations: Vec::new(),
+            stay_extension: chrono::Duration::zero(),
         }
     }
+
+    /// Six-character code derived from the ticket id, short enough to type at a kiosk.
+    pub fn short_code(&self) -> String {
+        const ALPHABET: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+        let hash = fnv1a_hash(&self.ticket_id);
+        (0..6)
+            .map(|i| ALPHABET[((hash >> (i * 5)) & 0x1f) as usize] as char)
+            .collect()
+    }
 }
 
+/// FNV-1a, used where a hash must stay stable across runs and platforms.
+pub(crate) fn fnv1a_hash(value: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in value.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[derive(Debug, Clone)]
 pub struct ParkingCharge {
+    pub ticket_id: String,
+    pub entry_time: DateTime<Utc>,
+    /// End of the billed stay: the exit, an earlier payment, or the time quoted for.
+    pub billed_until: DateTime<Utc>,
     pub total: f32,
     pub chargeback: f32,
+    /// Amount taken off the total by an eligibility discount.
+    pub discount: f32,
+    /// Penalty for staying past the maximum stay.
+    pub fine: f32,
+    /// Taken ahead of exit with `pay_ticket`; part of `total`.
+    pub paid: f32,
+    /// How the total was computed, one line per pricing step; the lines sum to `total`.
+    pub breakdown: Vec<ChargeLine>,
+}
+
+impl ParkingCharge {
+    /// Length of the billed stay.
+    pub fn duration(&self) -> chrono::Duration {
+        self.billed_until.signed_duration_since(self.entry_time)
+    }
+
+    /// What is still owed once the payment taken ahead of exit is counted.
+    pub fn amount_due(&self) -> f32 {
+        (self.total - self.paid).max(0.0)
+    }
 }
 
 impl ParkingLot {
@@ -81,124 +444,1219 @@ impl ParkingLot {
             uid,
             floors: Arc::new(Mutex::new(HashMap::new())),
             active_tickets: Arc::new(Mutex::new(HashMap::new())),
+            templates: TemplateSet::default(),
+            notifier: None,
+            contacts: Mutex::new(HashMap::new()),
+            ticket_ids: Box::new(UuidIds),
+            webhooks: WebhookDispatcher::default(),
+            pricing_experiment: None,
+            discounts: DiscountSchedule::default(),
+            manual_holds: Mutex::new(Vec::new()),
+            reservation_pricing: ReservationPricing::default(),
+            compatibility: Arc::default(),
+            length_unit: LengthUnit::default(),
+            transit_hold: None,
+            schedule: OperatingSchedule::default(),
+            closed_floors: Mutex::new(HashSet::new()),
+            pricing: Arc::new(FlatHourly::default()),
+            allocation: Box::new(BestFit),
+            no_parking_zones: Mutex::new(Vec::new()),
+            parking_zones: Mutex::new(HashMap::new()),
+            zone_incidents: Mutex::new(Vec::new()),
+            drop_off_zones: Mutex::new(DropOffZones::default()),
+            maintenance: Mutex::new(MaintenanceSchedule::default()),
+            app_sessions: Mutex::new(AppSessions::default()),
+            payment_processors: HashMap::new(),
+            pre_authorization: None,
+            payments: Mutex::new(HashMap::new()),
+            cash_rounding: None,
+            payment_required_before_exit: false,
+            exit_grace: chrono::Duration::minutes(15),
+            paying: Mutex::new(HashSet::new()),
+            entering_plates: Mutex::new(HashSet::new()),
+            fraud_detector: None,
+            fraud_reviews: Mutex::new(Vec::new()),
+            ticket_signing_key: None,
+            view: Arc::default(),
+            entrance_panels: Mutex::new(HashMap::new()),
+            exit_panels: Mutex::new(HashMap::new()),
+            gate_metrics: Mutex::new(GateMetricsBook::default()),
+            reservations: Mutex::new(HashMap::new()),
+            reservation_grace: chrono::Duration::minutes(15),
+            standing_reservations: Mutex::new(HashMap::new()),
+            valet: Mutex::new(ValetDesk::default()),
+            leases: Mutex::new(HashMap::new()),
+            passes: PassRegistry::default(),
+            limits: InventoryLimits::default(),
+            occupancy_limits: OccupancyLimits::default(),
+            vehicle_type_cap_refusals: Mutex::new(HashMap::new()),
+            rejections: Mutex::new(HashMap::new()),
+            entry_policies: EntryPolicies::default(),
+            blocked_plates: Mutex::new(HashMap::new()),
+            overstay_policy: OverstayPolicy::default(),
+            reported_overstays: Mutex::new(HashSet::new()),
+            fence: None,
+            draining: Arc::new(AtomicBool::new(false)),
+            archive: None,
+            ticket_history: TicketHistory::default(),
+            occupancy_log: Mutex::new(OccupancyLog::default()),
+            admission: None,
+            entry_queue: Mutex::new(EntryQueue::default()),
+            charging_rate: 0.0,
+            charging_sessions: Mutex::new(HashMap::new()),
+            custody_sessions: Mutex::new(HashMap::new()),
+            evacuations: Mutex::new(Vec::new()),
+            audit_log: Mutex::new(Vec::new()),
+            spot_conversions: Mutex::new(Vec::new()),
+            subscribers: Subscribers::default(),
+            clock: Box::new(SystemClock),
         }
     }
 
-    fn generate_ticket_id(&self) -> String {
-        static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
-        format!("TKT_{}", COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst))
+    pub fn with_pricing_strategy(mut self, strategy: Box<dyn PricingStrategy>) -> Self {
+        self.pricing = strategy.into();
+        self
     }
 
-    pub fn add_floor(&mut self, floor: ParkingFloor) {
-        let mut floors = self.floors.lock().unwrap();
-        floors.insert(floor.id, floor);
+    /// Replaces the lot's pricing for vehicles entering afterwards. Vehicles already
+    /// parked keep the rates they entered under.
+    pub fn set_pricing_strategy(&mut self, strategy: Box<dyn PricingStrategy>) {
+        self.pricing = strategy.into();
     }
 
-    pub fn get_floor_by_id(&self, id: u32) -> Option<ParkingFloor> {
-        let floors = self.floors.lock().unwrap();
-        floors.get(&id).cloned()
+    pub fn with_allocation_strategy(mut self, strategy: Box<dyn AllocationStrategy>) -> Self {
+        self.allocation = strategy;
+        self
     }
 
-    pub fn display_info(&self) -> ParkingLotDisplayBoard {
-        let floors = self.floors.lock().unwrap();
-        ParkingLotDisplayBoard {
-            uid: self.uid.clone(),
-            num_floors: floors.len() as u32,
-            num_empty_spots: floors
-                .values()
-                .map(|f| f.spots.lock().unwrap().len() as u32)
-                .sum(),
-            num_parked_vehicles: floors
-                .values()
-                .map(|f| {
-                    f.spots
-                        .lock()
-                        .unwrap()
-                        .values()
-                        .filter(|s| !s.is_free)
-                        .count() as u32
-                })
-                .sum(),
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    pub fn uid(&self) -> &str {
+        &self.uid
+    }
+
+    pub fn webhooks(&self) -> &WebhookDispatcher {
+        &self.webhooks
+    }
+
+    /// Endpoint registration, transport and retry configuration.
+    pub fn webhooks_mut(&mut self) -> &mut WebhookDispatcher {
+        &mut self.webhooks
+    }
+
+    /// Sends the webhook deliveries that are due: new events, and retries whose backoff has
+    /// elapsed. Call periodically; raising an event only queues its deliveries.
+    pub fn deliver_webhooks(&self) -> usize {
+        self.webhooks.process_due(self.now())
+    }
+
+    fn emit(&self, event: ParkingEvent) {
+        self.publish_event(event);
+    }
+
+    fn publish_event(&self, event: ParkingEvent) {
+        let now = self.now();
+        if matches!(
+            event,
+            ParkingEvent::VehicleParked { .. } | ParkingEvent::VehicleUnparked { .. }
+        ) {
+            self.snapshot();
         }
+        self.webhooks.enqueue(&event, now);
+        self.notify_subscribers(&event);
     }
-}
 
-pub trait Parkable {
-    fn park_vehicle(&self, vehicle: Vehicle) -> Result<ParkingTicket, String>;
-    fn unpark_vehicle(&self, ticket_id: String) -> Result<ParkingCharge, String>;
-}
+    /// Starts (or with `None`, stops) a pricing experiment for newly entering vehicles and
+    /// returns the one it replaces. Tickets already issued keep the variant they were
+    /// tagged with: they are billed at its rates, and their stays still count towards the
+    /// replaced experiment's report as they end.
+    pub fn set_pricing_experiment(
+        &mut self,
+        experiment: Option<PricingExperiment>,
+    ) -> Option<Arc<PricingExperiment>> {
+        std::mem::replace(&mut self.pricing_experiment, experiment.map(Arc::new))
+    }
 
-impl Parkable for ParkingLot {
-    fn park_vehicle(&self, vehicle: Vehicle) -> Result<ParkingTicket, String> {
-        let available_spot = {
-            let floors = self.floors.lock().unwrap();
-            floors.values().find_map(|floor| {
-                floor.find_available_spot(vehicle.vehicle_type.clone())
-            })
-        }.ok_or("No available spots")?;
+    pub fn pricing_experiment(&self) -> Option<&PricingExperiment> {
+        self.pricing_experiment.as_deref()
+    }
 
-        let (floor_number, spot_id) = available_spot;
+    pub fn variant_report(&self) -> Vec<VariantStats> {
+        self.pricing_experiment
+            .as_ref()
+            .map(|e| e.report())
+            .unwrap_or_default()
+    }
 
-        // Assign vehicle to spot
-        let mut floors = self.floors.lock().unwrap();
-        let floor = floors.get_mut(&floor_number).unwrap();
-        let mut spots = floor.spots.lock().unwrap();
-        let spot = spots.get_mut(&spot_id).unwrap();
-        spot.assign_vehicle(vehicle.clone())?;
+    pub fn set_discount(&mut self, eligibility: Eligibility, percent: f32) {
+        self.discounts.set(eligibility, percent);
+    }
+
+    pub fn remove_discount(&mut self, eligibility: Eligibility) {
+        self.discounts.remove(eligibility);
+    }
 
-        // Create ticket
+    /// Creates and stores the ticket for a vehicle that has just been assigned `spot_id`.
+    fn issue_ticket(
+        &self,
+        vehicle: Vehicle,
+        spot_id: String,
+        effects: &mut Effects,
+    ) -> Result<ParkingTicket, ParkingError> {
+        self.issue_ticket_with(vehicle, spot_id, |_| {}, effects)
+    }
+
+    /// Like `issue_ticket`, with `terms` applied to the ticket before its rates are locked
+    /// and it's stored.
+    fn issue_ticket_with(
+        &self,
+        vehicle: Vehicle,
+        spot_id: String,
+        terms: impl FnOnce(&mut ParkingTicket),
+        effects: &mut Effects,
+    ) -> Result<ParkingTicket, ParkingError> {
         let ticket_id = self.generate_ticket_id();
-        let ticket = ParkingTicket::new(ticket_id, vehicle, spot_id);
-
-        // Store active ticket
-        let mut tickets = self.active_tickets.lock().unwrap();
-        let ticket_clone = ticket.clone();
-        tickets.insert(ticket.ticket_id.clone(), ticket);
-
-        println!("Vehicle parked successfully. Ticket ID: {}", ticket_clone.ticket_id);
-        Ok(ticket_clone)
-    }
-
-    fn unpark_vehicle(&self, ticket_id: String) -> Result<ParkingCharge, String> {
-        // Find and remove ticket
-        let mut tickets = self.active_tickets.lock().unwrap();
-        let mut ticket = tickets.remove(&ticket_id).ok_or("Invalid ticket ID")?;
-        
-        // Calculate parking duration and charge
-        let now = Utc::now();
-        let duration = now.signed_duration_since(ticket.entry_time);
-        let hours = duration.num_hours() as f32;
-        let rate = 10.0; // $10 per hour
-        let total = hours * rate;
-        
-        // Free the parking spot
-        let mut floors = self.floors.lock().unwrap();
+        let mut ticket = ParkingTicket::new(ticket_id, vehicle, spot_id, self.now());
+        if let Some((floor_id, spot_type)) =
+            self.parked_spot(&ticket.spot_id, &ticket.vehicle.license_plate)?
+        {
+            ticket.floor_id = Some(floor_id);
+            ticket.spot_type = Some(spot_type);
+        }
+        // Entry policies already turned away vehicles whose passes ran out
+        match self.pass_for_entry(&ticket.vehicle, ticket.entry_time) {
+            Ok(Some(pass)) => ticket.pass_id = Some(pass.pass_id),
+            Ok(None) | Err(ParkingError::PassExpired) => {}
+            Err(e) => return Err(e),
+        }
+        if let Some(experiment) = &self.pricing_experiment {
+            ticket.pricing_variant = Some(
+                experiment
+                    .assign(&ticket.vehicle.license_plate)
+                    .name
+                    .clone(),
+            );
+            ticket.experiment = Some(experiment.clone());
+        }
+        terms(&mut ticket);
+        self.lock_rates(&mut ticket);
+
+        match self.active_tickets.lock()?.entry(ticket.ticket_id.clone()) {
+            Entry::Occupied(_) => return Err(ParkingError::DuplicateTicket(ticket.ticket_id)),
+            Entry::Vacant(slot) => slot.insert(ticket.clone()),
+        };
+
+        let parked = ParkingEvent::VehicleParked {
+            ticket_id: ticket.ticket_id.clone(),
+            license_plate: ticket.vehicle.license_plate.clone(),
+            spot_id: ticket.spot_id.clone(),
+            at: ticket.entry_time,
+        };
+        self.apply_effect(Effect::Event(parked), effects)?;
+        Ok(ticket)
+    }
+
+    /// Unparks on behalf of `user`, applying the best discount their currently valid
+    /// verifications qualify for at this lot.
+    pub fn unpark_vehicle_for_user(
+        &self,
+        ticket_id: String,
+        user: &User,
+    ) -> Result<ParkingCharge, ParkingError> {
+        let eligibilities = user.active_eligibilities(self.now());
+        let discount = self.discounts.best_discount(&eligibilities);
+        self.checkout(ticket_id, discount, None, &mut Effects::immediate())
+    }
+
+    /// Prices a stay from entry until `until`: the pricing strategy's lines, an optional
+    /// eligibility discount, any overstay fine, then EV charging so far.
+    fn price_stay(
+        &self,
+        ticket: &ParkingTicket,
+        until: DateTime<Utc>,
+        discount: Option<(Eligibility, f32)>,
+    ) -> ParkingCharge {
+        let duration = until.signed_duration_since(ticket.entry_time);
+        let strategy = ticket
+            .rate_plan
+            .clone()
+            .unwrap_or_else(|| self.rate_plan_for(ticket));
+        // Stays on a leased spot are covered by the lessee's lease billing
+        let lease = self.lease_covering(ticket);
+        let pass = ticket
+            .pass_id
+            .as_deref()
+            .and_then(|id| self.passes.pass(id));
+        let mut breakdown = match (&lease, &pass) {
+            (Some(lease), _) => vec![
+                ChargeLine::new(format!("Lease {} ({})", lease.lease_id, lease.lessee), 0.0)
+                    .with_kind(ChargeKind::Coverage),
+            ],
+            (None, Some(pass)) => {
+                let mut lines = vec![
+                    ChargeLine::new(format!("{:?} pass {}", pass.period, pass.pass_id), 0.0)
+                        .with_kind(ChargeKind::Coverage),
+                ];
+                // Time past the end of the pass is billed as usual
+                if until > pass.valid_until {
+                    let overstay =
+                        until.signed_duration_since(pass.valid_until.max(ticket.entry_time));
+                    lines.extend(strategy.quote(overstay, &ticket.vehicle.vehicle_type).lines);
+                }
+                lines
+            }
+            (None, None) => strategy.quote(duration, &ticket.vehicle.vehicle_type).lines,
+        };
+        let discount = discount.filter(|_| lease.is_none());
+        let gross: f32 = breakdown.iter().map(|l| l.amount).sum();
+        let discount = match discount {
+            Some((eligibility, percent)) => {
+                let amount = gross * percent / 100.0;
+                breakdown.push(
+                    ChargeLine::new(
+                        format!("{:?} discount ({}%)", eligibility, percent),
+                        -amount,
+                    )
+                    .with_kind(ChargeKind::Discount),
+                );
+                amount
+            }
+            None => 0.0,
+        };
+        let mut total = gross - discount;
+        // Fines aren't discounted; stays on leased spots are the lessee's business
+        let fine = match self.overstay(ticket, until).filter(|_| lease.is_none()) {
+            Some(overstay) if overstay.fine > 0.0 => {
+                breakdown.push(
+                    ChargeLine::new(
+                        format!(
+                            "Overstay fine ({} min over {} h)",
+                            overstay.overstay.num_minutes(),
+                            overstay.max_stay.num_hours()
+                        ),
+                        overstay.fine,
+                    )
+                    .with_kind(ChargeKind::Fine),
+                );
+                overstay.fine
+            }
+            _ => 0.0,
+        };
+        total += fine;
+        // Energy is billed in full; eligibility discounts only apply to parking
+        if let Some(line) = self.charging_line(&ticket.ticket_id) {
+            total += line.amount;
+            breakdown.push(line);
+        }
+        ParkingCharge {
+            ticket_id: ticket.ticket_id.clone(),
+            entry_time: ticket.entry_time,
+            billed_until: until,
+            total,
+            chargeback: 0.0,
+            discount,
+            fine,
+            paid: 0.0,
+            breakdown,
+        }
+    }
+
+    /// What the ticket would cost if the vehicle left now. Nothing is closed or charged.
+    pub fn estimate_charge(&self, ticket_id: &str) -> Result<ParkingCharge, ParkingError> {
+        self.estimate_charge_at(ticket_id, self.now(), None)
+    }
+
+    /// Estimate for leaving at `at`, as `user` if given so their discount is applied.
+    /// Tickets already paid are quoted as they will be at exit: up to the payment and with
+    /// its discount, or on to `at` once the exit grace has run out.
+    pub fn estimate_charge_at(
+        &self,
+        ticket_id: &str,
+        at: DateTime<Utc>,
+        user: Option<&User>,
+    ) -> Result<ParkingCharge, ParkingError> {
+        let ticket_id = &self.canonical_ticket_id(ticket_id);
+        let ticket = self
+            .active_tickets
+            .lock()?
+            .get(ticket_id)
+            .cloned()
+            .ok_or_else(|| self.missing_ticket(ticket_id))?;
+        let payments = self.payments_for_ticket(ticket_id)?;
+        let discount = match payments.first() {
+            Some(payment) => payment.discount,
+            None => {
+                user.and_then(|user| self.discounts.best_discount(&user.active_eligibilities(at)))
+            }
+        };
+        Ok(self.price_paid_stay(&ticket, &payments, at, discount))
+    }
+
+    /// Prices a stay left at `at`. A ticket paid ahead of exit is billed up to its latest
+    /// payment if it leaves within the exit grace, and on to `at` otherwise, with the
+    /// amount already taken recorded against the total.
+    fn price_paid_stay(
+        &self,
+        ticket: &ParkingTicket,
+        payments: &[Payment],
+        at: DateTime<Utc>,
+        discount: Option<(Eligibility, f32)>,
+    ) -> ParkingCharge {
+        let Some(latest) = payments.last() else {
+            return self.price_stay(ticket, at, discount);
+        };
+        let until = if at <= latest.paid_at + self.exit_grace {
+            latest.paid_at
+        } else {
+            at
+        };
+        let mut charge = self.price_stay(ticket, until, discount);
+        // Cash paid ahead of exit may have been rounded; bill what was actually taken
+        let rounding = to_cents(payments.iter().map(|p| p.rounding).sum());
+        if rounding != 0.0 {
+            charge
+                .breakdown
+                .push(ChargeLine::new("Cash rounding", rounding).with_kind(ChargeKind::Rounding));
+            charge.total += rounding;
+        }
+        charge.paid = to_cents(payments.iter().map(|p| p.amount).sum());
+        charge
+    }
+
+    /// Closes the ticket and frees its spot. An admin closing it (`closed_by`) lets the
+    /// vehicle out without payment, as does a running evacuation.
+    fn checkout(
+        &self,
+        ticket_id: String,
+        discount: Option<(Eligibility, f32)>,
+        closed_by: Option<&Admin>,
+        effects: &mut Effects,
+    ) -> Result<ParkingCharge, ParkingError> {
+        // Price the stay under the tickets lock, then screen it and settle the card hold
+        // with no lock held. The ticket is marked as being paid meanwhile, so neither a
+        // payment nor a second exit runs alongside.
+        let ticket_id = self.canonical_ticket_id(&ticket_id);
+        let (ticket, evacuating, now, mut charge) = {
+            let tickets = self.active_tickets.lock()?;
+            let ticket = tickets
+                .get(&ticket_id)
+                .ok_or_else(|| self.missing_ticket(&ticket_id))?;
+            let mut paying = self.paying.lock()?;
+            if paying.contains(&ticket_id) {
+                return Err(ParkingError::PaymentInProgress);
+            }
+            let payments = self.payments_for_ticket(&ticket_id)?;
+            // During an evacuation every exit is free and the gates stay open
+            let evacuating = self.active_evacuation().is_some();
+
+            // Calculate parking duration and charge. A ticket paid ahead of exit is billed as
+            // it was paid for, discount included, unless the driver stayed past the exit grace.
+            let now = self.now();
+            let charge = if evacuating {
+                ParkingCharge {
+                    ticket_id: ticket.ticket_id.clone(),
+                    entry_time: ticket.entry_time,
+                    billed_until: payments.last().map_or(now, |p| p.paid_at),
+                    total: 0.0,
+                    chargeback: 0.0,
+                    discount: 0.0,
+                    fine: 0.0,
+                    paid: 0.0,
+                    breakdown: vec![
+                        ChargeLine::new("Emergency evacuation", 0.0)
+                            .with_kind(ChargeKind::Adjustment),
+                    ],
+                }
+            } else {
+                let discount = payments.first().map_or(discount, |p| p.discount);
+                self.price_paid_stay(ticket, &payments, now, discount)
+            };
+            // Any balance still due, including a top-up for a stay billed on past the exit
+            // grace, must be paid first, unless a card hold will take it
+            if self.payment_required_before_exit
+                && !evacuating
+                && closed_by.is_none()
+                && charge.amount_due() > 0.0
+                && ticket.pre_authorization.is_none()
+            {
+                return Err(ParkingError::PaymentRequired);
+            }
+            paying.insert(ticket_id.clone());
+            (ticket.clone(), evacuating, now, charge)
+        };
+        let variant = self.pricing_variant_for(&ticket);
+        if let Some(admin) = closed_by {
+            charge.breakdown.push(admin::force_close_line(admin));
+        }
+        let billed_until = charge.billed_until;
+        let duration = charge.duration();
+        let total = charge.total;
+        let due = charge.amount_due();
+        let screened = if closed_by.is_none() && !evacuating {
+            self.screen_for_fraud(FraudOperation::Exit, &ticket, total)
+        } else {
+            Ok(())
+        };
+        // Settle the card hold placed at entry before the vehicle is let out, for whatever
+        // wasn't paid ahead of exit
+        let settled = screened.and_then(|()| self.settle_pre_authorization(&ticket, due, duration));
+
+        let mut tickets = self.active_tickets.lock()?;
+        self.paying.lock()?.remove(&ticket_id);
+        let captured = match settled {
+            Ok(captured) => captured,
+            Err(e) => {
+                let settling = matches!(
+                    e,
+                    ParkingError::PaymentFailed(_) | ParkingError::NoPaymentProcessor
+                );
+                if let Some(ticket) = tickets.get_mut(&ticket_id).filter(|_| settling) {
+                    ticket.payment_status = PaymentStatus::Failed;
+                }
+                return Err(e);
+            }
+        };
+        // Neither a second exit nor an expired transit claim closes a ticket marked as
+        // being paid, so a hold captured above always has its ticket to close
+        let mut ticket = tickets
+            .remove(&ticket_id)
+            .ok_or(ParkingError::InvalidTicket)?;
+
+        self.apply_effect(
+            Effect::StopCharging(ticket_id.clone(), billed_until),
+            effects,
+        )?;
+        if let Some((experiment, variant)) = variant.filter(|_| !evacuating) {
+            let stay = Effect::ExperimentStay {
+                experiment,
+                variant,
+                charge: total,
+                minutes: duration.num_minutes(),
+            };
+            self.apply_effect(stay, effects)?;
+        }
+
+        // Free the parking spot. Spot ids repeat across floors, so match the vehicle too.
+        let mut floors = self.floors.lock()?;
         for floor in floors.values_mut() {
-            let mut spots = floor.spots.lock().unwrap();
-            if let Some(spot) = spots.get_mut(&ticket.spot_id) {
-                spot.remove_vehicle();
+            let mut spots = floor.spots.lock()?;
+            if let Some(spot) = spots.get_mut(&ticket.spot_id).filter(|spot| {
+                spot.vehicle.as_ref().map(|v| &v.license_plate)
+                    == Some(&ticket.vehicle.license_plate)
+            }) {
+                spot.transition(TransitionCause::Unparked, now, |spot| spot.remove_vehicle());
                 break;
             }
         }
-        
+
         // Update ticket with exit time
         ticket.exit_time = Some(now);
         ticket.payment_status = PaymentStatus::Succeeded;
-        
-        // Return ticket to active_tickets for record keeping
-        tickets.insert(ticket_id.clone(), ticket);
-        
-        let charge = ParkingCharge {
+        let stay = StayRecord {
+            ticket_id: ticket_id.clone(),
+            lot_uid: self.uid.clone(),
+            lot_name: self.name.clone(),
+            license_plate: ticket.vehicle.license_plate.clone(),
+            entry_time: ticket.entry_time,
+            exit_time: now,
+            charge: total,
+        };
+        self.apply_effect(Effect::Archive(stay), effects)?;
+
+        let event = ParkingEvent::VehicleUnparked {
+            ticket_id: ticket_id.clone(),
+            license_plate: ticket.vehicle.license_plate.clone(),
             total,
-            chargeback: 0.0,
+            at: now,
         };
-        
-        println!("Vehicle unparked successfully. Total charge: ${:.2}", charge.total);
+
+        let receipt = self.notice_for(&ticket.vehicle.license_plate, |locale| {
+            self.receipt_notification(&ticket, &charge, locale)
+        });
+        let closed = CompletedTicket { ticket, total };
+        self.apply_effect(Effect::History(Box::new(closed)), effects)?;
+        self.apply_effect(Effect::Exit(now), effects)?;
+        drop(tickets);
+        drop(floors);
+        if captured {
+            let payment = ParkingEvent::PaymentReceived {
+                ticket_id,
+                amount: due,
+                method: PaymentMethodKind::Card,
+                at: now,
+            };
+            self.apply_effect(Effect::Event(payment), effects)?;
+        }
+        self.apply_effect(Effect::Event(event), effects)?;
+        if let Some((recipient, receipt)) = receipt {
+            self.apply_effect(Effect::Notify(recipient, receipt), effects)?;
+        }
         Ok(charge)
     }
+
+    /// Voids or captures the card hold placed when `ticket` entered, for the `due` amount
+    /// of a stay lasting `duration`. Returns whether anything was captured.
+    fn settle_pre_authorization(
+        &self,
+        ticket: &ParkingTicket,
+        due: f32,
+        duration: chrono::Duration,
+    ) -> Result<bool, ParkingError> {
+        let Some(authorization_id) = &ticket.pre_authorization else {
+            return Ok(false);
+        };
+        let free_window = self
+            .pre_authorization
+            .map_or(chrono::Duration::zero(), |p| p.free_window);
+        match self.payment_processors.get(&PaymentMethodKind::Card) {
+            None => Err(ParkingError::NoPaymentProcessor),
+            Some(processor) if due <= 0.0 || duration <= free_window => processor
+                .void(authorization_id)
+                .map(|()| false)
+                .map_err(ParkingError::PaymentFailed),
+            Some(processor) => processor
+                .capture(authorization_id, due)
+                .map(|_| true)
+                .map_err(ParkingError::PaymentFailed),
+        }
+    }
+
+    /// Locks the rates in force for `ticket` into it as it enters, with a surge on top when
+    /// they are demand-based and the lot is busy.
+    pub(crate) fn lock_rates(&self, ticket: &mut ParkingTicket) {
+        let plan = self.rate_plan_for(ticket);
+        let occupancy = self.occupancy_ratio();
+        ticket.surge = plan.surge_at(occupancy).map(|multiplier| Surge {
+            multiplier,
+            occupancy,
+        });
+        ticket.rate_plan = Some(match ticket.surge {
+            Some(surge) => Arc::new(Surged::new(plan, surge)),
+            None => plan,
+        });
+    }
+
+    /// Rates currently in force for `ticket`: its zone's pricing, else its experiment
+    /// variant's, else the lot's.
+    fn rate_plan_for(&self, ticket: &ParkingTicket) -> Arc<dyn PricingStrategy> {
+        let zone = ticket
+            .zone_id
+            .as_deref()
+            .and_then(|id| self.parking_zone(id));
+        match zone.and_then(|z| z.pricing.clone()) {
+            Some(zone_pricing) => zone_pricing,
+            None => self
+                .pricing_variant_for(ticket)
+                .and_then(|(experiment, name)| {
+                    experiment.variant(&name).map(|v| v.strategy.clone())
+                })
+                .unwrap_or_else(|| self.pricing.clone()),
+        }
+    }
+
+    /// Experiment and variant name a ticket is priced under, if it was tagged with one.
+    fn pricing_variant_for(
+        &self,
+        ticket: &ParkingTicket,
+    ) -> Option<(Arc<PricingExperiment>, String)> {
+        let name = ticket.pricing_variant.clone()?;
+        let experiment = ticket
+            .experiment
+            .clone()
+            .or_else(|| self.pricing_experiment.clone())?;
+        experiment.variant(&name)?;
+        Some((experiment, name))
+    }
+
+    /// Registers the processor that handles payments of the given kind. The card
+    /// processor also places and settles card-on-entry holds.
+    pub fn set_payment_processor(
+        &mut self,
+        kind: PaymentMethodKind,
+        processor: Box<dyn PaymentProcessor>,
+    ) {
+        self.payment_processors.insert(kind, processor);
+    }
+
+    /// Enables card-on-entry parking with the given hold amount and free window.
+    pub fn set_pre_authorization_policy(&mut self, policy: Option<PreAuthorizationPolicy>) {
+        self.pre_authorization = policy;
+    }
+
+    /// Parks a ticketless vehicle, placing a hold on `card_token` first. The hold is
+    /// captured for the final amount at exit, or voided if the stay was free.
+    pub fn park_with_card(
+        &self,
+        vehicle: Vehicle,
+        card_token: &str,
+    ) -> Result<ParkingTicket, ParkingError> {
+        let policy = self
+            .pre_authorization
+            .ok_or(ParkingError::PreAuthorizationDisabled)?;
+        let processor = self
+            .payment_processors
+            .get(&PaymentMethodKind::Card)
+            .ok_or(ParkingError::NoPaymentProcessor)?;
+
+        let authorization_id = processor
+            .authorize(card_token, policy.hold_amount)
+            .map_err(ParkingError::PaymentFailed)?;
+        let ticket = match self.park_vehicle(vehicle) {
+            Ok(ticket) => ticket,
+            Err(e) => {
+                // Nothing was parked, so the hold must not linger on the card
+                let _ = processor.void(&authorization_id);
+                return Err(e);
+            }
+        };
+
+        let mut tickets = self.active_tickets.lock()?;
+        let Some(stored) = tickets.get_mut(&ticket.ticket_id) else {
+            // Closed or voided before the hold was attached, so nothing will settle it
+            drop(tickets);
+            let _ = processor.void(&authorization_id);
+            return Err(ParkingError::InvalidTicket);
+        };
+        stored.pre_authorization = Some(authorization_id);
+        Ok(stored.clone())
+    }
+
+    /// Overrides the receipt or reminder template used by this lot for `locale`.
+    pub fn set_template(&mut self, kind: TemplateKind, locale: &str, template: Template) {
+        self.templates.insert(kind, locale, template);
+    }
+
+    fn ticket_template_values(&self, ticket: &ParkingTicket) -> HashMap<&'static str, String> {
+        HashMap::from([
+            ("lot_name", self.name.clone()),
+            ("lot_address", self.address.clone()),
+            ("ticket_id", ticket.ticket_id.clone()),
+            ("short_code", ticket.short_code()),
+            ("license_plate", ticket.vehicle.license_plate.clone()),
+            ("spot_id", ticket.spot_id.clone()),
+            (
+                "entry_time",
+                ticket.entry_time.format("%Y-%m-%d %H:%M UTC").to_string(),
+            ),
+        ])
+    }
+
+    pub fn receipt_notification(
+        &self,
+        ticket: &ParkingTicket,
+        charge: &ParkingCharge,
+        locale: &str,
+    ) -> Option<Notification> {
+        let mut values = self.ticket_template_values(ticket);
+        values.insert(
+            "exit_time",
+            ticket
+                .exit_time
+                .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_default(),
+        );
+        values.insert("total", format!("{:.2}", charge.total));
+        self.templates
+            .get(TemplateKind::Receipt, locale)
+            .map(|template| template.render(&values))
+    }
+
+    pub fn reminder_notification(
+        &self,
+        ticket: &ParkingTicket,
+        locale: &str,
+    ) -> Option<Notification> {
+        let values = self.ticket_template_values(ticket);
+        self.templates
+            .get(TemplateKind::Reminder, locale)
+            .map(|template| template.render(&values))
+    }
+
+    /// Sets the inventory caps. Floors already in the lot pick up the new spot cap but
+    /// keep any spots they already have.
+    pub fn set_inventory_limits(&mut self, limits: InventoryLimits) -> Result<(), ParkingError> {
+        self.limits = limits;
+        for floor in self.floors.lock()?.values() {
+            *floor.max_spots.lock()? = limits.max_spots_per_floor;
+        }
+        Ok(())
+    }
+
+    pub fn inventory_limits(&self) -> InventoryLimits {
+        self.limits
+    }
+
+    /// Adds a new floor. Fails if a floor with the same id exists (use `replace_floor`)
+    /// or if the floor would exceed the lot's limits.
+    pub fn add_floor(&mut self, floor: ParkingFloor) -> Result<(), ParkingError> {
+        let floor_id = floor.id;
+        {
+            let mut floors = self.floors.lock()?;
+            if floors.contains_key(&floor_id) {
+                return Err(ParkingError::DuplicateFloor(floor_id));
+            }
+            if let Some(max_floors) = self.limits.max_floors
+                && floors.len() as u32 >= max_floors
+            {
+                return Err(ParkingError::FloorLimitReached(max_floors));
+            }
+            self.apply_spot_limit(&floor)?;
+            floor.set_compatibility(&self.compatibility);
+            floors.insert(floor_id, floor);
+        }
+        self.emit_inventory_change(floor_id, InventoryChange::FloorAdded);
+        Ok(())
+    }
+
+    /// Swaps out an existing floor, returning the old one. The floor being replaced must
+    /// have no vehicles on it.
+    pub fn replace_floor(&mut self, floor: ParkingFloor) -> Result<ParkingFloor, ParkingError> {
+        let floor_id = floor.id;
+        let previous = {
+            let mut floors = self.floors.lock()?;
+            let existing = floors.get(&floor_id).ok_or(ParkingError::FloorNotFound)?;
+            if existing.spots.lock()?.values().any(|s| s.is_occupied()) {
+                return Err(ParkingError::FloorNotEmpty(floor_id));
+            }
+            self.apply_spot_limit(&floor)?;
+            floor.set_compatibility(&self.compatibility);
+            floors.insert(floor_id, floor).unwrap()
+        };
+        self.emit_inventory_change(floor_id, InventoryChange::FloorReplaced);
+        Ok(previous)
+    }
+
+    fn apply_spot_limit(&self, floor: &ParkingFloor) -> Result<(), ParkingError> {
+        if let Some(max_spots) = self.limits.max_spots_per_floor
+            && floor.spots.lock()?.len() as u32 > max_spots
+        {
+            return Err(ParkingError::SpotLimitReached {
+                floor_id: floor.id,
+                max_spots,
+            });
+        }
+        *floor.max_spots.lock()? = self.limits.max_spots_per_floor;
+        Ok(())
+    }
+
+    pub fn add_spot(
+        &self,
+        floor_id: u32,
+        spot: ParkingSpot,
+    ) -> Result<Vec<QuotaWarning>, ParkingError> {
+        let spot_id = spot.id.clone();
+        let warnings = self.with_floor_mut(floor_id, |floor| floor.add_spot(spot))?;
+        self.emit_inventory_change(floor_id, InventoryChange::SpotAdded { spot_id });
+        Ok(warnings)
+    }
+
+    pub fn remove_spot(
+        &self,
+        floor_id: u32,
+        spot_id: &str,
+    ) -> Result<Vec<QuotaWarning>, ParkingError> {
+        let warnings = self.with_floor_mut(floor_id, |floor| floor.remove_spot(spot_id))?;
+        let spot_id = spot_id.to_string();
+        self.emit_inventory_change(floor_id, InventoryChange::SpotRemoved { spot_id });
+        Ok(warnings)
+    }
+
+    fn with_floor_mut<T>(
+        &self,
+        floor_id: u32,
+        f: impl FnOnce(&mut ParkingFloor) -> Result<T, ParkingError>,
+    ) -> Result<T, ParkingError> {
+        let mut floors = self.floors.lock()?;
+        let floor = floors
+            .get_mut(&floor_id)
+            .ok_or(ParkingError::FloorNotFound)?;
+        f(floor)
+    }
+
+    fn emit_inventory_change(&self, floor_id: u32, change: InventoryChange) {
+        self.emit(ParkingEvent::InventoryChanged {
+            floor_id,
+            change,
+            at: self.now(),
+        });
+    }
+
+    /// Announces that `floor_id`, and with it possibly the whole lot, has no free spot
+    /// left or has reached its capacity. Called right after a spot on the floor is taken.
+    fn emit_capacity_events(
+        &self,
+        floor_id: u32,
+        effects: &mut Effects,
+    ) -> Result<(), ParkingError> {
+        let floor_full = match self.floors.lock()?.get(&floor_id) {
+            Some(floor) => {
+                let spots = floor.spots.lock()?;
+                let occupied = spots.values().filter(|spot| spot.is_occupied()).count() as u32;
+                spots.values().all(|spot| !spot.is_available())
+                    || self
+                        .occupancy_limits
+                        .floor_capacity(floor_id)
+                        .is_some_and(|capacity| occupied >= capacity)
+            }
+            None => false,
+        };
+        if !floor_full {
+            return Ok(());
+        }
+        let at = self.now();
+        self.apply_effect(
+            Effect::Event(ParkingEvent::FloorFull { floor_id, at }),
+            effects,
+        )?;
+        if self.is_full() {
+            self.apply_effect(Effect::Event(ParkingEvent::LotFull { at }), effects)?;
+        }
+        Ok(())
+    }
+
+    pub fn get_floor_by_id(&self, id: u32) -> Result<Option<ParkingFloor>, ParkingError> {
+        Ok(self.floors.lock()?.get(&id).cloned())
+    }
+
+    pub fn display_info(&self) -> Result<ParkingLotDisplayBoard, ParkingError> {
+        let mut board = ParkingLotDisplayBoard {
+            uid: self.uid.clone(),
+            num_floors: 0,
+            num_empty_spots: 0,
+            num_parked_vehicles: 0,
+            num_claimed_spots: 0,
+            num_reserved_spots: 0,
+            num_out_of_service_spots: 0,
+            num_closed_floors: 0,
+            available_by_vehicle_type: VehicleType::ALL
+                .iter()
+                .map(|vehicle_type| (vehicle_type.clone(), 0))
+                .collect(),
+            entry_queue_length: 0,
+            estimated_entry_wait: None,
+        };
+        {
+            let floors = self.floors.lock()?;
+            let closed_floors = self.closed_floors.lock()?;
+            board.num_floors = floors.len() as u32;
+            for floor in floors.values() {
+                let open = !closed_floors.contains(&floor.id);
+                if !open {
+                    board.num_closed_floors += 1;
+                }
+                for spot in floor.spots.lock()?.values() {
+                    // Spots on closed floors can't be taken, so they aren't advertised as empty
+                    if open && spot.is_available() {
+                        board.num_empty_spots += 1;
+                        for (vehicle_type, count) in &mut board.available_by_vehicle_type {
+                            if spot.is_compatible(vehicle_type) {
+                                *count += 1;
+                            }
+                        }
+                    }
+                    if spot.is_occupied() && !spot.is_claimed() {
+                        board.num_parked_vehicles += 1;
+                    }
+                    if spot.is_claimed() {
+                        board.num_claimed_spots += 1;
+                    }
+                    if spot.is_reserved() {
+                        board.num_reserved_spots += 1;
+                    }
+                    if spot.out_of_service_reason().is_some() {
+                        board.num_out_of_service_spots += 1;
+                    }
+                }
+            }
+        }
+        let queue = self.entry_queue.lock()?;
+        board.entry_queue_length = queue.waiting.len() as u32;
+        board.estimated_entry_wait = self.entry_wait(&queue, board.num_empty_spots);
+        Ok(board)
+    }
+
+    /// Stops new allocations on `floor_id`. Vehicles already there can still leave; the
+    /// returned status lists the spots that have to empty before the floor is drained.
+    pub fn close_floor(&self, floor_id: u32) -> Result<FloorDrainStatus, ParkingError> {
+        if !self.floors.lock()?.contains_key(&floor_id) {
+            return Err(ParkingError::FloorNotFound);
+        }
+        self.closed_floors.lock()?.insert(floor_id);
+        self.floor_drain_status(floor_id)
+    }
+
+    pub fn reopen_floor(&self, floor_id: u32) -> Result<(), ParkingError> {
+        if !self.floors.lock()?.contains_key(&floor_id) {
+            return Err(ParkingError::FloorNotFound);
+        }
+        self.closed_floors.lock()?.remove(&floor_id);
+        Ok(())
+    }
+
+    /// Whether the floor takes new vehicles right now, accounting for both manual closure
+    /// and the operating schedule.
+    pub fn is_floor_open(&self, floor_id: u32) -> Result<bool, ParkingError> {
+        Ok(!self.closed_floors.lock()?.contains(&floor_id)
+            && self.schedule.is_floor_open(floor_id, self.now()))
+    }
+
+    pub fn floor_drain_status(&self, floor_id: u32) -> Result<FloorDrainStatus, ParkingError> {
+        let floors = self.floors.lock()?;
+        let floor = floors.get(&floor_id).ok_or(ParkingError::FloorNotFound)?;
+        let mut occupied_spot_ids: Vec<String> = floor
+            .spots
+            .lock()?
+            .iter()
+            .filter(|(_, spot)| spot.is_occupied())
+            .map(|(id, _)| id.clone())
+            .collect();
+        occupied_spot_ids.sort();
+        Ok(FloorDrainStatus {
+            floor_id,
+            occupied_spot_ids,
+        })
+    }
+
+    pub fn set_operating_schedule(&mut self, schedule: OperatingSchedule) {
+        self.schedule = schedule;
+    }
+
+    pub fn operating_schedule(&self) -> &OperatingSchedule {
+        &self.schedule
+    }
+
+    pub fn operating_schedule_mut(&mut self) -> &mut OperatingSchedule {
+        &mut self.schedule
+    }
+
+    pub fn closure_periods(&self, from: DateTime<Utc>, until: DateTime<Utc>) -> Vec<ClosurePeriod> {
+        self.schedule.closure_periods(from, until)
+    }
+
+    /// When set, `park_vehicle` only claims the spot; the driver has `hold` to reach it
+    /// before `release_expired_claims` hands it back.
+    pub fn set_transit_hold(&mut self, hold: Option<chrono::Duration>) {
+        self.transit_hold = hold;
+    }
+
+    /// Floor and type of the spot `spot_id` that `license_plate` is parked on. Spot ids
+    /// repeat across floors, so the vehicle picks out the right one.
+    fn parked_spot(
+        &self,
+        spot_id: &str,
+        license_plate: &str,
+    ) -> Result<Option<(u32, SpotType)>, ParkingError> {
+        let floors = self.floors.lock()?;
+        for floor in floors.values() {
+            let spots = floor.spots.lock()?;
+            let parked = spots.get(spot_id).filter(|spot| {
+                spot.vehicle
+                    .as_ref()
+                    .is_some_and(|v| v.license_plate == license_plate)
+            });
+            if let Some(spot) = parked {
+                return Ok(Some((floor.id, spot.spot_type)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Runs `f` against the spot `spot_id` on floor `floor_id`.
+    fn with_floor_spot_mut<R>(
+        &self,
+        floor_id: u32,
+        spot_id: &str,
+        f: impl FnOnce(&mut ParkingSpot) -> R,
+    ) -> Result<Option<R>, ParkingError> {
+        let floors = self.floors.lock()?;
+        let Some(floor) = floors.get(&floor_id) else {
+            return Ok(None);
+        };
+        let mut spots = floor.spots.lock()?;
+        Ok(spots.get_mut(spot_id).map(f))
+    }
+
+    /// Sensor or attendant confirmation that the claimed vehicle reached `spot_id` on
+    /// floor `floor_id`.
+    pub fn confirm_arrival(&self, floor_id: u32, spot_id: &str) -> Result<(), ParkingError> {
+        let now = self.now();
+        self.with_floor_spot_mut(floor_id, spot_id, |spot| {
+            spot.transition(TransitionCause::ArrivalConfirmed, now, |spot| {
+                spot.confirm_occupied()
+            })
+        })?
+        .ok_or(ParkingError::SpotNotFound)?
+    }
+
+    /// Frees spots whose transit claim ran out before the driver arrived, voiding their
+    /// tickets along with any card hold, charging or custody session tied to them. A
+    /// ticket being paid or settled at the exit keeps its claim. Returns the voided ticket
+    /// ids.
+    pub fn release_expired_claims(&self, now: DateTime<Utc>) -> Result<Vec<String>, ParkingError> {
+        let voided: Vec<ParkingTicket> = {
+            let mut tickets = self.active_tickets.lock()?;
+            let paying = self.paying.lock()?;
+            let ticket_for = |spot_id: &str, plate: &str| {
+                tickets
+                    .values()
+                    .find(|t| {
+                        t.exit_time.is_none()
+                            && t.spot_id == spot_id
+                            && t.vehicle.license_plate == plate
+                    })
+                    .map(|t| t.ticket_id.clone())
+            };
+            // Spot ids repeat across floors, so each claim is known by its spot and vehicle
+            let mut expired = Vec::new();
+            let floors = self.floors.lock()?;
+            for floor in floors.values() {
+                for (spot_id, spot) in floor.spots.lock()?.iter_mut() {
+                    if spot.claimed_until().is_none_or(|until| until > now) {
+                        continue;
+                    }
+                    let ticket_id = spot
+                        .vehicle
+                        .as_ref()
+                        .and_then(|vehicle| ticket_for(spot_id, &vehicle.license_plate));
+                    if ticket_id.as_ref().is_some_and(|id| paying.contains(id)) {
+                        continue;
+                    }
+                    expired.extend(ticket_id);
+                    spot.transition(TransitionCause::ClaimExpired, now, |spot| {
+                        spot.remove_vehicle()
+                    });
+                }
+            }
+            expired
+                .iter()
+                .filter_map(|ticket_id| tickets.remove(ticket_id))
+                .collect()
+        };
+
+        for ticket in &voided {
+            let ticket_id = &ticket.ticket_id;
+            self.charging_sessions.lock()?.remove(ticket_id);
+            self.custody_sessions.lock()?.remove(ticket_id);
+            self.reported_overstays.lock()?.remove(ticket_id);
+            if let (Some(authorization_id), Some(processor)) = (
+                &ticket.pre_authorization,
+                self.payment_processors.get(&PaymentMethodKind::Card),
+            ) {
+                // Nothing will be parked on this ticket, so the hold must not linger
+                let _ = processor.void(authorization_id);
+            }
+            self.emit(ParkingEvent::TicketVoided {
+                ticket_id: ticket_id.clone(),
+                license_plate: ticket.vehicle.license_plate.clone(),
+                spot_id: ticket.spot_id.clone(),
+                at: now,
+            });
+        }
+        Ok(voided.into_iter().map(|t| t.ticket_id).collect())
+    }
+
+    /// Parks `vehicle` on a spot carrying every tag in `tags`. Refusals are counted in
+    /// the analytics report.
+    pub fn park_vehicle_with_tags(
+        &self,
+        vehicle: Vehicle,
+        tags: &[SpotTag],
+    ) -> Result<ParkingTicket, ParkingError> {
+        self.park_with_tags(vehicle, tags, &mut Effects::immediate())
+    }
+
+    fn park_with_tags(
+        &self,
+        vehicle: Vehicle,
+        tags: &[SpotTag],
+        effects: &mut Effects,
+    ) -> Result<ParkingTicket, ParkingError> {
+        let result = self.admit_and_park(vehicle.clone(), tags, effects);
+        if let Err(err) = &result {
+            self.record_rejection(&vehicle, err);
+        }
+        result
+    }
+
+    fn admit_and_park(
+        &self,
+        vehicle: Vehicle,
+        tags: &[SpotTag],
+        effects: &mut Effects,
+    ) -> Result<ParkingTicket, ParkingError> {
+        let now = self.now();
+        // Spots of reservations about to start aren't for walk-ins
+        self.hold_due_reservations(now);
+        let request = EntryRequest {
+            vehicle: &vehicle,
+            zone_id: None,
+            at: now,
+        };
+        if let Err(err) = self.check_entry(&request) {
+            if err == ParkingError::LotFull {
+                self.apply_effect(Effect::Event(ParkingEvent::LotFull { at: now }), effects)?;
+            }
+            return Err(err);
+        }
+        // Held until the ticket is stored, so the same plate can't enter alongside
+        let _plate = self.reserve_plate(&vehicle)?;
+
+        let claimed_until = self.transit_hold.map(|hold| now + hold);
+        let allocated = {
+            let floors = self.floors.lock()?;
+            let closed_floors = self.closed_floors.lock()?;
+            let floor_open =
+                |id: u32| !closed_floors.contains(&id) && self.schedule.is_floor_open(id, now);
+            self.allocate_spot(&floors, &vehicle, tags, floor_open, |spot| {
+                spot.transition(TransitionCause::Parked, now, |spot| {
+                    spot.assign_vehicle_until(vehicle.clone(), claimed_until)
+                })
+                .ok()
+            })
+        };
+        let (floor_id, spot_id) = match allocated {
+            Some((floor_id, spot_id, _)) => (Some(floor_id), spot_id),
+            None if vehicle.priority.is_some() => (
+                None,
+                self.allocate_priority_spot(&vehicle, tags, claimed_until)?,
+            ),
+            None => return Err(ParkingError::NoSpotAvailable),
+        };
+
+        let ticket = self.issue_ticket(vehicle, spot_id, effects)?;
+        if let Some(floor_id) = floor_id {
+            self.emit_capacity_events(floor_id, effects)?;
+        }
+        Ok(ticket)
+    }
+}
+
+pub trait Parkable {
+    fn park_vehicle(&self, vehicle: Vehicle) -> Result<ParkingTicket, ParkingError>;
+    fn unpark_vehicle(&self, ticket_id: String) -> Result<ParkingCharge, ParkingError>;
+}
+
+impl Parkable for ParkingLot {
+    fn park_vehicle(&self, vehicle: Vehicle) -> Result<ParkingTicket, ParkingError> {
+        self.park_vehicle_with_tags(vehicle, &[])
+    }
+
+    fn unpark_vehicle(&self, ticket_id: String) -> Result<ParkingCharge, ParkingError> {
+        self.checkout(ticket_id, None, None, &mut Effects::immediate())
+    }
 }
 
 impl ParkingLotDisplayBoard {
+    pub fn uid(&self) -> &str {
+        &self.uid
+    }
+
     pub fn num_floors(&self) -> u32 {
         self.num_floors
     }
@@ -206,18 +1664,86 @@ impl ParkingLotDisplayBoard {
     pub fn num_empty_spots(&self) -> u32 {
         self.num_empty_spots
     }
-    
+
     pub fn num_parked_vehicles(&self) -> u32 {
         self.num_parked_vehicles
     }
 
+    /// Spots handed out at the gate whose vehicle hasn't arrived yet.
+    pub fn num_claimed_spots(&self) -> u32 {
+        self.num_claimed_spots
+    }
+
+    /// Free spots held for upcoming reservations.
+    pub fn num_reserved_spots(&self) -> u32 {
+        self.num_reserved_spots
+    }
+
+    /// Spots closed for maintenance; they aren't counted as empty.
+    pub fn num_out_of_service_spots(&self) -> u32 {
+        self.num_out_of_service_spots
+    }
+
+    pub fn num_closed_floors(&self) -> u32 {
+        self.num_closed_floors
+    }
+
+    /// Empty spots a vehicle of this type could be parked in. A spot compatible with
+    /// several vehicle types counts towards each of them.
+    pub fn num_available_for(&self, vehicle_type: &VehicleType) -> u32 {
+        self.available_by_vehicle_type
+            .get(vehicle_type)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Vehicles waiting in the physical entry queue.
+    pub fn entry_queue_length(&self) -> u32 {
+        self.entry_queue_length
+    }
+
+    /// See `ParkingLot::estimated_entry_wait`.
+    pub fn estimated_entry_wait(&self) -> Option<chrono::Duration> {
+        self.estimated_entry_wait
+    }
+
+    /// One line per vehicle type for the entrance sign, e.g. `Truck: FULL`, followed by
+    /// the queue length and expected wait while vehicles are queued.
+    pub fn entry_signage(&self) -> Vec<String> {
+        let mut lines: Vec<String> = VehicleType::ALL
+            .iter()
+            .map(|vehicle_type| match self.num_available_for(vehicle_type) {
+                0 => format!("{:?}: FULL", vehicle_type),
+                n => format!("{:?}: {}", vehicle_type, n),
+            })
+            .collect();
+        if self.entry_queue_length > 0 {
+            let minutes = self
+                .estimated_entry_wait
+                .map_or(0, |wait| wait.num_minutes());
+            lines.push(match minutes {
+                0 => format!("Queue: {} waiting", self.entry_queue_length),
+                m => format!(
+                    "Queue: {} waiting, about {} min",
+                    self.entry_queue_length, m
+                ),
+            });
+        }
+        lines
+    }
 }
 
 // === PARKING FLOOR ===
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "persistence::SavedFloor", from = "persistence::SavedFloor")]
 pub struct ParkingFloor {
     id: u32,
     spots: Arc<Mutex<HashMap<String, ParkingSpot>>>,
+    quota: Arc<Mutex<SpotQuota>>,
+    /// Spot cap inherited from the lot's `InventoryLimits` when the floor is added.
+    max_spots: Arc<Mutex<Option<u32>>>,
+    /// Handed to spots added to the floor; the lot's policy once the floor is added.
+    compatibility: Arc<Mutex<Arc<CompatibilityPolicy>>>,
 }
 
 impl ParkingFloor {
@@ -225,6 +1751,9 @@ impl ParkingFloor {
         let mut floor = Self {
             id,
             spots: Arc::new(Mutex::new(HashMap::new())),
+            quota: Arc::new(Mutex::new(SpotQuota::default())),
+            max_spots: Arc::new(Mutex::new(None)),
+            compatibility: Arc::default(),
         };
         floor.initialize_spots();
         floor
@@ -240,98 +1769,534 @@ impl ParkingFloor {
         }
     }
 
-    pub fn add_spot(&mut self, spot: ParkingSpot) {
-        self.spots.lock().unwrap().insert(spot.id.clone(), spot);
+    pub fn id(&self) -> u32 {
+        self.id
     }
 
-    pub fn find_available_spot(&self, vehicle_type: VehicleType) -> Option<(u32, String)> {
-        let spots = self.spots.lock().unwrap();
-        for (spot_id, spot) in spots.iter() {
-            if spot.is_free && spot.is_compatible(&vehicle_type) {
-                return Some((self.id, spot_id.clone()));
+    pub(crate) fn set_compatibility(&self, policy: &Arc<CompatibilityPolicy>) {
+        *self.compatibility.lock().unwrap() = policy.clone();
+        for spot in self.spots.lock().unwrap().values_mut() {
+            spot.compatibility = policy.clone();
+        }
+    }
+
+    /// Sets the minimum spot-type ratios enforced by `add_spot`, `remove_spot` and
+    /// `convert_spot`. Existing inventory is not re-checked.
+    pub fn set_spot_quota(&mut self, quota: SpotQuota) {
+        *self.quota.lock().unwrap() = quota;
+    }
+
+    /// Checks an inventory change against the floor's quota. Only changes that break a
+    /// quota, or move an already-broken one further from its target, are reported.
+    fn check_quota(
+        &self,
+        spots: &HashMap<String, ParkingSpot>,
+        change: impl FnOnce(&mut HashMap<SpotType, u32>),
+    ) -> Result<Vec<QuotaWarning>, ParkingError> {
+        let quota = self.quota.lock()?;
+        let mut counts: HashMap<SpotType, u32> = HashMap::new();
+        for spot in spots.values() {
+            *counts.entry(spot.spot_type).or_insert(0) += 1;
+        }
+        let before = quota.shortfalls(self.id, &counts);
+        change(&mut counts);
+        let worsened: Vec<QuotaWarning> = quota
+            .shortfalls(self.id, &counts)
+            .into_iter()
+            .filter(|after| {
+                before
+                    .iter()
+                    .find(|b| b.spot_type == after.spot_type)
+                    .is_none_or(|b| {
+                        after.missing_spots > b.missing_spots || after.actual_ratio < b.actual_ratio
+                    })
+            })
+            .collect();
+
+        match (quota.enforcement(), worsened.first()) {
+            (QuotaEnforcement::Reject, Some(w)) => Err(ParkingError::QuotaViolation(w.clone())),
+            _ => Ok(worsened),
+        }
+    }
+
+    pub fn add_spot(&mut self, mut spot: ParkingSpot) -> Result<Vec<QuotaWarning>, ParkingError> {
+        let mut spots = self.spots.lock()?;
+        if spots.contains_key(&spot.id) {
+            return Err(ParkingError::DuplicateSpot(spot.id));
+        }
+        if let Some(max_spots) = *self.max_spots.lock()?
+            && spots.len() as u32 >= max_spots
+        {
+            return Err(ParkingError::SpotLimitReached {
+                floor_id: self.id,
+                max_spots,
+            });
+        }
+        let warnings = self.check_quota(&spots, |counts| {
+            *counts.entry(spot.spot_type).or_insert(0) += 1;
+        })?;
+        spot.compatibility = self.compatibility.lock()?.clone();
+        spots.insert(spot.id.clone(), spot);
+        Ok(warnings)
+    }
+
+    pub fn remove_spot(&mut self, spot_id: &str) -> Result<Vec<QuotaWarning>, ParkingError> {
+        let mut spots = self.spots.lock()?;
+        let spot = spots.get(spot_id).ok_or(ParkingError::SpotNotFound)?;
+        if spot.is_occupied() {
+            return Err(ParkingError::SpotOccupied);
+        }
+        let spot_type = spot.spot_type;
+        let warnings = self.check_quota(&spots, |counts| {
+            if let Some(count) = counts.get_mut(&spot_type) {
+                *count -= 1;
             }
+        })?;
+        spots.remove(spot_id);
+        Ok(warnings)
+    }
+
+    /// Changes the type of a free spot that isn't reserved or leased.
+    pub fn convert_spot(
+        &mut self,
+        spot_id: &str,
+        spot_type: SpotType,
+    ) -> Result<Vec<QuotaWarning>, ParkingError> {
+        let mut spots = self.spots.lock()?;
+        let spot = spots.get(spot_id).ok_or(ParkingError::SpotNotFound)?;
+        if spot.is_occupied() {
+            return Err(ParkingError::SpotOccupied);
+        }
+        if spot.is_reserved() || spot.is_leased() {
+            return Err(ParkingError::SpotUnavailable);
         }
-        None
+        let old_type = spot.spot_type;
+        let warnings = self.check_quota(&spots, |counts| {
+            if let Some(count) = counts.get_mut(&old_type) {
+                *count -= 1;
+            }
+            *counts.entry(spot_type).or_insert(0) += 1;
+        })?;
+        spots.get_mut(spot_id).unwrap().spot_type = spot_type;
+        Ok(warnings)
+    }
+
+    /// Smallest free spot that fits. Electric vehicles are steered to a charging spot when
+    /// the floor has one free.
+    pub fn find_available_spot(&self, vehicle_type: VehicleType) -> Option<(u32, String)> {
+        self.find_available_spot_with_tags(vehicle_type, &[])
+    }
+
+    /// Like `find_available_spot`, restricted to spots carrying every tag in `tags`.
+    pub fn find_available_spot_with_tags(
+        &self,
+        vehicle_type: VehicleType,
+        tags: &[SpotTag],
+    ) -> Option<(u32, String)> {
+        let spots = self.spots.lock().unwrap();
+        pick_spot(self.id, &spots, &vehicle_type, false, None, tags)
+            .map(|spot_id| (self.id, spot_id))
+    }
+
+    /// Like `find_available_spot_with_tags`, but also considers handicapped spots when
+    /// `vehicle` has a permit, and picks one of those first.
+    pub fn find_spot_for(&self, vehicle: &Vehicle, tags: &[SpotTag]) -> Option<(u32, String)> {
+        let spots = self.spots.lock().unwrap();
+        pick_spot(
+            self.id,
+            &spots,
+            &vehicle.vehicle_type,
+            vehicle.handicapped_permit,
+            vehicle.dimensions.as_ref(),
+            tags,
+        )
+        .map(|spot_id| (self.id, spot_id))
+    }
+
+    /// Takes a spot out of service, e.g. for painting or a broken charger, or returns it
+    /// to service with `SpotStatus::Free`. Occupied, reserved and leased spots can't be
+    /// taken out of service. A floor has no clock of its own, so the change is journaled at
+    /// `now`, normally the lot's `ParkingLot::now`.
+    pub fn set_spot_status(
+        &self,
+        spot_id: &str,
+        status: SpotStatus,
+        now: DateTime<Utc>,
+    ) -> Result<(), ParkingError> {
+        self.spots
+            .lock()?
+            .get_mut(spot_id)
+            .ok_or(ParkingError::SpotNotFound)?
+            .set_maintenance_status(status, now)
     }
 }
 
+/// Floor-local best fit, see `allocation::BestFit`.
+fn pick_spot(
+    floor_id: u32,
+    spots: &HashMap<String, ParkingSpot>,
+    vehicle_type: &VehicleType,
+    handicapped_permit: bool,
+    dimensions: Option<&Dimensions>,
+    tags: &[SpotTag],
+) -> Option<String> {
+    let mut candidates = spot_candidates(
+        std::iter::once((floor_id, spots)),
+        None,
+        vehicle_type,
+        handicapped_permit,
+        dimensions,
+        tags,
+        |_, _| true,
+    );
+    BestFit::smallest(&candidates).map(|i| candidates.swap_remove(i).spot_id)
+}
+
 // ===PARKING SPOT ===
-#[derive(Debug)]
+
+/// Where a spot is in its lifecycle. A free spot can be occupied, claimed, reserved or
+/// taken out of service; every other status only leads back to free, except that a
+/// reserved spot is occupied or claimed when its reservation checks in or is preempted,
+/// and a claimed spot is occupied once its vehicle arrives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpotStatus {
+    Free,
+    Occupied,
+    /// Assigned to a vehicle still driving to it, until this time.
+    Claimed(DateTime<Utc>),
+    /// Held for the reservation with this id.
+    Reserved(String),
+    /// Closed for maintenance, e.g. painting or a broken charger; carries the reason.
+    OutOfService(String),
+}
+
+impl SpotStatus {
+    pub fn can_become(&self, next: &SpotStatus) -> bool {
+        matches!(
+            (self, next),
+            (
+                SpotStatus::Free,
+                SpotStatus::Occupied
+                    | SpotStatus::Claimed(_)
+                    | SpotStatus::Reserved(_)
+                    | SpotStatus::OutOfService(_)
+            ) | (
+                SpotStatus::Reserved(_),
+                SpotStatus::Occupied | SpotStatus::Claimed(_)
+            ) | (SpotStatus::Claimed(_), SpotStatus::Occupied)
+                | (
+                    SpotStatus::Occupied
+                        | SpotStatus::Claimed(_)
+                        | SpotStatus::Reserved(_)
+                        | SpotStatus::OutOfService(_),
+                    SpotStatus::Free
+                )
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "persistence::SavedSpot", from = "persistence::SavedSpot")]
 pub struct ParkingSpot {
     id: String,
-    is_free: bool,
+    status: SpotStatus,
     spot_type: SpotType,
     vehicle: Option<Vehicle>,
+    /// Lease withholding this spot from public allocation.
+    leased_by: Option<String>,
+    /// Parking zone the spot belongs to; only `park_in_zone` allocates it.
+    zone: Option<String>,
+    tags: HashSet<SpotTag>,
+    journal: SpotJournal,
+    /// The policy of the floor the spot is on.
+    compatibility: Arc<CompatibilityPolicy>,
+    dimensions: Option<Dimensions>,
 }
 
 impl ParkingSpot {
     pub fn new(is_free: bool, spot_type: SpotType) -> Self {
-        static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
         Self {
-            id: format!("spot_{}", COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst)),
-            is_free,
+            id: format!("spot_{}", SPOT_COUNTER.fetch_add(1, Ordering::SeqCst)),
+            status: if is_free {
+                SpotStatus::Free
+            } else {
+                SpotStatus::Occupied
+            },
             spot_type,
             vehicle: None,
+            leased_by: None,
+            zone: None,
+            tags: HashSet::new(),
+            journal: SpotJournal::default(),
+            compatibility: Arc::default(),
+            dimensions: None,
         }
     }
 
-    pub fn assign_vehicle(&mut self, vehicle: Vehicle) -> Result<(), String> {
-        if !self.is_free {
-            return Err("Spot is already occupied".to_string());
+    pub fn assign_vehicle(&mut self, vehicle: Vehicle) -> Result<(), ParkingError> {
+        self.assign_vehicle_until(vehicle, None)
+    }
+
+    /// Assigns `vehicle` to the spot. With `claimed_until` the spot is only claimed for it
+    /// until then, while it drives to the spot.
+    pub(crate) fn assign_vehicle_until(
+        &mut self,
+        vehicle: Vehicle,
+        claimed_until: Option<DateTime<Utc>>,
+    ) -> Result<(), ParkingError> {
+        if self.is_occupied() {
+            return Err(ParkingError::SpotOccupied);
         }
-        
-        if !self.is_compatible(&vehicle.vehicle_type) {
-            return Err("Vehicle type not compatible with spot type".to_string());
+
+        if !self.accepts(&vehicle) {
+            return Err(ParkingError::IncompatibleVehicle);
         }
-        
+
+        self.set_status(claimed_until.map_or(SpotStatus::Occupied, SpotStatus::Claimed))?;
         self.vehicle = Some(vehicle);
-        self.is_free = false;
         Ok(())
     }
 
     pub fn remove_vehicle(&mut self) {
         self.vehicle = None;
-        self.is_free = true;
+        if self.is_occupied() {
+            self.status = SpotStatus::Free;
+        }
     }
 
-    pub fn is_compatible(&self, vehicle_type: &VehicleType) -> bool {
-        match (vehicle_type, &self.spot_type) {
-            (VehicleType::Motor, SpotType::Regular) => true,
-            (VehicleType::Motor, SpotType::Large) => true,
-            (VehicleType::Motor, SpotType::XLarge) => true,
-            (VehicleType::Bike, SpotType::Regular) => true,
-            (VehicleType::Truck, SpotType::Large) => true,
-            (VehicleType::Truck, SpotType::XLarge) => true,
-            (VehicleType::Motor, SpotType::Handicapped) => false,
-            (VehicleType::Truck, SpotType::Regular) => false,
-            (VehicleType::Truck, SpotType::Handicapped) => false,
-            (VehicleType::Bike, SpotType::Large) => true,
-            (VehicleType::Bike, SpotType::XLarge) => true,
-            (VehicleType::Bike, SpotType::Handicapped) => false,
+    pub fn status(&self) -> &SpotStatus {
+        &self.status
+    }
+
+    /// Moves the spot to `status`, if its lifecycle allows that.
+    pub(crate) fn set_status(&mut self, status: SpotStatus) -> Result<(), ParkingError> {
+        if !self.status.can_become(&status) {
+            return Err(ParkingError::InvalidSpotTransition {
+                from: self.status.clone(),
+                to: status,
+            });
         }
+        self.status = status;
+        Ok(())
+    }
+
+    /// Takes a free spot out of service, or returns an out-of-service spot to free.
+    /// Parking and reservations own the other statuses.
+    pub(crate) fn set_maintenance_status(
+        &mut self,
+        status: SpotStatus,
+        at: DateTime<Utc>,
+    ) -> Result<(), ParkingError> {
+        let cause = match (&self.status, &status) {
+            (SpotStatus::Occupied | SpotStatus::Claimed(_), SpotStatus::OutOfService(_)) => {
+                return Err(ParkingError::SpotOccupied);
+            }
+            (SpotStatus::Reserved(_), SpotStatus::OutOfService(_)) => {
+                return Err(ParkingError::SpotUnavailable);
+            }
+            (SpotStatus::Free, SpotStatus::OutOfService(_)) if self.is_leased() => {
+                return Err(ParkingError::SpotUnavailable);
+            }
+            (SpotStatus::Free, SpotStatus::OutOfService(_)) => TransitionCause::TakenOutOfService,
+            (SpotStatus::OutOfService(_), SpotStatus::Free) => TransitionCause::ReturnedToService,
+            _ => {
+                return Err(ParkingError::InvalidSpotTransition {
+                    from: self.status.clone(),
+                    to: status,
+                });
+            }
+        };
+        self.transition(cause, at, |spot| spot.set_status(status))
+    }
+
+    /// Occupied, or claimed for a vehicle on its way.
+    pub fn is_occupied(&self) -> bool {
+        matches!(self.status, SpotStatus::Occupied | SpotStatus::Claimed(_))
+    }
+
+    /// Free, in service and not held for a reservation or lease.
+    pub fn is_available(&self) -> bool {
+        self.status == SpotStatus::Free && self.leased_by.is_none()
+    }
+
+    pub fn out_of_service_reason(&self) -> Option<&str> {
+        match &self.status {
+            SpotStatus::OutOfService(reason) => Some(reason),
+            _ => None,
+        }
+    }
+
+    pub fn is_leased(&self) -> bool {
+        self.leased_by.is_some()
+    }
+
+    pub fn is_reserved(&self) -> bool {
+        self.reserved_by().is_some()
+    }
+
+    /// The reservation holding the spot.
+    pub fn reserved_by(&self) -> Option<&str> {
+        match &self.status {
+            SpotStatus::Reserved(reservation_id) => Some(reservation_id),
+            _ => None,
+        }
+    }
+
+    pub fn is_claimed(&self) -> bool {
+        self.claimed_until().is_some()
+    }
+
+    /// When the claim for a vehicle driving to the spot runs out.
+    pub fn claimed_until(&self) -> Option<DateTime<Utc>> {
+        match self.status {
+            SpotStatus::Claimed(until) => Some(until),
+            _ => None,
+        }
+    }
+
+    /// Turns a claimed spot into an occupied one.
+    pub fn confirm_occupied(&mut self) -> Result<(), ParkingError> {
+        if !self.is_claimed() {
+            return Err(ParkingError::SpotNotClaimed);
+        }
+        self.set_status(SpotStatus::Occupied)
+    }
+
+    /// Whether `vehicle` may park here. Handicapped spots need a permit as well as a fit
+    /// under the compatibility policy; every other spot type goes by `is_compatible`.
+    /// A vehicle must also fit the spot when both their sizes are known.
+    pub fn accepts(&self, vehicle: &Vehicle) -> bool {
+        self.admits(&vehicle.vehicle_type, vehicle.handicapped_permit)
+            && self.fits(vehicle.dimensions.as_ref())
+    }
+
+    /// Whether a vehicle of `dimensions` fits the spot. Vehicles or spots of unknown size
+    /// always fit.
+    pub fn fits(&self, dimensions: Option<&Dimensions>) -> bool {
+        match (dimensions, &self.dimensions) {
+            (Some(vehicle), Some(space)) => vehicle.fits_within(space),
+            _ => true,
+        }
+    }
+
+    fn admits(&self, vehicle_type: &VehicleType, handicapped_permit: bool) -> bool {
+        match self.spot_type {
+            SpotType::Handicapped => {
+                handicapped_permit && self.compatibility.allows(vehicle_type, self.spot_type)
+            }
+            _ => self.is_compatible(vehicle_type),
+        }
+    }
+
+    /// Compatibility by vehicle type alone, under the policy of the spot's floor.
+    /// Handicapped spots are never compatible here because they also need a permit, see
+    /// `accepts`.
+    pub fn is_compatible(&self, vehicle_type: &VehicleType) -> bool {
+        self.spot_type != SpotType::Handicapped
+            && self.compatibility.allows(vehicle_type, self.spot_type)
     }
 
     pub fn get_id(&self) -> &str {
         &self.id
     }
-}
 
-#[derive(Debug, Clone)]
+    pub fn spot_type(&self) -> SpotType {
+        self.spot_type
+    }
+
+    /// The vehicle parked on (or driving to) the spot.
+    pub fn vehicle(&self) -> Option<&Vehicle> {
+        self.vehicle.as_ref()
+    }
+
+    pub fn with_tag(mut self, tag: SpotTag) -> Self {
+        self.tags.insert(tag);
+        self
+    }
+
+    pub fn with_dimensions(mut self, dimensions: Dimensions) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+
+    pub fn dimensions(&self) -> Option<&Dimensions> {
+        self.dimensions.as_ref()
+    }
 
+    pub fn tags(&self) -> &HashSet<SpotTag> {
+        &self.tags
+    }
+
+    pub fn has_tags(&self, tags: &[SpotTag]) -> bool {
+        tags.iter().all(|tag| self.tags.contains(tag))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Vehicle {
     vehicle_type: VehicleType,
     model: String,
     license_plate: String,
+    handicapped_permit: bool,
+    priority: Option<PriorityClass>,
+    dimensions: Option<Dimensions>,
 }
 
 impl Vehicle {
+    /// A valid plate is stored trimmed and in upper case, see `plate::LicensePlate`.
     pub fn new(vehicle_type: VehicleType, model: String, license_plate: String) -> Self {
         Self {
             vehicle_type,
             model,
-            license_plate,
+            license_plate: plate::normalize_plate(&license_plate),
+            handicapped_permit: false,
+            priority: None,
+            dimensions: None,
         }
     }
+
+    /// Marks the vehicle as displaying a handicapped permit, so it may use handicapped spots.
+    pub fn with_handicapped_permit(mut self) -> Self {
+        self.handicapped_permit = true;
+        self
+    }
+
+    pub fn has_handicapped_permit(&self) -> bool {
+        self.handicapped_permit
+    }
+
+    /// Marks an emergency or service vehicle, which may override capacity limits and held
+    /// spots, see `priority`.
+    pub fn with_priority(mut self, class: PriorityClass) -> Self {
+        self.priority = Some(class);
+        self
+    }
+
+    pub fn priority(&self) -> Option<PriorityClass> {
+        self.priority
+    }
+
+    /// Records the vehicle's size, checked against spot sizes and clearance gates.
+    pub fn with_dimensions(mut self, dimensions: Dimensions) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+
+    pub fn dimensions(&self) -> Option<&Dimensions> {
+        self.dimensions.as_ref()
+    }
+
+    pub fn vehicle_type(&self) -> &VehicleType {
+        &self.vehicle_type
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn license_plate(&self) -> &str {
+        &self.license_plate
+    }
 }
 
 // === ACCOUNT ===
@@ -347,6 +2312,9 @@ pub struct User {
     name: String,
     phone: String,
     vehicles: HashMap<String, Vehicle>,
+    verifications: Vec<Verification>,
+    verification_audit: Vec<VerificationAudit>,
+    pass_ids: Vec<String>,
 }
 
 impl User {
@@ -355,8 +2323,79 @@ impl User {
             name,
             phone,
             vehicles: HashMap::new(),
+            verifications: Vec::new(),
+            verification_audit: Vec::new(),
+            pass_ids: Vec::new(),
         }
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn phone(&self) -> &str {
+        &self.phone
+    }
+
+    /// Records that `granted_by` verified this account for `eligibility` from `granted_at`,
+    /// usually the lot's `now()`, until `expires_at`, replacing any earlier verification of
+    /// the same kind.
+    pub fn grant_verification(
+        &mut self,
+        eligibility: Eligibility,
+        granted_by: String,
+        granted_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) {
+        self.verifications.retain(|v| v.eligibility != eligibility);
+        self.verifications.push(Verification {
+            eligibility,
+            granted_by: granted_by.clone(),
+            granted_at,
+            expires_at,
+        });
+        self.verification_audit.push(VerificationAudit {
+            eligibility,
+            action: VerificationAction::Granted,
+            actor: granted_by,
+            at: granted_at,
+        });
+    }
+
+    /// Withdraws the verification for `eligibility`, recording the revocation at `at`.
+    pub fn revoke_verification(
+        &mut self,
+        eligibility: Eligibility,
+        revoked_by: String,
+        at: DateTime<Utc>,
+    ) {
+        let before = self.verifications.len();
+        self.verifications.retain(|v| v.eligibility != eligibility);
+        if self.verifications.len() != before {
+            self.verification_audit.push(VerificationAudit {
+                eligibility,
+                action: VerificationAction::Revoked,
+                actor: revoked_by,
+                at,
+            });
+        }
+    }
+
+    pub fn verifications(&self) -> &[Verification] {
+        &self.verifications
+    }
+
+    pub fn active_eligibilities(&self, now: DateTime<Utc>) -> Vec<Eligibility> {
+        self.verifications
+            .iter()
+            .filter(|v| v.is_valid_at(now))
+            .map(|v| v.eligibility)
+            .collect()
+    }
+
+    pub fn verification_audit(&self) -> &[VerificationAudit] {
+        &self.verification_audit
+    }
 }
 
 impl Account for User {
@@ -374,3 +2413,374 @@ impl Account for User {
         self.vehicles.get(&vehicle_id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lot_with_floor() -> ParkingLot {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot
+    }
+
+    #[test]
+    fn test_transit_claim_expires_unless_arrival_is_confirmed() {
+        let mut lot = lot_with_floor();
+        lot.set_transit_hold(Some(chrono::Duration::minutes(5)));
+
+        let arrived = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "AAA111".into(),
+            ))
+            .unwrap();
+        let no_show = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "BBB222".into(),
+            ))
+            .unwrap();
+        assert_eq!(lot.display_info().unwrap().num_claimed_spots(), 2);
+        let floor = lot.get_floor_by_id(1).unwrap().unwrap();
+        let status = |spot_id: &str| floor.spots.lock().unwrap()[spot_id].status().clone();
+        assert!(matches!(status(&no_show.spot_id), SpotStatus::Claimed(_)));
+        assert_eq!(
+            floor.set_spot_status(
+                &no_show.spot_id,
+                SpotStatus::OutOfService("Oil".into()),
+                lot.now()
+            ),
+            Err(ParkingError::SpotOccupied)
+        );
+
+        lot.confirm_arrival(1, &arrived.spot_id).unwrap();
+        assert_eq!(status(&arrived.spot_id), SpotStatus::Occupied);
+        let voided = lot
+            .release_expired_claims(Utc::now() + chrono::Duration::minutes(6))
+            .unwrap();
+
+        assert_eq!(voided, vec![no_show.ticket_id]);
+        assert_eq!(lot.display_info().unwrap().num_claimed_spots(), 0);
+        assert_eq!(lot.display_info().unwrap().num_parked_vehicles(), 1);
+    }
+
+    #[test]
+    fn test_expired_claim_leaves_same_spot_id_on_other_floor_alone() {
+        let mut lot = lot_with_floor();
+        lot.add_floor(ParkingFloor::new(2)).unwrap();
+        lot.set_transit_hold(Some(chrono::Duration::minutes(5)));
+        for floor_id in [1, 2] {
+            let floor = lot.get_floor_by_id(floor_id).unwrap().unwrap();
+            let mut spots = floor.spots.lock().unwrap();
+            spots.retain(|spot_id, _| spot_id == "spot_3");
+        }
+
+        let arrived = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "AAA111".into(),
+            ))
+            .unwrap();
+        let no_show = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "BBB222".into(),
+            ))
+            .unwrap();
+        assert_eq!(arrived.spot_id, no_show.spot_id);
+        let arrived_floor = lot.locate_vehicle("AAA111").unwrap().floor_id;
+        lot.confirm_arrival(arrived_floor, &arrived.spot_id)
+            .unwrap();
+        // Already confirmed on this floor, not the other one
+        assert_eq!(
+            lot.confirm_arrival(arrived_floor, &arrived.spot_id),
+            Err(ParkingError::SpotNotClaimed)
+        );
+
+        let voided = lot
+            .release_expired_claims(Utc::now() + chrono::Duration::minutes(6))
+            .unwrap();
+        assert_eq!(voided, vec![no_show.ticket_id]);
+        lot.unpark_vehicle(arrived.ticket_id).unwrap();
+        assert_eq!(lot.display_info().unwrap().num_empty_spots(), 2);
+    }
+
+    #[test]
+    fn test_closed_floor_drains_and_takes_no_new_vehicles() {
+        let lot = lot_with_floor();
+        let ticket = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "AAA111".into(),
+            ))
+            .unwrap();
+
+        let status = lot.close_floor(1).unwrap();
+        assert_eq!(status.occupied_spot_ids, vec![ticket.spot_id.clone()]);
+        assert_eq!(lot.display_info().unwrap().num_empty_spots(), 0);
+        assert!(
+            lot.park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "BBB222".into()
+            ))
+            .is_err()
+        );
+
+        lot.unpark_vehicle(ticket.ticket_id).unwrap();
+        assert!(lot.floor_drain_status(1).unwrap().is_drained());
+
+        lot.reopen_floor(1).unwrap();
+        assert_eq!(lot.display_info().unwrap().num_empty_spots(), 10);
+    }
+
+    #[test]
+    fn test_duplicate_floor_and_spot_cap_are_rejected() {
+        let mut lot = lot_with_floor();
+        lot.set_inventory_limits(InventoryLimits {
+            max_floors: Some(2),
+            max_spots_per_floor: Some(11),
+        })
+        .unwrap();
+
+        assert_eq!(
+            lot.add_floor(ParkingFloor::new(1)),
+            Err(ParkingError::DuplicateFloor(1))
+        );
+        lot.add_floor(ParkingFloor::new(2)).unwrap();
+        assert_eq!(
+            lot.add_floor(ParkingFloor::new(3)),
+            Err(ParkingError::FloorLimitReached(2))
+        );
+
+        lot.add_spot(1, ParkingSpot::new(true, SpotType::Large))
+            .unwrap();
+        assert_eq!(
+            lot.add_spot(1, ParkingSpot::new(true, SpotType::Large)),
+            Err(ParkingError::SpotLimitReached {
+                floor_id: 1,
+                max_spots: 11
+            })
+        );
+
+        let previous = lot.replace_floor(ParkingFloor::new(1)).unwrap();
+        assert_eq!(previous.spots.lock().unwrap().len(), 11);
+        assert_eq!(lot.display_info().unwrap().num_floors(), 2);
+    }
+
+    #[test]
+    fn test_board_reports_availability_per_vehicle_type() {
+        let lot = lot_with_floor();
+        let board = lot.display_info().unwrap();
+        assert_eq!(board.num_available_for(&VehicleType::Motor), 10);
+        assert_eq!(board.num_available_for(&VehicleType::Truck), 0);
+        assert_eq!(board.entry_signage()[1], "Truck: FULL");
+
+        lot.add_spot(1, ParkingSpot::new(true, SpotType::Large))
+            .unwrap();
+        let board = lot.display_info().unwrap();
+        assert_eq!(board.num_available_for(&VehicleType::Truck), 1);
+        assert_eq!(board.num_available_for(&VehicleType::Bike), 11);
+    }
+
+    #[test]
+    fn test_estimate_leaves_ticket_open() {
+        let lot = lot_with_floor();
+        let ticket = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "AAA111".into(),
+            ))
+            .unwrap();
+
+        let later = ticket.entry_time + chrono::Duration::hours(3);
+        let estimate = lot
+            .estimate_charge_at(&ticket.ticket_id, later, None)
+            .unwrap();
+        assert_eq!(estimate.total, 30.0);
+        assert_eq!(estimate.ticket_id, ticket.ticket_id);
+        assert_eq!(estimate.duration(), chrono::Duration::hours(3));
+        assert_eq!(lot.estimate_charge(&ticket.ticket_id).unwrap().total, 0.0);
+        assert_eq!(lot.display_info().unwrap().num_parked_vehicles(), 1);
+
+        lot.unpark_vehicle(ticket.ticket_id.clone()).unwrap();
+        assert!(matches!(
+            lot.estimate_charge(&ticket.ticket_id),
+            Err(ParkingError::TicketClosed)
+        ));
+    }
+
+    #[test]
+    fn test_rate_change_mid_stay_keeps_entry_rates() {
+        let mut lot = lot_with_floor();
+        let early = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "EARLY1".into(),
+            ))
+            .unwrap();
+        lot.set_pricing_strategy(Box::new(FlatHourly::new(20.0)));
+        let late = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "LATE01".into(),
+            ))
+            .unwrap();
+        for ticket in lot.active_tickets.lock().unwrap().values_mut() {
+            ticket.entry_time -= chrono::Duration::hours(2);
+        }
+
+        assert_eq!(lot.unpark_vehicle(early.ticket_id).unwrap().total, 20.0);
+        assert_eq!(lot.unpark_vehicle(late.ticket_id).unwrap().total, 40.0);
+    }
+
+    #[test]
+    fn test_concurrent_parking_never_double_books() {
+        let lot = lot_with_floor();
+        let capacity = lot
+            .display_info()
+            .unwrap()
+            .num_available_for(&VehicleType::Motor) as usize;
+
+        let tickets: Vec<ParkingTicket> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..16)
+                .map(|t| {
+                    let lot = &lot;
+                    scope.spawn(move || {
+                        (0..4)
+                            .filter_map(|i| {
+                                let plate = format!("T{t}-{i}");
+                                lot.park_vehicle(Vehicle::new(
+                                    VehicleType::Motor,
+                                    "Kia".into(),
+                                    plate,
+                                ))
+                                .ok()
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect()
+        });
+
+        assert_eq!(tickets.len(), capacity.min(64));
+        let spots: HashSet<&String> = tickets.iter().map(|t| &t.spot_id).collect();
+        assert_eq!(spots.len(), tickets.len());
+        let board = lot.display_info().unwrap();
+        assert_eq!(board.num_parked_vehicles() as usize, tickets.len());
+        assert_eq!(board.num_available_for(&VehicleType::Motor), 0);
+        assert_eq!(lot.active_tickets.lock().unwrap().len(), tickets.len());
+    }
+
+    #[test]
+    fn test_concurrent_park_and_unpark_keep_counts_consistent() {
+        let lot = lot_with_floor();
+        let empty = lot.display_info().unwrap().num_empty_spots();
+
+        std::thread::scope(|scope| {
+            for t in 0..8 {
+                let lot = &lot;
+                scope.spawn(move || {
+                    for i in 0..50 {
+                        let vehicle =
+                            Vehicle::new(VehicleType::Motor, "Kia".into(), format!("C{t}-{i}"));
+                        if let Ok(ticket) = lot.park_vehicle(vehicle) {
+                            lot.unpark_vehicle(ticket.ticket_id).unwrap();
+                        }
+                    }
+                });
+            }
+        });
+
+        let board = lot.display_info().unwrap();
+        assert_eq!(board.num_empty_spots(), empty);
+        assert_eq!(board.num_parked_vehicles(), 0);
+        assert!(lot.active_tickets.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_permit_holders_get_handicapped_spots() {
+        let lot = lot_with_floor();
+        lot.add_spot(1, ParkingSpot::new(true, SpotType::Handicapped))
+            .unwrap();
+
+        let regular = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "AAA111".into(),
+            ))
+            .unwrap();
+        let permit = lot
+            .park_vehicle(
+                Vehicle::new(VehicleType::Motor, "Kia".into(), "BBB222".into())
+                    .with_handicapped_permit(),
+            )
+            .unwrap();
+
+        let floor = lot.get_floor_by_id(1).unwrap().unwrap();
+        let spot_type = |spot_id: &str| floor.spots.lock().unwrap()[spot_id].spot_type;
+        assert_ne!(spot_type(&regular.spot_id), SpotType::Handicapped);
+        assert_eq!(spot_type(&permit.spot_id), SpotType::Handicapped);
+        assert!(
+            lot.handicapped_compliance_report()
+                .unwrap()
+                .violations
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_out_of_service_spots_are_skipped_and_not_advertised() {
+        let lot = lot_with_floor();
+        let floor = lot.get_floor_by_id(1).unwrap().unwrap();
+        for i in 0..9 {
+            let reason = SpotStatus::OutOfService("Repainting lines".into());
+            floor
+                .set_spot_status(&format!("spot_{i}"), reason, lot.now())
+                .unwrap();
+        }
+        let board = lot.display_info().unwrap();
+        assert_eq!(board.num_empty_spots(), 1);
+        assert_eq!(board.num_out_of_service_spots(), 9);
+
+        let car = |plate: &str| Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into());
+        let ticket = lot.park_vehicle(car("OOS001")).unwrap();
+        assert!(lot.park_vehicle(car("OOS002")).is_err());
+        assert_eq!(
+            floor.set_spot_status(
+                "spot_9",
+                SpotStatus::OutOfService("Pothole".into()),
+                lot.now()
+            ),
+            Err(ParkingError::SpotOccupied)
+        );
+        assert_eq!(
+            floor.set_spot_status("spot_0", SpotStatus::Occupied, lot.now()),
+            Err(ParkingError::InvalidSpotTransition {
+                from: SpotStatus::OutOfService("Repainting lines".into()),
+                to: SpotStatus::Occupied,
+            })
+        );
+
+        floor
+            .set_spot_status("spot_0", SpotStatus::Free, lot.now())
+            .unwrap();
+        lot.park_vehicle(car("OOS002")).unwrap();
+        lot.unpark_vehicle(ticket.ticket_id).unwrap();
+        assert_eq!(lot.display_info().unwrap().num_empty_spots(), 1);
+    }
+}
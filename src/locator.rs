@@ -0,0 +1,81 @@
+//! "Find my car": where a vehicle is parked, looked up by license plate.
+
+use chrono::{DateTime, Utc};
+
+use crate::{ParkingLot, ParkingTicket, plate::normalize_plate};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VehicleLocation {
+    pub ticket_id: String,
+    pub floor_id: u32,
+    pub spot_id: String,
+    pub entry_time: DateTime<Utc>,
+}
+
+impl ParkingLot {
+    pub fn active_ticket(&self, ticket_id: &str) -> Option<ParkingTicket> {
+        let ticket_id = self.canonical_ticket_id(ticket_id);
+        self.active_tickets.lock().unwrap().get(&ticket_id).cloned()
+    }
+
+    /// The open ticket of the vehicle with `license_plate`, if it's parked here.
+    pub fn active_ticket_for_plate(&self, license_plate: &str) -> Option<ParkingTicket> {
+        let license_plate = normalize_plate(license_plate);
+        self.active_tickets
+            .lock()
+            .unwrap()
+            .values()
+            .find(|t| t.vehicle.license_plate == license_plate)
+            .cloned()
+    }
+
+    /// Floor and spot the vehicle with `license_plate` is parked on.
+    pub fn locate_vehicle(&self, license_plate: &str) -> Option<VehicleLocation> {
+        let ticket = self.active_ticket_for_plate(license_plate)?;
+        let floors = self.floors.lock().unwrap();
+        // Spot ids repeat across floors, so match on the vehicle as well
+        let floor_id = floors.values().find_map(|floor| {
+            let spots = floor.spots.lock().unwrap();
+            spots
+                .get(&ticket.spot_id)?
+                .vehicle()
+                .filter(|v| v.license_plate == ticket.vehicle.license_plate)
+                .map(|_| floor.id)
+        })?;
+        Some(VehicleLocation {
+            ticket_id: ticket.ticket_id,
+            floor_id,
+            spot_id: ticket.spot_id,
+            entry_time: ticket.entry_time,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, Vehicle, VehicleType};
+
+    #[test]
+    fn test_locate_vehicle_by_plate() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.add_floor(ParkingFloor::new(2)).unwrap();
+        lot.close_floor(1).unwrap();
+
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "FIND01".into());
+        let ticket = lot.park_vehicle(car).unwrap();
+        let location = lot.locate_vehicle("FIND01").unwrap();
+        assert_eq!(location.floor_id, 2);
+        assert_eq!(location.spot_id, ticket.spot_id);
+        assert_eq!(location.entry_time, ticket.entry_time);
+        assert_eq!(
+            lot.active_ticket_for_plate("FIND01").unwrap().ticket_id,
+            ticket.ticket_id
+        );
+
+        lot.unpark_vehicle(ticket.ticket_id).unwrap();
+        assert!(lot.locate_vehicle("FIND01").is_none());
+        assert!(lot.active_ticket_for_plate("FIND01").is_none());
+    }
+}
@@ -1,79 +1,364 @@
+//! Command-line front end for running a lot. Every command loads the lot from a state
+//! file, applies one operation and saves the lot back, so the state carries over between
+//! invocations.
+
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    path::{Path, PathBuf},
+    process,
+};
+
+use chrono::{Duration, Utc};
 use parking_lot::{
-    Account, Parkable, ParkingFloor, ParkingLot, ParkingSpot, SpotType, User, Vehicle, VehicleType,
+    Parkable, ParkingFloor, ParkingLot, ParkingSpot, SpotType, Vehicle, VehicleType,
 };
 
+const DEFAULT_STATE_FILE: &str = "parking-lot.json";
+
+const USAGE: &str = "\
+Usage: parking-lot [--state FILE] <command> [options]
+
+Commands:
+  init [--name NAME] [--address ADDRESS] [--uid UID] [--floors N] [--force]
+  spot add --floor ID [--type TYPE] [--count N]
+  park --plate PLATE [--type TYPE] [--model MODEL]
+  unpark --ticket TICKET
+  status
+  report [--today]
+  serve [--addr HOST:PORT]    (feature `server`; type `stop` to stop and save)
+
+The state file defaults to $PARKING_LOT_STATE, then ./parking-lot.json.
+The lot's unique id, --uid, defaults to 1.
+Spot types: regular, large, xlarge, handicapped, electric.
+Vehicle types: motor, truck, bike, electric.
+";
+
 fn main() {
-    println!("🅿️ Parking Lot Project Demo");
-    println!("Low-Level Design for Interview purposes");
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(output) => print!("{output}"),
+        Err(e) => {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+    }
+}
 
-    let mut parking_lot = ParkingLot::new(
-        "Park-Wella Parking Hub".into(),
-        "Lagos, Nigeria".into(),
-        "1234".into(),
-    );
+/// Command words and `--option value` pairs. An option not followed by a value is a flag.
+#[derive(Debug, Default)]
+struct Args {
+    words: Vec<String>,
+    options: HashMap<String, String>,
+    flags: HashSet<String>,
+}
 
-    // ===============
-    // For compactness sake, each floor will initialize ten spots with the regular type. However,
-    // an API, `add_spot()` will be exposed for each floor to add more spots as needed. This is illustrated below.
-    // ===============
+impl Args {
+    fn parse(args: &[String]) -> Self {
+        let mut parsed = Args::default();
+        let mut args = args.iter().peekable();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) => match args.next_if(|next| !next.starts_with("--")) {
+                    Some(value) => {
+                        parsed.options.insert(name.to_string(), value.clone());
+                    }
+                    None => {
+                        parsed.flags.insert(name.to_string());
+                    }
+                },
+                None => parsed.words.push(arg.clone()),
+            }
+        }
+        parsed
+    }
 
-    for i in 1..=5 {
-        parking_lot.add_floor(ParkingFloor::new(i));
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
     }
 
-    let mut floor1 = parking_lot.get_floor_by_id(1).unwrap();
+    fn required(&self, name: &str) -> Result<&str, String> {
+        self.option(name).ok_or(format!("--{name} is required"))
+    }
 
-    for _ in 0..5 {
-        floor1.add_spot(ParkingSpot::new(
-            true,
-            SpotType::Large, // Assume big trucks should be on base floor
+    fn number(&self, name: &str, default: u32) -> Result<u32, String> {
+        self.option(name).map_or(Ok(default), |value| {
+            value
+                .parse()
+                .map_err(|_| format!("--{name} must be a number, not '{value}'"))
+        })
+    }
+}
+
+/// Runs one command and returns what it prints.
+fn run(args: &[String]) -> Result<String, String> {
+    let args = Args::parse(args);
+    let state = args.option("state").map_or_else(
+        || {
+            env::var("PARKING_LOT_STATE")
+                .map_or_else(|_| PathBuf::from(DEFAULT_STATE_FILE), PathBuf::from)
+        },
+        PathBuf::from,
+    );
+    let words: Vec<&str> = args.words.iter().map(String::as_str).collect();
+    match words[..] {
+        ["init"] => init(&args, &state),
+        ["spot", "add"] => {
+            let lot = load(&state)?;
+            let output = add_spots(&lot, &args)?;
+            save(&lot, &state)?;
+            Ok(output)
+        }
+        ["park"] => {
+            let lot = load(&state)?;
+            let output = park(&lot, &args)?;
+            save(&lot, &state)?;
+            Ok(output)
+        }
+        ["unpark"] => {
+            let lot = load(&state)?;
+            let output = unpark(&lot, &args)?;
+            save(&lot, &state)?;
+            Ok(output)
+        }
+        ["status"] => status(&load(&state)?),
+        ["report"] => report(&load(&state)?, args.flags.contains("today")),
+        #[cfg(feature = "server")]
+        ["serve"] => serve(
+            load(&state)?,
+            args.option("addr").unwrap_or("127.0.0.1:8080"),
+            &state,
+        ),
+        [] | ["help"] => Ok(USAGE.to_string()),
+        _ => Err(format!("unknown command '{}'\n\n{USAGE}", words.join(" "))),
+    }
+}
+
+fn load(state: &Path) -> Result<ParkingLot, String> {
+    if !state.exists() {
+        return Err(format!(
+            "no lot at {}; create one with `init`",
+            state.display()
         ));
     }
+    ParkingLot::load_from_file(state).map_err(|e| e.to_string())
+}
 
-    parking_lot.display_info();
+fn save(lot: &ParkingLot, state: &Path) -> Result<(), String> {
+    lot.save_to_file(state).map_err(|e| e.to_string())
+}
 
-    let vehicle1 = Vehicle::new(VehicleType::Motor, "Toyota".into(), "ABC123".into());
-    let vehicle2 = Vehicle::new(VehicleType::Truck, "Mac".into(), "XYZ789".into());
-    let vehicle3 = Vehicle::new(VehicleType::Bike, "Suzuki".into(), "DEF456".into());
+fn init(args: &Args, state: &Path) -> Result<String, String> {
+    if state.exists() && !args.flags.contains("force") {
+        return Err(format!(
+            "{} already exists; pass --force to replace it",
+            state.display()
+        ));
+    }
+    let name = args.option("name").unwrap_or("Parking Lot");
+    let floors = args.number("floors", 1)?;
+    let mut lot = ParkingLot::new(
+        name.to_string(),
+        args.option("address").unwrap_or_default().to_string(),
+        args.option("uid").unwrap_or("1").to_string(),
+    );
+    for id in 1..=floors {
+        lot.add_floor(ParkingFloor::new(id))
+            .map_err(|e| e.to_string())?;
+    }
+    save(&lot, state)?;
+    Ok(format!(
+        "Created {name} with {floors} floor(s) of 10 regular spots in {}\n",
+        state.display()
+    ))
+}
 
-    {
-        // Book a Parking spot
-        let mut user1 = User::new("Larry".into(), "123".into());
+fn add_spots(lot: &ParkingLot, args: &Args) -> Result<String, String> {
+    let floor_id = args
+        .required("floor")?
+        .parse()
+        .map_err(|_| "--floor must be a floor id".to_string())?;
+    let spot_type = spot_type(args.option("type").unwrap_or("regular"))?;
+    let count = args.number("count", 1)?;
+    let mut output = String::new();
+    for _ in 0..count {
+        let warnings = lot
+            .add_spot(floor_id, ParkingSpot::new(true, spot_type))
+            .map_err(|e| e.to_string())?;
+        for warning in warnings {
+            output.push_str(&format!(
+                "warning: floor {} is {} {:?} spot(s) short of its quota\n",
+                warning.floor_id, warning.missing_spots, warning.spot_type
+            ));
+        }
+    }
+    output.insert_str(
+        0,
+        &format!("Added {count} {spot_type:?} spot(s) to floor {floor_id}\n"),
+    );
+    Ok(output)
+}
 
-        user1.register_vehicle(vehicle1.clone());
-        user1.register_vehicle(vehicle2.clone());
-        user1.register_vehicle(vehicle3.clone());
-        
+fn park(lot: &ParkingLot, args: &Args) -> Result<String, String> {
+    let vehicle = Vehicle::try_new(
+        vehicle_type(args.option("type").unwrap_or("motor"))?,
+        args.option("model").unwrap_or_default().to_string(),
+        args.required("plate")?,
+    )
+    .map_err(|e| e.to_string())?;
+    let ticket = lot.park_vehicle(vehicle).map_err(|e| e.to_string())?;
+    Ok(format!(
+        "Parked {} on {}\nTicket: {}\nEntry: {}\n",
+        ticket.vehicle.license_plate(),
+        ticket.spot_id,
+        ticket.ticket_id,
+        ticket.entry_time.format("%Y-%m-%d %H:%M UTC")
+    ))
+}
 
-        match parking_lot.park_vehicle(vehicle1) {
-            Ok(ticket) => println!("Motor parked with ticket id: {}", ticket.ticket_id),
-            Err(e) => eprintln!("An error occured while getting your parking ticket {e}"),
-        };
-        match parking_lot.park_vehicle(vehicle2) {
-            Ok(ticket) => println!("Motor parked with ticket id: {}", ticket.ticket_id),
-            Err(e) => eprintln!("An error occured while getting your parking ticket {e}"),
-        };
-        let mut test_ticket = String::new();
+fn unpark(lot: &ParkingLot, args: &Args) -> Result<String, String> {
+    let charge = lot
+        .unpark_vehicle(args.required("ticket")?.to_string())
+        .map_err(|e| e.to_string())?;
+    let mut output = format!(
+        "Ticket {} closed after {} minute(s)\n",
+        charge.ticket_id,
+        charge.duration().num_minutes()
+    );
+    for line in &charge.breakdown {
+        output.push_str(&format!(
+            "  {:<32} {:>8.2}\n",
+            line.description, line.amount
+        ));
+    }
+    output.push_str(&format!("Total: {:.2}\n", charge.total));
+    Ok(output)
+}
 
+fn status(lot: &ParkingLot) -> Result<String, String> {
+    let board = lot.display_info().map_err(|e| e.to_string())?;
+    let occupancy = lot.occupancy_report();
+    let mut output = match lot.address() {
+        "" => format!("{}\n", lot.name()),
+        address => format!("{} ({address})\n", lot.name()),
+    };
+    output.push_str(&format!(
+        "Floors: {}  Parked: {}  Free: {}  Reserved: {}  Out of service: {}\n",
+        board.num_floors(),
+        board.num_parked_vehicles(),
+        board.num_empty_spots(),
+        board.num_reserved_spots(),
+        board.num_out_of_service_spots()
+    ));
+    for floor in &occupancy.floors {
+        output.push_str(&format!(
+            "  Floor {}: {}/{} occupied\n",
+            floor.floor_id, floor.occupied, floor.spots
+        ));
+    }
+    Ok(output)
+}
 
-        match parking_lot.park_vehicle(vehicle3) {
-            Ok(ticket) => {
-                println!("Motor parked with ticket id: {}", ticket.ticket_id);
-                test_ticket = ticket.ticket_id;
-            }
-            Err(e) => eprintln!("An error occured while getting your parking ticket {e}"),
-        };
+fn report(lot: &ParkingLot, today: bool) -> Result<String, String> {
+    let now = Utc::now();
+    let start = if today {
+        now.date_naive().and_hms_opt(0, 0, 0).unwrap().and_utc()
+    } else {
+        chrono::DateTime::<Utc>::MIN_UTC
+    };
+    let history = lot.ticket_history();
+    let closed: Vec<_> = history
+        .completed_tickets()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|completed| completed.ticket.exit_time.is_some_and(|exit| exit >= start))
+        .collect();
+    let stay_minutes: i64 = closed
+        .iter()
+        .filter_map(|completed| {
+            let ticket = &completed.ticket;
+            ticket
+                .exit_time
+                .map(|exit| (exit - ticket.entry_time).num_minutes())
+        })
+        .sum();
+    let period = if today {
+        now.format("%Y-%m-%d").to_string()
+    } else {
+        "all time".to_string()
+    };
+    let mut output = format!("Report for {period}\n");
+    output.push_str(&format!(
+        "Vehicles parked now: {}\n",
+        lot.display_info()
+            .map_err(|e| e.to_string())?
+            .num_parked_vehicles()
+    ));
+    output.push_str(&format!("Stays ended: {}\n", closed.len()));
+    output.push_str(&format!(
+        "Average stay: {} minute(s)\n",
+        stay_minutes.checked_div(closed.len() as i64).unwrap_or(0)
+    ));
+    // An empty f32 sum is -0.0
+    let revenue = history
+        .revenue_between(start, now + Duration::seconds(1))
+        .map_err(|e| e.to_string())?
+        + 0.0;
+    output.push_str(&format!("Revenue: {revenue:.2}\n"));
+    Ok(output)
+}
 
-        parking_lot.display_info();
+/// Serves the lot over HTTP until `stop` is typed on standard input, then drains the lot
+/// and saves it back to the state file.
+#[cfg(feature = "server")]
+fn serve(lot: ParkingLot, addr: &str, state: &Path) -> Result<String, String> {
+    use parking_lot::server::{ApiServer, StopSignal};
+    use std::io::BufRead;
 
-        match parking_lot.unpark_vehicle(test_ticket.to_string()) {
-            Ok(charge) => println!(
-                "Vehicle successfully unparked. Grand total: {}, chargeback: {}",
-                charge.total, charge.chargeback
-            ),
-            Err(e) => eprintln!("An error occured while unparking: {e}"),
-        }
+    let listener = std::net::TcpListener::bind(addr).map_err(|e| e.to_string())?;
+    eprintln!(
+        "Serving {} on http://{addr}; type `stop` to stop",
+        lot.name()
+    );
+    let stop = StopSignal::new();
+    {
+        let stop = stop.clone();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+                if line.trim() == "stop" {
+                    stop.stop();
+                    return;
+                }
+            }
+        });
     }
+    let report = ApiServer::new(std::sync::Arc::new(lot))
+        .serve_until_stopped(
+            listener,
+            &stop,
+            std::time::Duration::from_secs(10),
+            Some(state),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(format!(
+        "Stopped; saved {} open ticket(s) to {}\n",
+        report.open_tickets,
+        state.display()
+    ))
+}
+
+fn spot_type(name: &str) -> Result<SpotType, String> {
+    SpotType::ALL
+        .into_iter()
+        .find(|spot_type| format!("{spot_type:?}").eq_ignore_ascii_case(name))
+        .ok_or(format!("unknown spot type '{name}'"))
+}
+
+fn vehicle_type(name: &str) -> Result<VehicleType, String> {
+    VehicleType::ALL
+        .into_iter()
+        .find(|vehicle_type| format!("{vehicle_type:?}").eq_ignore_ascii_case(name))
+        .ok_or(format!("unknown vehicle type '{name}'"))
 }
 
 #[cfg(test)]
@@ -89,9 +374,52 @@ mod tests {
         );
 
         for i in 1..=5 {
-            parking_lot.add_floor(ParkingFloor::new(i));
+            parking_lot.add_floor(ParkingFloor::new(i)).unwrap();
         }
 
-        assert_eq!(parking_lot.display_info().num_floors(), 5);
+        assert_eq!(parking_lot.display_info().unwrap().num_floors(), 5);
+    }
+
+    #[test]
+    fn test_commands_share_state_through_the_state_file() {
+        let state = env::temp_dir().join(format!("cli-{}.json", process::id()));
+        let cli = |command: &str| {
+            let mut args = vec!["--state".to_string(), state.display().to_string()];
+            args.extend(command.split(' ').map(String::from));
+            run(&args)
+        };
+
+        cli("init --name Hub --floors 2 --force").unwrap();
+        assert!(
+            cli("init --name Hub")
+                .unwrap_err()
+                .contains("already exists")
+        );
+        cli("spot add --floor 2 --type large --count 3").unwrap();
+        assert!(cli("spot add --floor 2 --type huge").is_err());
+
+        let parked = cli("park --plate ABC123 --type truck").unwrap();
+        let ticket = parked
+            .lines()
+            .find_map(|line| line.strip_prefix("Ticket: "))
+            .unwrap()
+            .to_string();
+        assert!(
+            cli("park --plate abc123")
+                .unwrap_err()
+                .contains("already parked")
+        );
+        assert!(cli("status").unwrap().contains("Parked: 1"));
+
+        cli(&format!("unpark --ticket {ticket}")).unwrap();
+        let report = cli("report --today").unwrap();
+        std::fs::remove_file(&state).unwrap();
+        assert!(report.contains("Stays ended: 1"));
+        assert!(report.contains("Vehicles parked now: 0"));
+        assert!(
+            cli("status")
+                .unwrap_err()
+                .contains("create one with `init`")
+        );
     }
 }
@@ -0,0 +1,310 @@
+//! Recurring maintenance windows for whole floors, e.g. floor 3 every Tuesday
+//! 01:00-04:00. `run_maintenance_windows` takes the floor's free spots out of service while
+//! a window is on and returns them to service once it's over; spots still occupied or
+//! reserved when it starts are taken out by a later run once they're free. Reservations
+//! overlapping a window are never placed on its floor, and availability calendars leave the
+//! floor out of the slots a window touches.
+//!
+//! Only spots a window took out of service are returned to service by it, so spots closed
+//! by hand stay closed.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{ParkingError, ParkingLot, SpotStatus, standing::RecurrenceRule};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaintenanceWindow {
+    pub window_id: String,
+    pub floor_id: u32,
+    pub rule: RecurrenceRule,
+    /// Shown as the out-of-service reason on the floor's spots.
+    pub reason: String,
+}
+
+impl MaintenanceWindow {
+    pub fn new(window_id: String, floor_id: u32, rule: RecurrenceRule, reason: String) -> Self {
+        Self {
+            window_id,
+            floor_id,
+            rule,
+            reason,
+        }
+    }
+
+    fn overlaps(&self, from: DateTime<Utc>, until: DateTime<Utc>) -> bool {
+        !self.rule.occurrences(from, until).is_empty()
+    }
+
+    fn is_on(&self, at: DateTime<Utc>) -> bool {
+        let time = at.time();
+        self.rule.days.contains(&at.weekday()) && self.rule.start <= time && time < self.rule.end
+    }
+}
+
+/// Spots changed by one run of the maintenance windows, by floor id and spot key.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MaintenanceRun {
+    pub taken_out: Vec<(u32, String)>,
+    pub restored: Vec<(u32, String)>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct MaintenanceSchedule {
+    windows: Vec<MaintenanceWindow>,
+    /// Spot keys each window has out of service now.
+    closed_spots: HashMap<String, Vec<String>>,
+}
+
+impl MaintenanceSchedule {
+    /// Each window with the spot keys it has out of service now.
+    pub(crate) fn saved_windows(&self) -> Vec<(MaintenanceWindow, Vec<String>)> {
+        self.windows
+            .iter()
+            .map(|window| {
+                let closed = self
+                    .closed_spots
+                    .get(&window.window_id)
+                    .cloned()
+                    .unwrap_or_default();
+                (window.clone(), closed)
+            })
+            .collect()
+    }
+
+    pub(crate) fn restore(&mut self, window: MaintenanceWindow, closed: Vec<String>) {
+        self.closed_spots.insert(window.window_id.clone(), closed);
+        self.windows.push(window);
+    }
+}
+
+impl ParkingLot {
+    pub fn add_maintenance_window(&self, window: MaintenanceWindow) -> Result<(), ParkingError> {
+        if !window.rule.is_valid() {
+            return Err(ParkingError::InvalidMaintenanceWindow);
+        }
+        if !self.floors.lock()?.contains_key(&window.floor_id) {
+            return Err(ParkingError::FloorNotFound);
+        }
+        let mut maintenance = self.maintenance.lock()?;
+        if maintenance
+            .windows
+            .iter()
+            .any(|w| w.window_id == window.window_id)
+        {
+            return Err(ParkingError::DuplicateMaintenanceWindow(window.window_id));
+        }
+        maintenance.windows.push(window);
+        Ok(())
+    }
+
+    pub fn maintenance_windows(&self) -> Vec<MaintenanceWindow> {
+        self.maintenance.lock().unwrap().windows.clone()
+    }
+
+    /// Whether a maintenance window on `floor_id` overlaps `from..until`.
+    pub fn floor_under_maintenance(
+        &self,
+        floor_id: u32,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> bool {
+        self.maintenance
+            .lock()
+            .unwrap()
+            .windows
+            .iter()
+            .any(|window| window.floor_id == floor_id && window.overlaps(from, until))
+    }
+
+    /// Whether a maintenance window took the spot out of service and still holds it. Lock
+    /// `floors` first, as `run_maintenance_windows` does.
+    pub(crate) fn held_for_maintenance(
+        &self,
+        floor_id: u32,
+        spot_id: &str,
+    ) -> Result<bool, ParkingError> {
+        Ok(self
+            .spots_held_for_maintenance()?
+            .contains(&(floor_id, spot_id.to_string())))
+    }
+
+    /// Floor and spot id of every spot maintenance windows hold out of service. Lock
+    /// `floors` first, as `run_maintenance_windows` does.
+    pub(crate) fn spots_held_for_maintenance(
+        &self,
+    ) -> Result<HashSet<(u32, String)>, ParkingError> {
+        let maintenance = self.maintenance.lock()?;
+        Ok(maintenance
+            .windows
+            .iter()
+            .filter_map(|window| {
+                let closed = maintenance.closed_spots.get(&window.window_id)?;
+                Some(closed.iter().map(|id| (window.floor_id, id.clone())))
+            })
+            .flatten()
+            .collect())
+    }
+
+    /// Takes free spots out of service on floors whose window is on, and returns spots to
+    /// service on floors whose window has ended. Meant to be called every few minutes.
+    pub fn run_maintenance_windows(&self) -> MaintenanceRun {
+        let now = self.now();
+        let mut run = MaintenanceRun::default();
+        let floors = self.floors.lock().unwrap();
+        let mut maintenance = self.maintenance.lock().unwrap();
+        let MaintenanceSchedule {
+            windows,
+            closed_spots,
+        } = &mut *maintenance;
+        for window in windows.iter() {
+            let Some(floor) = floors.get(&window.floor_id) else {
+                continue;
+            };
+            let closed = closed_spots.entry(window.window_id.clone()).or_default();
+            let mut spots = floor.spots.lock().unwrap();
+            if window.is_on(now) {
+                let mut keys: Vec<&String> = spots.keys().collect();
+                keys.sort();
+                let keys: Vec<String> = keys.into_iter().cloned().collect();
+                for key in keys {
+                    let spot = spots.get_mut(&key).unwrap();
+                    let closing = SpotStatus::OutOfService(window.reason.clone());
+                    if spot.is_available() && spot.set_maintenance_status(closing, now).is_ok() {
+                        closed.push(key.clone());
+                        run.taken_out.push((window.floor_id, key));
+                    }
+                }
+            } else {
+                for key in closed.drain(..) {
+                    if let Some(spot) = spots.get_mut(&key)
+                        && spot.set_maintenance_status(SpotStatus::Free, now).is_ok()
+                    {
+                        run.restored.push((window.floor_id, key));
+                    }
+                }
+            }
+        }
+        run
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, SpotType, Vehicle, VehicleType, clock::MockClock};
+    use chrono::{Duration, NaiveTime, TimeZone, Weekday};
+
+    #[test]
+    fn test_window_closes_and_reopens_floor_and_blocks_reservations() {
+        // A Tuesday, 00:30
+        let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 1, 2, 0, 30, 0).unwrap());
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()));
+        lot.add_floor(ParkingFloor::new(3)).unwrap();
+        let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let window = MaintenanceWindow::new(
+            "resurface".into(),
+            3,
+            RecurrenceRule::new(vec![Weekday::Tue], at(1, 0), at(4, 0)),
+            "Resurfacing".into(),
+        );
+        lot.add_maintenance_window(window.clone()).unwrap();
+        assert_eq!(
+            lot.add_maintenance_window(window),
+            Err(ParkingError::DuplicateMaintenanceWindow("resurface".into()))
+        );
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "MNT001".into());
+        let ticket = lot.park_vehicle(car).unwrap();
+        let guest = Vehicle::new(VehicleType::Motor, "Kia".into(), "MNT002".into());
+
+        let now = lot.now();
+        assert_eq!(
+            lot.reserve_spot(guest.clone(), now, now + Duration::hours(1))
+                .unwrap_err(),
+            ParkingError::NoSpotAvailable
+        );
+        assert!(lot.run_maintenance_windows().taken_out.is_empty());
+
+        clock.advance(Duration::hours(1));
+        assert_eq!(lot.run_maintenance_windows().taken_out.len(), 9);
+        lot.unpark_vehicle(ticket.ticket_id).unwrap();
+        assert_eq!(
+            lot.run_maintenance_windows().taken_out,
+            vec![(3, ticket.spot_id.clone())]
+        );
+        let floor = lot.get_floor_by_id(3).unwrap().unwrap();
+        assert_eq!(
+            floor.spots.lock().unwrap()[&ticket.spot_id].out_of_service_reason(),
+            Some("Resurfacing")
+        );
+
+        clock.advance(Duration::hours(3));
+        assert_eq!(lot.run_maintenance_windows().restored.len(), 10);
+        assert!(lot.run_maintenance_windows().restored.is_empty());
+        let now = lot.now();
+        lot.reserve_spot(guest, now, now + Duration::hours(1))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_windows_need_a_floor_and_a_valid_rule() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let at = |h| NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+        let window = |floor_id, rule| {
+            MaintenanceWindow::new("sweep".into(), floor_id, rule, "Sweeping".into())
+        };
+
+        assert_eq!(
+            lot.add_maintenance_window(window(
+                2,
+                RecurrenceRule::new(vec![Weekday::Mon], at(1), at(2))
+            )),
+            Err(ParkingError::FloorNotFound)
+        );
+        assert_eq!(
+            lot.add_maintenance_window(window(
+                1,
+                RecurrenceRule::new(vec![Weekday::Mon], at(2), at(1))
+            )),
+            Err(ParkingError::InvalidMaintenanceWindow)
+        );
+    }
+
+    #[test]
+    fn test_calendar_leaves_out_floors_under_maintenance() {
+        // A Monday, 00:00
+        let from = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(MockClock::new(from)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.add_floor(ParkingFloor::new(2)).unwrap();
+        let at = |h| NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+        lot.add_maintenance_window(MaintenanceWindow::new(
+            "sweep".into(),
+            2,
+            RecurrenceRule::new(vec![Weekday::Mon], at(1), at(2)),
+            "Sweeping".into(),
+        ))
+        .unwrap();
+
+        let slots = lot
+            .availability_calendar(
+                SpotType::Regular,
+                from,
+                from + Duration::hours(3),
+                Duration::hours(1),
+            )
+            .unwrap();
+        let reservable: Vec<u32> = slots.iter().map(|s| s.reservable).collect();
+        assert_eq!(reservable, vec![20, 10, 20]);
+
+        let guest = Vehicle::new(VehicleType::Motor, "Kia".into(), "MNT003".into());
+        let window = (from + Duration::hours(1), from + Duration::hours(2));
+        let reservation = lot.reserve_spot(guest, window.0, window.1).unwrap();
+        assert_eq!(reservation.floor_id, 1);
+    }
+}
@@ -0,0 +1,327 @@
+//! Receipt and reminder messages built from per-lot, per-locale templates.
+//!
+//! Templates use `{placeholder}` substitution. Unknown placeholders are left in place so a
+//! typo shows up in the rendered message instead of silently disappearing.
+//!
+//! With a notifier set, the lot sends them itself to owners who left a contact for their
+//! plate: a receipt when the vehicle leaves, a reminder when it first overstays and a
+//! notice when an attendant moves it. A message the notifier can't deliver doesn't hold up
+//! the operation that sent it.
+
+use std::{collections::HashMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ParkingLot, error::ParkingError, plate::normalize_plate};
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TemplateKind {
+    Receipt,
+    Reminder,
+    Relocation,
+}
+
+/// A rendered, ready-to-send message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivery channel for rendered notifications (email, SMS gateway, ...).
+pub trait Notifier: fmt::Debug + Send + Sync {
+    fn send(&self, recipient: &str, notification: &Notification) -> Result<(), String>;
+}
+
+/// Where to reach a vehicle's owner, and the locale their messages are rendered in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Contact {
+    /// Address the notifier understands: an email address, a phone number, ...
+    pub recipient: String,
+    pub locale: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    pub subject: String,
+    pub body: String,
+}
+
+impl Template {
+    pub fn new(subject: impl Into<String>, body: impl Into<String>) -> Self {
+        Self {
+            subject: subject.into(),
+            body: body.into(),
+        }
+    }
+
+    pub fn render(&self, values: &HashMap<&str, String>) -> Notification {
+        Notification {
+            subject: substitute(&self.subject, values),
+            body: substitute(&self.body, values),
+        }
+    }
+}
+
+fn substitute(text: &str, values: &HashMap<&str, String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}') {
+            Some(end) => {
+                let key = &after[..end];
+                match values.get(key) {
+                    Some(value) => out.push_str(value),
+                    None => out.push_str(&rest[start..start + end + 2]),
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Templates for one lot, keyed by kind and locale.
+#[derive(Debug, Clone)]
+pub struct TemplateSet {
+    templates: HashMap<(TemplateKind, String), Template>,
+}
+
+impl Default for TemplateSet {
+    fn default() -> Self {
+        let mut set = Self {
+            templates: HashMap::new(),
+        };
+        set.insert(
+            TemplateKind::Receipt,
+            DEFAULT_LOCALE,
+            Template::new(
+                "Your receipt from {lot_name}",
+                "Thanks for parking at {lot_name}.\n\n\
+                 Ticket: {ticket_id} ({short_code})\n\
+                 Plate: {license_plate}\n\
+                 Entry: {entry_time}\n\
+                 Exit: {exit_time}\n\
+                 Total: ${total}\n",
+            ),
+        );
+        set.insert(
+            TemplateKind::Reminder,
+            DEFAULT_LOCALE,
+            Template::new(
+                "Your vehicle is still parked at {lot_name}",
+                "Vehicle {license_plate} has been parked at spot {spot_id} since {entry_time}.\n\
+                 Ticket: {ticket_id} ({short_code})\n",
+            ),
+        );
+        set.insert(
+            TemplateKind::Relocation,
+            DEFAULT_LOCALE,
+            Template::new(
+                "Your vehicle has been moved at {lot_name}",
+                "Vehicle {license_plate} was moved from spot {from_spot_id} to spot {spot_id} \
+                 on floor {floor_id} ({reason}).\n\
+                 Ticket: {ticket_id} ({short_code})\n",
+            ),
+        );
+        set
+    }
+}
+
+impl TemplateSet {
+    pub fn insert(&mut self, kind: TemplateKind, locale: &str, template: Template) {
+        self.templates
+            .insert((kind, locale.to_ascii_lowercase()), template);
+    }
+
+    /// Looks up `locale` exactly, then its language (`fr-CA` -> `fr`), then the default locale.
+    pub fn get(&self, kind: TemplateKind, locale: &str) -> Option<&Template> {
+        let locale = locale.to_ascii_lowercase();
+        let language = locale.split(['-', '_']).next().unwrap_or(DEFAULT_LOCALE);
+        [locale.as_str(), language, DEFAULT_LOCALE]
+            .into_iter()
+            .find_map(|candidate| self.templates.get(&(kind, candidate.to_string())))
+    }
+}
+
+impl ParkingLot {
+    pub fn set_notifier(&mut self, notifier: Box<dyn Notifier>) {
+        self.notifier = Some(notifier);
+    }
+
+    /// Sends notices about stays of the vehicle with `license_plate` to `recipient`,
+    /// rendered for `locale`. Replaces any earlier contact for the plate.
+    pub fn set_contact(&self, license_plate: &str, recipient: &str, locale: &str) {
+        self.contacts.lock().unwrap().insert(
+            normalize_plate(license_plate),
+            Contact {
+                recipient: recipient.to_string(),
+                locale: locale.to_string(),
+            },
+        );
+    }
+
+    /// Stops notices for the plate. Returns whether it had a contact.
+    pub fn remove_contact(&self, license_plate: &str) -> bool {
+        self.contacts
+            .lock()
+            .unwrap()
+            .remove(&normalize_plate(license_plate))
+            .is_some()
+    }
+
+    pub fn contact_for(&self, license_plate: &str) -> Option<Contact> {
+        self.contacts
+            .lock()
+            .unwrap()
+            .get(&normalize_plate(license_plate))
+            .cloned()
+    }
+
+    /// Reminds the owner of a parked vehicle that it is still here.
+    pub fn send_reminder(&self, ticket_id: &str) -> Result<(), ParkingError> {
+        let notifier = self.notifier.as_ref().ok_or(ParkingError::NoNotifier)?;
+        let ticket = self
+            .active_ticket(ticket_id)
+            .ok_or(ParkingError::InvalidTicket)?;
+        let contact = self
+            .contact_for(&ticket.vehicle.license_plate)
+            .ok_or(ParkingError::NoContact)?;
+        let reminder = self
+            .reminder_notification(&ticket, &contact.locale)
+            .ok_or_else(|| ParkingError::NotificationFailed("no reminder template".into()))?;
+        notifier
+            .send(&contact.recipient, &reminder)
+            .map_err(ParkingError::NotificationFailed)
+    }
+
+    /// Renders a notice for the owner of `license_plate`, if there is a notifier and they
+    /// left a contact.
+    pub(crate) fn notice_for(
+        &self,
+        license_plate: &str,
+        render: impl FnOnce(&str) -> Option<Notification>,
+    ) -> Option<(String, Notification)> {
+        self.notifier.as_ref()?;
+        let contact = self.contact_for(license_plate)?;
+        render(&contact.locale).map(|notification| (contact.recipient, notification))
+    }
+
+    /// Sends a notice the lot raised itself.
+    pub(crate) fn notify(&self, (recipient, notification): (String, Notification)) {
+        self.deliver(&recipient, &notification);
+    }
+
+    /// Hands a notice to the notifier; one that can't be delivered is dropped.
+    pub(crate) fn deliver(&self, recipient: &str, notification: &Notification) {
+        if let Some(notifier) = &self.notifier {
+            let _ = notifier.send(recipient, notification);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Parkable, ParkingFloor, Vehicle, VehicleType, batch::BatchOperation, clock::MockClock,
+        overstay::OverstayPolicy,
+    };
+    use chrono::Duration;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Default, Clone)]
+    struct Outbox {
+        sent: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl Notifier for Outbox {
+        fn send(&self, recipient: &str, notification: &Notification) -> Result<(), String> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push((recipient.to_string(), notification.subject.clone()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_template_falls_back_to_language_then_default_locale() {
+        let mut set = TemplateSet::default();
+        set.insert(
+            TemplateKind::Receipt,
+            "fr",
+            Template::new("Reçu de {lot_name}", "Total : {total} $"),
+        );
+
+        let values = HashMap::from([("lot_name", "Hub".to_string())]);
+        let french = set
+            .get(TemplateKind::Receipt, "fr-CA")
+            .unwrap()
+            .render(&values);
+        let fallback = set
+            .get(TemplateKind::Receipt, "de")
+            .unwrap()
+            .render(&values);
+
+        assert_eq!(french.subject, "Reçu de Hub");
+        assert_eq!(french.body, "Total : {total} $");
+        assert_eq!(fallback.subject, "Your receipt from Hub");
+    }
+
+    #[test]
+    fn test_owners_with_a_contact_get_reminders_and_receipts() {
+        let clock = MockClock::default();
+        let outbox = Outbox::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_overstay_policy(OverstayPolicy::default().with_max_stay(Duration::hours(1)));
+        let park = |plate: &str| {
+            lot.park_vehicle(Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into()))
+                .unwrap()
+        };
+        let known = park("MSG001");
+        let unknown = park("MSG002");
+        lot.set_contact("MSG001", "ada@example.com", "en");
+        assert_eq!(
+            lot.send_reminder(&known.ticket_id),
+            Err(ParkingError::NoNotifier)
+        );
+        lot.set_notifier(Box::new(outbox.clone()));
+        assert_eq!(
+            lot.send_reminder(&unknown.ticket_id),
+            Err(ParkingError::NoContact)
+        );
+
+        clock.advance(Duration::hours(2));
+        assert_eq!(lot.find_overstays(lot.now()).len(), 2);
+        // An exit that's rolled back sends no receipt
+        let outcome = lot
+            .apply_batch(vec![
+                BatchOperation::Unpark(known.ticket_id.clone()),
+                BatchOperation::Unpark("missing".into()),
+            ])
+            .unwrap();
+        assert!(!outcome.committed);
+        lot.unpark_vehicle(known.ticket_id).unwrap();
+        lot.unpark_vehicle(unknown.ticket_id).unwrap();
+        let to_ada = |subject: &str| ("ada@example.com".to_string(), subject.to_string());
+        assert_eq!(
+            *outbox.sent.lock().unwrap(),
+            vec![
+                to_ada("Your vehicle is still parked at Hub"),
+                to_ada("Your receipt from Hub"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! Several lots run by one operator, e.g. the car parks of a campus. Arriving vehicles
+//! are sent to the nearest lot that would let them in, and reporting covers every lot.
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    Parkable, ParkingCharge, ParkingLot, ParkingTicket, Vehicle, entry_policy::EntryRequest,
+    error::ParkingError,
+};
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl GeoPoint {
+    pub fn new(latitude: f64, longitude: f64) -> Self {
+        Self {
+            latitude,
+            longitude,
+        }
+    }
+
+    /// Great-circle distance in kilometres.
+    pub fn distance_km(&self, other: &GeoPoint) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.longitude - self.longitude).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+#[derive(Debug)]
+struct OperatedLot {
+    lot: ParkingLot,
+    location: GeoPoint,
+}
+
+/// A ticket issued by the lot a vehicle was routed to.
+#[derive(Debug, Clone)]
+pub struct RoutedTicket {
+    pub lot_uid: String,
+    pub distance_km: f64,
+    pub ticket: ParkingTicket,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LotSummary {
+    pub lot_uid: String,
+    pub name: String,
+    pub parked: u32,
+    pub empty_spots: u32,
+    pub revenue: f32,
+}
+
+/// Occupancy now and revenue for a period, per lot and in total.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperatorReport {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// In the order the lots were added.
+    pub lots: Vec<LotSummary>,
+}
+
+impl OperatorReport {
+    pub fn total_parked(&self) -> u32 {
+        self.lots.iter().map(|l| l.parked).sum()
+    }
+
+    pub fn total_empty_spots(&self) -> u32 {
+        self.lots.iter().map(|l| l.empty_spots).sum()
+    }
+
+    pub fn total_revenue(&self) -> f32 {
+        self.lots.iter().map(|l| l.revenue).sum()
+    }
+
+    /// Parked vehicles as a share of parked plus empty spots, from 0 to 1.
+    pub fn occupancy_rate(&self) -> f32 {
+        let capacity = self.total_parked() + self.total_empty_spots();
+        if capacity == 0 {
+            0.0
+        } else {
+            self.total_parked() as f32 / capacity as f32
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParkingOperator {
+    name: String,
+    lots: Vec<OperatedLot>,
+}
+
+impl ParkingOperator {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            lots: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn add_lot(&mut self, lot: ParkingLot, location: GeoPoint) -> Result<(), ParkingError> {
+        if self.lot(lot.uid()).is_some() {
+            return Err(ParkingError::DuplicateLot(lot.uid().to_string()));
+        }
+        self.lots.push(OperatedLot { lot, location });
+        Ok(())
+    }
+
+    /// Stops operating the lot and hands it back.
+    pub fn remove_lot(&mut self, lot_uid: &str) -> Option<ParkingLot> {
+        let index = self.lots.iter().position(|o| o.lot.uid() == lot_uid)?;
+        Some(self.lots.remove(index).lot)
+    }
+
+    pub fn lot(&self, lot_uid: &str) -> Option<&ParkingLot> {
+        self.lots
+            .iter()
+            .map(|o| &o.lot)
+            .find(|lot| lot.uid() == lot_uid)
+    }
+
+    pub fn lot_mut(&mut self, lot_uid: &str) -> Option<&mut ParkingLot> {
+        self.lots
+            .iter_mut()
+            .map(|o| &mut o.lot)
+            .find(|lot| lot.uid() == lot_uid)
+    }
+
+    pub fn lots(&self) -> impl Iterator<Item = &ParkingLot> {
+        self.lots.iter().map(|o| &o.lot)
+    }
+
+    /// Parks `vehicle` at the nearest lot to `from` that admits it. When none does, the
+    /// nearest lot's refusal is returned.
+    pub fn park_vehicle(
+        &self,
+        vehicle: Vehicle,
+        from: GeoPoint,
+    ) -> Result<RoutedTicket, ParkingError> {
+        let mut by_distance: Vec<(f64, &ParkingLot)> = self
+            .lots
+            .iter()
+            .map(|o| (o.location.distance_km(&from), &o.lot))
+            .collect();
+        by_distance.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let (_, nearest) = by_distance.first().ok_or(ParkingError::NoSpotAvailable)?;
+        let Some((distance_km, lot)) = by_distance.iter().find(|(_, lot)| lot.can_admit(&vehicle))
+        else {
+            nearest.check_entry(&EntryRequest {
+                vehicle: &vehicle,
+                zone_id: None,
+                at: nearest.now(),
+            })?;
+            return Err(ParkingError::NoSpotAvailable);
+        };
+        let ticket = lot.park_vehicle(vehicle)?;
+        Ok(RoutedTicket {
+            lot_uid: lot.uid().to_string(),
+            distance_km: *distance_km,
+            ticket,
+        })
+    }
+
+    /// Checks the vehicle out of whichever lot issued the ticket.
+    pub fn unpark_vehicle(&self, ticket_id: &str) -> Result<ParkingCharge, ParkingError> {
+        self.lots()
+            .find(|lot| lot.active_ticket(ticket_id).is_some())
+            .ok_or(ParkingError::InvalidTicket)?
+            .unpark_vehicle(ticket_id.to_string())
+    }
+
+    /// Current occupancy of every lot, with revenue from stays that ended in `[start, end)`.
+    pub fn report(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<OperatorReport, ParkingError> {
+        Ok(OperatorReport {
+            start,
+            end,
+            lots: self
+                .lots()
+                .map(|lot| {
+                    let board = lot.display_info()?;
+                    Ok(LotSummary {
+                        lot_uid: lot.uid().to_string(),
+                        name: lot.name().to_string(),
+                        parked: board.num_parked_vehicles(),
+                        empty_spots: board.num_empty_spots(),
+                        revenue: lot.ticket_history().revenue_between(start, end)?,
+                    })
+                })
+                .collect::<Result<_, ParkingError>>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParkingFloor, VehicleType, clock::MockClock, pricing::FlatHourly};
+    use chrono::Duration;
+
+    #[test]
+    fn test_routes_to_nearest_lot_with_room_and_reports_all_lots() {
+        let clock = MockClock::default();
+        let lot = |uid: &str| {
+            let mut lot = ParkingLot::new(format!("Lot {uid}"), "Lagos".into(), uid.into())
+                .with_clock(Box::new(clock.clone()))
+                .with_pricing_strategy(Box::new(FlatHourly::new(10.0)));
+            lot.add_floor(ParkingFloor::new(1)).unwrap();
+            lot
+        };
+        let mut operator = ParkingOperator::new("Campus".into());
+        let library = GeoPoint::new(6.5158, 3.3898);
+        operator.add_lot(lot("north"), library).unwrap();
+        operator
+            .add_lot(lot("south"), GeoPoint::new(6.5000, 3.3900))
+            .unwrap();
+        assert_eq!(
+            operator.add_lot(lot("north"), library).unwrap_err(),
+            ParkingError::DuplicateLot("north".into())
+        );
+
+        let car = |plate: &str| Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into());
+        let mut tickets = Vec::new();
+        for n in 0..11 {
+            tickets.push(
+                operator
+                    .park_vehicle(car(&format!("C{n}")), library)
+                    .unwrap(),
+            );
+        }
+        assert!(tickets[..10].iter().all(|t| t.lot_uid == "north"));
+        assert_eq!(tickets[10].lot_uid, "south");
+        assert!(tickets[10].distance_km > 1.0);
+
+        clock.advance(Duration::hours(2));
+        let charge = operator
+            .unpark_vehicle(&tickets[10].ticket.ticket_id)
+            .unwrap();
+        assert_eq!(charge.total, 20.0);
+
+        let now = operator.lot("south").unwrap().now();
+        let report = operator
+            .report(now - Duration::days(1), now + Duration::days(1))
+            .unwrap();
+        let uids: Vec<&str> = report.lots.iter().map(|l| l.lot_uid.as_str()).collect();
+        assert_eq!(uids, vec!["north", "south"]);
+        assert_eq!(report.total_parked(), 10);
+        assert_eq!(report.lots[1].revenue, 20.0);
+        assert_eq!(report.total_revenue(), 20.0);
+        assert_eq!(report.occupancy_rate(), 0.5);
+    }
+}
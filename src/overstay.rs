@@ -0,0 +1,196 @@
+//! Maximum stays and overstay fines. A lot may cap how long a vehicle stays, overall or
+//! per spot type; a stay past its cap is fined by the policy's schedule when the ticket
+//! is priced, and reported once to subscribers the first time a scan finds it.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{ParkingLot, ParkingTicket, SpotType, events::ParkingEvent};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OverstayPolicy {
+    max_stay: Option<Duration>,
+    spot_types: HashMap<SpotType, Duration>,
+    /// `(overstay, fine)` tiers, ordered by overstay.
+    fines: Vec<(Duration, f32)>,
+}
+
+impl OverstayPolicy {
+    /// Caps every stay at `max_stay`.
+    pub fn with_max_stay(mut self, max_stay: Duration) -> Self {
+        self.max_stay = Some(max_stay);
+        self
+    }
+
+    /// Caps stays on `spot_type` spots, instead of the lot-wide cap.
+    pub fn with_spot_type_max_stay(mut self, spot_type: SpotType, max_stay: Duration) -> Self {
+        self.spot_types.insert(spot_type, max_stay);
+        self
+    }
+
+    /// Fines an overstay of at least `overstay` by `fine`. A stay is fined by the highest
+    /// tier it reaches; a tier at zero fines any overstay.
+    pub fn with_fine(mut self, overstay: Duration, fine: f32) -> Self {
+        self.fines.push((overstay, fine));
+        self.fines.sort_by_key(|(overstay, _)| *overstay);
+        self
+    }
+
+    pub fn max_stay_for(&self, spot_type: Option<SpotType>) -> Option<Duration> {
+        spot_type
+            .and_then(|t| self.spot_types.get(&t).copied())
+            .or(self.max_stay)
+    }
+
+    pub fn fine_for(&self, overstay: Duration) -> f32 {
+        if overstay <= Duration::zero() {
+            return 0.0;
+        }
+        self.fines
+            .iter()
+            .rev()
+            .find(|(threshold, _)| overstay >= *threshold)
+            .map_or(0.0, |(_, fine)| *fine)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overstay {
+    pub ticket_id: String,
+    pub license_plate: String,
+    pub spot_id: String,
+    pub entry_time: DateTime<Utc>,
+    pub max_stay: Duration,
+    /// Time past `max_stay`.
+    pub overstay: Duration,
+    pub fine: f32,
+}
+
+impl ParkingLot {
+    pub fn set_overstay_policy(&mut self, policy: OverstayPolicy) {
+        self.overstay_policy = policy;
+    }
+
+    pub fn overstay_policy(&self) -> &OverstayPolicy {
+        &self.overstay_policy
+    }
+
+    /// Open tickets past their maximum stay at `now`, longest overstay first. Tickets
+    /// found for the first time are announced with a `VehicleOverstayed` event, and their
+    /// owners are sent a reminder if they left a contact.
+    pub fn find_overstays(&self, now: DateTime<Utc>) -> Vec<Overstay> {
+        let mut overstays: Vec<Overstay> = self
+            .active_tickets
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|ticket| self.overstay(ticket, now))
+            .collect();
+        overstays.sort_by(|a, b| {
+            b.overstay
+                .cmp(&a.overstay)
+                .then_with(|| a.ticket_id.cmp(&b.ticket_id))
+        });
+
+        let new: Vec<&Overstay> = {
+            let mut reported = self.reported_overstays.lock().unwrap();
+            // Forget tickets that have since closed
+            reported.retain(|id| overstays.iter().any(|o| &o.ticket_id == id));
+            overstays
+                .iter()
+                .filter(|o| reported.insert(o.ticket_id.clone()))
+                .collect()
+        };
+        for overstay in new {
+            self.emit(ParkingEvent::VehicleOverstayed {
+                ticket_id: overstay.ticket_id.clone(),
+                license_plate: overstay.license_plate.clone(),
+                max_stay_minutes: overstay.max_stay.num_minutes(),
+                at: now,
+            });
+            if let Some(ticket) = self.active_ticket(&overstay.ticket_id)
+                && let Some(reminder) = self.notice_for(&overstay.license_plate, |locale| {
+                    self.reminder_notification(&ticket, locale)
+                })
+            {
+                self.notify(reminder);
+            }
+        }
+        overstays
+    }
+
+    /// How far `ticket` is past its maximum stay at `until`, if it is.
+    pub(crate) fn overstay(
+        &self,
+        ticket: &ParkingTicket,
+        until: DateTime<Utc>,
+    ) -> Option<Overstay> {
+        let max_stay = self.overstay_policy.max_stay_for(ticket.spot_type)?;
+        let max_stay = max_stay + ticket.stay_extension;
+        let overstay = until.signed_duration_since(ticket.entry_time) - max_stay;
+        (overstay > Duration::zero()).then(|| Overstay {
+            ticket_id: ticket.ticket_id.clone(),
+            license_plate: ticket.vehicle.license_plate.clone(),
+            spot_id: ticket.spot_id.clone(),
+            entry_time: ticket.entry_time,
+            max_stay,
+            overstay,
+            fine: self.overstay_policy.fine_for(overstay),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Parkable, ParkingFloor, ParkingSpot, Vehicle, VehicleType, clock::MockClock,
+        pricing::FlatHourly,
+    };
+
+    #[test]
+    fn test_overstays_are_reported_once_and_fined_at_exit() {
+        let clock = MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_pricing_strategy(Box::new(FlatHourly::new(10.0)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.add_spot(1, ParkingSpot::new(true, SpotType::Electric))
+            .unwrap();
+        lot.set_overstay_policy(
+            OverstayPolicy::default()
+                .with_max_stay(Duration::hours(24))
+                .with_spot_type_max_stay(SpotType::Electric, Duration::hours(4))
+                .with_fine(Duration::zero(), 20.0)
+                .with_fine(Duration::hours(6), 50.0),
+        );
+        let events = lot.events();
+
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "LONG01".into());
+        let ev = Vehicle::new(VehicleType::Electric, "Leaf".into(), "EV0001".into());
+        let car = lot.park_vehicle(car).unwrap();
+        let ev = lot.park_vehicle(ev).unwrap();
+        assert_eq!(ev.spot_type, Some(SpotType::Electric));
+
+        clock.advance(Duration::hours(5));
+        let overstays = lot.find_overstays(lot.now());
+        assert_eq!(overstays.len(), 1);
+        assert_eq!(overstays[0].ticket_id, ev.ticket_id);
+        assert_eq!(overstays[0].overstay, Duration::hours(1));
+        assert_eq!(overstays[0].fine, 20.0);
+        assert_eq!(lot.find_overstays(lot.now()).len(), 1);
+        let reported: Vec<ParkingEvent> = events
+            .try_iter()
+            .filter(|e| matches!(e, ParkingEvent::VehicleOverstayed { .. }))
+            .collect();
+        assert_eq!(reported.len(), 1);
+
+        clock.advance(Duration::hours(5));
+        let charge = lot.unpark_vehicle(ev.ticket_id).unwrap();
+        assert_eq!(charge.fine, 50.0);
+        assert_eq!(charge.total, 150.0);
+        let charge = lot.unpark_vehicle(car.ticket_id).unwrap();
+        assert_eq!((charge.fine, charge.total), (0.0, 100.0));
+    }
+}
@@ -0,0 +1,240 @@
+//! Entrance and exit panels. A lot can have several of each; vehicles are ticketed at a
+//! named entrance and pay and leave at an exit, and each panel keeps its own counters.
+
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Parkable, ParkingCharge, ParkingLot, ParkingTicket, Vehicle,
+    error::ParkingError,
+    payment::{Payment, PaymentMethod},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PanelStats {
+    pub vehicles_processed: u32,
+    pub revenue_collected: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntrancePanel {
+    pub id: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub(crate) stats: PanelStats,
+}
+
+impl EntrancePanel {
+    pub fn new(id: String, name: String) -> Self {
+        Self {
+            id,
+            name,
+            stats: PanelStats::default(),
+        }
+    }
+
+    /// Entrances only count vehicles; they never collect revenue.
+    pub fn stats(&self) -> PanelStats {
+        self.stats
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExitPanel {
+    pub id: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub(crate) stats: PanelStats,
+}
+
+impl ExitPanel {
+    pub fn new(id: String, name: String) -> Self {
+        Self {
+            id,
+            name,
+            stats: PanelStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> PanelStats {
+        self.stats
+    }
+}
+
+impl ParkingLot {
+    pub fn add_entrance_panel(&self, panel: EntrancePanel) -> Result<(), ParkingError> {
+        let mut panels = self.entrance_panels.lock()?;
+        if panels.contains_key(&panel.id) {
+            return Err(ParkingError::DuplicatePanel(panel.id));
+        }
+        panels.insert(panel.id.clone(), panel);
+        Ok(())
+    }
+
+    pub fn add_exit_panel(&self, panel: ExitPanel) -> Result<(), ParkingError> {
+        let mut panels = self.exit_panels.lock()?;
+        if panels.contains_key(&panel.id) {
+            return Err(ParkingError::DuplicatePanel(panel.id));
+        }
+        panels.insert(panel.id.clone(), panel);
+        Ok(())
+    }
+
+    /// Entrance panels, ordered by id.
+    pub fn entrance_panels(&self) -> Vec<EntrancePanel> {
+        let mut panels: Vec<EntrancePanel> = self
+            .entrance_panels
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect();
+        panels.sort_by(|a, b| a.id.cmp(&b.id));
+        panels
+    }
+
+    /// Exit panels, ordered by id.
+    pub fn exit_panels(&self) -> Vec<ExitPanel> {
+        let mut panels: Vec<ExitPanel> =
+            self.exit_panels.lock().unwrap().values().cloned().collect();
+        panels.sort_by(|a, b| a.id.cmp(&b.id));
+        panels
+    }
+
+    /// Parks `vehicle` through the given entrance, which is recorded on the ticket.
+    pub fn park_at_entrance(
+        &self,
+        panel_id: &str,
+        vehicle: Vehicle,
+    ) -> Result<ParkingTicket, ParkingError> {
+        if !self.entrance_panels.lock()?.contains_key(panel_id) {
+            return Err(ParkingError::PanelNotFound(panel_id.to_string()));
+        }
+        let started = Instant::now();
+        let parked = self.park_vehicle(vehicle);
+        self.record_gate(panel_id, started, parked.is_ok());
+        let ticket = parked?;
+
+        let ticket = {
+            let mut tickets = self.active_tickets.lock()?;
+            let stored = tickets.get_mut(&ticket.ticket_id).unwrap();
+            stored.entrance_id = Some(panel_id.to_string());
+            stored.clone()
+        };
+        if let Some(panel) = self.entrance_panels.lock()?.get_mut(panel_id) {
+            panel.stats.vehicles_processed += 1;
+        }
+        Ok(ticket)
+    }
+
+    /// Takes payment for a ticket at an exit panel ahead of the vehicle leaving. Takes the
+    /// printed ticket code, see `ticket_signing`.
+    pub fn pay_at_exit(
+        &self,
+        panel_id: &str,
+        ticket_id: &str,
+        method: PaymentMethod,
+    ) -> Result<Payment, ParkingError> {
+        if !self.exit_panels.lock()?.contains_key(panel_id) {
+            return Err(ParkingError::PanelNotFound(panel_id.to_string()));
+        }
+        let ticket_id = self.verify_ticket_code(ticket_id)?;
+        let payment = self.pay_ticket(&ticket_id, method)?;
+        if let Some(panel) = self.exit_panels.lock()?.get_mut(panel_id) {
+            panel.stats.revenue_collected += payment.amount;
+        }
+        Ok(payment)
+    }
+
+    /// Lets a vehicle out through an exit panel. The stay's charge counts towards the
+    /// panel's revenue unless the ticket was already paid with `pay_ticket`, in which case
+    /// the revenue was booked where it was paid. Takes the printed ticket code, see
+    /// `ticket_signing`.
+    pub fn unpark_at_exit(
+        &self,
+        panel_id: &str,
+        ticket_id: &str,
+    ) -> Result<ParkingCharge, ParkingError> {
+        if !self.exit_panels.lock()?.contains_key(panel_id) {
+            return Err(ParkingError::PanelNotFound(panel_id.to_string()));
+        }
+        let started = Instant::now();
+        let ticket_id = match self.verify_ticket_code(ticket_id) {
+            Ok(ticket_id) => ticket_id,
+            Err(err) => {
+                self.record_gate(panel_id, started, false);
+                return Err(err);
+            }
+        };
+        let ticket_id = ticket_id.as_str();
+        let prepaid = !self.payments_for(ticket_id).is_empty();
+        // Stamp the exit before unparking so the panel is kept in the ticket history.
+        self.set_exit_id(ticket_id, Some(panel_id.to_string()));
+        let charge = match self.unpark_vehicle(ticket_id.to_string()) {
+            Ok(charge) => charge,
+            Err(err) => {
+                self.set_exit_id(ticket_id, None);
+                self.record_gate(panel_id, started, false);
+                return Err(err);
+            }
+        };
+        self.record_gate(panel_id, started, true);
+        if let Some(panel) = self.exit_panels.lock()?.get_mut(panel_id) {
+            panel.stats.vehicles_processed += 1;
+            if !prepaid {
+                panel.stats.revenue_collected += charge.total;
+            }
+        }
+        Ok(charge)
+    }
+
+    fn set_exit_id(&self, ticket_id: &str, exit_id: Option<String>) {
+        if let Some(ticket) = self.active_tickets.lock().unwrap().get_mut(ticket_id) {
+            ticket.exit_id = exit_id;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ParkingFloor, VehicleType,
+        payment::{CashProcessor, PaymentMethodKind},
+    };
+
+    #[test]
+    fn test_panels_record_route_and_stats() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_payment_processor(PaymentMethodKind::Cash, Box::new(CashProcessor));
+        lot.add_entrance_panel(EntrancePanel::new("north".into(), "North gate".into()))
+            .unwrap();
+        lot.add_exit_panel(ExitPanel::new("south".into(), "South gate".into()))
+            .unwrap();
+
+        let vehicle = Vehicle::new(VehicleType::Motor, "Kia".into(), "AAA111".into());
+        let ticket = lot.park_at_entrance("north", vehicle).unwrap();
+        assert_eq!(ticket.entrance_id.as_deref(), Some("north"));
+        assert!(
+            lot.park_at_entrance(
+                "west",
+                Vehicle::new(VehicleType::Motor, "Kia".into(), "BBB222".into())
+            )
+            .is_err()
+        );
+
+        let payment = lot
+            .pay_at_exit("south", &ticket.ticket_id, PaymentMethod::Cash)
+            .unwrap();
+        lot.unpark_at_exit("south", &ticket.ticket_id).unwrap();
+
+        assert_eq!(lot.entrance_panels()[0].stats().vehicles_processed, 1);
+        let exit = lot.exit_panels()[0].stats();
+        assert_eq!(exit.vehicles_processed, 1);
+        assert_eq!(exit.revenue_collected, payment.amount);
+        let closed = &lot.ticket_history().completed_tickets().unwrap()[0].ticket;
+        assert_eq!(closed.exit_id.as_deref(), Some("south"));
+    }
+}
@@ -0,0 +1,424 @@
+//! Parking zones: named groups of spots on a floor run as sub-lots, e.g. a visitor and a
+//! staff zone on floor 2. A zone has its own capacity and, optionally, its own allocation
+//! strategy and pricing. Zoned spots are only handed out by `park_in_zone`; walk-ins
+//! parked with `park_vehicle` get spots outside every zone.
+
+use std::{collections::HashSet, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ParkingLot, ParkingTicket, Vehicle, allocation::AllocationStrategy, batch::Effects,
+    entry_policy::EntryRequest, error::ParkingError, events::ParkingEvent,
+    journal::TransitionCause, pricing::PricingStrategy,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ParkingZone {
+    pub zone_id: String,
+    pub floor_id: u32,
+    pub spot_ids: Vec<String>,
+    /// Most vehicles parked in the zone at once; by default one per spot.
+    pub capacity: Option<u32>,
+    /// Falls back to the lot's strategy when unset. Not saved.
+    #[serde(skip)]
+    pub(crate) allocation: Option<Box<dyn AllocationStrategy>>,
+    /// Falls back to the lot's pricing when unset.
+    #[serde(default, with = "crate::pricing::saved")]
+    pub(crate) pricing: Option<Arc<dyn PricingStrategy>>,
+}
+
+impl ParkingZone {
+    pub fn new(zone_id: String, floor_id: u32, spot_ids: Vec<String>) -> Self {
+        Self {
+            zone_id,
+            floor_id,
+            spot_ids,
+            capacity: None,
+            allocation: None,
+            pricing: None,
+        }
+    }
+
+    pub fn with_capacity(mut self, capacity: u32) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    pub fn with_allocation_strategy(mut self, strategy: Box<dyn AllocationStrategy>) -> Self {
+        self.allocation = Some(strategy);
+        self
+    }
+
+    pub fn with_pricing_strategy(mut self, strategy: Box<dyn PricingStrategy>) -> Self {
+        self.pricing = Some(strategy.into());
+        self
+    }
+
+    fn effective_capacity(&self) -> u32 {
+        self.capacity.unwrap_or(self.spot_ids.len() as u32)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoneOccupancy {
+    pub zone_id: String,
+    pub spots: u32,
+    pub occupied: u32,
+    /// Spots nobody is parked on or holding.
+    pub free: u32,
+    pub capacity: u32,
+}
+
+impl ZoneOccupancy {
+    /// Vehicles the zone can still take, limited by both free spots and capacity.
+    pub fn available(&self) -> u32 {
+        self.free.min(self.capacity.saturating_sub(self.occupied))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloorOccupancy {
+    pub floor_id: u32,
+    pub spots: u32,
+    pub occupied: u32,
+    pub zones: Vec<ZoneOccupancy>,
+}
+
+impl FloorOccupancy {
+    pub fn zone(&self, zone_id: &str) -> Option<&ZoneOccupancy> {
+        self.zones.iter().find(|z| z.zone_id == zone_id)
+    }
+
+    /// Spots on the floor outside every zone.
+    pub fn unzoned_spots(&self) -> u32 {
+        self.spots - self.zones.iter().map(|z| z.spots).sum::<u32>()
+    }
+}
+
+/// Occupancy of the whole lot, broken down by floor and then zone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OccupancyReport {
+    pub spots: u32,
+    pub occupied: u32,
+    pub floors: Vec<FloorOccupancy>,
+}
+
+impl OccupancyReport {
+    pub fn floor(&self, floor_id: u32) -> Option<&FloorOccupancy> {
+        self.floors.iter().find(|f| f.floor_id == floor_id)
+    }
+
+    pub fn zone(&self, zone_id: &str) -> Option<&ZoneOccupancy> {
+        self.floors.iter().find_map(|f| f.zone(zone_id))
+    }
+}
+
+impl ParkingLot {
+    /// Adds a zone over existing spots of its floor. A spot belongs to at most one zone.
+    pub fn add_parking_zone(&self, zone: ParkingZone) -> Result<(), ParkingError> {
+        let mut zones = self.parking_zones.lock()?;
+        if zones.contains_key(&zone.zone_id) {
+            return Err(ParkingError::DuplicateZone(zone.zone_id));
+        }
+        let floors = self.floors.lock()?;
+        let floor = floors
+            .get(&zone.floor_id)
+            .ok_or(ParkingError::FloorNotFound)?;
+        let mut spots = floor.spots.lock()?;
+        let mut listed = HashSet::new();
+        for spot_id in &zone.spot_ids {
+            if !listed.insert(spot_id) {
+                return Err(ParkingError::ZoneSpotRepeated(spot_id.clone()));
+            }
+            match spots.get(spot_id) {
+                None => return Err(ParkingError::SpotNotFound),
+                Some(spot) if spot.zone.is_some() => {
+                    return Err(ParkingError::SpotAlreadyZoned(spot_id.clone()));
+                }
+                Some(_) => {}
+            }
+        }
+        for spot_id in &zone.spot_ids {
+            spots.get_mut(spot_id).unwrap().zone = Some(zone.zone_id.clone());
+        }
+        zones.insert(zone.zone_id.clone(), Arc::new(zone));
+        Ok(())
+    }
+
+    /// Dissolves the zone; its spots return to general allocation.
+    pub fn remove_parking_zone(&self, zone_id: &str) -> Result<(), ParkingError> {
+        let zone = self
+            .parking_zones
+            .lock()?
+            .remove(zone_id)
+            .ok_or(ParkingError::ZoneNotFound)?;
+        if let Some(floor) = self.floors.lock()?.get(&zone.floor_id) {
+            for spot in floor.spots.lock()?.values_mut() {
+                if spot.zone.as_deref() == Some(zone_id) {
+                    spot.zone = None;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn parking_zone(&self, zone_id: &str) -> Option<Arc<ParkingZone>> {
+        self.parking_zones.lock().unwrap().get(zone_id).cloned()
+    }
+
+    /// Parks `vehicle` in the zone, using the zone's allocation strategy. Fails with
+    /// `ZoneFull` once the zone holds as many vehicles as its capacity. Like
+    /// `park_vehicle`, refusals count in analytics and a filled floor is announced.
+    pub fn park_in_zone(
+        &self,
+        zone_id: &str,
+        vehicle: Vehicle,
+    ) -> Result<ParkingTicket, ParkingError> {
+        let result = self.admit_and_park_in_zone(zone_id, vehicle.clone());
+        if let Err(err) = &result {
+            self.record_rejection(&vehicle, err);
+        }
+        result
+    }
+
+    fn admit_and_park_in_zone(
+        &self,
+        zone_id: &str,
+        vehicle: Vehicle,
+    ) -> Result<ParkingTicket, ParkingError> {
+        let now = self.now();
+        // Spots of reservations about to start aren't for walk-ins
+        self.hold_due_reservations(now);
+        let request = EntryRequest {
+            vehicle: &vehicle,
+            zone_id: Some(zone_id),
+            at: now,
+        };
+        if let Err(err) = self.check_entry(&request) {
+            if err == ParkingError::LotFull {
+                self.emit(ParkingEvent::LotFull { at: now });
+            }
+            return Err(err);
+        }
+        let _plate = self.reserve_plate(&vehicle)?;
+        let zone = self
+            .parking_zone(zone_id)
+            .ok_or(ParkingError::ZoneNotFound)?;
+
+        let claimed_until = self.transit_hold.map(|hold| now + hold);
+        let (_, spot_id, _) = {
+            let floors = self.floors.lock()?;
+            let floor = floors
+                .get(&zone.floor_id)
+                .ok_or(ParkingError::ZoneNotFound)?;
+            let occupied = floor
+                .spots
+                .lock()?
+                .values()
+                .filter(|s| s.is_occupied() && s.zone.as_deref() == Some(zone_id))
+                .count() as u32;
+            if occupied >= zone.effective_capacity() {
+                return Err(ParkingError::ZoneFull);
+            }
+            let closed_floors = self.closed_floors.lock()?;
+            let floor_open = |id: u32| {
+                id == zone.floor_id
+                    && !closed_floors.contains(&id)
+                    && self.schedule.is_floor_open(id, now)
+            };
+            self.allocate_spot_in(Some(&zone), &floors, &vehicle, &[], floor_open, |spot| {
+                spot.transition(TransitionCause::Parked, now, |spot| {
+                    spot.assign_vehicle_until(vehicle.clone(), claimed_until)
+                })
+                .ok()
+            })
+        }
+        .ok_or(ParkingError::NoSpotAvailable)?;
+
+        // The zone goes on the ticket before its rates are locked, so it's billed at the
+        // zone's pricing from the moment it's stored
+        let effects = &mut Effects::immediate();
+        let ticket = self.issue_ticket_with(
+            vehicle,
+            spot_id,
+            |ticket| ticket.zone_id = Some(zone_id.to_string()),
+            effects,
+        )?;
+        self.emit_capacity_events(zone.floor_id, effects)?;
+        Ok(ticket)
+    }
+
+    /// Occupancy rolled up from zones to floors to the lot.
+    pub fn occupancy_report(&self) -> OccupancyReport {
+        let zones = self.parking_zones.lock().unwrap();
+        let floors = self.floors.lock().unwrap();
+        let mut floor_ids: Vec<u32> = floors.keys().copied().collect();
+        floor_ids.sort_unstable();
+
+        let floors: Vec<FloorOccupancy> = floor_ids
+            .into_iter()
+            .map(|floor_id| {
+                let spots = floors[&floor_id].spots.lock().unwrap();
+                let mut floor_zones: Vec<ZoneOccupancy> = zones
+                    .values()
+                    .filter(|zone| zone.floor_id == floor_id)
+                    .map(|zone| {
+                        let in_zone = || {
+                            spots
+                                .values()
+                                .filter(|s| s.zone.as_deref() == Some(zone.zone_id.as_str()))
+                        };
+                        ZoneOccupancy {
+                            zone_id: zone.zone_id.clone(),
+                            spots: in_zone().count() as u32,
+                            occupied: in_zone().filter(|s| s.is_occupied()).count() as u32,
+                            free: in_zone().filter(|s| s.is_available()).count() as u32,
+                            capacity: zone.effective_capacity(),
+                        }
+                    })
+                    .collect();
+                floor_zones.sort_by(|a, b| a.zone_id.cmp(&b.zone_id));
+                FloorOccupancy {
+                    floor_id,
+                    spots: spots.len() as u32,
+                    occupied: spots.values().filter(|s| s.is_occupied()).count() as u32,
+                    zones: floor_zones,
+                }
+            })
+            .collect();
+        OccupancyReport {
+            spots: floors.iter().map(|f| f.spots).sum(),
+            occupied: floors.iter().map(|f| f.occupied).sum(),
+            floors,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Parkable, ParkingFloor, VehicleType, allocation::NearestFloor, analytics::RejectionReason,
+        capacity::OccupancyLimits, pricing::FlatHourly,
+    };
+
+    fn car(plate: &str) -> Vehicle {
+        Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into())
+    }
+
+    #[test]
+    fn test_zones_have_own_capacity_pricing_and_roll_up() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(2)).unwrap();
+        let staff = ParkingZone::new(
+            "2B-staff".into(),
+            2,
+            vec!["spot_0".into(), "spot_1".into(), "spot_2".into()],
+        )
+        .with_capacity(2)
+        .with_allocation_strategy(Box::new(NearestFloor))
+        .with_pricing_strategy(Box::new(FlatHourly::new(0.0)));
+        lot.add_parking_zone(staff).unwrap();
+        assert!(
+            lot.add_parking_zone(ParkingZone::new("2A".into(), 2, vec!["spot_2".into()]))
+                .is_err()
+        );
+
+        let walk_in = lot.park_vehicle(car("VISITOR")).unwrap();
+        assert!(!["spot_0", "spot_1", "spot_2"].contains(&walk_in.spot_id.as_str()));
+        let first = lot.park_in_zone("2B-staff", car("STAFF1")).unwrap();
+        assert_eq!(first.zone_id.as_deref(), Some("2B-staff"));
+        lot.park_in_zone("2B-staff", car("STAFF2")).unwrap();
+        assert!(matches!(
+            lot.park_in_zone("2B-staff", car("STAFF3")),
+            Err(ParkingError::ZoneFull)
+        ));
+        assert!(matches!(
+            lot.park_in_zone("nowhere", car("STAFF3")),
+            Err(ParkingError::ZoneNotFound)
+        ));
+
+        let report = lot.occupancy_report();
+        assert_eq!((report.spots, report.occupied), (10, 3));
+        let floor = report.floor(2).unwrap();
+        assert_eq!(floor.unzoned_spots(), 7);
+        let zone = report.zone("2B-staff").unwrap();
+        assert_eq!((zone.occupied, zone.free, zone.available()), (2, 1, 0));
+
+        let charge = lot.estimate_charge(&first.ticket_id).unwrap();
+        assert_eq!(charge.total, 0.0);
+    }
+
+    #[test]
+    fn test_zone_errors_are_typed() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(2)).unwrap();
+        let zone = |id: &str, floor_id, spots: &[&str]| {
+            ParkingZone::new(
+                id.into(),
+                floor_id,
+                spots.iter().map(|s| s.to_string()).collect(),
+            )
+        };
+        lot.add_parking_zone(zone("staff", 2, &["spot_0"])).unwrap();
+
+        assert_eq!(
+            lot.add_parking_zone(zone("staff", 2, &["spot_1"])),
+            Err(ParkingError::DuplicateZone("staff".into()))
+        );
+        assert_eq!(
+            lot.add_parking_zone(zone("visitors", 3, &["spot_1"])),
+            Err(ParkingError::FloorNotFound)
+        );
+        assert_eq!(
+            lot.add_parking_zone(zone("visitors", 2, &["spot_1", "spot_1"])),
+            Err(ParkingError::ZoneSpotRepeated("spot_1".into()))
+        );
+        assert_eq!(
+            lot.add_parking_zone(zone("visitors", 2, &["spot_0"])),
+            Err(ParkingError::SpotAlreadyZoned("spot_0".into()))
+        );
+        assert_eq!(
+            lot.add_parking_zone(zone("visitors", 2, &["nope"])),
+            Err(ParkingError::SpotNotFound)
+        );
+        assert_eq!(
+            lot.remove_parking_zone("visitors"),
+            Err(ParkingError::ZoneNotFound)
+        );
+        lot.remove_parking_zone("staff").unwrap();
+    }
+
+    #[test]
+    fn test_zone_tickets_are_issued_with_their_zone_and_count_like_walk_ins() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_pricing_strategy(Box::new(FlatHourly::new(5.0)));
+        lot.add_floor(ParkingFloor::new(2)).unwrap();
+        lot.set_occupancy_limits(OccupancyLimits::default().with_floor_capacity(2, 1));
+        let staff = ParkingZone::new("staff".into(), 2, vec!["spot_0".into()])
+            .with_pricing_strategy(Box::new(FlatHourly::new(0.0)));
+        lot.add_parking_zone(staff).unwrap();
+        let events = lot.events();
+
+        let ticket = lot.park_in_zone("staff", car("STAFF1")).unwrap();
+        let stored = lot.active_tickets.lock().unwrap()[&ticket.ticket_id].clone();
+        assert_eq!(stored.zone_id.as_deref(), Some("staff"));
+        assert!(stored.rate_plan.is_some());
+        assert_eq!(lot.estimate_charge(&ticket.ticket_id).unwrap().total, 0.0);
+        assert!(
+            events
+                .try_iter()
+                .any(|e| matches!(e, ParkingEvent::FloorFull { floor_id, .. } if floor_id == 2))
+        );
+
+        assert!(lot.park_in_zone("staff", car("STAFF2")).is_err());
+        let report = lot.analytics_report().unwrap();
+        assert_eq!(
+            report
+                .rejections
+                .get(&(VehicleType::Motor, RejectionReason::Full)),
+            Some(&1)
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! Weekly and monthly parking passes. A pass is sold to a user for one of their registered
+//! vehicles and is valid at one lot or, if not tied to a lot, at every lot sharing the
+//! registry. Stays on a valid pass aren't billed by the hour; only time past the end of
+//! the pass is.
+
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Months, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ParkingLot, SpotType, User, Vehicle, VehicleType, calendar::SpotHold, error::ParkingError,
+    plate::normalize_plate,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PassPeriod {
+    Weekly,
+    Monthly,
+}
+
+impl PassPeriod {
+    /// End of one period starting at `from`. Monthly periods end on the same day of the
+    /// next month, or its last day if that month is shorter.
+    pub fn end_of(self, from: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            PassPeriod::Weekly => from + Duration::weeks(1),
+            PassPeriod::Monthly => from.checked_add_months(Months::new(1)).unwrap(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParkingPass {
+    pub pass_id: String,
+    pub holder: String,
+    pub license_plate: String,
+    /// Passes saved before they recorded a vehicle type were all sold for cars.
+    #[serde(default = "car")]
+    pub vehicle_type: VehicleType,
+    /// The only lot the pass is valid at; `None` for every lot.
+    pub lot_uid: Option<String>,
+    pub period: PassPeriod,
+    pub valid_from: DateTime<Utc>,
+    pub valid_until: DateTime<Utc>,
+    /// Price paid per period.
+    pub price: f32,
+    pub renewals: u32,
+}
+
+fn car() -> VehicleType {
+    VehicleType::Motor
+}
+
+impl ParkingPass {
+    pub fn is_valid_at(&self, at: DateTime<Utc>) -> bool {
+        self.valid_from <= at && at < self.valid_until
+    }
+
+    pub fn is_accepted_at(&self, lot_uid: &str) -> bool {
+        self.lot_uid.as_deref().is_none_or(|uid| uid == lot_uid)
+    }
+}
+
+/// Clones share the same passes, so one registry can be attached to several lots.
+#[derive(Debug, Clone, Default)]
+pub struct PassRegistry {
+    passes: Arc<Mutex<Vec<ParkingPass>>>,
+}
+
+impl PassRegistry {
+    /// Sells `user` a pass for their registered vehicle `license_plate`, valid for one
+    /// period from `from`.
+    pub fn purchase(
+        &self,
+        user: &mut User,
+        license_plate: &str,
+        period: PassPeriod,
+        lot_uid: Option<String>,
+        from: DateTime<Utc>,
+        price: f32,
+    ) -> Result<ParkingPass, ParkingError> {
+        let license_plate = normalize_plate(license_plate);
+        let vehicle = user
+            .vehicles
+            .values()
+            .find(|v| v.license_plate == license_plate)
+            .ok_or(ParkingError::VehicleNotRegistered)?;
+        let vehicle_type = vehicle.vehicle_type.clone();
+        let mut passes = self.passes.lock()?;
+        let pass = ParkingPass {
+            pass_id: format!("PASS_{}", passes.len() + 1),
+            holder: user.name.clone(),
+            license_plate,
+            vehicle_type,
+            lot_uid,
+            period,
+            valid_from: from,
+            valid_until: period.end_of(from),
+            price,
+            renewals: 0,
+        };
+        passes.push(pass.clone());
+        user.pass_ids.push(pass.pass_id.clone());
+        Ok(pass)
+    }
+
+    /// Extends the pass by one period. A pass renewed before it ends runs on without a
+    /// gap; a lapsed one starts again at `now`.
+    pub fn renew(&self, pass_id: &str, now: DateTime<Utc>) -> Result<ParkingPass, ParkingError> {
+        let mut passes = self.passes.lock()?;
+        let pass = passes
+            .iter_mut()
+            .find(|p| p.pass_id == pass_id)
+            .ok_or(ParkingError::PassNotFound)?;
+        if pass.valid_until <= now {
+            pass.valid_from = now;
+            pass.valid_until = pass.period.end_of(now);
+        } else {
+            pass.valid_until = pass.period.end_of(pass.valid_until);
+        }
+        pass.renewals += 1;
+        Ok(pass.clone())
+    }
+
+    pub fn pass(&self, pass_id: &str) -> Option<ParkingPass> {
+        self.passes
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|p| p.pass_id == pass_id)
+            .cloned()
+    }
+
+    /// Every pass sold, oldest first.
+    pub(crate) fn all_passes(&self) -> Result<Vec<ParkingPass>, ParkingError> {
+        Ok(self.passes.lock()?.clone())
+    }
+
+    /// Adds a pass restored from saved state.
+    pub(crate) fn restore(&self, pass: ParkingPass) {
+        self.passes.lock().unwrap().push(pass);
+    }
+
+    /// Passes for `license_plate` accepted at lot `lot_uid`, oldest first.
+    pub fn passes_for(
+        &self,
+        license_plate: &str,
+        lot_uid: &str,
+    ) -> Result<Vec<ParkingPass>, ParkingError> {
+        let license_plate = normalize_plate(license_plate);
+        Ok(self
+            .passes
+            .lock()?
+            .iter()
+            .filter(|p| p.license_plate == license_plate && p.is_accepted_at(lot_uid))
+            .cloned()
+            .collect())
+    }
+}
+
+impl User {
+    /// Passes bought by this user, oldest first.
+    pub fn passes(&self, registry: &PassRegistry) -> Vec<ParkingPass> {
+        self.pass_ids
+            .iter()
+            .filter_map(|id| registry.pass(id))
+            .collect()
+    }
+}
+
+impl ParkingLot {
+    /// Honours the passes in `registry` when vehicles park here.
+    pub fn set_pass_registry(&mut self, registry: PassRegistry) {
+        self.passes = registry;
+    }
+
+    /// The registry whose passes are honoured here, to share with other lots.
+    pub fn pass_registry(&self) -> PassRegistry {
+        self.passes.clone()
+    }
+
+    /// The pass `vehicle` enters on at `at`, if it has a valid one here. A vehicle whose
+    /// passes for this lot have all run out is turned away until one is renewed.
+    pub(crate) fn pass_for_entry(
+        &self,
+        vehicle: &Vehicle,
+        at: DateTime<Utc>,
+    ) -> Result<Option<ParkingPass>, ParkingError> {
+        let passes = self.passes.passes_for(&vehicle.license_plate, &self.uid)?;
+        if let Some(pass) = passes.iter().find(|p| p.is_valid_at(at)) {
+            return Ok(Some(pass.clone()));
+        }
+        if !passes.is_empty() && passes.iter().all(|p| p.valid_until <= at) {
+            return Err(ParkingError::PassExpired);
+        }
+        Ok(None)
+    }
+
+    /// A hold on a spot of `spot_type` over each pass valid here whose vehicle may park on
+    /// it, since its holder may turn up at any time.
+    pub(crate) fn pass_holds(&self, spot_type: SpotType) -> Result<Vec<SpotHold>, ParkingError> {
+        Ok(self
+            .passes
+            .all_passes()?
+            .into_iter()
+            .filter(|pass| {
+                pass.is_accepted_at(&self.uid)
+                    && self.compatibility.allows(&pass.vehicle_type, spot_type)
+            })
+            .map(|pass| SpotHold::new(spot_type, pass.valid_from, pass.valid_until))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Account, Parkable, ParkingFloor, VehicleType};
+
+    #[test]
+    fn test_pass_holder_parks_without_hourly_billing() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let registry = PassRegistry::default();
+        lot.set_pass_registry(registry.clone());
+
+        let mut user = User::new("Ada".into(), "123".into());
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "PASS1".into());
+        user.register_vehicle(car.clone());
+        assert!(matches!(
+            registry.purchase(
+                &mut user,
+                "OTHER",
+                PassPeriod::Monthly,
+                None,
+                Utc::now(),
+                90.0
+            ),
+            Err(ParkingError::VehicleNotRegistered)
+        ));
+        let started = Utc::now() - Duration::days(8);
+        let pass = registry
+            .purchase(
+                &mut user,
+                "PASS1",
+                PassPeriod::Monthly,
+                Some("1".into()),
+                started,
+                90.0,
+            )
+            .unwrap();
+        assert_eq!(user.passes(&registry), vec![pass.clone()]);
+
+        let ticket = lot.park_vehicle(car.clone()).unwrap();
+        assert_eq!(ticket.pass_id.as_deref(), Some(pass.pass_id.as_str()));
+        let mut entry_time = Utc::now() - Duration::hours(3);
+        lot.active_tickets
+            .lock()
+            .unwrap()
+            .get_mut(&ticket.ticket_id)
+            .unwrap()
+            .entry_time = entry_time;
+        assert_eq!(lot.unpark_vehicle(ticket.ticket_id).unwrap().total, 0.0);
+
+        // A lapsed weekly pass turns the vehicle away until it's renewed
+        let weekly = Vehicle::new(VehicleType::Motor, "Kia".into(), "WEEK1".into());
+        user.register_vehicle(weekly.clone());
+        let lapsed = registry
+            .purchase(&mut user, "WEEK1", PassPeriod::Weekly, None, started, 25.0)
+            .unwrap();
+        assert!(matches!(
+            lot.park_vehicle(weekly.clone()),
+            Err(ParkingError::PassExpired)
+        ));
+        let renewed = registry.renew(&lapsed.pass_id, Utc::now()).unwrap();
+        assert_eq!(renewed.renewals, 1);
+        assert!(renewed.is_valid_at(Utc::now()));
+        let ticket = lot.park_vehicle(weekly).unwrap();
+
+        // Time past the end of the pass is billed as usual
+        entry_time = renewed.valid_until - Duration::hours(1);
+        {
+            let mut tickets = lot.active_tickets.lock().unwrap();
+            tickets.get_mut(&ticket.ticket_id).unwrap().entry_time = entry_time;
+        }
+        let charge = lot
+            .estimate_charge_at(
+                &ticket.ticket_id,
+                renewed.valid_until + Duration::hours(2),
+                None,
+            )
+            .unwrap();
+        assert_eq!(charge.total, 20.0);
+    }
+}
@@ -0,0 +1,1062 @@
+//! Payment processing. Tickets are paid through a processor registered for the payment
+//! method's kind (cash, card, prepaid account). Card-on-entry (pay-by-plate) lots place a
+//! pre-authorization hold when the vehicle enters and capture the final amount, or void
+//! the hold, at exit. Where small coins are out of circulation, cash payments can be
+//! rounded to a coarser increment; card and account payments stay exact.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    ParkingLot, PaymentStatus, User, eligibility::Eligibility, error::ParkingError,
+    events::ParkingEvent, fraud::FraudOperation,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "method_reference")]
+pub enum PaymentMethod {
+    Cash,
+    /// Tokenised card, as returned by the card terminal.
+    Card(String),
+    /// Prepaid account id.
+    Prepaid(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PaymentMethodKind {
+    Cash,
+    Card,
+    Prepaid,
+}
+
+impl PaymentMethod {
+    pub fn kind(&self) -> PaymentMethodKind {
+        match self {
+            PaymentMethod::Cash => PaymentMethodKind::Cash,
+            PaymentMethod::Card(_) => PaymentMethodKind::Card,
+            PaymentMethod::Prepaid(_) => PaymentMethodKind::Prepaid,
+        }
+    }
+}
+
+/// A settled payment for a ticket. The lot keeps every payment taken for a ticket, oldest
+/// first: a top-up for time billed past the exit grace is a payment of its own.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Payment {
+    pub ticket_id: String,
+    /// Amount taken, including any cash rounding.
+    pub amount: f32,
+    /// Cash rounding adjustment on top of the stay's price; zero for other methods.
+    pub rounding: f32,
+    /// Eligibility discount the stay was paid with; the exit charge keeps it.
+    #[serde(with = "discount")]
+    pub discount: Option<(Eligibility, f32)>,
+    #[serde(flatten)]
+    pub method: PaymentMethod,
+    pub paid_at: DateTime<Utc>,
+    pub transaction_id: String,
+}
+
+/// Discounts are saved as `{"eligibility": "Student", "percent": 20.0}`.
+mod discount {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Discount {
+        eligibility: Eligibility,
+        percent: f32,
+    }
+
+    pub(super) fn serialize<S: Serializer>(
+        discount: &Option<(Eligibility, f32)>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        discount
+            .map(|(eligibility, percent)| Discount {
+                eligibility,
+                percent,
+            })
+            .serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<(Eligibility, f32)>, D::Error> {
+        let discount = Option::<Discount>::deserialize(deserializer)?;
+        Ok(discount.map(|discount| (discount.eligibility, discount.percent)))
+    }
+}
+
+pub trait PaymentProcessor: fmt::Debug + Send + Sync {
+    /// Takes `amount` using `method`; returns the transaction id.
+    fn charge(&self, method: &PaymentMethod, amount: f32) -> Result<String, String>;
+
+    /// Places a hold of `amount` on the card; returns the authorization id.
+    fn authorize(&self, _card_token: &str, _amount: f32) -> Result<String, String> {
+        Err("Processor does not support pre-authorization".to_string())
+    }
+    /// Settles `amount` (at most the held amount) against an authorization; returns the
+    /// transaction id.
+    fn capture(&self, _authorization_id: &str, _amount: f32) -> Result<String, String> {
+        Err("Processor does not support pre-authorization".to_string())
+    }
+    /// Releases a hold without charging anything.
+    fn void(&self, _authorization_id: &str) -> Result<(), String> {
+        Err("Processor does not support pre-authorization".to_string())
+    }
+    /// Gives back `amount` taken by `charge` in transaction `transaction_id`.
+    fn refund(
+        &self,
+        _method: &PaymentMethod,
+        _transaction_id: &str,
+        _amount: f32,
+    ) -> Result<(), String> {
+        Err("Processor does not support refunds".to_string())
+    }
+}
+
+fn next_transaction_id(prefix: &str) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    format!("{}_{}", prefix, COUNTER.fetch_add(1, Ordering::SeqCst))
+}
+
+/// Cash taken by an attendant or pay station. Always succeeds.
+#[derive(Debug, Default)]
+pub struct CashProcessor;
+
+impl PaymentProcessor for CashProcessor {
+    fn charge(&self, method: &PaymentMethod, _amount: f32) -> Result<String, String> {
+        match method {
+            PaymentMethod::Cash => Ok(next_transaction_id("CASH")),
+            _ => Err("Cash processor only accepts cash".to_string()),
+        }
+    }
+
+    fn refund(
+        &self,
+        _method: &PaymentMethod,
+        _transaction_id: &str,
+        _amount: f32,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// In-memory card processor for development and tests. Every token is approved unless
+/// it has been marked as declined.
+#[derive(Debug, Default)]
+pub struct MockCardProcessor {
+    declined: HashSet<String>,
+    holds: Mutex<HashMap<String, f32>>,
+}
+
+impl MockCardProcessor {
+    pub fn decline(mut self, card_token: &str) -> Self {
+        self.declined.insert(card_token.to_string());
+        self
+    }
+
+    fn check_token(&self, card_token: &str) -> Result<(), String> {
+        if self.declined.contains(card_token) {
+            return Err("Card declined".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl PaymentProcessor for MockCardProcessor {
+    fn charge(&self, method: &PaymentMethod, _amount: f32) -> Result<String, String> {
+        match method {
+            PaymentMethod::Card(token) => {
+                self.check_token(token)?;
+                Ok(next_transaction_id("CARD"))
+            }
+            _ => Err("Card processor only accepts cards".to_string()),
+        }
+    }
+
+    fn authorize(&self, card_token: &str, amount: f32) -> Result<String, String> {
+        self.check_token(card_token)?;
+        let authorization_id = next_transaction_id("AUTH");
+        self.holds
+            .lock()
+            .unwrap()
+            .insert(authorization_id.clone(), amount);
+        Ok(authorization_id)
+    }
+
+    fn capture(&self, authorization_id: &str, amount: f32) -> Result<String, String> {
+        let held = self
+            .holds
+            .lock()
+            .unwrap()
+            .remove(authorization_id)
+            .ok_or("Authorization not found")?;
+        if amount > held {
+            return Err(format!("Capture of {amount} exceeds hold of {held}"));
+        }
+        Ok(next_transaction_id("CARD"))
+    }
+
+    fn void(&self, authorization_id: &str) -> Result<(), String> {
+        self.holds
+            .lock()
+            .unwrap()
+            .remove(authorization_id)
+            .map(|_| ())
+            .ok_or_else(|| "Authorization not found".to_string())
+    }
+
+    fn refund(
+        &self,
+        _method: &PaymentMethod,
+        _transaction_id: &str,
+        _amount: f32,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Prepaid account balances. Clones share the same balances, so a handle kept by the
+/// caller sees charges made through the lot.
+#[derive(Debug, Default, Clone)]
+pub struct PrepaidAccounts {
+    balances: Arc<Mutex<HashMap<String, f32>>>,
+}
+
+impl PrepaidAccounts {
+    pub fn top_up(&self, account_id: &str, amount: f32) {
+        *self
+            .balances
+            .lock()
+            .unwrap()
+            .entry(account_id.to_string())
+            .or_insert(0.0) += amount;
+    }
+
+    pub fn balance(&self, account_id: &str) -> Option<f32> {
+        self.balances.lock().unwrap().get(account_id).copied()
+    }
+}
+
+impl PaymentProcessor for PrepaidAccounts {
+    fn charge(&self, method: &PaymentMethod, amount: f32) -> Result<String, String> {
+        let PaymentMethod::Prepaid(account_id) = method else {
+            return Err("Prepaid processor only accepts prepaid accounts".to_string());
+        };
+        let mut balances = self.balances.lock().unwrap();
+        let balance = balances.get_mut(account_id).ok_or("Account not found")?;
+        if *balance < amount {
+            return Err("Insufficient balance".to_string());
+        }
+        *balance -= amount;
+        Ok(next_transaction_id("PREPAID"))
+    }
+
+    fn refund(
+        &self,
+        method: &PaymentMethod,
+        _transaction_id: &str,
+        amount: f32,
+    ) -> Result<(), String> {
+        let PaymentMethod::Prepaid(account_id) = method else {
+            return Err("Prepaid processor only accepts prepaid accounts".to_string());
+        };
+        self.top_up(account_id, amount);
+        Ok(())
+    }
+}
+
+/// Rounds cash amounts to the nearest `increment`, e.g. 0.05. Halves round up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CashRounding {
+    pub increment: f32,
+}
+
+impl CashRounding {
+    pub fn new(increment: f32) -> Self {
+        Self { increment }
+    }
+
+    pub fn round(&self, amount: f32) -> f32 {
+        to_cents((amount / self.increment).round() * self.increment)
+    }
+}
+
+pub(crate) fn to_cents(amount: f32) -> f32 {
+    (amount * 100.0).round() / 100.0
+}
+
+/// Payments taken with one kind of method over a period.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodSettlement {
+    pub method: PaymentMethodKind,
+    pub payments: u32,
+    /// Total taken, rounding included.
+    pub amount: f32,
+    /// Net cash rounding within `amount`.
+    pub rounding: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettlementReport {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// One row per method used, cash first.
+    pub methods: Vec<MethodSettlement>,
+}
+
+impl SettlementReport {
+    pub fn total(&self) -> f32 {
+        self.methods.iter().map(|m| m.amount).sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PreAuthorizationPolicy {
+    /// Amount held on the card at entry.
+    pub hold_amount: f32,
+    /// Stays no longer than this are free and their hold is voided.
+    pub free_window: Duration,
+}
+
+impl PreAuthorizationPolicy {
+    pub fn new(hold_amount: f32, free_window: Duration) -> Self {
+        Self {
+            hold_amount,
+            free_window,
+        }
+    }
+}
+
+impl ParkingLot {
+    /// When set, `unpark_vehicle` refuses tickets that haven't been paid with
+    /// `pay_ticket` (card-on-entry tickets settle their hold at exit instead).
+    pub fn set_payment_required_before_exit(&mut self, required: bool) {
+        self.payment_required_before_exit = required;
+    }
+
+    /// How long a driver has to leave after paying with `pay_ticket`; 15 minutes unless
+    /// set. Leaving later bills the whole stay up to the exit, less what was paid.
+    pub fn set_exit_grace(&mut self, grace: Duration) {
+        self.exit_grace = grace;
+    }
+
+    /// Rounds cash payments taken with `pay_ticket`; `None` takes cash to the cent.
+    pub fn set_cash_rounding(&mut self, rounding: Option<CashRounding>) {
+        self.cash_rounding = rounding;
+    }
+
+    /// Pays for a stay before exit. The amount is the lot's price for the stay so far plus
+    /// any EV charging, which ends here; eligibility discounts are not applied. Cash is
+    /// rounded if the lot has a cash rounding rule. A declined payment marks the ticket
+    /// `Failed` and can be retried. The vehicle then has the exit grace to leave; once it
+    /// runs out, paying again takes the time billed since, and a ticket still within it is
+    /// `AlreadyPaid`.
+    pub fn pay_ticket(
+        &self,
+        ticket_id: &str,
+        method: PaymentMethod,
+    ) -> Result<Payment, ParkingError> {
+        self.pay_ticket_with(ticket_id, method, None)
+    }
+
+    /// Pays for a stay before exit as `pay_ticket` does, with `user`'s best discount.
+    pub fn pay_ticket_for_user(
+        &self,
+        ticket_id: &str,
+        user: &User,
+        method: PaymentMethod,
+    ) -> Result<Payment, ParkingError> {
+        let eligibilities = user.active_eligibilities(self.now());
+        let discount = self.discounts.best_discount(&eligibilities);
+        self.pay_ticket_with(ticket_id, method, discount)
+    }
+
+    /// The fraud detector and the processor are called with no lock held; the ticket is
+    /// marked as being paid meanwhile so a second payment, or the exit, is refused. A
+    /// charge that completes after the ticket was closed some other way is refunded.
+    pub(crate) fn pay_ticket_with(
+        &self,
+        ticket_id: &str,
+        method: PaymentMethod,
+        discount: Option<(Eligibility, f32)>,
+    ) -> Result<Payment, ParkingError> {
+        let ticket_id = &self.canonical_ticket_id(ticket_id);
+        let now = self.now();
+        let (ticket, amount, rounding, discount) = {
+            let tickets = self.active_tickets.lock()?;
+            let ticket = tickets.get(ticket_id).ok_or(ParkingError::InvalidTicket)?;
+            if ticket.exit_time.is_some() {
+                return Err(ParkingError::TicketClosed);
+            }
+            // A paid ticket is topped up with the paid stay's balance, at its own discount
+            let paid = self.payments_for_ticket(ticket_id)?;
+            let (price, discount) = match paid.first() {
+                Some(first) => {
+                    let due = self
+                        .price_paid_stay(ticket, &paid, now, first.discount)
+                        .amount_due();
+                    if due <= 0.0 {
+                        return Err(ParkingError::AlreadyPaid);
+                    }
+                    (due, first.discount)
+                }
+                None => (self.price_stay(ticket, now, discount).total, discount),
+            };
+            let amount = match (&method, self.cash_rounding) {
+                (PaymentMethod::Cash, Some(rounding)) => rounding.round(price),
+                _ => price,
+            };
+            if !self.paying.lock()?.insert(ticket_id.clone()) {
+                return Err(ParkingError::PaymentInProgress);
+            }
+            (ticket.clone(), amount, to_cents(amount - price), discount)
+        };
+
+        let screened = self
+            .screen_for_fraud(FraudOperation::Payment, &ticket, amount)
+            .and_then(|()| {
+                self.payment_processors
+                    .get(&method.kind())
+                    .ok_or(ParkingError::NoPaymentProcessor)
+            });
+        let processor = match screened {
+            Ok(processor) => processor,
+            Err(e) => {
+                self.paying.lock()?.remove(ticket_id);
+                return Err(e);
+            }
+        };
+        let result = processor.charge(&method, amount);
+        // The ticket stays marked until the payment is recorded, under the tickets lock
+        // `checkout` takes, so an exit never sees the ticket paid but not yet recorded
+        let mut tickets = self.active_tickets.lock()?;
+        self.paying.lock()?.remove(ticket_id);
+        let transaction_id = result.map_err(|e| {
+            if let Some(ticket) = tickets.get_mut(ticket_id) {
+                ticket.payment_status = PaymentStatus::Failed;
+            }
+            ParkingError::PaymentFailed(e)
+        })?;
+        let Some(ticket) = tickets.get_mut(ticket_id) else {
+            drop(tickets);
+            processor
+                .refund(&method, &transaction_id, amount)
+                .map_err(ParkingError::PaymentFailed)?;
+            return Err(ParkingError::TicketClosed);
+        };
+        ticket.payment_status = PaymentStatus::Succeeded;
+        self.stop_charging_at(ticket_id, now)?;
+        let payment = Payment {
+            ticket_id: ticket_id.to_string(),
+            amount,
+            rounding,
+            discount,
+            method,
+            paid_at: now,
+            transaction_id,
+        };
+        self.payments
+            .lock()?
+            .entry(ticket_id.to_string())
+            .or_default()
+            .push(payment.clone());
+        drop(tickets);
+        self.emit(ParkingEvent::PaymentReceived {
+            ticket_id: ticket_id.to_string(),
+            amount,
+            method: payment.method.kind(),
+            at: now,
+        });
+        Ok(payment)
+    }
+
+    /// Payments taken for a ticket, oldest first; empty if it hasn't been paid.
+    pub fn payments_for(&self, ticket_id: &str) -> Vec<Payment> {
+        self.payments_for_ticket(ticket_id).unwrap_or_default()
+    }
+
+    /// Total taken for a ticket across its payments, rounding included.
+    pub fn amount_paid(&self, ticket_id: &str) -> f32 {
+        to_cents(self.payments_for(ticket_id).iter().map(|p| p.amount).sum())
+    }
+
+    pub(crate) fn payments_for_ticket(
+        &self,
+        ticket_id: &str,
+    ) -> Result<Vec<Payment>, ParkingError> {
+        Ok(self
+            .payments
+            .lock()?
+            .get(ticket_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    /// Payments taken with `pay_ticket` in `[start, end)`, per method. A top-up is reported
+    /// under its own method and time, apart from the ticket's earlier payments. Card holds
+    /// captured at exit are settled by the card processor and aren't included.
+    pub fn settlement_report(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<SettlementReport, ParkingError> {
+        let payments = self.payments.lock()?;
+        let methods = [
+            PaymentMethodKind::Cash,
+            PaymentMethodKind::Card,
+            PaymentMethodKind::Prepaid,
+        ]
+        .into_iter()
+        .filter_map(|method| {
+            let taken: Vec<&Payment> = payments
+                .values()
+                .flatten()
+                .filter(|p| p.method.kind() == method && p.paid_at >= start && p.paid_at < end)
+                .collect();
+            (!taken.is_empty()).then(|| MethodSettlement {
+                method,
+                payments: taken.len() as u32,
+                amount: taken.iter().map(|p| p.amount).sum(),
+                rounding: to_cents(taken.iter().map(|p| p.rounding).sum()),
+            })
+        })
+        .collect();
+        Ok(SettlementReport {
+            start,
+            end,
+            methods,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, ParkingLot, Vehicle, VehicleType};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Default, Clone)]
+    struct RecordingProcessor {
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl PaymentProcessor for RecordingProcessor {
+        fn charge(&self, _method: &PaymentMethod, amount: f32) -> Result<String, String> {
+            self.calls.lock().unwrap().push(format!("charge {amount}"));
+            Ok("txn_1".to_string())
+        }
+
+        fn authorize(&self, card_token: &str, amount: f32) -> Result<String, String> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("authorize {card_token} {amount}"));
+            Ok("auth_1".to_string())
+        }
+
+        fn capture(&self, authorization_id: &str, amount: f32) -> Result<String, String> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("capture {authorization_id} {amount}"));
+            Ok("txn_1".to_string())
+        }
+
+        fn void(&self, authorization_id: &str) -> Result<(), String> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("void {authorization_id}"));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_hold_is_voided_for_stay_inside_free_window() {
+        let processor = RecordingProcessor::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_payment_processor(PaymentMethodKind::Card, Box::new(processor.clone()));
+        lot.set_pre_authorization_policy(Some(PreAuthorizationPolicy::new(
+            50.0,
+            Duration::minutes(15),
+        )));
+
+        let vehicle = Vehicle::new(VehicleType::Motor, "Kia".into(), "AAA111".into());
+        let ticket = lot.park_with_card(vehicle, "card_tok").unwrap();
+        assert_eq!(ticket.pre_authorization.as_deref(), Some("auth_1"));
+
+        lot.unpark_vehicle(ticket.ticket_id).unwrap();
+        assert_eq!(
+            *processor.calls.lock().unwrap(),
+            vec!["authorize card_tok 50", "void auth_1"]
+        );
+    }
+
+    #[test]
+    fn test_expired_transit_claim_voids_the_card_hold_and_clears_the_ticket() {
+        let processor = RecordingProcessor::default();
+        let clock = crate::clock::MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_transit_hold(Some(Duration::minutes(5)));
+        lot.set_payment_processor(PaymentMethodKind::Card, Box::new(processor.clone()));
+        lot.set_pre_authorization_policy(Some(PreAuthorizationPolicy::new(
+            50.0,
+            Duration::minutes(15),
+        )));
+        let events = lot.events();
+
+        let vehicle = Vehicle::new(VehicleType::Motor, "Kia".into(), "AAA111".into());
+        let ticket = lot.park_with_card(vehicle, "card_tok").unwrap();
+        lot.start_custody(&ticket.ticket_id, "owner".into())
+            .unwrap();
+        clock.advance(Duration::minutes(6));
+        let voided = lot.release_expired_claims(lot.now()).unwrap();
+
+        assert_eq!(voided, vec![ticket.ticket_id.clone()]);
+        assert_eq!(
+            *processor.calls.lock().unwrap(),
+            vec!["authorize card_tok 50", "void auth_1"]
+        );
+        assert!(lot.custody_session(&ticket.ticket_id).is_none());
+        assert!(events.try_iter().any(|event| matches!(
+            event,
+            crate::events::ParkingEvent::TicketVoided { ticket_id, .. }
+                if ticket_id == ticket.ticket_id
+        )));
+    }
+
+    #[test]
+    fn test_gated_lot_refuses_exit_until_ticket_is_paid() {
+        let accounts = PrepaidAccounts::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_payment_required_before_exit(true);
+        lot.set_payment_processor(PaymentMethodKind::Prepaid, Box::new(accounts.clone()));
+
+        let vehicle = Vehicle::new(VehicleType::Motor, "Kia".into(), "AAA111".into());
+        let ticket = lot.park_vehicle(vehicle).unwrap();
+        for ticket in lot.active_tickets.lock().unwrap().values_mut() {
+            ticket.entry_time -= Duration::minutes(61);
+        }
+        assert!(matches!(
+            lot.unpark_vehicle(ticket.ticket_id.clone()),
+            Err(ParkingError::PaymentRequired)
+        ));
+
+        let method = PaymentMethod::Prepaid("acct_1".into());
+        assert_eq!(
+            lot.pay_ticket(&ticket.ticket_id, method.clone()),
+            Err(ParkingError::PaymentFailed("Account not found".into()))
+        );
+        assert!(matches!(
+            lot.active_tickets.lock().unwrap()[&ticket.ticket_id].payment_status,
+            PaymentStatus::Failed
+        ));
+
+        accounts.top_up("acct_1", 20.0);
+        let payment = lot.pay_ticket(&ticket.ticket_id, method).unwrap();
+        assert_eq!(accounts.balance("acct_1"), Some(20.0 - payment.amount));
+        assert!(lot.unpark_vehicle(ticket.ticket_id).is_ok());
+
+        // A stay too short to be billed owes nothing and leaves without paying
+        let vehicle = Vehicle::new(VehicleType::Motor, "Kia".into(), "BBB222".into());
+        let ticket = lot.park_vehicle(vehicle).unwrap();
+        assert_eq!(lot.unpark_vehicle(ticket.ticket_id).unwrap().total, 0.0);
+    }
+
+    #[test]
+    fn test_cash_is_rounded_and_card_is_exact() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_pricing_strategy(Box::new(crate::pricing::FlatHourly::new(2.37)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_payment_processor(PaymentMethodKind::Cash, Box::new(CashProcessor));
+        lot.set_payment_processor(
+            PaymentMethodKind::Card,
+            Box::new(MockCardProcessor::default()),
+        );
+        lot.set_cash_rounding(Some(CashRounding::new(0.05)));
+
+        let cash = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "CASH01".into(),
+            ))
+            .unwrap();
+        let card = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "CARD01".into(),
+            ))
+            .unwrap();
+        for ticket in lot.active_tickets.lock().unwrap().values_mut() {
+            ticket.entry_time -= Duration::minutes(61);
+        }
+
+        let paid_cash = lot
+            .pay_ticket(&cash.ticket_id, PaymentMethod::Cash)
+            .unwrap();
+        assert_eq!((paid_cash.amount, paid_cash.rounding), (2.35, -0.02));
+        let paid_card = lot
+            .pay_ticket(&card.ticket_id, PaymentMethod::Card("tok".into()))
+            .unwrap();
+        assert_eq!((paid_card.amount, paid_card.rounding), (2.37, 0.0));
+
+        let charge = lot.unpark_vehicle(cash.ticket_id).unwrap();
+        let line = charge.breakdown.last().unwrap();
+        assert_eq!(
+            (line.description.as_str(), line.amount),
+            ("Cash rounding", -0.02)
+        );
+        assert!((charge.total - 2.35).abs() < 1e-6);
+
+        let now = Utc::now();
+        let report = lot
+            .settlement_report(now - Duration::hours(1), now + Duration::hours(1))
+            .unwrap();
+        let cash_row = &report.methods[0];
+        assert_eq!(cash_row.method, PaymentMethodKind::Cash);
+        assert_eq!((cash_row.amount, cash_row.rounding), (2.35, -0.02));
+        assert_eq!(report.methods[1].rounding, 0.0);
+        assert!((report.total() - 4.72).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_paid_stay_keeps_its_discount_and_bills_time_past_the_exit_grace() {
+        let clock = crate::clock::MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_pricing_strategy(Box::new(crate::pricing::FlatHourly::new(2.0)));
+        let mut user = crate::User::new("Ada".into(), "0800".into());
+        user.grant_verification(
+            Eligibility::Student,
+            "desk".into(),
+            lot.now(),
+            lot.now() + Duration::days(1),
+        );
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_payment_processor(PaymentMethodKind::Cash, Box::new(CashProcessor));
+        lot.set_discount(Eligibility::Student, 50.0);
+        let park = |plate: &str| {
+            lot.park_vehicle(Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into()))
+                .unwrap()
+        };
+        let discounted = park("PAID01");
+        let lingering = park("PAID02");
+        clock.advance(Duration::hours(2));
+
+        let payment = lot
+            .pay_ticket_for_user(&discounted.ticket_id, &user, PaymentMethod::Cash)
+            .unwrap();
+        assert_eq!(payment.amount, 2.0);
+        lot.pay_ticket(&lingering.ticket_id, PaymentMethod::Cash)
+            .unwrap();
+        clock.advance(Duration::minutes(10));
+        let charge = lot.unpark_vehicle(discounted.ticket_id).unwrap();
+        assert_eq!(
+            (charge.total, charge.discount, charge.amount_due()),
+            (2.0, 2.0, 0.0)
+        );
+        assert_eq!(charge.billed_until, payment.paid_at);
+
+        clock.advance(Duration::hours(2));
+        let estimate = lot.estimate_charge(&lingering.ticket_id).unwrap();
+        let charge = lot.unpark_vehicle(lingering.ticket_id).unwrap();
+        assert_eq!(charge.billed_until, lot.now());
+        assert_eq!(
+            (charge.total, charge.paid, charge.amount_due()),
+            (8.0, 4.0, 4.0)
+        );
+        assert_eq!(estimate.amount_due(), 4.0);
+    }
+
+    #[test]
+    fn test_gated_lot_takes_a_top_up_for_time_past_the_exit_grace() {
+        let clock = crate::clock::MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_pricing_strategy(Box::new(crate::pricing::FlatHourly::new(2.0)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_payment_required_before_exit(true);
+        lot.set_payment_processor(PaymentMethodKind::Cash, Box::new(CashProcessor));
+        let ticket = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "LATE01".into(),
+            ))
+            .unwrap();
+        clock.advance(Duration::hours(1));
+        assert_eq!(
+            lot.pay_ticket(&ticket.ticket_id, PaymentMethod::Cash)
+                .unwrap()
+                .amount,
+            2.0
+        );
+        assert_eq!(
+            lot.pay_ticket(&ticket.ticket_id, PaymentMethod::Cash),
+            Err(ParkingError::AlreadyPaid)
+        );
+
+        clock.advance(Duration::hours(5));
+        assert_eq!(
+            lot.unpark_vehicle(ticket.ticket_id.clone()).unwrap_err(),
+            ParkingError::PaymentRequired
+        );
+        let top_up = lot
+            .pay_ticket(&ticket.ticket_id, PaymentMethod::Cash)
+            .unwrap();
+        assert_eq!(top_up.amount, 10.0);
+        let payments = lot.payments_for(&ticket.ticket_id);
+        assert_eq!(
+            payments.iter().map(|p| p.amount).collect::<Vec<_>>(),
+            vec![2.0, 10.0]
+        );
+        assert_ne!(payments[0].transaction_id, payments[1].transaction_id);
+        assert_eq!(lot.amount_paid(&ticket.ticket_id), 12.0);
+        let charge = lot.unpark_vehicle(ticket.ticket_id).unwrap();
+        assert_eq!(
+            (charge.total, charge.paid, charge.amount_due()),
+            (12.0, 12.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_settlement_reports_a_top_up_under_its_own_method_and_time() {
+        let clock = crate::clock::MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_pricing_strategy(Box::new(crate::pricing::FlatHourly::new(2.37)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_payment_processor(PaymentMethodKind::Cash, Box::new(CashProcessor));
+        lot.set_payment_processor(
+            PaymentMethodKind::Card,
+            Box::new(MockCardProcessor::default()),
+        );
+        lot.set_cash_rounding(Some(CashRounding::new(0.05)));
+        let ticket = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "SPLIT1".into(),
+            ))
+            .unwrap();
+        clock.advance(Duration::hours(1));
+        let cash = lot
+            .pay_ticket(&ticket.ticket_id, PaymentMethod::Cash)
+            .unwrap();
+        assert_eq!((cash.amount, cash.rounding), (2.35, -0.02));
+
+        let boundary = lot.now() + Duration::hours(1);
+        clock.advance(Duration::hours(3));
+        let card = lot
+            .pay_ticket(&ticket.ticket_id, PaymentMethod::Card("tok".into()))
+            .unwrap();
+        // Four hours at 2.37 less the cash rounding already credited and the cash paid
+        assert!((card.amount - 7.11).abs() < 1e-4);
+        assert_eq!(card.rounding, 0.0);
+
+        let before = lot
+            .settlement_report(boundary - Duration::days(1), boundary)
+            .unwrap();
+        assert_eq!(
+            before.methods,
+            vec![MethodSettlement {
+                method: PaymentMethodKind::Cash,
+                payments: 1,
+                amount: 2.35,
+                rounding: -0.02,
+            }]
+        );
+        let after = lot
+            .settlement_report(boundary, boundary + Duration::days(1))
+            .unwrap();
+        assert_eq!(
+            after.methods,
+            vec![MethodSettlement {
+                method: PaymentMethodKind::Card,
+                payments: 1,
+                amount: card.amount,
+                rounding: 0.0,
+            }]
+        );
+    }
+
+    /// Card processor whose charges and captures wait at `gate` twice: once on starting,
+    /// once before they complete.
+    #[derive(Debug, Clone)]
+    struct GatedProcessor {
+        gate: Arc<std::sync::Barrier>,
+        refunds: Arc<Mutex<Vec<f32>>>,
+    }
+
+    impl PaymentProcessor for GatedProcessor {
+        fn charge(&self, _method: &PaymentMethod, _amount: f32) -> Result<String, String> {
+            self.gate.wait();
+            self.gate.wait();
+            Ok("txn_1".to_string())
+        }
+
+        fn authorize(&self, _card_token: &str, _amount: f32) -> Result<String, String> {
+            Ok("auth_1".to_string())
+        }
+
+        fn capture(&self, _authorization_id: &str, _amount: f32) -> Result<String, String> {
+            self.gate.wait();
+            self.gate.wait();
+            Ok("txn_2".to_string())
+        }
+
+        fn refund(
+            &self,
+            _method: &PaymentMethod,
+            _transaction_id: &str,
+            amount: f32,
+        ) -> Result<(), String> {
+            self.refunds.lock().unwrap().push(amount);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_exit_waits_for_a_payment_in_flight_and_late_charges_are_refunded() {
+        let processor = GatedProcessor {
+            gate: Arc::new(std::sync::Barrier::new(2)),
+            refunds: Arc::default(),
+        };
+        let clock = crate::clock::MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_pricing_strategy(Box::new(crate::pricing::FlatHourly::new(10.0)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_payment_processor(PaymentMethodKind::Card, Box::new(processor.clone()));
+        let lot = Arc::new(lot);
+        let park = |plate: &str| {
+            lot.park_vehicle(Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into()))
+                .unwrap()
+                .ticket_id
+        };
+        let (paid, closed) = (park("PAY001"), park("PAY002"));
+        clock.advance(Duration::hours(3));
+        let pay = |ticket_id: &str| {
+            let (lot, ticket_id) = (lot.clone(), ticket_id.to_string());
+            std::thread::spawn(move || {
+                lot.pay_ticket(&ticket_id, PaymentMethod::Card("tok".into()))
+            })
+        };
+
+        let payment = pay(&paid);
+        processor.gate.wait();
+        assert_eq!(
+            lot.unpark_vehicle(paid.clone()).unwrap_err(),
+            ParkingError::PaymentInProgress
+        );
+        assert_eq!(
+            lot.pay_ticket(&paid, PaymentMethod::Card("tok".into()))
+                .unwrap_err(),
+            ParkingError::PaymentInProgress
+        );
+        processor.gate.wait();
+        assert_eq!(payment.join().unwrap().unwrap().amount, 30.0);
+        let charge = lot.unpark_vehicle(paid).unwrap();
+        assert_eq!((charge.total, charge.amount_due()), (30.0, 0.0));
+
+        // The ticket is closed some other way while its charge is in flight
+        let payment = pay(&closed);
+        processor.gate.wait();
+        lot.active_tickets.lock().unwrap().remove(&closed);
+        processor.gate.wait();
+        assert_eq!(
+            payment.join().unwrap().unwrap_err(),
+            ParkingError::TicketClosed
+        );
+        assert_eq!(*processor.refunds.lock().unwrap(), vec![30.0]);
+        assert!(lot.payments_for(&closed).is_empty());
+    }
+
+    #[test]
+    fn test_lot_keeps_working_while_an_exit_capture_is_in_flight() {
+        let processor = GatedProcessor {
+            gate: Arc::new(std::sync::Barrier::new(2)),
+            refunds: Arc::default(),
+        };
+        let clock = crate::clock::MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_pricing_strategy(Box::new(crate::pricing::FlatHourly::new(10.0)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_payment_processor(PaymentMethodKind::Card, Box::new(processor.clone()));
+        lot.set_pre_authorization_policy(Some(PreAuthorizationPolicy::new(
+            50.0,
+            Duration::minutes(15),
+        )));
+        let lot = Arc::new(lot);
+        let car = |plate: &str| Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into());
+        let ticket = lot.park_with_card(car("HOLD01"), "tok").unwrap();
+        clock.advance(Duration::hours(2));
+
+        let exit = {
+            let (lot, ticket_id) = (lot.clone(), ticket.ticket_id.clone());
+            std::thread::spawn(move || lot.unpark_vehicle(ticket_id))
+        };
+        processor.gate.wait();
+        let other = lot.park_vehicle(car("WALK01")).unwrap();
+        assert!(lot.estimate_charge(&other.ticket_id).is_ok());
+        assert_eq!(
+            lot.unpark_vehicle(ticket.ticket_id.clone()).unwrap_err(),
+            ParkingError::PaymentInProgress
+        );
+        processor.gate.wait();
+        assert_eq!(exit.join().unwrap().unwrap().total, 20.0);
+        assert!(lot.active_ticket(&ticket.ticket_id).is_none());
+    }
+
+    #[test]
+    fn test_expired_transit_claim_leaves_a_ticket_settling_its_hold_alone() {
+        let processor = GatedProcessor {
+            gate: Arc::new(std::sync::Barrier::new(2)),
+            refunds: Arc::default(),
+        };
+        let clock = crate::clock::MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_pricing_strategy(Box::new(crate::pricing::FlatHourly::new(10.0)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_transit_hold(Some(Duration::minutes(5)));
+        lot.set_payment_processor(PaymentMethodKind::Card, Box::new(processor.clone()));
+        lot.set_pre_authorization_policy(Some(PreAuthorizationPolicy::new(
+            50.0,
+            Duration::minutes(15),
+        )));
+        let lot = Arc::new(lot);
+        let vehicle = Vehicle::new(VehicleType::Motor, "Kia".into(), "HOLD02".into());
+        let ticket = lot.park_with_card(vehicle, "tok").unwrap();
+        clock.advance(Duration::hours(2));
+
+        let exit = {
+            let (lot, ticket_id) = (lot.clone(), ticket.ticket_id.clone());
+            std::thread::spawn(move || lot.unpark_vehicle(ticket_id))
+        };
+        processor.gate.wait();
+        assert!(lot.release_expired_claims(lot.now()).unwrap().is_empty());
+        processor.gate.wait();
+        assert_eq!(exit.join().unwrap().unwrap().total, 20.0);
+        assert!(processor.refunds.lock().unwrap().is_empty());
+        assert_eq!(lot.display_info().unwrap().num_claimed_spots(), 0);
+    }
+}
@@ -0,0 +1,289 @@
+//! Printable PDF rendering for tickets and receipts (enabled with the `pdf` feature).
+//!
+//! Pages are A6-sized and use the built-in Helvetica font, so documents need no embedded
+//! resources and can go straight to a kiosk printer or an email attachment. A receipt with
+//! more charge lines than fit on a page continues on the next.
+//! Tickets carry their short code and a QR code of the printed ticket code, drawn as vector
+//! squares so kiosks can scan it at any print resolution.
+
+use qrcode::{Color, QrCode};
+
+use crate::{ParkingCharge, ParkingLot, ParkingTicket};
+
+const PAGE_WIDTH: f32 = 298.0;
+const PAGE_HEIGHT: f32 = 420.0;
+const MARGIN: f32 = 24.0;
+/// Side of a printed QR code, quiet zone included.
+const QR_SIZE: f32 = 110.0;
+/// Blank modules around a QR code, as scanners expect.
+const QR_QUIET_ZONE: usize = 4;
+
+enum Block {
+    Text { size: f32, text: String },
+    Qr(QrCode),
+}
+
+#[derive(Default)]
+struct PdfDocument {
+    blocks: Vec<Block>,
+}
+
+impl PdfDocument {
+    fn line(&mut self, size: f32, text: impl Into<String>) -> &mut Self {
+        self.blocks.push(Block::Text {
+            size,
+            text: text.into(),
+        });
+        self
+    }
+
+    fn qr_code(&mut self, code: QrCode) -> &mut Self {
+        self.blocks.push(Block::Qr(code));
+        self
+    }
+
+    /// One content stream per page. Blocks run down the page and on to a new one when
+    /// the next wouldn't fit above the bottom margin.
+    fn content_streams(&self) -> Vec<String> {
+        let mut pages = Vec::new();
+        let mut stream = String::new();
+        let mut y = PAGE_HEIGHT - MARGIN;
+        for block in &self.blocks {
+            let height = match block {
+                Block::Text { size, .. } => size * 1.4,
+                Block::Qr(_) => QR_SIZE,
+            };
+            if y - height < MARGIN && !stream.is_empty() {
+                pages.push(std::mem::take(&mut stream));
+                y = PAGE_HEIGHT - MARGIN;
+            }
+            y -= height;
+            match block {
+                Block::Text { size, text } => {
+                    stream.push_str(&format!(
+                        "BT /F1 {} Tf 1 0 0 1 {} {} Tm ({}) Tj ET\n",
+                        size,
+                        MARGIN,
+                        y,
+                        escape(text)
+                    ));
+                }
+                Block::Qr(code) => {
+                    let modules = code.width() + 2 * QR_QUIET_ZONE;
+                    let module = QR_SIZE / modules as f32;
+                    // Module rows run top to bottom; PDF y runs bottom to top
+                    for row in 0..code.width() {
+                        for col in 0..code.width() {
+                            if code[(col, row)] == Color::Dark {
+                                stream.push_str(&format!(
+                                    "{:.2} {:.2} {:.2} {:.2} re\n",
+                                    MARGIN + (col + QR_QUIET_ZONE) as f32 * module,
+                                    y + (modules - QR_QUIET_ZONE - row - 1) as f32 * module,
+                                    module,
+                                    module
+                                ));
+                            }
+                        }
+                    }
+                    stream.push_str("f\n");
+                }
+            }
+        }
+        pages.push(stream);
+        pages
+    }
+
+    fn render(&self) -> Vec<u8> {
+        let pages = self.content_streams();
+        // Catalog, page tree and font, then each page followed by its content stream
+        let page_ids: Vec<usize> = (0..pages.len()).map(|i| 4 + 2 * i).collect();
+        let kids: Vec<String> = page_ids.iter().map(|id| format!("{id} 0 R")).collect();
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                kids.join(" "),
+                pages.len()
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+        ];
+        for (content, page_id) in pages.iter().zip(&page_ids) {
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH,
+                PAGE_HEIGHT,
+                page_id + 1
+            ));
+            objects.push(format!(
+                "<< /Length {} >>\nstream\n{}endstream",
+                content.len(),
+                content
+            ));
+        }
+
+        let mut out = String::from("%PDF-1.4\n");
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.push_str(&format!("{} 0 obj\n{}\nendobj\n", i + 1, object));
+        }
+
+        let xref_offset = out.len();
+        out.push_str(&format!(
+            "xref\n0 {}\n0000000000 65535 f \n",
+            objects.len() + 1
+        ));
+        for offset in offsets {
+            out.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        out.push_str(&format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref_offset
+        ));
+        out.into_bytes()
+    }
+}
+
+/// Escapes a string for a PDF literal; anything outside printable ASCII becomes `?`
+/// since the standard Helvetica encoding can't represent it.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            ' '..='~' => escaped.push(c),
+            _ => escaped.push('?'),
+        }
+    }
+    escaped
+}
+
+fn header(page: &mut PdfDocument, lot: &ParkingLot, title: &str) {
+    page.line(16.0, lot.name())
+        .line(9.0, lot.address())
+        .line(12.0, "")
+        .line(14.0, title);
+}
+
+pub fn render_ticket(lot: &ParkingLot, ticket: &ParkingTicket) -> Vec<u8> {
+    let mut page = PdfDocument::default();
+    header(&mut page, lot, "PARKING TICKET");
+    page.line(10.0, format!("Ticket: {}", ticket.ticket_id))
+        .line(10.0, format!("Plate: {}", ticket.vehicle.license_plate()))
+        .line(
+            10.0,
+            format!(
+                "Vehicle: {:?} ({})",
+                ticket.vehicle.vehicle_type(),
+                ticket.vehicle.model()
+            ),
+        )
+        .line(10.0, format!("Spot: {}", ticket.spot_id))
+        .line(
+            10.0,
+            format!("Entry: {}", ticket.entry_time.format("%Y-%m-%d %H:%M UTC")),
+        )
+        .line(12.0, "")
+        .line(10.0, "Short code")
+        .line(28.0, ticket.short_code());
+    // Ticket codes are far below a QR code's capacity
+    if let Ok(code) = QrCode::new(lot.ticket_code(ticket)) {
+        page.qr_code(code);
+    }
+    page.line(8.0, "Keep this ticket. Present it or the code at exit.");
+    page.render()
+}
+
+pub fn render_receipt(lot: &ParkingLot, ticket: &ParkingTicket, charge: &ParkingCharge) -> Vec<u8> {
+    let mut page = PdfDocument::default();
+    header(&mut page, lot, "RECEIPT");
+    page.line(
+        10.0,
+        format!("Ticket: {} ({})", ticket.ticket_id, ticket.short_code()),
+    )
+    .line(10.0, format!("Plate: {}", ticket.vehicle.license_plate()))
+    .line(
+        10.0,
+        format!("Entry: {}", ticket.entry_time.format("%Y-%m-%d %H:%M UTC")),
+    );
+    if let Some(exit_time) = ticket.exit_time {
+        page.line(
+            10.0,
+            format!("Exit: {}", exit_time.format("%Y-%m-%d %H:%M UTC")),
+        );
+    }
+    page.line(12.0, "");
+    // Itemised, so adjustments such as cash rounding show on the receipt
+    for line in &charge.breakdown {
+        page.line(10.0, format!("{}: ${:.2}", line.description, line.amount));
+    }
+    page.line(12.0, format!("Total: ${:.2}", charge.total));
+    if charge.chargeback > 0.0 {
+        page.line(10.0, format!("Chargeback: ${:.2}", charge.chargeback));
+    }
+    page.line(10.0, format!("Payment: {:?}", ticket.payment_status));
+    page.render()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Vehicle, VehicleType, pricing::ChargeLine};
+
+    #[test]
+    fn test_rendered_ticket_is_a_well_formed_pdf() {
+        let lot = ParkingLot::new("Hub".into(), "Lagos (Ikeja)".into(), "1".into());
+        let vehicle = Vehicle::new(VehicleType::Motor, "Toyota".into(), "ABC123".into());
+        let ticket = ParkingTicket::new("TKT_1".into(), vehicle, "spot_1".into(), lot.now());
+
+        let bytes = render_ticket(&lot, &ticket);
+        let text = String::from_utf8(bytes).unwrap();
+
+        assert!(text.starts_with("%PDF-1.4"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains(&ticket.short_code()));
+        assert!(text.contains("Lagos \\(Ikeja\\)"));
+        // The QR code is drawn as filled squares after the short code
+        let qr = text.find(" re\n").unwrap();
+        assert!(qr > text.find(&ticket.short_code()).unwrap());
+        assert!(text[qr..].contains("\nf\n"));
+    }
+
+    #[test]
+    fn test_long_receipts_continue_on_new_pages() {
+        let lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        let vehicle = Vehicle::new(VehicleType::Motor, "Toyota".into(), "ABC123".into());
+        let ticket = ParkingTicket::new("TKT_1".into(), vehicle, "spot_1".into(), lot.now());
+        let breakdown: Vec<ChargeLine> = (0..100)
+            .map(|hour| ChargeLine::new(format!("Hour {hour}"), 1.0))
+            .collect();
+        let charge = ParkingCharge {
+            ticket_id: ticket.ticket_id.clone(),
+            entry_time: ticket.entry_time,
+            billed_until: ticket.entry_time,
+            total: 100.0,
+            chargeback: 0.0,
+            discount: 0.0,
+            fine: 0.0,
+            paid: 0.0,
+            breakdown,
+        };
+
+        let text = String::from_utf8(render_receipt(&lot, &ticket, &charge)).unwrap();
+        let pages = text.matches("/Type /Page ").count();
+        assert!(pages > 1);
+        assert!(text.contains(&format!("/Count {pages}")));
+        assert!(text.contains("(Hour 99: $1.00)"));
+        assert!(text.contains("(Total: $100.00)"));
+        // Every line sits between the page margins
+        for line in text.lines().filter(|line| line.ends_with(" Tj ET")) {
+            let y: f32 = line.split(' ').nth(9).unwrap().parse().unwrap();
+            assert!((MARGIN..PAGE_HEIGHT - MARGIN).contains(&y), "{line}");
+        }
+    }
+}
@@ -0,0 +1,1388 @@
+//! Saving and restoring lot state as JSON.
+//!
+//! A snapshot holds everything the lot records while running: floors and spots with each
+//! spot's recent transitions, open and closed tickets with the rates locked into them,
+//! reservations and standing series, leases, payments, passes, owners' contacts, valet
+//! cars, app sessions, zones, maintenance windows, reviews, citations, the audit log and
+//! analytics, down to whether the lot was shutting down. Snapshots from version 1, which
+//! left most of that out, still load.
+//!
+//! What isn't saved is configuration supplied in code, which has to be set up again after
+//! loading: pricing and allocation strategies, payment processors, the notifier, cash
+//! rounding, the exit grace, webhooks, templates, schedules, experiments, reservation
+//! pricing, the compatibility policy, discounts, quotas, vehicle type caps, overstay, entry
+//! and admission policies, the ticket archive, the fraud detector, the ticket signing key,
+//! the length unit and replication fences. Custom pricing strategies and ticket id
+//! generators can't be saved either: tickets whose rates came from one are billed at the
+//! rates set after loading, and ids are UUIDs until a generator is set again. Webhook
+//! deliveries still queued are dropped; `shutdown` delivers them before saving.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    InventoryLimits, LEASE_COUNTER, ParkingFloor, ParkingLot, ParkingSpot, ParkingTicket,
+    RESERVATION_COUNTER, SPOT_COUNTER, SpotStatus, SpotType, TICKET_COUNTER, VALET_COUNTER,
+    Vehicle, VehicleType,
+    analytics::{OccupancySample, RejectionReason},
+    app::AppSession,
+    audit::AuditEntry,
+    calendar::SpotHold,
+    charging::ChargingSession,
+    conversion::SpotConversion,
+    custody::CustodySession,
+    drop_off::{Citation, DropOffZone, Visit},
+    error::ParkingError,
+    evacuation::Evacuation,
+    fraud::FraudReview,
+    gate_metrics::GateMetrics,
+    history::CompletedTicket,
+    journal::{SpotJournal, SpotTransition},
+    lease::SpotLease,
+    maintenance::MaintenanceWindow,
+    notification::Contact,
+    panel::{EntrancePanel, ExitPanel},
+    parking_zone::ParkingZone,
+    pass::ParkingPass,
+    payment::Payment,
+    quota::SpotQuota,
+    reservation::Reservation,
+    standing::StandingReservation,
+    tags::SpotTag,
+    ticket_id::SavedIdGenerator,
+    units::Dimensions,
+    valet::ValetTicket,
+    zones::{NoParkingZone, ZoneIncident},
+};
+
+const FORMAT_VERSION: u32 = 2;
+/// Snapshots from before the lot's running state was saved in full. They load with no
+/// parking zones, app sessions, valet cars, analytics or other state added in version 2.
+const FIRST_VERSION: u32 = 1;
+
+/// Numbers of files being written, so concurrent saves don't share a temporary file.
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+impl ParkingLot {
+    /// Writes the lot's state to `path` as JSON. The file is written next to `path` first
+    /// and renamed over it, so a crash mid-save leaves the previous snapshot intact.
+    pub fn save_to_file(&self, path: impl AsRef<Path>) -> Result<(), ParkingError> {
+        let path = path.as_ref();
+        let text = self.snapshot_text()?;
+        let temp = temp_path(path);
+        let written = fs::File::create(&temp)
+            .and_then(|mut file| {
+                file.write_all(text.as_bytes())?;
+                file.sync_all()
+            })
+            .and_then(|()| fs::rename(&temp, path));
+        written.map_err(|e| {
+            let _ = fs::remove_file(&temp);
+            ParkingError::Storage(e.to_string())
+        })
+    }
+
+    /// Rebuilds a lot from a file written by `save_to_file`.
+    pub fn load_from_file(path: impl AsRef<Path>) -> Result<ParkingLot, ParkingError> {
+        let text = fs::read_to_string(path).map_err(|e| ParkingError::Storage(e.to_string()))?;
+        ParkingLot::from_snapshot_text(&text)
+    }
+
+    /// The lot's state as the JSON `save_to_file` writes, for keeping it somewhere other
+    /// than a file.
+    pub fn snapshot_text(&self) -> Result<String, ParkingError> {
+        serde_json::to_string(&self.to_snapshot()?)
+            .map_err(|e| ParkingError::Storage(e.to_string()))
+    }
+
+    /// Rebuilds a lot from text returned by `snapshot_text`.
+    pub fn from_snapshot_text(text: &str) -> Result<ParkingLot, ParkingError> {
+        serde_json::from_str(text).map_err(|e| ParkingError::Storage(e.to_string()))
+    }
+
+    fn to_snapshot(&self) -> Result<Snapshot, ParkingError> {
+        // Taken in checkout's order and released before anything else is
+        // locked, since exits reach most other maps while holding both
+        let (tickets, floors) = {
+            let tickets = self.active_tickets.lock()?;
+            let floors = self.floors.lock()?;
+            (
+                sorted(tickets.values().cloned(), |ticket| ticket.ticket_id.clone()),
+                sorted(floors.values().cloned().map(SavedFloor::from), |floor| {
+                    floor.id
+                }),
+            )
+        };
+        let parking_zones = sorted(
+            self.parking_zones
+                .lock()?
+                .values()
+                .map(|zone| saved_zone(zone)),
+            |zone| zone.zone_id.clone(),
+        );
+        let drop_off = self.drop_off_zones.lock()?;
+        let drop_off_visits = sorted(
+            drop_off
+                .visits
+                .iter()
+                .map(|((zone_id, plate), visit)| SavedVisit {
+                    zone_id: zone_id.clone(),
+                    license_plate: plate.clone(),
+                    arrived_at: visit.arrived_at,
+                    cited: visit.cited,
+                }),
+            |visit| (visit.zone_id.clone(), visit.license_plate.clone()),
+        );
+        let valet = self.valet.lock()?;
+        let gate_metrics = self.gate_metrics.lock()?;
+        let entry_queue = self.entry_queue.lock()?;
+
+        Ok(Snapshot {
+            version: FORMAT_VERSION,
+            name: self.name.clone(),
+            address: self.address.clone(),
+            uid: self.uid.clone(),
+            ticket_ids: self.ticket_ids.saved(),
+            limits: self.limits,
+            floors,
+            closed_floors: sorted(self.closed_floors.lock()?.iter().copied(), |id| *id),
+            no_parking_zones: self.no_parking_zones.lock()?.clone(),
+            entrance_panels: self.entrance_panels(),
+            exit_panels: self.exit_panels(),
+            tickets,
+            ticket_history: self.ticket_history.completed_tickets()?,
+            reservations: sorted(self.reservations.lock()?.values().cloned(), |r| {
+                r.reservation_id.clone()
+            }),
+            leases: sorted(
+                self.leases.lock()?.values().map(|lease| SavedLease {
+                    lease: lease.clone(),
+                    spot_ids: Vec::new(),
+                }),
+                |saved| saved.lease.lease_id.clone(),
+            ),
+            payments: sorted(self.payments.lock()?.values().flatten().cloned(), |p| {
+                p.ticket_id.clone()
+            }),
+            charging_sessions: sorted(self.charging_sessions.lock()?.values().cloned(), |c| {
+                c.ticket_id.clone()
+            }),
+            custody_sessions: sorted(self.custody_sessions.lock()?.values().cloned(), |c| {
+                c.ticket_id.clone()
+            }),
+            evacuations: self.evacuations.lock()?.clone(),
+            passes: self.passes.all_passes()?,
+            maintenance_windows: self
+                .maintenance
+                .lock()?
+                .saved_windows()
+                .into_iter()
+                .map(|(window, closed_spots)| SavedMaintenanceWindow {
+                    window,
+                    closed_spots,
+                })
+                .collect(),
+            drop_off_zones: drop_off.zones.clone(),
+            drop_off_visits,
+            citations: drop_off.citations.clone(),
+            fraud_reviews: self.fraud_reviews.lock()?.clone(),
+            parking_zones,
+            zone_incidents: self.zone_incidents.lock()?.clone(),
+            spot_holds: self.manual_holds.lock()?.clone(),
+            standing_reservations: sorted(
+                self.standing_reservations.lock()?.values().cloned(),
+                |series| series.series_id.clone(),
+            ),
+            valet: SavedValet {
+                tickets: sorted(valet.tickets.values().cloned(), |ticket| {
+                    ticket.valet_ticket_id.clone()
+                }),
+                queue: valet.queue.iter().cloned().collect(),
+            },
+            app_sessions: sorted(
+                self.app_sessions.lock()?.sessions.values().cloned(),
+                |session| session.token.clone(),
+            ),
+            gate_metrics: SavedGateMetrics {
+                gates: metrics_entries(&gate_metrics.gates),
+                attendants: metrics_entries(&gate_metrics.attendants),
+                on_duty: sorted(
+                    gate_metrics
+                        .on_duty
+                        .iter()
+                        .map(|(panel_id, attendant)| OnDuty {
+                            panel_id: panel_id.clone(),
+                            attendant: attendant.clone(),
+                        }),
+                    |duty| duty.panel_id.clone(),
+                ),
+            },
+            audit_log: self.audit_log.lock()?.clone(),
+            spot_conversions: self.spot_conversions.lock()?.clone(),
+            reported_overstays: sorted(self.reported_overstays.lock()?.iter().cloned(), |id| {
+                id.clone()
+            }),
+            blocked_plates: sorted(
+                self.blocked_plates
+                    .lock()?
+                    .iter()
+                    .map(|(plate, reason)| BlockedPlate {
+                        license_plate: plate.clone(),
+                        reason: reason.clone(),
+                    }),
+                |blocked| blocked.license_plate.clone(),
+            ),
+            contacts: sorted(
+                self.contacts
+                    .lock()?
+                    .iter()
+                    .map(|(plate, contact)| SavedContact {
+                        license_plate: plate.clone(),
+                        contact: contact.clone(),
+                    }),
+                |saved| saved.license_plate.clone(),
+            ),
+            vehicle_type_cap_refusals: sorted(
+                self.vehicle_type_cap_refusals
+                    .lock()?
+                    .iter()
+                    .map(|(vehicle_type, count)| CapRefusals {
+                        vehicle_type: vehicle_type.clone(),
+                        count: *count,
+                    }),
+                |refusals| format!("{:?}", refusals.vehicle_type),
+            ),
+            rejections: sorted(
+                self.rejections
+                    .lock()?
+                    .iter()
+                    .map(|((vehicle_type, reason), count)| Rejections {
+                        vehicle_type: vehicle_type.clone(),
+                        reason: *reason,
+                        count: *count,
+                    }),
+                |rejections| {
+                    (
+                        format!("{:?}", rejections.vehicle_type),
+                        rejections.reason.as_str(),
+                    )
+                },
+            ),
+            occupancy_samples: self.occupancy_log.lock()?.samples.iter().cloned().collect(),
+            entry_queue: SavedEntryQueue {
+                waiting: entry_queue.waiting.iter().cloned().collect(),
+                exit_credits: entry_queue.exit_credits,
+                recent_exits: entry_queue.recent_exits.iter().copied().collect(),
+            },
+        })
+    }
+
+    fn from_snapshot(snapshot: Snapshot) -> Result<ParkingLot, String> {
+        if snapshot.version != FORMAT_VERSION && snapshot.version != FIRST_VERSION {
+            return Err("Unsupported snapshot version".to_string());
+        }
+        let mut lot = ParkingLot::new(snapshot.name, snapshot.address, snapshot.uid);
+        if let Some(ids) = snapshot.ticket_ids {
+            lot.ticket_ids = ids.into_generator();
+        }
+        lot.limits = snapshot.limits;
+        for floor in snapshot.floors.into_iter().map(ParkingFloor::from) {
+            for spot in floor.spots.lock().unwrap().values() {
+                bump_counter(&SPOT_COUNTER, &spot.id)?;
+            }
+            *floor.max_spots.lock().unwrap() = lot.limits.max_spots_per_floor;
+            lot.floors.lock().unwrap().insert(floor.id, floor);
+        }
+        lot.closed_floors
+            .get_mut()
+            .unwrap()
+            .extend(snapshot.closed_floors);
+        lot.no_parking_zones
+            .get_mut()
+            .unwrap()
+            .extend(snapshot.no_parking_zones);
+        for panel in snapshot.entrance_panels {
+            lot.entrance_panels
+                .get_mut()
+                .unwrap()
+                .insert(panel.id.clone(), panel);
+        }
+        for panel in snapshot.exit_panels {
+            lot.exit_panels
+                .get_mut()
+                .unwrap()
+                .insert(panel.id.clone(), panel);
+        }
+        for mut ticket in snapshot.tickets {
+            if ticket.floor_id.is_none() {
+                // Saved before tickets named their floor and lease; the spot they're
+                // parked on does
+                let parked = lot.floors.lock().unwrap().values().find_map(|floor| {
+                    let spots = floor.spots.lock().unwrap();
+                    let spot = spots.get(&ticket.spot_id)?;
+                    spot.vehicle()
+                        .filter(|v| v.license_plate == ticket.vehicle.license_plate)?;
+                    Some((floor.id, spot.leased_by.clone()))
+                });
+                if let Some((floor_id, leased_by)) = parked {
+                    ticket.floor_id = Some(floor_id);
+                    if ticket.lease_id.is_none() {
+                        ticket.lease_id = leased_by;
+                    }
+                }
+            }
+            bump_ticket_counter(&lot, &ticket.ticket_id)?;
+            lot.active_tickets
+                .lock()
+                .unwrap()
+                .insert(ticket.ticket_id.clone(), ticket);
+        }
+        for entry in snapshot.ticket_history {
+            bump_ticket_counter(&lot, &entry.ticket.ticket_id)?;
+            lot.ticket_history.record(entry).unwrap();
+        }
+        for reservation in snapshot.reservations {
+            bump_counter(&RESERVATION_COUNTER, &reservation.reservation_id)?;
+            lot.reservations
+                .get_mut()
+                .unwrap()
+                .insert(reservation.reservation_id.clone(), reservation);
+        }
+        for saved in snapshot.leases {
+            let lease = saved.into_lease(&lot.floors.lock().unwrap())?;
+            bump_counter(&LEASE_COUNTER, &lease.lease_id)?;
+            lot.leases
+                .get_mut()
+                .unwrap()
+                .insert(lease.lease_id.clone(), lease);
+        }
+        for payment in snapshot.payments {
+            lot.payments
+                .get_mut()
+                .unwrap()
+                .entry(payment.ticket_id.clone())
+                .or_default()
+                .push(payment);
+        }
+        for session in snapshot.charging_sessions {
+            lot.charging_sessions
+                .get_mut()
+                .unwrap()
+                .insert(session.ticket_id.clone(), session);
+        }
+        for session in snapshot.custody_sessions {
+            if session.intervals.is_empty() {
+                return Err(format!(
+                    "Custody session for '{}' has no intervals",
+                    session.ticket_id
+                ));
+            }
+            lot.custody_sessions
+                .get_mut()
+                .unwrap()
+                .insert(session.ticket_id.clone(), session);
+        }
+        lot.evacuations
+            .get_mut()
+            .unwrap()
+            .extend(snapshot.evacuations);
+        for pass in snapshot.passes {
+            lot.passes.restore(pass);
+        }
+        for saved in snapshot.maintenance_windows {
+            lot.maintenance
+                .get_mut()
+                .unwrap()
+                .restore(saved.window, saved.closed_spots);
+        }
+        let drop_off = lot.drop_off_zones.get_mut().unwrap();
+        drop_off.zones.extend(snapshot.drop_off_zones);
+        for visit in snapshot.drop_off_visits {
+            drop_off.visits.insert(
+                (visit.zone_id, visit.license_plate),
+                Visit {
+                    arrived_at: visit.arrived_at,
+                    cited: visit.cited,
+                },
+            );
+        }
+        drop_off.citations.extend(snapshot.citations);
+        lot.fraud_reviews
+            .get_mut()
+            .unwrap()
+            .extend(snapshot.fraud_reviews);
+        for zone in snapshot.parking_zones {
+            lot.add_parking_zone(zone).map_err(|e| e.to_string())?;
+        }
+        lot.zone_incidents
+            .get_mut()
+            .unwrap()
+            .extend(snapshot.zone_incidents);
+        lot.manual_holds
+            .get_mut()
+            .unwrap()
+            .extend(snapshot.spot_holds);
+        for series in snapshot.standing_reservations {
+            bump_counter(&RESERVATION_COUNTER, &series.series_id)?;
+            lot.standing_reservations
+                .get_mut()
+                .unwrap()
+                .insert(series.series_id.clone(), series);
+        }
+        let desk = lot.valet.get_mut().unwrap();
+        for ticket in snapshot.valet.tickets {
+            bump_counter(&VALET_COUNTER, &ticket.valet_ticket_id)?;
+            desk.tickets.insert(ticket.valet_ticket_id.clone(), ticket);
+        }
+        desk.queue = snapshot.valet.queue.into();
+        for session in snapshot.app_sessions {
+            lot.app_sessions
+                .get_mut()
+                .unwrap()
+                .sessions
+                .insert(session.token.clone(), session);
+        }
+        let book = lot.gate_metrics.get_mut().unwrap();
+        for entry in snapshot.gate_metrics.gates {
+            book.gates.insert(entry.id, entry.metrics);
+        }
+        for entry in snapshot.gate_metrics.attendants {
+            book.attendants.insert(entry.id, entry.metrics);
+        }
+        for duty in snapshot.gate_metrics.on_duty {
+            book.on_duty.insert(duty.panel_id, duty.attendant);
+        }
+        lot.audit_log.get_mut().unwrap().extend(snapshot.audit_log);
+        lot.spot_conversions
+            .get_mut()
+            .unwrap()
+            .extend(snapshot.spot_conversions);
+        lot.reported_overstays
+            .get_mut()
+            .unwrap()
+            .extend(snapshot.reported_overstays);
+        for blocked in snapshot.blocked_plates {
+            lot.blocked_plates
+                .get_mut()
+                .unwrap()
+                .insert(blocked.license_plate, blocked.reason);
+        }
+        for saved in snapshot.contacts {
+            lot.contacts
+                .get_mut()
+                .unwrap()
+                .insert(saved.license_plate, saved.contact);
+        }
+        for refusals in snapshot.vehicle_type_cap_refusals {
+            lot.vehicle_type_cap_refusals
+                .get_mut()
+                .unwrap()
+                .insert(refusals.vehicle_type, refusals.count);
+        }
+        for rejections in snapshot.rejections {
+            lot.rejections.get_mut().unwrap().insert(
+                (rejections.vehicle_type, rejections.reason),
+                rejections.count,
+            );
+        }
+        lot.occupancy_log
+            .get_mut()
+            .unwrap()
+            .samples
+            .extend(snapshot.occupancy_samples);
+        let entry_queue = lot.entry_queue.get_mut().unwrap();
+        entry_queue.waiting.extend(snapshot.entry_queue.waiting);
+        entry_queue.exit_credits = snapshot.entry_queue.exit_credits;
+        entry_queue
+            .recent_exits
+            .extend(snapshot.entry_queue.recent_exits);
+        lot.publish_view().map_err(|e| e.to_string())?;
+        Ok(lot)
+    }
+}
+
+impl Serialize for ParkingLot {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_snapshot()
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ParkingLot {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        ParkingLot::from_snapshot(Snapshot::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Moves `counter` past the number at the end of a restored id such as `TKT_41` or
+/// `PWH-2024-000041`.
+fn bump_counter(counter: &AtomicU64, id: &str) -> Result<(), String> {
+    let sequence = id.rsplit(['_', '-']).next().unwrap_or(id);
+    match sequence.parse::<u64>() {
+        Ok(n) => move_past(counter, id, n),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Moves the ticket counter past a restored ticket id. Ids are read with the lot's own
+/// generator first, since formats can use any separator.
+fn bump_ticket_counter(lot: &ParkingLot, id: &str) -> Result<(), String> {
+    match lot.ticket_ids.sequence(id) {
+        Some(n) => move_past(&TICKET_COUNTER, id, n),
+        None => bump_counter(&TICKET_COUNTER, id),
+    }
+}
+
+fn move_past(counter: &AtomicU64, id: &str, sequence: u64) -> Result<(), String> {
+    let next = sequence
+        .checked_add(1)
+        .ok_or_else(|| format!("Id '{id}' is out of range"))?;
+    counter.fetch_max(next, Ordering::SeqCst);
+    Ok(())
+}
+
+/// A file beside `path` to write a snapshot to before renaming it over `path`.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    path.with_file_name(name)
+}
+
+fn sorted<T, K: Ord>(items: impl Iterator<Item = T>, key: impl Fn(&T) -> K) -> Vec<T> {
+    let mut items: Vec<T> = items.collect();
+    items.sort_by_key(key);
+    items
+}
+
+/// The saved part of a parking zone; its allocation strategy is configuration.
+fn saved_zone(zone: &ParkingZone) -> ParkingZone {
+    ParkingZone {
+        zone_id: zone.zone_id.clone(),
+        floor_id: zone.floor_id,
+        spot_ids: zone.spot_ids.clone(),
+        capacity: zone.capacity,
+        allocation: None,
+        pricing: zone.pricing.clone(),
+    }
+}
+
+fn metrics_entries(metrics: &HashMap<String, GateMetrics>) -> Vec<GateMetricsEntry> {
+    sorted(
+        metrics.iter().map(|(id, metrics)| GateMetricsEntry {
+            id: id.clone(),
+            metrics: metrics.clone(),
+        }),
+        |entry| entry.id.clone(),
+    )
+}
+
+/// Durations saved as whole seconds.
+pub(crate) mod seconds {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(
+        duration: &chrono::Duration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(duration.num_seconds())
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<chrono::Duration, D::Error> {
+        i64::deserialize(deserializer).map(chrono::Duration::seconds)
+    }
+}
+
+/// Durations saved as whole milliseconds.
+pub(crate) mod millis {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(
+        duration: &Duration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_millis().try_into().unwrap_or(u64::MAX))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+// --- saved forms ---
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    version: u32,
+    name: String,
+    address: String,
+    uid: String,
+    /// Unset when the lot's generator can't be saved.
+    #[serde(default)]
+    ticket_ids: Option<SavedIdGenerator>,
+    limits: InventoryLimits,
+    floors: Vec<SavedFloor>,
+    closed_floors: Vec<u32>,
+    no_parking_zones: Vec<NoParkingZone>,
+    entrance_panels: Vec<EntrancePanel>,
+    exit_panels: Vec<ExitPanel>,
+    tickets: Vec<ParkingTicket>,
+    ticket_history: Vec<CompletedTicket>,
+    reservations: Vec<Reservation>,
+    leases: Vec<SavedLease>,
+    payments: Vec<Payment>,
+    charging_sessions: Vec<ChargingSession>,
+    custody_sessions: Vec<CustodySession>,
+    evacuations: Vec<Evacuation>,
+    // Added in version 2
+    #[serde(default)]
+    passes: Vec<ParkingPass>,
+    #[serde(default)]
+    maintenance_windows: Vec<SavedMaintenanceWindow>,
+    #[serde(default)]
+    drop_off_zones: Vec<DropOffZone>,
+    #[serde(default)]
+    drop_off_visits: Vec<SavedVisit>,
+    #[serde(default)]
+    citations: Vec<Citation>,
+    #[serde(default)]
+    fraud_reviews: Vec<FraudReview>,
+    #[serde(default)]
+    parking_zones: Vec<ParkingZone>,
+    #[serde(default)]
+    zone_incidents: Vec<ZoneIncident>,
+    #[serde(default)]
+    spot_holds: Vec<SpotHold>,
+    #[serde(default)]
+    standing_reservations: Vec<StandingReservation>,
+    #[serde(default)]
+    valet: SavedValet,
+    #[serde(default)]
+    app_sessions: Vec<AppSession>,
+    #[serde(default)]
+    gate_metrics: SavedGateMetrics,
+    #[serde(default)]
+    audit_log: Vec<AuditEntry>,
+    #[serde(default)]
+    spot_conversions: Vec<SpotConversion>,
+    #[serde(default)]
+    reported_overstays: Vec<String>,
+    #[serde(default)]
+    blocked_plates: Vec<BlockedPlate>,
+    #[serde(default)]
+    contacts: Vec<SavedContact>,
+    #[serde(default)]
+    vehicle_type_cap_refusals: Vec<CapRefusals>,
+    #[serde(default)]
+    rejections: Vec<Rejections>,
+    #[serde(default)]
+    occupancy_samples: Vec<OccupancySample>,
+    #[serde(default)]
+    entry_queue: SavedEntryQueue,
+}
+
+/// Spots are saved with the flags they had before `SpotStatus`, so older snapshots load.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SavedSpot {
+    id: String,
+    is_free: bool,
+    spot_type: SpotType,
+    /// Saved in millimetres; snapshots from before sizes were kept have none.
+    #[serde(default)]
+    dimensions: Option<Dimensions>,
+    vehicle: Option<Vehicle>,
+    claimed_until: Option<DateTime<Utc>>,
+    reserved_by: Option<String>,
+    leased_by: Option<String>,
+    out_of_service: Option<String>,
+    tags: Vec<SpotTag>,
+    #[serde(default)]
+    transitions: VecDeque<SpotTransition>,
+}
+
+impl From<ParkingSpot> for SavedSpot {
+    fn from(spot: ParkingSpot) -> Self {
+        let mut tags: Vec<SpotTag> = spot.tags.iter().cloned().collect();
+        tags.sort();
+        SavedSpot {
+            is_free: !spot.is_occupied(),
+            claimed_until: spot.claimed_until(),
+            reserved_by: spot.reserved_by().map(String::from),
+            out_of_service: spot.out_of_service_reason().map(String::from),
+            id: spot.id,
+            spot_type: spot.spot_type,
+            dimensions: spot.dimensions,
+            vehicle: spot.vehicle,
+            leased_by: spot.leased_by,
+            tags,
+            transitions: spot.journal.entries,
+        }
+    }
+}
+
+impl From<SavedSpot> for ParkingSpot {
+    fn from(saved: SavedSpot) -> Self {
+        let status = match (saved.reserved_by, saved.out_of_service) {
+            _ if !saved.is_free => match saved.claimed_until {
+                Some(until) => SpotStatus::Claimed(until),
+                None => SpotStatus::Occupied,
+            },
+            (Some(reservation_id), _) => SpotStatus::Reserved(reservation_id),
+            (None, Some(reason)) => SpotStatus::OutOfService(reason),
+            (None, None) => SpotStatus::Free,
+        };
+        ParkingSpot {
+            id: saved.id,
+            status,
+            spot_type: saved.spot_type,
+            vehicle: saved.vehicle,
+            leased_by: saved.leased_by,
+            zone: None,
+            tags: saved.tags.into_iter().collect(),
+            journal: SpotJournal {
+                entries: saved.transitions,
+            },
+            compatibility: Arc::default(),
+            dimensions: saved.dimensions,
+        }
+    }
+}
+
+/// Spots by the key the floor files them under, which can differ from their id.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SavedFloor {
+    id: u32,
+    spots: Vec<KeyedSpot>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyedSpot {
+    key: String,
+    #[serde(flatten)]
+    spot: ParkingSpot,
+}
+
+impl From<ParkingFloor> for SavedFloor {
+    fn from(floor: ParkingFloor) -> Self {
+        let spots = floor.spots.lock().unwrap().clone();
+        SavedFloor {
+            id: floor.id,
+            spots: sorted(
+                spots.into_iter().map(|(key, spot)| KeyedSpot { key, spot }),
+                |keyed| keyed.key.clone(),
+            ),
+        }
+    }
+}
+
+impl From<SavedFloor> for ParkingFloor {
+    fn from(saved: SavedFloor) -> Self {
+        ParkingFloor {
+            id: saved.id,
+            spots: Arc::new(Mutex::new(
+                saved
+                    .spots
+                    .into_iter()
+                    .map(|keyed| (keyed.key, keyed.spot))
+                    .collect(),
+            )),
+            quota: Arc::new(Mutex::new(SpotQuota::default())),
+            max_spots: Arc::new(Mutex::new(None)),
+            compatibility: Arc::default(),
+        }
+    }
+}
+
+/// Leases saved before they recorded floors list bare spot ids in `spot_ids`.
+#[derive(Serialize, Deserialize)]
+struct SavedLease {
+    #[serde(flatten)]
+    lease: SpotLease,
+    #[serde(default, skip_serializing)]
+    spot_ids: Vec<String>,
+}
+
+impl SavedLease {
+    /// Puts each bare spot id on the floor whose spot still holds the lease, else the
+    /// lowest floor with that spot id.
+    fn into_lease(self, floors: &HashMap<u32, ParkingFloor>) -> Result<SpotLease, String> {
+        let mut lease = self.lease;
+        for spot_id in self.spot_ids {
+            let mut floor_ids: Vec<u32> = floors
+                .values()
+                .filter(|floor| floor.spots.lock().unwrap().contains_key(&spot_id))
+                .map(|floor| floor.id)
+                .collect();
+            floor_ids.sort_unstable();
+            let holder = floor_ids.iter().copied().find(|id| {
+                floors[id].spots.lock().unwrap()[&spot_id]
+                    .leased_by
+                    .as_deref()
+                    == Some(lease.lease_id.as_str())
+            });
+            let floor_id = holder
+                .or(floor_ids.first().copied())
+                .ok_or("Leased spot not found")?;
+            lease.spots.push((floor_id, spot_id));
+        }
+        Ok(lease)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedMaintenanceWindow {
+    #[serde(flatten)]
+    window: MaintenanceWindow,
+    closed_spots: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedVisit {
+    zone_id: String,
+    license_plate: String,
+    arrived_at: DateTime<Utc>,
+    cited: bool,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SavedValet {
+    tickets: Vec<ValetTicket>,
+    queue: Vec<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SavedGateMetrics {
+    gates: Vec<GateMetricsEntry>,
+    attendants: Vec<GateMetricsEntry>,
+    on_duty: Vec<OnDuty>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GateMetricsEntry {
+    id: String,
+    #[serde(flatten)]
+    metrics: GateMetrics,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OnDuty {
+    panel_id: String,
+    attendant: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BlockedPlate {
+    license_plate: String,
+    reason: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedContact {
+    license_plate: String,
+    #[serde(flatten)]
+    contact: Contact,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CapRefusals {
+    vehicle_type: VehicleType,
+    count: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Rejections {
+    vehicle_type: VehicleType,
+    reason: RejectionReason,
+    count: u32,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SavedEntryQueue {
+    waiting: Vec<Vehicle>,
+    exit_credits: u32,
+    recent_exits: Vec<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Parkable,
+        fraud::{FraudDecision, FraudOperation},
+        pass::PassPeriod,
+        standing::RecurrenceRule,
+    };
+    use chrono::{Duration, NaiveTime, Weekday};
+
+    #[test]
+    fn test_saved_lot_restores_tickets_and_spot_assignments() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos \"Main\"".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.add_floor(ParkingFloor::new(2)).unwrap();
+        lot.close_floor(2).unwrap();
+        let ticket = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "AAA111".into(),
+            ))
+            .unwrap();
+
+        let path = std::env::temp_dir().join(format!("lot-{}.json", ticket.ticket_id));
+        lot.save_to_file(&path).unwrap();
+        let restored = ParkingLot::load_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(restored.address(), "Lagos \"Main\"");
+        assert_eq!(restored.display_info().unwrap().num_parked_vehicles(), 1);
+        assert_eq!(restored.display_info().unwrap().num_closed_floors(), 1);
+        let next = restored
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "BBB222".into(),
+            ))
+            .unwrap();
+        assert_ne!(next.ticket_id, ticket.ticket_id);
+        assert!(restored.unpark_vehicle(ticket.ticket_id).is_ok());
+    }
+
+    #[test]
+    fn test_rates_locked_at_entry_survive_a_reload() {
+        use crate::pricing::{DailyCap, FlatHourly, RateTier, Tiered};
+
+        let first_hour_free = Tiered::new(vec![
+            RateTier {
+                hours: Some(1),
+                rate: 0.0,
+            },
+            RateTier {
+                hours: None,
+                rate: 5.0,
+            },
+        ]);
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_pricing_strategy(Box::new(DailyCap::new(Box::new(first_hour_free), 40.0)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "RATE01".into());
+        let ticket = lot.park_vehicle(car).unwrap();
+
+        let mut restored = ParkingLot::from_snapshot_text(&lot.snapshot_text().unwrap()).unwrap();
+        restored.set_pricing_strategy(Box::new(FlatHourly::new(99.0)));
+        let at = |hours| ticket.entry_time + Duration::hours(hours);
+        let charge = |hours| {
+            restored
+                .estimate_charge_at(&ticket.ticket_id, at(hours), None)
+                .unwrap()
+                .total
+        };
+        assert_eq!((charge(3), charge(24)), (10.0, 40.0));
+    }
+
+    #[test]
+    fn test_surge_locked_at_entry_survives_a_reload() {
+        use crate::pricing::{ChargeKind, DynamicPricing, FlatHourly};
+
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_pricing_strategy(Box::new(
+                DynamicPricing::new(Box::new(FlatHourly::new(10.0))).with_surge(0.5, 2.0),
+            ));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let car = |n: usize| Vehicle::new(VehicleType::Motor, "Kia".into(), format!("SRG{n}"));
+        let tickets: Vec<_> = (0..7).map(|n| lot.park_vehicle(car(n)).unwrap()).collect();
+        let surged = tickets.last().unwrap();
+        assert!(surged.surge.is_some());
+
+        let restored = ParkingLot::from_snapshot_text(&lot.snapshot_text().unwrap()).unwrap();
+        let ticket = restored.active_ticket(&surged.ticket_id).unwrap();
+        assert_eq!(ticket.surge, surged.surge);
+        let charge = restored
+            .estimate_charge_at(
+                &ticket.ticket_id,
+                ticket.entry_time + Duration::hours(2),
+                None,
+            )
+            .unwrap();
+        assert_eq!(charge.total, 40.0);
+        assert!(charge.breakdown.iter().any(|l| l.kind == ChargeKind::Surge));
+    }
+
+    #[test]
+    fn test_passes_survive_a_reload() {
+        use crate::{Account, User, pass::PassRegistry};
+
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let registry = PassRegistry::default();
+        lot.set_pass_registry(registry.clone());
+        let mut user = User::new("Ada".into(), "123".into());
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "PASS1".into());
+        user.register_vehicle(car.clone());
+        let pass = registry
+            .purchase(
+                &mut user,
+                "PASS1",
+                PassPeriod::Monthly,
+                None,
+                Utc::now(),
+                90.0,
+            )
+            .unwrap();
+        let ticket = lot.park_vehicle(car).unwrap();
+
+        let restored = ParkingLot::from_snapshot_text(&lot.snapshot_text().unwrap()).unwrap();
+        assert_eq!(restored.pass_registry().pass(&pass.pass_id), Some(pass));
+        let charge = restored
+            .estimate_charge_at(
+                &ticket.ticket_id,
+                ticket.entry_time + Duration::hours(5),
+                None,
+            )
+            .unwrap();
+        assert_eq!(charge.total, 0.0);
+    }
+
+    #[test]
+    fn test_maintenance_windows_survive_a_reload_and_reopen_their_spots() {
+        use crate::clock::MockClock;
+        use chrono::TimeZone;
+
+        // A Tuesday, 01:30
+        let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 1, 2, 1, 30, 0).unwrap());
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let window = MaintenanceWindow::new(
+            "resurface".into(),
+            1,
+            RecurrenceRule::new(vec![Weekday::Tue], at(1, 0), at(4, 0)),
+            "Resurfacing".into(),
+        );
+        lot.add_maintenance_window(window.clone()).unwrap();
+        assert_eq!(lot.run_maintenance_windows().taken_out.len(), 10);
+
+        let restored = ParkingLot::from_snapshot_text(&lot.snapshot_text().unwrap())
+            .unwrap()
+            .with_clock(Box::new(clock.clone()));
+        assert_eq!(restored.maintenance_windows(), vec![window]);
+        clock.advance(Duration::hours(3));
+        assert_eq!(restored.run_maintenance_windows().restored.len(), 10);
+        assert_eq!(
+            restored.display_info().unwrap().num_out_of_service_spots(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_fraud_holds_survive_a_reload() {
+        use crate::fraud::{FraudCheck, FraudDetector};
+
+        #[derive(Debug)]
+        struct HoldExits;
+
+        impl FraudDetector for HoldExits {
+            fn assess(&self, check: &FraudCheck) -> FraudDecision {
+                match check.operation {
+                    FraudOperation::Exit => FraudDecision::Hold("manual check".into()),
+                    FraudOperation::Payment => FraudDecision::Allow,
+                }
+            }
+        }
+
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_fraud_detector(Box::new(HoldExits));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "FRD001".into());
+        let ticket = lot.park_vehicle(car).unwrap();
+        assert_eq!(
+            lot.unpark_vehicle(ticket.ticket_id.clone()).unwrap_err(),
+            ParkingError::HeldForReview("fraud_1".into())
+        );
+
+        let restored = ParkingLot::from_snapshot_text(&lot.snapshot_text().unwrap())
+            .unwrap()
+            .with_fraud_detector(Box::new(HoldExits));
+        assert_eq!(restored.fraud_reviews(), lot.fraud_reviews());
+        restored.clear_fraud_hold("fraud_1", "staff_7").unwrap();
+        restored.unpark_vehicle(ticket.ticket_id).unwrap();
+    }
+
+    #[test]
+    fn test_drop_off_visits_and_citations_survive_a_reload() {
+        use crate::clock::MockClock;
+
+        let clock = MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let zone = DropOffZone::new("kerb_1".into(), 1, Duration::minutes(10)).with_fine(25.0);
+        lot.add_drop_off_zone(zone.clone()).unwrap();
+        lot.record_drop_off_arrival("kerb_1", "SLOW 1").unwrap();
+        clock.advance(Duration::minutes(11));
+        assert_eq!(lot.scan_drop_off_zones().len(), 1);
+        lot.record_drop_off_arrival("kerb_1", "SLOW 2").unwrap();
+
+        let restored = ParkingLot::from_snapshot_text(&lot.snapshot_text().unwrap())
+            .unwrap()
+            .with_clock(Box::new(clock.clone()));
+        assert_eq!(restored.drop_off_zones(), vec![zone]);
+        assert_eq!(restored.citations(), lot.citations());
+        clock.advance(Duration::minutes(11));
+        let cited = restored.scan_drop_off_zones();
+        assert_eq!(cited.len(), 1);
+        assert_eq!(
+            (
+                cited[0].citation_id.as_str(),
+                cited[0].license_plate.as_str()
+            ),
+            ("cit_2", "SLOW 2")
+        );
+    }
+
+    #[test]
+    fn test_zones_id_format_standing_series_and_app_sessions_survive_a_reload() {
+        use crate::{Account, User, ticket_id::TicketIdFormat};
+
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_ticket_id_format(TicketIdFormat::new("PWH").with_sequence_width(6));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let zoned = vec!["spot_0".to_string(), "spot_1".to_string()];
+        lot.add_parking_zone(ParkingZone::new("visitors".into(), 1, zoned).with_capacity(1))
+            .unwrap();
+        let mut ada = User::new("Ada".into(), "0800".into());
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "RUN001".into());
+        ada.register_vehicle(car.clone());
+        let ticket = lot.park_in_zone("visitors", car.clone()).unwrap();
+        let session = lot.open_app_session(&ada);
+        lot.block_plate("RUN003", "Unpaid fines".into());
+        lot.set_contact("RUN001", "ada@example.com", "fr");
+        let at = |h| NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+        let series = lot
+            .create_standing_reservation(
+                car,
+                RecurrenceRule::weekdays(at(8), at(18)),
+                vec![SpotTag::from("near-elevator")],
+                Utc::now() + Duration::days(7),
+                None,
+            )
+            .unwrap();
+
+        let restored = ParkingLot::from_snapshot_text(&lot.snapshot_text().unwrap()).unwrap();
+        let zone = restored
+            .occupancy_report()
+            .zone("visitors")
+            .cloned()
+            .unwrap();
+        assert_eq!((zone.spots, zone.occupied, zone.capacity), (2, 1, 1));
+        let walk_in = restored
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "RUN002".into(),
+            ))
+            .unwrap();
+        assert!(!["spot_0", "spot_1"].contains(&walk_in.spot_id.as_str()));
+        assert!(walk_in.ticket_id.starts_with("PWH_"));
+        assert!(
+            restored
+                .active_ticket(&ticket.ticket_id.to_lowercase())
+                .is_some()
+        );
+        let status = restored.app_parking_status(&session.token).unwrap();
+        assert_eq!(status[0].ticket.ticket_id, ticket.ticket_id);
+        let restored_series = restored.standing_reservation(&series.series_id).unwrap();
+        assert_eq!(restored_series.rule, series.rule);
+        assert_eq!(restored_series.tags, series.tags);
+        assert_eq!(restored_series.from, series.from);
+        assert_eq!(restored.blocked_plates(), lot.blocked_plates());
+        assert_eq!(restored.contact_for("RUN001"), lot.contact_for("RUN001"));
+        let spot = |lot: &ParkingLot| {
+            lot.get_floor_by_id(1)
+                .unwrap()
+                .unwrap()
+                .spots
+                .lock()
+                .unwrap()[&ticket.spot_id]
+                .transitions()
+        };
+        assert_eq!(spot(&restored), spot(&lot));
+    }
+
+    #[test]
+    fn test_version_1_snapshots_still_load() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.block_plate("OLD002", "Unpaid fines".into());
+        let ticket = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "OLD001".into(),
+            ))
+            .unwrap();
+        let serde_json::Value::Object(mut snapshot) = serde_json::to_value(&lot).unwrap() else {
+            unreachable!()
+        };
+        snapshot.retain(|key, _| {
+            [
+                "version",
+                "name",
+                "address",
+                "uid",
+                "limits",
+                "floors",
+                "closed_floors",
+                "no_parking_zones",
+                "entrance_panels",
+                "exit_panels",
+                "tickets",
+                "ticket_history",
+                "reservations",
+                "leases",
+                "payments",
+                "charging_sessions",
+                "custody_sessions",
+                "evacuations",
+            ]
+            .contains(&key.as_str())
+        });
+        snapshot.insert("version".into(), FIRST_VERSION.into());
+
+        let restored: ParkingLot = serde_json::from_value(snapshot.into()).unwrap();
+        assert!(restored.active_ticket(&ticket.ticket_id).is_some());
+        assert!(restored.blocked_plates().is_empty());
+    }
+
+    #[test]
+    fn test_ids_past_the_counter_range_are_rejected() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let ticket = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "MAX001".into(),
+            ))
+            .unwrap();
+        let text = lot
+            .snapshot_text()
+            .unwrap()
+            .replace(&ticket.ticket_id, &format!("TKT_{}", u64::MAX));
+
+        assert!(matches!(
+            ParkingLot::from_snapshot_text(&text),
+            Err(ParkingError::Storage(message)) if message.contains("out of range")
+        ));
+    }
+
+    #[test]
+    fn test_saving_replaces_the_previous_file_without_leaving_a_temporary_one() {
+        let lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        let dir = std::env::temp_dir().join(format!("lot-save-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("lot.json");
+        fs::write(&path, "previous").unwrap();
+
+        lot.save_to_file(&path).unwrap();
+        let entries = fs::read_dir(&dir).unwrap().count();
+        let restored = ParkingLot::load_from_file(&path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(entries, 1);
+        assert_eq!(restored.unwrap().address(), "Lagos");
+    }
+
+    #[test]
+    fn test_snapshots_taken_while_vehicles_leave_do_not_deadlock() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let lot = Arc::new(lot);
+        let (done, finished) = std::sync::mpsc::channel();
+
+        for t in 0..4 {
+            let lot = Arc::clone(&lot);
+            let done = done.clone();
+            std::thread::spawn(move || {
+                for i in 0..100 {
+                    let vehicle =
+                        Vehicle::new(VehicleType::Motor, "Kia".into(), format!("S{t}-{i}"));
+                    if let Ok(ticket) = lot.park_vehicle(vehicle) {
+                        lot.unpark_vehicle(ticket.ticket_id).unwrap();
+                    }
+                }
+                done.send(()).unwrap();
+            });
+        }
+        {
+            let lot = Arc::clone(&lot);
+            std::thread::spawn(move || {
+                for _ in 0..200 {
+                    lot.snapshot_text().unwrap();
+                }
+                done.send(()).unwrap();
+            });
+        }
+
+        for _ in 0..5 {
+            finished
+                .recv_timeout(std::time::Duration::from_secs(30))
+                .expect("saving while unparking deadlocked");
+        }
+    }
+
+    #[test]
+    fn test_restored_ids_in_any_separator_move_the_counter_past_them() {
+        use crate::ticket_id::TicketIdFormat;
+        let format = TicketIdFormat::new("LOT").with_separator('/');
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_ticket_id_format(format.clone());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let ticket = lot
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "SEP001".into(),
+            ))
+            .unwrap();
+        let restored_id = "LOT/900000000";
+        let text = lot
+            .snapshot_text()
+            .unwrap()
+            .replace(&ticket.ticket_id, restored_id);
+
+        let restored = ParkingLot::from_snapshot_text(&text).unwrap();
+        let next = restored
+            .park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "SEP002".into(),
+            ))
+            .unwrap();
+
+        assert!(format.parse(&next.ticket_id).unwrap().sequence > 900_000_000);
+        assert!(restored.active_ticket(restored_id).is_some());
+    }
+}
@@ -0,0 +1,184 @@
+//! License plates. A plate is letters and digits, optionally split into groups by single
+//! spaces or hyphens, e.g. `ABC 123` or `LND-482-KJ`. Plates compare in upper case, so
+//! `abc123` is the same vehicle as `ABC123`.
+//!
+//! Entry refuses vehicles whose plate isn't valid, and vehicles whose plate is already
+//! parked in the lot or being given a spot by another entry.
+
+use std::{collections::HashMap, fmt};
+
+use crate::{ParkingLot, ParkingTicket, Vehicle, VehicleType, error::ParkingError};
+
+/// Letters and digits in a plate, not counting separators.
+pub const MAX_PLATE_CHARS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LicensePlate(String);
+
+impl LicensePlate {
+    /// Validates `plate`, trimming it and converting it to upper case.
+    pub fn parse(plate: &str) -> Result<Self, ParkingError> {
+        let normalized = plate.trim().to_ascii_uppercase();
+        let invalid = || ParkingError::InvalidLicensePlate(plate.to_string());
+        let groups: Vec<&str> = normalized.split([' ', '-']).collect();
+        if groups.iter().any(|group| group.is_empty()) {
+            return Err(invalid());
+        }
+        let chars: usize = groups.iter().map(|group| group.len()).sum();
+        let alphanumeric = groups
+            .iter()
+            .all(|group| group.bytes().all(|b| b.is_ascii_alphanumeric()));
+        if !alphanumeric || chars > MAX_PLATE_CHARS {
+            return Err(invalid());
+        }
+        Ok(Self(normalized))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for LicensePlate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<LicensePlate> for String {
+    fn from(plate: LicensePlate) -> Self {
+        plate.0
+    }
+}
+
+impl Vehicle {
+    /// Like `new`, refusing a plate that isn't valid.
+    pub fn try_new(
+        vehicle_type: VehicleType,
+        model: String,
+        license_plate: &str,
+    ) -> Result<Self, ParkingError> {
+        let plate = LicensePlate::parse(license_plate)?;
+        Ok(Self::new(vehicle_type, model, plate.into()))
+    }
+}
+
+/// `plate` as vehicles store it and plate lookups key by it: trimmed and in upper case if
+/// it's valid, unchanged otherwise, so plates that aren't valid only match exactly.
+pub(crate) fn normalize_plate(plate: &str) -> String {
+    LicensePlate::parse(plate).map_or_else(|_| plate.to_string(), String::from)
+}
+
+/// A plate held for an entry in progress; released on drop.
+pub(crate) struct PlateReservation<'a> {
+    lot: &'a ParkingLot,
+    plate: LicensePlate,
+}
+
+impl Drop for PlateReservation<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut entering) = self.lot.entering_plates.lock() {
+            entering.remove(&self.plate);
+        }
+    }
+}
+
+impl ParkingLot {
+    /// Fails if `vehicle`'s plate isn't valid or a vehicle with the same plate is parked.
+    pub(crate) fn check_plate(&self, vehicle: &Vehicle) -> Result<(), ParkingError> {
+        let plate = LicensePlate::parse(vehicle.license_plate())?;
+        let tickets = self.active_tickets.lock()?;
+        if is_parked(&tickets, &plate) {
+            return Err(ParkingError::PlateAlreadyParked(plate.into()));
+        }
+        Ok(())
+    }
+
+    /// Like `check_plate`, and holds the plate until the returned reservation drops. A
+    /// plate already held by another entry is refused as parked. The entry stores its
+    /// ticket before dropping the reservation, so no second vehicle with the plate gets
+    /// through in between.
+    pub(crate) fn reserve_plate(
+        &self,
+        vehicle: &Vehicle,
+    ) -> Result<PlateReservation<'_>, ParkingError> {
+        let plate = LicensePlate::parse(vehicle.license_plate())?;
+        let tickets = self.active_tickets.lock()?;
+        let mut entering = self.entering_plates.lock()?;
+        if is_parked(&tickets, &plate) || !entering.insert(plate.clone()) {
+            return Err(ParkingError::PlateAlreadyParked(plate.into()));
+        }
+        Ok(PlateReservation { lot: self, plate })
+    }
+}
+
+fn is_parked(tickets: &HashMap<String, ParkingTicket>, plate: &LicensePlate) -> bool {
+    tickets.values().any(|ticket| {
+        LicensePlate::parse(ticket.vehicle.license_plate()).is_ok_and(|p| p == *plate)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor};
+
+    #[test]
+    fn test_invalid_and_already_parked_plates_are_refused() {
+        assert_eq!(
+            LicensePlate::parse(" lnd-482-kj ").unwrap().as_str(),
+            "LND-482-KJ"
+        );
+        for bad in ["", "AB--12", "ABC_123", "-AB12", "ABCDEFGHIJK", "ÄB12"] {
+            assert_eq!(
+                LicensePlate::parse(bad),
+                Err(ParkingError::InvalidLicensePlate(bad.into()))
+            );
+        }
+
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let car = |plate: &str| Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into());
+        assert_eq!(
+            lot.park_vehicle(car("AB#12")).unwrap_err(),
+            ParkingError::InvalidLicensePlate("AB#12".into())
+        );
+        let ticket = lot.park_vehicle(car("abc 123")).unwrap();
+        assert_eq!(
+            lot.park_vehicle(car("ABC 123")).unwrap_err(),
+            ParkingError::PlateAlreadyParked("ABC 123".into())
+        );
+        let location = lot.locate_vehicle("ABC 123").unwrap();
+        assert_eq!(location.ticket_id, ticket.ticket_id);
+        assert!(lot.active_ticket_for_plate(" abc 123").is_some());
+        lot.unpark_vehicle(ticket.ticket_id).unwrap();
+        lot.park_vehicle(Vehicle::try_new(VehicleType::Motor, "Kia".into(), "abc 123").unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_the_same_plate_entering_at_once_gets_one_spot() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        for _ in 0..200 {
+            let tickets: Vec<ParkingTicket> = std::thread::scope(|scope| {
+                let entries: Vec<_> = ["TWN 001", "twn 001", " TWN 001", "Twn 001"]
+                    .into_iter()
+                    .map(|plate| {
+                        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into());
+                        let lot = &lot;
+                        scope.spawn(move || lot.park_vehicle(car))
+                    })
+                    .collect();
+                entries
+                    .into_iter()
+                    .filter_map(|entry| entry.join().unwrap().ok())
+                    .collect()
+            });
+            assert_eq!(tickets.len(), 1);
+            for ticket in tickets {
+                lot.unpark_vehicle(ticket.ticket_id).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,518 @@
+//! Pricing strategies. A strategy turns a stay's duration and vehicle type into a total
+//! plus the itemised lines that produced it, which end up on the `ParkingCharge`.
+
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use chrono::Duration;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::VehicleType;
+
+/// What a charge line accounts for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChargeKind {
+    /// Time billed at the stay's rates.
+    Rate,
+    /// A cap reducing what the rates came to.
+    Cap,
+    Discount,
+    Fine,
+    Energy,
+    /// A lease or pass covering the stay.
+    Coverage,
+    Rounding,
+    /// Staff or emergency overrides.
+    Adjustment,
+    /// Demand surcharge locked in at entry.
+    Surge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChargeLine {
+    pub description: String,
+    pub amount: f32,
+    pub kind: ChargeKind,
+}
+
+impl ChargeLine {
+    /// A `Rate` line.
+    pub fn new(description: impl Into<String>, amount: f32) -> Self {
+        Self {
+            description: description.into(),
+            amount,
+            kind: ChargeKind::Rate,
+        }
+    }
+
+    pub fn with_kind(mut self, kind: ChargeKind) -> Self {
+        self.kind = kind;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PriceQuote {
+    pub lines: Vec<ChargeLine>,
+}
+
+impl PriceQuote {
+    pub fn total(&self) -> f32 {
+        self.lines.iter().map(|l| l.amount).sum()
+    }
+}
+
+pub trait PricingStrategy: fmt::Debug + Send + Sync {
+    fn quote(&self, duration: Duration, vehicle_type: &VehicleType) -> PriceQuote;
+
+    /// Multiplier for vehicles entering while the lot is `occupancy` full (0 to 1), for
+    /// demand-based strategies. `None` leaves the rates as they are.
+    fn surge_at(&self, _occupancy: f32) -> Option<f32> {
+        None
+    }
+
+    /// The strategy as saved with the lot, so rates locked into tickets survive a reload.
+    /// Strategies a `SavedPricing` can't describe return `None`; their tickets are billed
+    /// at the rates configured after loading.
+    fn saved(&self) -> Option<SavedPricing> {
+        None
+    }
+}
+
+/// A built-in strategy as saved with the lot, see `PricingStrategy::saved`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum SavedPricing {
+    FlatHourly {
+        rate: f32,
+    },
+    Tiered {
+        tiers: Vec<RateTier>,
+    },
+    PerVehicleType {
+        default: Box<SavedPricing>,
+        strategies: Vec<VehicleTypePricing>,
+    },
+    DailyCap {
+        inner: Box<SavedPricing>,
+        cap: f32,
+    },
+    DynamicPricing {
+        base: Box<SavedPricing>,
+        surges: Vec<SurgeStep>,
+    },
+    Surged {
+        inner: Box<SavedPricing>,
+        #[serde(flatten)]
+        surge: Surge,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VehicleTypePricing {
+    pub vehicle_type: VehicleType,
+    pub strategy: SavedPricing,
+}
+
+/// A `DynamicPricing` step.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SurgeStep {
+    pub occupancy: f32,
+    pub multiplier: f32,
+}
+
+impl SavedPricing {
+    /// Rebuilds the strategy that was saved.
+    pub fn into_strategy(self) -> Box<dyn PricingStrategy> {
+        match self {
+            SavedPricing::FlatHourly { rate } => Box::new(FlatHourly::new(rate)),
+            SavedPricing::Tiered { tiers } => Box::new(Tiered::new(tiers)),
+            SavedPricing::PerVehicleType {
+                default,
+                strategies,
+            } => Box::new(strategies.into_iter().fold(
+                PerVehicleType::new(default.into_strategy()),
+                |strategy, entry| strategy.with(entry.vehicle_type, entry.strategy.into_strategy()),
+            )),
+            SavedPricing::DailyCap { inner, cap } => {
+                Box::new(DailyCap::new(inner.into_strategy(), cap))
+            }
+            SavedPricing::DynamicPricing { base, surges } => Box::new(surges.into_iter().fold(
+                DynamicPricing::new(base.into_strategy()),
+                |strategy, step| strategy.with_surge(step.occupancy, step.multiplier),
+            )),
+            SavedPricing::Surged { inner, surge } => {
+                Box::new(Surged::new(Arc::from(inner.into_strategy()), surge))
+            }
+        }
+    }
+}
+
+/// Serde helpers saving an optional strategy as its `SavedPricing`, or `null` when it
+/// can't be saved.
+pub(crate) mod saved {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        strategy: &Option<Arc<dyn PricingStrategy>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        strategy
+            .as_ref()
+            .and_then(|strategy| strategy.saved())
+            .serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Arc<dyn PricingStrategy>>, D::Error> {
+        let saved = Option::<SavedPricing>::deserialize(deserializer)?;
+        Ok(saved.map(|saved| Arc::from(saved.into_strategy())))
+    }
+}
+
+/// Stays are billed per completed hour.
+fn billable_hours(duration: Duration) -> u32 {
+    duration.num_hours().max(0) as u32
+}
+
+#[derive(Debug, Clone)]
+pub struct FlatHourly {
+    pub rate: f32,
+}
+
+impl FlatHourly {
+    pub fn new(rate: f32) -> Self {
+        Self { rate }
+    }
+}
+
+impl Default for FlatHourly {
+    /// $10 per hour.
+    fn default() -> Self {
+        Self::new(10.0)
+    }
+}
+
+impl PricingStrategy for FlatHourly {
+    fn quote(&self, duration: Duration, _vehicle_type: &VehicleType) -> PriceQuote {
+        let hours = billable_hours(duration);
+        PriceQuote {
+            lines: vec![ChargeLine::new(
+                format!("{} h x ${:.2}", hours, self.rate),
+                hours as f32 * self.rate,
+            )],
+        }
+    }
+
+    fn saved(&self) -> Option<SavedPricing> {
+        Some(SavedPricing::FlatHourly { rate: self.rate })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateTier {
+    /// Hours billed at this rate; `None` covers the rest of the stay.
+    pub hours: Option<u32>,
+    pub rate: f32,
+}
+
+/// Consecutive hourly tiers, e.g. first hour free then $5/h:
+/// `Tiered::new(vec![RateTier { hours: Some(1), rate: 0.0 }, RateTier { hours: None, rate: 5.0 }])`.
+#[derive(Debug, Clone)]
+pub struct Tiered {
+    tiers: Vec<RateTier>,
+}
+
+impl Tiered {
+    pub fn new(tiers: Vec<RateTier>) -> Self {
+        Self { tiers }
+    }
+}
+
+impl PricingStrategy for Tiered {
+    fn quote(&self, duration: Duration, _vehicle_type: &VehicleType) -> PriceQuote {
+        let mut remaining = billable_hours(duration);
+        let mut lines = Vec::new();
+        for tier in &self.tiers {
+            if remaining == 0 {
+                break;
+            }
+            let hours = tier.hours.map_or(remaining, |h| h.min(remaining));
+            lines.push(ChargeLine::new(
+                format!("{} h x ${:.2}", hours, tier.rate),
+                hours as f32 * tier.rate,
+            ));
+            remaining -= hours;
+        }
+        PriceQuote { lines }
+    }
+
+    fn saved(&self) -> Option<SavedPricing> {
+        Some(SavedPricing::Tiered {
+            tiers: self.tiers.clone(),
+        })
+    }
+}
+
+/// Delegates to a different strategy per vehicle type, falling back to `default`.
+#[derive(Debug)]
+pub struct PerVehicleType {
+    default: Box<dyn PricingStrategy>,
+    strategies: HashMap<VehicleType, Box<dyn PricingStrategy>>,
+}
+
+impl PerVehicleType {
+    pub fn new(default: Box<dyn PricingStrategy>) -> Self {
+        Self {
+            default,
+            strategies: HashMap::new(),
+        }
+    }
+
+    pub fn with(mut self, vehicle_type: VehicleType, strategy: Box<dyn PricingStrategy>) -> Self {
+        self.strategies.insert(vehicle_type, strategy);
+        self
+    }
+}
+
+impl PricingStrategy for PerVehicleType {
+    fn quote(&self, duration: Duration, vehicle_type: &VehicleType) -> PriceQuote {
+        self.strategies
+            .get(vehicle_type)
+            .unwrap_or(&self.default)
+            .quote(duration, vehicle_type)
+    }
+
+    fn saved(&self) -> Option<SavedPricing> {
+        let mut vehicle_types: Vec<&VehicleType> = self.strategies.keys().collect();
+        vehicle_types.sort_by_key(|vehicle_type| format!("{vehicle_type:?}"));
+        let strategies = vehicle_types
+            .into_iter()
+            .map(|vehicle_type| {
+                Some(VehicleTypePricing {
+                    vehicle_type: vehicle_type.clone(),
+                    strategy: self.strategies[vehicle_type].saved()?,
+                })
+            })
+            .collect::<Option<_>>()?;
+        Some(SavedPricing::PerVehicleType {
+            default: Box::new(self.default.saved()?),
+            strategies,
+        })
+    }
+}
+
+/// Caps what `inner` charges for each 24-hour period of a stay.
+#[derive(Debug)]
+pub struct DailyCap {
+    inner: Box<dyn PricingStrategy>,
+    cap: f32,
+}
+
+impl DailyCap {
+    pub fn new(inner: Box<dyn PricingStrategy>, cap: f32) -> Self {
+        Self { inner, cap }
+    }
+}
+
+impl PricingStrategy for DailyCap {
+    fn quote(&self, duration: Duration, vehicle_type: &VehicleType) -> PriceQuote {
+        let mut quote = self.inner.quote(duration, vehicle_type);
+
+        // Price each 24-hour period as the difference of the cumulative charge, so tiers
+        // carry on from one period into the next instead of restarting
+        let cumulative = |d: Duration| self.inner.quote(d, vehicle_type).total();
+        let mut capped = 0.0;
+        let mut day = 0;
+        while Duration::days(day) < duration {
+            let start = Duration::days(day);
+            let end = Duration::days(day + 1).min(duration);
+            capped += (cumulative(end) - cumulative(start)).min(self.cap);
+            day += 1;
+        }
+
+        let adjustment = capped - quote.total();
+        if adjustment < 0.0 {
+            quote.lines.push(
+                ChargeLine::new(format!("Daily cap ${:.2}", self.cap), adjustment)
+                    .with_kind(ChargeKind::Cap),
+            );
+        }
+        quote
+    }
+
+    fn saved(&self) -> Option<SavedPricing> {
+        Some(SavedPricing::DailyCap {
+            inner: Box::new(self.inner.saved()?),
+            cap: self.cap,
+        })
+    }
+}
+
+/// Scales `base` with occupancy at entry, e.g. +50% once the lot is over 90% full. The
+/// surge is locked into the ticket as the vehicle enters, so a busy lot emptying later
+/// doesn't change what an early arrival pays. Quoted on its own it charges the base rates.
+#[derive(Debug)]
+pub struct DynamicPricing {
+    base: Box<dyn PricingStrategy>,
+    /// `(occupancy, multiplier)` steps, ordered by occupancy.
+    surges: Vec<(f32, f32)>,
+}
+
+impl DynamicPricing {
+    pub fn new(base: Box<dyn PricingStrategy>) -> Self {
+        Self {
+            base,
+            surges: Vec::new(),
+        }
+    }
+
+    /// Multiplies the base rates by `multiplier` for entries above `occupancy` (0 to 1).
+    /// The highest step passed applies.
+    pub fn with_surge(mut self, occupancy: f32, multiplier: f32) -> Self {
+        self.surges.push((occupancy, multiplier));
+        self.surges.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self
+    }
+}
+
+impl PricingStrategy for DynamicPricing {
+    fn quote(&self, duration: Duration, vehicle_type: &VehicleType) -> PriceQuote {
+        self.base.quote(duration, vehicle_type)
+    }
+
+    fn surge_at(&self, occupancy: f32) -> Option<f32> {
+        self.surges
+            .iter()
+            .rev()
+            .find(|(threshold, _)| occupancy > *threshold)
+            .map(|(_, multiplier)| *multiplier)
+    }
+
+    fn saved(&self) -> Option<SavedPricing> {
+        Some(SavedPricing::DynamicPricing {
+            base: Box::new(self.base.saved()?),
+            surges: self
+                .surges
+                .iter()
+                .map(|&(occupancy, multiplier)| SurgeStep {
+                    occupancy,
+                    multiplier,
+                })
+                .collect(),
+        })
+    }
+}
+
+/// The surge a ticket was issued under.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Surge {
+    pub multiplier: f32,
+    /// Lot occupancy at entry, from 0 to 1.
+    pub occupancy: f32,
+}
+
+/// Rates locked in at entry with a surge on top.
+#[derive(Debug)]
+pub struct Surged {
+    inner: Arc<dyn PricingStrategy>,
+    surge: Surge,
+}
+
+impl Surged {
+    pub fn new(inner: Arc<dyn PricingStrategy>, surge: Surge) -> Self {
+        Self { inner, surge }
+    }
+}
+
+impl PricingStrategy for Surged {
+    fn quote(&self, duration: Duration, vehicle_type: &VehicleType) -> PriceQuote {
+        let mut quote = self.inner.quote(duration, vehicle_type);
+        let surcharge = quote.total() * (self.surge.multiplier - 1.0);
+        if surcharge != 0.0 {
+            quote.lines.push(
+                ChargeLine::new(
+                    format!(
+                        "Surge x{:.2} at {:.0}% full",
+                        self.surge.multiplier,
+                        self.surge.occupancy * 100.0
+                    ),
+                    surcharge,
+                )
+                .with_kind(ChargeKind::Surge),
+            );
+        }
+        quote
+    }
+
+    fn saved(&self) -> Option<SavedPricing> {
+        Some(SavedPricing::Surged {
+            inner: Box::new(self.inner.saved()?),
+            surge: self.surge,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiered_with_daily_cap_itemises_each_step() {
+        let strategy = DailyCap::new(
+            Box::new(Tiered::new(vec![
+                RateTier {
+                    hours: Some(1),
+                    rate: 0.0,
+                },
+                RateTier {
+                    hours: None,
+                    rate: 5.0,
+                },
+            ])),
+            40.0,
+        );
+
+        let short = strategy.quote(Duration::minutes(150), &VehicleType::Motor);
+        assert_eq!(short.total(), 5.0);
+        assert_eq!(short.lines.len(), 2);
+
+        // 26 h: day one capped at 40, then 2 h at $5
+        let long = strategy.quote(Duration::hours(26), &VehicleType::Motor);
+        assert_eq!(long.total(), 50.0);
+        assert_eq!(long.lines.last().unwrap().description, "Daily cap $40.00");
+        assert_eq!(long.lines.last().unwrap().kind, ChargeKind::Cap);
+    }
+
+    #[test]
+    fn test_surge_is_locked_in_at_entry() {
+        use crate::{Parkable, ParkingFloor, ParkingLot, Vehicle, clock::MockClock};
+
+        let clock = MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_pricing_strategy(Box::new(
+                DynamicPricing::new(Box::new(FlatHourly::new(10.0))).with_surge(0.8, 1.5),
+            ));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+
+        let car = |n: usize| Vehicle::new(VehicleType::Motor, "Kia".into(), format!("SRG{n}"));
+        let tickets: Vec<_> = (0..9).map(|n| lot.park_vehicle(car(n)).unwrap()).collect();
+        assert_eq!(lot.occupancy_ratio(), 0.9);
+        assert!(tickets[7].surge.is_none());
+        let surge = tickets[8].surge.unwrap();
+        assert_eq!((surge.multiplier, surge.occupancy), (1.5, 0.9));
+
+        clock.advance(Duration::hours(2));
+        for ticket in &tickets[..8] {
+            assert_eq!(
+                lot.unpark_vehicle(ticket.ticket_id.clone()).unwrap().total,
+                20.0
+            );
+        }
+        // The lot has emptied, but the late arrival keeps the rate it was quoted
+        let charge = lot.unpark_vehicle(tickets[8].ticket_id.clone()).unwrap();
+        assert_eq!(charge.total, 30.0);
+        assert!(charge.breakdown.iter().any(|l| l.kind == ChargeKind::Surge));
+    }
+}
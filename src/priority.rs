@@ -0,0 +1,172 @@
+//! Priority allocation for emergency and service vehicles. When the lot has no free spot
+//! for one, it may park on a closed floor and, failing that, take a spot held by a lease
+//! or reservation. Priority vehicles also go to the front of the entry queue and skip
+//! one-in-one-out metering. Every such override is recorded in the audit log.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ParkingLot, ParkingSpot, SpotStatus, Vehicle, audit::AuditAction, error::ParkingError,
+    journal::TransitionCause, reservation::ReservationStatus, tags::SpotTag,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PriorityClass {
+    Ambulance,
+    Maintenance,
+    Security,
+}
+
+impl ParkingLot {
+    /// Finds a spot for a priority vehicle the normal allocation had none for, and parks
+    /// it there. Closed floors are tried first; a held spot is only taken when there's no
+    /// other, leased spots before reserved ones since they don't cost anyone a booking.
+    pub(crate) fn allocate_priority_spot(
+        &self,
+        vehicle: &Vehicle,
+        tags: &[SpotTag],
+        claimed_until: Option<DateTime<Utc>>,
+    ) -> Result<String, ParkingError> {
+        let now = self.now();
+        let actor = priority_actor(vehicle);
+        let claim = |spot: &mut ParkingSpot| {
+            spot.transition(TransitionCause::Parked, now, |spot| {
+                spot.assign_vehicle_until(vehicle.clone(), claimed_until)
+            })
+            .ok()
+        };
+
+        let on_closed_floor = {
+            let floors = self.floors.lock()?;
+            self.allocate_spot(&floors, vehicle, tags, |_| true, claim)
+        };
+        if let Some((floor_id, spot_id, _)) = on_closed_floor {
+            self.audit(
+                actor,
+                AuditAction::ClosedFloorEntered {
+                    floor_id,
+                    spot_id: spot_id.clone(),
+                },
+            );
+            return Ok(spot_id);
+        }
+
+        let (spot_id, held_by, reservation) = {
+            let floors = self.floors.lock()?;
+            let mut floor_ids: Vec<u32> = floors.keys().copied().collect();
+            floor_ids.sort_unstable();
+            let mut held = Vec::new();
+            for floor_id in floor_ids {
+                let spots = floors[&floor_id].spots.lock()?;
+                let mut spot_ids: Vec<(&String, bool)> = spots
+                    .iter()
+                    .filter(|(_, spot)| {
+                        (spot.is_reserved()
+                            || (spot.is_leased() && spot.status() == &SpotStatus::Free))
+                            && spot.accepts(vehicle)
+                            && spot.has_tags(tags)
+                    })
+                    .map(|(id, spot)| (id, spot.is_leased()))
+                    .collect();
+                spot_ids.sort();
+                held.extend(
+                    spot_ids
+                        .into_iter()
+                        .map(|(id, leased)| (floor_id, id.clone(), leased)),
+                );
+            }
+            held.sort_by_key(|(_, _, leased)| !leased);
+            let (floor_id, spot_id, _) = held
+                .into_iter()
+                .next()
+                .ok_or(ParkingError::NoSpotAvailable)?;
+
+            let mut spots = floors[&floor_id].spots.lock()?;
+            let spot = spots.get_mut(&spot_id).unwrap();
+            let reservation = spot.reserved_by().map(String::from);
+            let held_by = reservation.clone().or(spot.leased_by.clone()).unwrap();
+            spot.transition(TransitionCause::Preempted, now, |spot| {
+                spot.assign_vehicle_until(vehicle.clone(), claimed_until)
+            })?;
+            (spot_id, held_by, reservation)
+        };
+
+        if let Some(reservation_id) = reservation
+            && let Some(reservation) = self.reservations.lock()?.get_mut(&reservation_id)
+        {
+            reservation.status = ReservationStatus::Preempted;
+        }
+        self.audit(
+            actor,
+            AuditAction::SpotPreempted {
+                spot_id: spot_id.clone(),
+                held_by,
+            },
+        );
+        Ok(spot_id)
+    }
+}
+
+/// How a priority vehicle is named in the audit log, e.g. `Ambulance AMB001`.
+pub(crate) fn priority_actor(vehicle: &Vehicle) -> String {
+    match vehicle.priority {
+        Some(class) => format!("{:?} {}", class, vehicle.license_plate),
+        None => vehicle.license_plate.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, VehicleType};
+    use chrono::Duration;
+
+    #[test]
+    fn test_ambulance_preempts_reservation_when_lot_is_full() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let now = Utc::now();
+        let guest = Vehicle::new(VehicleType::Motor, "Kia".into(), "GUEST".into());
+        let reservation = lot
+            .reserve_spot(guest, now, now + Duration::hours(2))
+            .unwrap();
+        for n in 0..9 {
+            lot.park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                format!("CAR{n}"),
+            ))
+            .unwrap();
+        }
+
+        let ambulance = Vehicle::new(VehicleType::Motor, "Sprinter".into(), "AMB001".into())
+            .with_priority(PriorityClass::Ambulance);
+        let ticket = lot.park_vehicle(ambulance).unwrap();
+        assert_eq!(ticket.spot_id, reservation.spot_id);
+        assert_eq!(
+            lot.get_reservation(&reservation.reservation_id)
+                .unwrap()
+                .status,
+            ReservationStatus::Preempted
+        );
+
+        let log = lot.audit_log();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].actor, "Ambulance AMB001");
+        assert_eq!(
+            log[0].action,
+            AuditAction::SpotPreempted {
+                spot_id: reservation.spot_id,
+                held_by: reservation.reservation_id,
+            }
+        );
+
+        let maintenance = Vehicle::new(VehicleType::Motor, "Hilux".into(), "MNT001".into())
+            .with_priority(PriorityClass::Maintenance);
+        assert!(matches!(
+            lot.park_vehicle(maintenance),
+            Err(ParkingError::NoSpotAvailable)
+        ));
+    }
+}
@@ -0,0 +1,118 @@
+//! Per-floor minimum ratios for protected spot types (e.g. Handicapped), checked whenever a
+//! floor's spot inventory changes.
+
+use std::collections::HashMap;
+
+use crate::SpotType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuotaEnforcement {
+    /// Changes that would break a quota fail with an error.
+    #[default]
+    Reject,
+    /// Changes go through and the broken quotas are reported back as warnings.
+    Warn,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SpotQuota {
+    minimum_ratios: HashMap<SpotType, f32>,
+    enforcement: QuotaEnforcement,
+}
+
+/// A quota that a floor falls short of after an inventory change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaWarning {
+    pub floor_id: u32,
+    pub spot_type: SpotType,
+    pub required_ratio: f32,
+    pub actual_ratio: f32,
+    /// Spots of this type needed to meet the quota at the floor's current size.
+    pub missing_spots: u32,
+}
+
+impl SpotQuota {
+    pub fn new(enforcement: QuotaEnforcement) -> Self {
+        Self {
+            minimum_ratios: HashMap::new(),
+            enforcement,
+        }
+    }
+
+    /// Requires at least `ratio` (0..=1) of the floor's spots to be of `spot_type`.
+    pub fn with_minimum(mut self, spot_type: SpotType, ratio: f32) -> Self {
+        self.minimum_ratios.insert(spot_type, ratio.clamp(0.0, 1.0));
+        self
+    }
+
+    pub fn enforcement(&self) -> QuotaEnforcement {
+        self.enforcement
+    }
+
+    /// Quotas broken by a floor with the given per-type counts.
+    pub(crate) fn shortfalls(
+        &self,
+        floor_id: u32,
+        counts: &HashMap<SpotType, u32>,
+    ) -> Vec<QuotaWarning> {
+        let total: u32 = counts.values().sum();
+        let mut shortfalls: Vec<QuotaWarning> = self
+            .minimum_ratios
+            .iter()
+            .filter_map(|(spot_type, required_ratio)| {
+                let count = counts.get(spot_type).copied().unwrap_or(0);
+                let actual_ratio = if total == 0 {
+                    1.0
+                } else {
+                    count as f32 / total as f32
+                };
+                let required = (*required_ratio as f64 * total as f64 - 1e-6).ceil() as u32;
+                (count < required).then_some(QuotaWarning {
+                    floor_id,
+                    spot_type: *spot_type,
+                    required_ratio: *required_ratio,
+                    actual_ratio,
+                    missing_spots: required - count,
+                })
+            })
+            .collect();
+        shortfalls.sort_by_key(|w| format!("{:?}", w.spot_type));
+        shortfalls
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParkingFloor, ParkingSpot, error::ParkingError};
+
+    #[test]
+    fn test_floor_rejects_changes_that_dilute_handicapped_quota() {
+        let mut floor = ParkingFloor::new(1);
+        floor.set_spot_quota(
+            SpotQuota::new(QuotaEnforcement::Reject).with_minimum(SpotType::Handicapped, 0.1),
+        );
+
+        assert!(
+            floor
+                .add_spot(ParkingSpot::new(true, SpotType::Large))
+                .is_err()
+        );
+
+        let handicapped = ParkingSpot::new(true, SpotType::Handicapped);
+        let handicapped_id = handicapped.get_id().to_string();
+        assert!(floor.add_spot(handicapped).unwrap().is_empty());
+        assert!(matches!(
+            floor.remove_spot(&handicapped_id),
+            Err(ParkingError::QuotaViolation(QuotaWarning {
+                spot_type: SpotType::Handicapped,
+                ..
+            }))
+        ));
+        assert!(
+            floor
+                .convert_spot(&handicapped_id, SpotType::Regular)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,420 @@
+//! Suggestions for rebalancing spot inventory. The lot compares how busy each spot type
+//! has been with the vehicles it turned away, and suggests converting idle spots of one
+//! type into a type that was short, e.g. "convert 5 XLarge to Regular on floor 4".
+//!
+//! Utilisation comes from the ticket history over the advisor's period; turned-away
+//! vehicles are counted since the lot started, see `AnalyticsReport::rejections`.
+//! A suggestion can be replayed against the stays of a past period with
+//! `simulate_rebalancing` before it's applied with `apply_rebalancing`. The replay only
+//! covers vehicles the lot admitted, so it shows what the change would cost that traffic.
+
+use std::{cmp::Reverse, collections::HashMap, fmt};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    ParkingLot, SpotType, Vehicle, VehicleType, allocation::BestFit, analytics::RejectionReason,
+    error::ParkingError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RebalancingAdvisor {
+    /// How far back utilisation is measured.
+    pub period: Duration,
+    /// Share of the time a spot type should be busy; types below it have spots to spare.
+    pub target_utilization: f32,
+    /// Vehicles of a type that must have been turned away before more spots are suggested
+    /// for it.
+    pub min_rejections: u32,
+}
+
+impl Default for RebalancingAdvisor {
+    fn default() -> Self {
+        Self {
+            period: Duration::days(7),
+            target_utilization: 0.85,
+            min_rejections: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RebalancingSuggestion {
+    pub floor_id: u32,
+    pub from: SpotType,
+    pub to: SpotType,
+    pub count: u32,
+    /// Vehicles turned away for lack of a `to` spot.
+    pub turned_away: u32,
+    /// How busy `from` spots were over the period, from 0 to 1.
+    pub from_utilization: f32,
+}
+
+impl fmt::Display for RebalancingSuggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "convert {} {:?} to {:?} on floor {}",
+            self.count, self.from, self.to, self.floor_id
+        )
+    }
+}
+
+/// Past stays replayed against the lot's inventory as it is and with a suggestion applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RebalancingSimulation {
+    pub stays: u32,
+    pub refused_before: u32,
+    pub refused_after: u32,
+}
+
+/// A stay as entry and exit time; open stays exit now.
+#[derive(Debug)]
+struct Stay {
+    entry: DateTime<Utc>,
+    exit: DateTime<Utc>,
+    spot_type: Option<SpotType>,
+    vehicle: Vehicle,
+}
+
+impl ParkingLot {
+    /// Conversions that would make room for the vehicles turned away, taking the least
+    /// busy spot types first. A type is suggested for conversion only down to what keeps
+    /// it under the target utilisation.
+    pub fn rebalancing_suggestions(
+        &self,
+        advisor: &RebalancingAdvisor,
+    ) -> Result<Vec<RebalancingSuggestion>, ParkingError> {
+        let now = self.now();
+        let from = now - advisor.period;
+        let period_hours = hours(advisor.period);
+        let totals = self.spot_type_totals()?;
+        let mut available = self.available_by_floor()?;
+
+        let mut busy_hours: HashMap<SpotType, f32> = HashMap::new();
+        let mut stays: HashMap<VehicleType, (f32, u32)> = HashMap::new();
+        for stay in self.stays_between(from, now)? {
+            let overlap = hours(stay.exit.min(now) - stay.entry.max(from));
+            if let Some(spot_type) = stay.spot_type {
+                *busy_hours.entry(spot_type).or_insert(0.0) += overlap;
+            }
+            let length = hours(stay.exit - stay.entry);
+            let stay = stays
+                .entry(stay.vehicle.vehicle_type.clone())
+                .or_insert((0.0, 0));
+            stay.0 += length;
+            stay.1 += 1;
+        }
+        let utilization = |spot_type: SpotType| match totals.get(&spot_type) {
+            Some(&total) if total > 0 => {
+                busy_hours.get(&spot_type).copied().unwrap_or(0.0) / (total as f32 * period_hours)
+            }
+            _ => 0.0,
+        };
+        // Spots of each type that could go while staying under the target utilisation
+        let mut spare: HashMap<SpotType, u32> = totals
+            .iter()
+            .map(|(&spot_type, &total)| {
+                let needed = (utilization(spot_type) * total as f32 / advisor.target_utilization)
+                    .ceil() as u32;
+                (spot_type, total.saturating_sub(needed))
+            })
+            .collect();
+
+        let rejections = self.rejections.lock()?.clone();
+        let mut suggestions = Vec::new();
+        for vehicle_type in VehicleType::ALL {
+            let turned_away: u32 = [RejectionReason::Full, RejectionReason::Incompatible]
+                .iter()
+                .filter_map(|reason| rejections.get(&(vehicle_type.clone(), *reason)))
+                .sum();
+            if turned_away < advisor.min_rejections {
+                continue;
+            }
+            let Some(to) = SpotType::ALL
+                .into_iter()
+                .filter(|&t| {
+                    t != SpotType::Handicapped && self.compatibility.allows(&vehicle_type, t)
+                })
+                .min_by_key(|&t| BestFit::size(t))
+            else {
+                continue;
+            };
+            let (stay_hours, stay_count) = stays.get(&vehicle_type).copied().unwrap_or((1.0, 1));
+            let lost_hours = turned_away as f32 * stay_hours / stay_count as f32;
+            let mut needed =
+                ((lost_hours / period_hours / advisor.target_utilization).ceil() as u32).max(1);
+
+            // Accessible spots are never suggested for conversion
+            let mut sources: Vec<SpotType> = SpotType::ALL
+                .into_iter()
+                .filter(|&t| t != to && t != SpotType::Handicapped)
+                .collect();
+            sources.sort_by(|a, b| utilization(*a).total_cmp(&utilization(*b)));
+            for source in sources {
+                while needed > 0 && spare.get(&source).copied().unwrap_or(0) > 0 {
+                    let Some((&floor_id, free)) = available
+                        .iter_mut()
+                        .filter(|(_, free)| free.get(&source).copied().unwrap_or(0) > 0)
+                        .max_by_key(|(floor_id, free)| (free[&source], Reverse(**floor_id)))
+                    else {
+                        break;
+                    };
+                    let spare = spare.get_mut(&source).unwrap();
+                    let count = needed.min(*spare).min(free[&source]);
+                    *free.get_mut(&source).unwrap() -= count;
+                    *spare -= count;
+                    needed -= count;
+                    suggestions.push(RebalancingSuggestion {
+                        floor_id,
+                        from: source,
+                        to,
+                        count,
+                        turned_away,
+                        from_utilization: utilization(source),
+                    });
+                }
+            }
+        }
+        Ok(suggestions)
+    }
+
+    /// Replays the stays of the last `period` against the current inventory and against
+    /// the inventory with `suggestion` applied, counting the stays that would have found
+    /// no spot.
+    pub fn simulate_rebalancing(
+        &self,
+        suggestion: &RebalancingSuggestion,
+        period: Duration,
+    ) -> Result<RebalancingSimulation, ParkingError> {
+        let now = self.now();
+        let stays = self.stays_between(now - period, now)?;
+        let before = self.spot_type_totals()?;
+        let mut after = before.clone();
+        let moved = suggestion
+            .count
+            .min(after.get(&suggestion.from).copied().unwrap_or(0));
+        *after.entry(suggestion.from).or_insert(0) -= moved;
+        *after.entry(suggestion.to).or_insert(0) += moved;
+        Ok(RebalancingSimulation {
+            stays: stays.len() as u32,
+            refused_before: self.replay(&stays, before),
+            refused_after: self.replay(&stays, after),
+        })
+    }
+
+    /// Converts free spots on the suggestion's floor, as by `convert_spot`, and returns
+    /// their ids. Converts fewer if fewer are free.
+    pub fn apply_rebalancing(
+        &self,
+        suggestion: &RebalancingSuggestion,
+    ) -> Result<Vec<String>, ParkingError> {
+        let spot_ids: Vec<String> = {
+            let floors = self.floors.lock()?;
+            let floor = floors
+                .get(&suggestion.floor_id)
+                .ok_or(ParkingError::FloorNotFound)?;
+            let spots = floor.spots.lock()?;
+            let mut spot_ids: Vec<&String> = spots
+                .iter()
+                .filter(|(_, spot)| spot.spot_type == suggestion.from && spot.is_available())
+                .map(|(spot_id, _)| spot_id)
+                .collect();
+            spot_ids.sort();
+            spot_ids
+                .into_iter()
+                .take(suggestion.count as usize)
+                .cloned()
+                .collect()
+        };
+        for spot_id in &spot_ids {
+            self.convert_spot(suggestion.floor_id, spot_id, suggestion.to)?;
+        }
+        Ok(spot_ids)
+    }
+
+    fn spot_type_totals(&self) -> Result<HashMap<SpotType, u32>, ParkingError> {
+        let mut totals = HashMap::new();
+        for floor in self.floors.lock()?.values() {
+            for spot in floor.spots.lock()?.values() {
+                *totals.entry(spot.spot_type).or_insert(0) += 1;
+            }
+        }
+        Ok(totals)
+    }
+
+    fn available_by_floor(&self) -> Result<HashMap<u32, HashMap<SpotType, u32>>, ParkingError> {
+        self.floors
+            .lock()?
+            .iter()
+            .map(|(&floor_id, floor)| {
+                let mut free = HashMap::new();
+                for spot in floor.spots.lock()?.values() {
+                    *free.entry(spot.spot_type).or_insert(0) += u32::from(spot.is_available());
+                }
+                Ok((floor_id, free))
+            })
+            .collect()
+    }
+
+    /// Stays overlapping `from..until`, closed and still open.
+    fn stays_between(
+        &self,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<Stay>, ParkingError> {
+        let now = self.now();
+        let closed = self
+            .ticket_history
+            .completed_tickets()?
+            .into_iter()
+            .map(|completed| completed.ticket);
+        let open = self
+            .active_tickets
+            .lock()?
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        Ok(closed
+            .chain(open)
+            .map(|ticket| {
+                let exit = ticket.exit_time.unwrap_or(now);
+                Stay {
+                    entry: ticket.entry_time,
+                    exit,
+                    spot_type: ticket.spot_type,
+                    vehicle: ticket.vehicle,
+                }
+            })
+            .filter(|stay| stay.entry < until && from < stay.exit)
+            .collect())
+    }
+
+    /// Stays that find no spot when parked in entry order, each taking the smallest free
+    /// spot type that fits it.
+    fn replay(&self, stays: &[Stay], mut free: HashMap<SpotType, u32>) -> u32 {
+        // Departures sort before arrivals at the same time
+        let mut events: Vec<(DateTime<Utc>, bool, usize)> = stays
+            .iter()
+            .enumerate()
+            .flat_map(|(i, stay)| [(stay.entry, true, i), (stay.exit, false, i)])
+            .collect();
+        events.sort();
+        let mut parked: HashMap<usize, SpotType> = HashMap::new();
+        let mut refused = 0;
+        for (_, arriving, i) in events {
+            if !arriving {
+                if let Some(spot_type) = parked.remove(&i) {
+                    *free.get_mut(&spot_type).unwrap() += 1;
+                }
+                continue;
+            }
+            let vehicle = &stays[i].vehicle;
+            let spot_type = SpotType::ALL
+                .into_iter()
+                .filter(|&t| free.get(&t).copied().unwrap_or(0) > 0)
+                .filter(|&t| {
+                    (t != SpotType::Handicapped || vehicle.handicapped_permit)
+                        && self.compatibility.allows(&vehicle.vehicle_type, t)
+                })
+                .min_by_key(|&t| BestFit::size(t));
+            match spot_type {
+                Some(spot_type) => {
+                    *free.get_mut(&spot_type).unwrap() -= 1;
+                    parked.insert(i, spot_type);
+                }
+                None => refused += 1,
+            }
+        }
+        refused
+    }
+}
+
+fn hours(duration: Duration) -> f32 {
+    duration.num_seconds() as f32 / 3600.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Parkable, ParkingFloor, ParkingSpot, clock::MockClock, compatibility::CompatibilityPolicy,
+    };
+
+    #[test]
+    fn test_idle_spots_are_suggested_for_the_type_turned_away() {
+        let clock = MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_compatibility_policy(
+                CompatibilityPolicy::empty()
+                    .allow(VehicleType::Motor, SpotType::Regular)
+                    .allow(VehicleType::Truck, SpotType::XLarge),
+            );
+        let mut floor = ParkingFloor::new(4);
+        floor.spots.lock().unwrap().clear();
+        for spot_type in [SpotType::Regular; 2]
+            .into_iter()
+            .chain([SpotType::XLarge; 6])
+        {
+            floor.add_spot(ParkingSpot::new(true, spot_type)).unwrap();
+        }
+        lot.add_floor(floor).unwrap();
+
+        let car = |n: u32| Vehicle::new(VehicleType::Motor, "Kia".into(), format!("RBL{n:03}"));
+        let tickets: Vec<_> = (0..2).map(|n| lot.park_vehicle(car(n)).unwrap()).collect();
+        for n in 2..12 {
+            assert!(lot.park_vehicle(car(n)).is_err());
+        }
+        clock.advance(Duration::hours(4));
+        for ticket in tickets {
+            lot.unpark_vehicle(ticket.ticket_id).unwrap();
+        }
+
+        let advisor = RebalancingAdvisor {
+            period: Duration::days(1),
+            ..RebalancingAdvisor::default()
+        };
+        let suggestions = lot.rebalancing_suggestions(&advisor).unwrap();
+        assert_eq!(suggestions.len(), 1);
+        let suggestion = &suggestions[0];
+        // Ten 4-hour stays lost in a day fill about two spots
+        assert_eq!(
+            suggestion.to_string(),
+            "convert 2 XLarge to Regular on floor 4"
+        );
+        assert_eq!(
+            (suggestion.turned_away, suggestion.from_utilization),
+            (10, 0.0)
+        );
+
+        let simulation = lot
+            .simulate_rebalancing(suggestion, Duration::days(1))
+            .unwrap();
+        assert_eq!(
+            simulation,
+            RebalancingSimulation {
+                stays: 2,
+                refused_before: 0,
+                refused_after: 0,
+            }
+        );
+        // With one Regular spot fewer, one of the two cars would have been turned away
+        let shrunk = RebalancingSuggestion {
+            from: SpotType::Regular,
+            to: SpotType::XLarge,
+            count: 1,
+            ..suggestion.clone()
+        };
+        assert_eq!(
+            lot.simulate_rebalancing(&shrunk, Duration::days(1))
+                .unwrap()
+                .refused_after,
+            1
+        );
+
+        assert_eq!(lot.apply_rebalancing(suggestion).unwrap().len(), 2);
+        assert_eq!(lot.spot_conversions().len(), 2);
+        assert!(lot.park_vehicle(car(20)).is_ok());
+    }
+}
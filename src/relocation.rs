@@ -0,0 +1,211 @@
+//! Moving parked vehicles, e.g. off a flooded floor or out of a bay due for maintenance.
+//! The ticket follows the vehicle to its new spot and keeps its entry time and rates, so
+//! billing carries on as if it had never moved.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ParkingLot, ParkingTicket,
+    batch::Effects,
+    error::ParkingError,
+    events::ParkingEvent,
+    journal::TransitionCause,
+    notification::{Notification, TemplateKind},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Relocation {
+    pub at: DateTime<Utc>,
+    pub from_floor_id: u32,
+    pub from_spot_id: String,
+    pub floor_id: u32,
+    pub spot_id: String,
+    pub reason: String,
+}
+
+impl ParkingLot {
+    /// Moves the vehicle on `ticket_id` to a free compatible spot, on `to_floor` if given.
+    /// Closed floors are skipped. A vehicle parked in a zone stays billed by the zone. The
+    /// owner is told where it went if they left a contact.
+    pub fn relocate_vehicle(
+        &self,
+        ticket_id: &str,
+        to_floor: Option<u32>,
+        reason: &str,
+    ) -> Result<Relocation, ParkingError> {
+        let ticket_id = self.canonical_ticket_id(ticket_id);
+        let now = self.now();
+        let mut tickets = self.active_tickets.lock()?;
+        let ticket = tickets
+            .get_mut(&ticket_id)
+            .ok_or(ParkingError::InvalidTicket)?;
+
+        let relocation = {
+            let floors = self.floors.lock()?;
+            // Spot ids repeat across floors, so match the vehicle too
+            let mut from_floor_id = None;
+            for floor in floors.values() {
+                let parked_here = floor
+                    .spots
+                    .lock()?
+                    .get(&ticket.spot_id)
+                    .and_then(|spot| spot.vehicle.as_ref())
+                    .is_some_and(|v| v.license_plate == ticket.vehicle.license_plate);
+                if parked_here {
+                    from_floor_id = Some(floor.id);
+                    break;
+                }
+            }
+            let from_floor_id = from_floor_id.ok_or(ParkingError::SpotNotFound)?;
+
+            let closed_floors = self.closed_floors.lock()?;
+            let floor_open = |id: u32| {
+                !closed_floors.contains(&id)
+                    && self.schedule.is_floor_open(id, now)
+                    && to_floor.is_none_or(|to_floor| to_floor == id)
+            };
+            let vehicle = ticket.vehicle.clone();
+            let (floor_id, spot_id, _) = self
+                .allocate_spot(&floors, &vehicle, &[], floor_open, |spot| {
+                    spot.transition(TransitionCause::Relocated, now, |spot| {
+                        spot.assign_vehicle(vehicle.clone())
+                    })
+                    .ok()
+                })
+                .ok_or(ParkingError::NoSpotAvailable)?;
+            if let Some(spot) = floors[&from_floor_id]
+                .spots
+                .lock()?
+                .get_mut(&ticket.spot_id)
+            {
+                spot.transition(TransitionCause::Relocated, now, |spot| {
+                    spot.remove_vehicle()
+                });
+            }
+
+            Relocation {
+                at: now,
+                from_floor_id,
+                from_spot_id: std::mem::replace(&mut ticket.spot_id, spot_id.clone()),
+                floor_id,
+                spot_id,
+                reason: reason.to_string(),
+            }
+        };
+        ticket.floor_id = Some(relocation.floor_id);
+        ticket.relocations.push(relocation.clone());
+        let license_plate = ticket.vehicle.license_plate.clone();
+        let notice = self.notice_for(&license_plate, |locale| {
+            self.relocation_notification(ticket, locale)
+        });
+        drop(tickets);
+
+        self.emit(ParkingEvent::VehicleRelocated {
+            ticket_id,
+            license_plate,
+            from_spot_id: relocation.from_spot_id.clone(),
+            spot_id: relocation.spot_id.clone(),
+            floor_id: relocation.floor_id,
+            at: now,
+        });
+        self.emit_capacity_events(relocation.floor_id, &mut Effects::immediate())?;
+        if let Some(notice) = notice {
+            self.notify(notice);
+        }
+        Ok(relocation)
+    }
+
+    /// The notice telling the owner where their vehicle was last moved to. `None` if it
+    /// never moved.
+    pub fn relocation_notification(
+        &self,
+        ticket: &ParkingTicket,
+        locale: &str,
+    ) -> Option<Notification> {
+        let relocation = ticket.relocations.last()?;
+        let mut values = self.ticket_template_values(ticket);
+        values.insert("from_spot_id", relocation.from_spot_id.clone());
+        values.insert("floor_id", relocation.floor_id.to_string());
+        values.insert("reason", relocation.reason.clone());
+        self.templates
+            .get(TemplateKind::Relocation, locale)
+            .map(|template| template.render(&values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Parkable, ParkingFloor, Vehicle, VehicleType, clock::MockClock, notification::Notifier,
+        pricing::FlatHourly,
+    };
+    use chrono::Duration;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Default, Clone)]
+    struct Outbox {
+        sent: Arc<Mutex<Vec<(String, Notification)>>>,
+    }
+
+    impl Notifier for Outbox {
+        fn send(&self, recipient: &str, notification: &Notification) -> Result<(), String> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push((recipient.to_string(), notification.clone()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_relocated_vehicle_keeps_its_ticket_and_billing() {
+        let clock = MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_pricing_strategy(Box::new(FlatHourly::new(10.0)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.add_floor(ParkingFloor::new(2)).unwrap();
+        let outbox = Outbox::default();
+        lot.set_notifier(Box::new(outbox.clone()));
+        lot.set_contact("MOVE01", "+2348000000000", "en");
+        let events = lot.events();
+
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "MOVE01".into());
+        let ticket = lot.park_vehicle(car).unwrap();
+        clock.advance(Duration::hours(2));
+        let relocation = lot
+            .relocate_vehicle(&ticket.ticket_id, Some(2), "flooding")
+            .unwrap();
+        assert_eq!((relocation.from_floor_id, relocation.floor_id), (1, 2));
+        assert_eq!(relocation.from_spot_id, ticket.spot_id);
+        assert_eq!(
+            lot.locate_vehicle("MOVE01").unwrap().floor_id,
+            relocation.floor_id
+        );
+        assert_eq!(lot.occupancy_report().floor(1).unwrap().occupied, 0);
+        assert!(
+            events
+                .try_iter()
+                .any(|e| matches!(e, ParkingEvent::VehicleRelocated { floor_id: 2, .. }))
+        );
+
+        let moved = lot.active_ticket(&ticket.ticket_id).unwrap();
+        let message = lot.relocation_notification(&moved, "en").unwrap();
+        assert!(message.body.contains("on floor 2 (flooding)"));
+        assert_eq!(
+            *outbox.sent.lock().unwrap(),
+            vec![("+2348000000000".to_string(), message)]
+        );
+        assert_eq!(
+            lot.relocate_vehicle(&ticket.ticket_id, Some(9), "flooding"),
+            Err(ParkingError::NoSpotAvailable)
+        );
+
+        clock.advance(Duration::hours(1));
+        let charge = lot.unpark_vehicle(ticket.ticket_id).unwrap();
+        assert_eq!(charge.total, 30.0);
+        assert_eq!(lot.occupancy_report().occupied, 0);
+    }
+}
@@ -0,0 +1,287 @@
+//! Warm-standby replication between two instances of a lot, for garages that can't close
+//! while a server is replaced.
+//!
+//! The primary runs a `ReplicationSource`, which numbers the lot's events and ships them
+//! to the standby together with a full checkpoint of the lot every few events. The
+//! standby keeps the latest checkpoint and the events that came after it. Promoting the
+//! standby rebuilds the lot from that checkpoint; the events after it are handed back for
+//! reconciliation, so at most `checkpoint_every` shipped events are lost.
+//!
+//! A `Fence` keeps a stale primary from allocating spots after a standby has taken over.
+//! It stands in for a record both instances can reach, such as a database row. Every
+//! promotion advances its epoch, and an instance holding an older epoch is refused at
+//! entry and when booking reservations.
+//!
+//! Promoted lots are restored like `load_from_file`, so configuration supplied in code has
+//! to be set up again.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+    mpsc::Receiver,
+};
+
+use crate::{ParkingLot, error::ParkingError, events::ParkingEvent};
+
+/// The shared epoch counter instances are fenced by.
+#[derive(Debug, Clone, Default)]
+pub struct Fence {
+    epoch: Arc<AtomicU64>,
+}
+
+impl Fence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::SeqCst)
+    }
+
+    /// Moves to a new epoch, fencing off every instance holding an older one.
+    fn advance(&self) -> u64 {
+        self.epoch.fetch_add(1, Ordering::SeqCst) + 1
+    }
+}
+
+/// The epoch a lot was granted under a fence.
+#[derive(Debug, Clone)]
+pub(crate) struct FenceLease {
+    fence: Fence,
+    epoch: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplicationMessage {
+    Event {
+        seq: u64,
+        event: ParkingEvent,
+    },
+    /// The whole lot as of event `seq`, in the `save_to_file` format.
+    Checkpoint {
+        seq: u64,
+        snapshot: String,
+    },
+}
+
+/// The primary's side of replication.
+#[derive(Debug)]
+pub struct ReplicationSource {
+    events: Receiver<ParkingEvent>,
+    seq: u64,
+    checkpoint_every: u64,
+    checkpointed_seq: Option<u64>,
+}
+
+impl ReplicationSource {
+    /// Starts recording `lot`'s events. A checkpoint is shipped at least once every
+    /// `checkpoint_every` events, which bounds what a promotion can lose.
+    pub fn new(lot: &ParkingLot, checkpoint_every: u64) -> Self {
+        Self {
+            events: lot.events(),
+            seq: 0,
+            checkpoint_every: checkpoint_every.max(1),
+            checkpointed_seq: None,
+        }
+    }
+
+    /// The messages to ship since the last call: new events in order, then a checkpoint
+    /// when one is due. The first call always ends with a checkpoint.
+    pub fn poll(&mut self, lot: &ParkingLot) -> Result<Vec<ReplicationMessage>, ParkingError> {
+        let mut messages: Vec<ReplicationMessage> = self
+            .events
+            .try_iter()
+            .map(|event| {
+                self.seq += 1;
+                ReplicationMessage::Event {
+                    seq: self.seq,
+                    event,
+                }
+            })
+            .collect();
+        let due = self
+            .checkpointed_seq
+            .is_none_or(|seq| self.seq - seq >= self.checkpoint_every);
+        if due {
+            messages.push(self.checkpoint(lot)?);
+        }
+        Ok(messages)
+    }
+
+    /// A checkpoint of the lot now, e.g. before planned maintenance.
+    pub fn checkpoint(&mut self, lot: &ParkingLot) -> Result<ReplicationMessage, ParkingError> {
+        self.checkpointed_seq = Some(self.seq);
+        Ok(ReplicationMessage::Checkpoint {
+            seq: self.seq,
+            snapshot: lot.snapshot_text()?,
+        })
+    }
+}
+
+/// The standby's side of replication.
+#[derive(Debug, Default)]
+pub struct WarmStandby {
+    checkpoint: Option<(u64, String)>,
+    /// Events after the checkpoint.
+    pending: Vec<(u64, ParkingEvent)>,
+    last_seq: u64,
+}
+
+/// A standby taking over as primary.
+#[derive(Debug)]
+pub struct Promotion {
+    pub lot: ParkingLot,
+    /// The fence epoch the new primary holds.
+    pub epoch: u64,
+    /// Last event the restored lot reflects.
+    pub checkpoint_seq: u64,
+    /// Events the primary reported after the checkpoint. They aren't in the restored lot
+    /// and have to be reconciled by hand, e.g. a car that parked but has no ticket.
+    pub unapplied: Vec<ParkingEvent>,
+}
+
+impl WarmStandby {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes the next message from the primary. Events must arrive in sequence; a
+    /// checkpoint may skip ahead.
+    pub fn apply(&mut self, message: ReplicationMessage) -> Result<(), ParkingError> {
+        match message {
+            ReplicationMessage::Event { seq, event } => {
+                if seq != self.last_seq + 1 {
+                    return Err(ParkingError::ReplicationGap {
+                        expected: self.last_seq + 1,
+                        received: seq,
+                    });
+                }
+                self.last_seq = seq;
+                self.pending.push((seq, event));
+            }
+            ReplicationMessage::Checkpoint { seq, snapshot } => {
+                self.pending.retain(|(pending, _)| *pending > seq);
+                self.last_seq = self.last_seq.max(seq);
+                self.checkpoint = Some((seq, snapshot));
+            }
+        }
+        Ok(())
+    }
+
+    /// Last event received from the primary.
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    /// Events a promotion now would lose.
+    pub fn lag(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Restores the lot from the latest checkpoint and fences off every older instance.
+    pub fn promote(self, fence: &Fence) -> Result<Promotion, ParkingError> {
+        let (checkpoint_seq, snapshot) = self.checkpoint.ok_or(ParkingError::StandbyNotReady)?;
+        let mut lot = ParkingLot::from_snapshot_text(&snapshot)?;
+        let epoch = fence.advance();
+        lot.fence = Some(FenceLease {
+            fence: fence.clone(),
+            epoch,
+        });
+        Ok(Promotion {
+            lot,
+            epoch,
+            checkpoint_seq,
+            unapplied: self.pending.into_iter().map(|(_, event)| event).collect(),
+        })
+    }
+}
+
+impl ParkingLot {
+    /// Runs the lot under `fence` at its current epoch, as the primary.
+    pub fn join_fence(&mut self, fence: &Fence) {
+        self.fence = Some(FenceLease {
+            fence: fence.clone(),
+            epoch: fence.epoch(),
+        });
+    }
+
+    /// The epoch this instance holds, when it runs under a fence.
+    pub fn fence_epoch(&self) -> Option<u64> {
+        self.fence.as_ref().map(|lease| lease.epoch)
+    }
+
+    /// Fails once another instance has been promoted past this one's epoch.
+    pub(crate) fn check_fence(&self) -> Result<(), ParkingError> {
+        match &self.fence {
+            Some(lease) if lease.fence.epoch() != lease.epoch => Err(ParkingError::Fenced {
+                epoch: lease.epoch,
+                current: lease.fence.epoch(),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, Vehicle, VehicleType};
+
+    #[test]
+    fn test_promoted_standby_fences_off_the_old_primary() {
+        let mut primary = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        primary.add_floor(ParkingFloor::new(1)).unwrap();
+        let fence = Fence::new();
+        primary.join_fence(&fence);
+        let mut source = ReplicationSource::new(&primary, 2);
+        let mut standby = WarmStandby::new();
+
+        let car = |plate: &str| Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into());
+        let first = primary.park_vehicle(car("REP001")).unwrap();
+        for message in source.poll(&primary).unwrap() {
+            standby.apply(message).unwrap();
+        }
+        assert_eq!(standby.lag(), 0);
+        primary.park_vehicle(car("REP002")).unwrap();
+        for message in source.poll(&primary).unwrap() {
+            standby.apply(message).unwrap();
+        }
+        assert_eq!(standby.lag(), 1);
+        assert_eq!(
+            standby.apply(ReplicationMessage::Event {
+                seq: 9,
+                event: ParkingEvent::LotFull { at: primary.now() },
+            }),
+            Err(ParkingError::ReplicationGap {
+                expected: 3,
+                received: 9
+            })
+        );
+
+        let promotion = standby.promote(&fence).unwrap();
+        assert_eq!(promotion.checkpoint_seq, 1);
+        assert!(matches!(
+            &promotion.unapplied[..],
+            [ParkingEvent::VehicleParked { license_plate, .. }] if license_plate == "REP002"
+        ));
+        let new_primary = promotion.lot;
+        assert_eq!(
+            new_primary
+                .active_ticket(&first.ticket_id)
+                .unwrap()
+                .vehicle
+                .license_plate,
+            "REP001"
+        );
+
+        // The old primary can't hand out spots the new one also thinks are free
+        assert_eq!(
+            primary.park_vehicle(car("REP003")).unwrap_err(),
+            ParkingError::Fenced {
+                epoch: 0,
+                current: 1
+            }
+        );
+        new_primary.park_vehicle(car("REP003")).unwrap();
+    }
+}
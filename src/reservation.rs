@@ -0,0 +1,517 @@
+//! Advance spot reservations.
+//!
+//! Booking picks a compatible spot that no other reservation has for an overlapping window,
+//! but only holds it from the start of the window, less a grace period (15 minutes unless
+//! set), until the driver checks in, the reservation is cancelled, or the window ends
+//! without an arrival. Held spots are skipped when allocating to walk-ins. Until then the
+//! spot stays free; if a walk-in is still on it when the hold starts, the reservation moves
+//! to another free spot with the same tags. Drivers may check in from the start of the hold.
+//!
+//! Holds start as vehicles park, and by `hold_due_reservations`, meant to run periodically.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ParkingLot, ParkingSpot, ParkingTicket, RESERVATION_COUNTER, SpotStatus, Vehicle,
+    batch::Effects, calendar::SpotHold, error::ParkingError, events::ParkingEvent,
+    journal::TransitionCause, tags::SpotTag,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReservationStatus {
+    Pending,
+    Fulfilled,
+    Cancelled,
+    Expired,
+    /// A priority vehicle took the held spot.
+    Preempted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reservation {
+    pub reservation_id: String,
+    pub vehicle: Vehicle,
+    pub floor_id: u32,
+    pub spot_id: String,
+    pub from: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub status: ReservationStatus,
+    /// Ticket issued when the reservation was checked in.
+    pub ticket_id: Option<String>,
+    /// Standing reservation this occurrence was booked for; `None` for one-off bookings.
+    pub series_id: Option<String>,
+}
+
+impl Reservation {
+    /// When the spot is held from, and the driver may check in.
+    fn hold_from(&self, grace: Duration) -> DateTime<Utc> {
+        self.from - grace
+    }
+}
+
+/// Floor and spot of every pending reservation other than `except` whose hold overlaps
+/// `from..until`.
+fn booked_spots(
+    reservations: &HashMap<String, Reservation>,
+    grace: Duration,
+    from: DateTime<Utc>,
+    until: DateTime<Utc>,
+    except: &str,
+) -> Vec<(u32, String)> {
+    reservations
+        .values()
+        .filter(|r| {
+            r.status == ReservationStatus::Pending
+                && r.reservation_id != except
+                && r.hold_from(grace) < until
+                && from < r.until
+        })
+        .map(|r| (r.floor_id, r.spot_id.clone()))
+        .collect()
+}
+
+impl ParkingLot {
+    /// How early before its window a reservation holds its spot and may be checked in.
+    pub fn set_reservation_grace(&mut self, grace: Duration) {
+        self.reservation_grace = grace;
+    }
+
+    pub(crate) fn generate_reservation_id(&self) -> String {
+        format!(
+            "RSV_{}",
+            RESERVATION_COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+        )
+    }
+
+    /// Holds a spot compatible with `vehicle` for the `from..until` window.
+    pub fn reserve_spot(
+        &self,
+        vehicle: Vehicle,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Reservation, ParkingError> {
+        self.reserve_spot_with_tags(vehicle, from, until, &[])
+    }
+
+    /// Like `reserve_spot`, holding a spot that carries every tag in `tags`.
+    pub fn reserve_spot_with_tags(
+        &self,
+        vehicle: Vehicle,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+        tags: &[SpotTag],
+    ) -> Result<Reservation, ParkingError> {
+        self.book_reservation(vehicle, from, until, tags, None)
+    }
+
+    pub(crate) fn book_reservation(
+        &self,
+        vehicle: Vehicle,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+        tags: &[SpotTag],
+        series_id: Option<String>,
+    ) -> Result<Reservation, ParkingError> {
+        if until <= from || until <= self.now() {
+            return Err(ParkingError::InvalidReservationWindow);
+        }
+        self.check_fence()?;
+        self.check_not_draining()?;
+
+        let now = self.now();
+        self.hold_due_reservations(now);
+        let reservation_id = self.generate_reservation_id();
+        let hold_from = from - self.reservation_grace;
+        let mut reservations = self.reservations.lock()?;
+        let booked = booked_spots(&reservations, self.reservation_grace, hold_from, until, "");
+        let (floor_id, spot_id, _) = {
+            let floors = self.floors.lock()?;
+            let closed_floors = self.closed_floors.lock()?;
+            self.allocate_spot_where(
+                &floors,
+                &vehicle,
+                tags,
+                |id| !closed_floors.contains(&id) && !self.floor_under_maintenance(id, from, until),
+                |floor_id, spot_id| !booked.iter().any(|b| b.0 == floor_id && b.1 == spot_id),
+                |spot| {
+                    if hold_from > now {
+                        return Some(());
+                    }
+                    spot.transition(TransitionCause::Reserved, now, |spot| {
+                        spot.set_status(SpotStatus::Reserved(reservation_id.clone()))
+                    })
+                    .ok()
+                },
+            )
+        }
+        .ok_or(ParkingError::NoSpotAvailable)?;
+
+        let reservation = Reservation {
+            reservation_id: reservation_id.clone(),
+            vehicle,
+            floor_id,
+            spot_id,
+            from,
+            until,
+            status: ReservationStatus::Pending,
+            ticket_id: None,
+            series_id,
+        };
+        reservations.insert(reservation_id, reservation.clone());
+        drop(reservations);
+        self.emit(ParkingEvent::SpotReserved {
+            reservation_id: reservation.reservation_id.clone(),
+            license_plate: reservation.vehicle.license_plate.clone(),
+            spot_id: reservation.spot_id.clone(),
+            from,
+            until,
+        });
+        if hold_from <= now {
+            self.emit_capacity_events(floor_id, &mut Effects::immediate())?;
+        }
+        Ok(reservation)
+    }
+
+    pub fn get_reservation(&self, reservation_id: &str) -> Option<Reservation> {
+        self.reservations
+            .lock()
+            .unwrap()
+            .get(reservation_id)
+            .cloned()
+    }
+
+    /// Converts a pending reservation into a ticket for the held spot.
+    pub fn check_in_reservation(
+        &self,
+        reservation_id: &str,
+    ) -> Result<ParkingTicket, ParkingError> {
+        let now = self.now();
+        let mut reservations = self.reservations.lock()?;
+        let reservation = reservations
+            .get(reservation_id)
+            .ok_or(ParkingError::ReservationNotFound)?;
+        if reservation.status != ReservationStatus::Pending {
+            return Err(ParkingError::ReservationNotPending(reservation.status));
+        }
+        if self.active_evacuation().is_some() {
+            return Err(ParkingError::EvacuationInProgress);
+        }
+        if reservation.until <= now {
+            return Err(ParkingError::ReservationExpired);
+        }
+        if now < reservation.hold_from(self.reservation_grace) {
+            return Err(ParkingError::ReservationNotStarted);
+        }
+
+        self.hold_reservation_spot(&mut reservations, reservation_id)?;
+        let reservation = reservations.get_mut(reservation_id).unwrap();
+        self.with_floor_spot_mut(reservation.floor_id, &reservation.spot_id, |spot| {
+            spot.transition(TransitionCause::CheckedIn, now, |spot| {
+                spot.assign_vehicle(reservation.vehicle.clone())
+            })
+        })?
+        .ok_or(ParkingError::NoSpotAvailable)??;
+
+        let ticket = self.issue_ticket(
+            reservation.vehicle.clone(),
+            reservation.spot_id.clone(),
+            &mut Effects::immediate(),
+        )?;
+        reservation.status = ReservationStatus::Fulfilled;
+        reservation.ticket_id = Some(ticket.ticket_id.clone());
+        Ok(ticket)
+    }
+
+    pub fn cancel_reservation(&self, reservation_id: &str) -> Result<(), ParkingError> {
+        let mut reservations = self.reservations.lock()?;
+        let reservation = reservations
+            .get_mut(reservation_id)
+            .ok_or(ParkingError::ReservationNotFound)?;
+        if reservation.status != ReservationStatus::Pending {
+            return Err(ParkingError::ReservationNotPending(reservation.status));
+        }
+        self.release_reserved_spot(reservation, TransitionCause::ReservationCancelled)?;
+        reservation.status = ReservationStatus::Cancelled;
+        Ok(())
+    }
+
+    /// Releases the spots of pending reservations whose window ended before `now`.
+    /// Returns the expired reservation ids.
+    pub fn release_expired_reservations(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<String>, ParkingError> {
+        let mut reservations = self.reservations.lock()?;
+        let mut expired = Vec::new();
+        for reservation in reservations
+            .values_mut()
+            .filter(|r| r.status == ReservationStatus::Pending && r.until <= now)
+        {
+            self.release_reserved_spot(reservation, TransitionCause::ReservationExpired)?;
+            reservation.status = ReservationStatus::Expired;
+            expired.push(reservation.reservation_id.clone());
+        }
+        expired.sort();
+        Ok(expired)
+    }
+
+    fn release_reserved_spot(
+        &self,
+        reservation: &Reservation,
+        cause: TransitionCause,
+    ) -> Result<(), ParkingError> {
+        let now = self.now();
+        let reservation_id = reservation.reservation_id.as_str();
+        self.with_floor_spot_mut(reservation.floor_id, &reservation.spot_id, |spot| {
+            if spot.reserved_by() == Some(reservation_id) {
+                // Reserved spots can always be freed
+                let _ = spot.transition(cause, now, |spot| spot.set_status(SpotStatus::Free));
+            }
+        })?;
+        Ok(())
+    }
+
+    /// Holds the spots of pending reservations whose hold has started by `now`. Returns
+    /// the reservations newly holding a spot.
+    pub fn hold_due_reservations(&self, now: DateTime<Utc>) -> Vec<String> {
+        let mut reservations = self.reservations.lock().unwrap();
+        let mut due: Vec<String> = reservations
+            .values()
+            .filter(|r| {
+                r.status == ReservationStatus::Pending
+                    && r.hold_from(self.reservation_grace) <= now
+                    && now < r.until
+            })
+            .map(|r| r.reservation_id.clone())
+            .collect();
+        due.sort();
+        due.retain(|reservation_id| {
+            self.hold_reservation_spot(&mut reservations, reservation_id)
+                .is_ok_and(|newly_held| newly_held)
+        });
+        due
+    }
+
+    /// Marks the reservation's spot reserved, moving the reservation to another free spot
+    /// carrying the same tags if a vehicle is on its own. Returns whether the hold is new.
+    fn hold_reservation_spot(
+        &self,
+        reservations: &mut HashMap<String, Reservation>,
+        reservation_id: &str,
+    ) -> Result<bool, ParkingError> {
+        let now = self.now();
+        let reservation = &reservations[reservation_id];
+        let (floor_id, spot_id) = (reservation.floor_id, reservation.spot_id.clone());
+        let claim = |spot: &mut ParkingSpot| {
+            spot.transition(TransitionCause::Reserved, now, |spot| {
+                spot.set_status(SpotStatus::Reserved(reservation_id.to_string()))
+            })
+            .ok()
+        };
+        let held = self.with_floor_spot_mut(floor_id, &spot_id, |spot| {
+            if spot.reserved_by() == Some(reservation_id) {
+                return Some(false);
+            }
+            if !spot.is_available() {
+                return None;
+            }
+            claim(spot).map(|_| true)
+        })?;
+        if let Some(Some(newly_held)) = held {
+            return Ok(newly_held);
+        }
+
+        let tags: Vec<SpotTag> = self
+            .with_floor_spot_mut(floor_id, &spot_id, |spot| {
+                spot.tags().iter().cloned().collect()
+            })?
+            .unwrap_or_default();
+        let (from, until) = (reservation.from, reservation.until);
+        let hold_from = reservation.hold_from(self.reservation_grace);
+        let booked = booked_spots(
+            reservations,
+            self.reservation_grace,
+            hold_from,
+            until,
+            reservation_id,
+        );
+        let vehicle = reservation.vehicle.clone();
+        let (floor_id, spot_id, _) = {
+            let floors = self.floors.lock()?;
+            let closed_floors = self.closed_floors.lock()?;
+            self.allocate_spot_where(
+                &floors,
+                &vehicle,
+                &tags,
+                |id| !closed_floors.contains(&id) && !self.floor_under_maintenance(id, from, until),
+                |floor_id, spot_id| !booked.iter().any(|b| b.0 == floor_id && b.1 == spot_id),
+                claim,
+            )
+        }
+        .ok_or(ParkingError::NoSpotAvailable)?;
+        let reservation = reservations.get_mut(reservation_id).unwrap();
+        reservation.floor_id = floor_id;
+        reservation.spot_id = spot_id;
+        Ok(true)
+    }
+
+    /// Calendar holds for every pending reservation.
+    pub(crate) fn reservation_holds(&self) -> Result<Vec<SpotHold>, ParkingError> {
+        let pending: Vec<Reservation> = self
+            .reservations
+            .lock()?
+            .values()
+            .filter(|r| r.status == ReservationStatus::Pending)
+            .cloned()
+            .collect();
+        let mut holds = Vec::new();
+        for r in pending {
+            if let Some(spot_type) =
+                self.with_floor_spot_mut(r.floor_id, &r.spot_id, |spot| spot.spot_type)?
+            {
+                holds.push(SpotHold::new(spot_type, r.from, r.until));
+            }
+        }
+        Ok(holds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, VehicleType, clock::MockClock};
+
+    #[test]
+    fn test_reserved_spot_is_kept_from_walk_ins_until_check_in() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+
+        let now = Utc::now();
+        let reservation = lot
+            .reserve_spot(
+                Vehicle::new(VehicleType::Motor, "Kia".into(), "RES001".into()),
+                now,
+                now + Duration::hours(2),
+            )
+            .unwrap();
+        assert_eq!(lot.display_info().unwrap().num_reserved_spots(), 1);
+
+        for n in 0..9 {
+            lot.park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                format!("WALK{n}"),
+            ))
+            .unwrap();
+        }
+        assert!(
+            lot.park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "LATE".into()
+            ))
+            .is_err()
+        );
+
+        let ticket = lot
+            .check_in_reservation(&reservation.reservation_id)
+            .unwrap();
+        assert_eq!(ticket.spot_id, reservation.spot_id);
+        assert_eq!(lot.display_info().unwrap().num_reserved_spots(), 0);
+        assert_eq!(lot.display_info().unwrap().num_parked_vehicles(), 10);
+    }
+
+    #[test]
+    fn test_expired_reservation_releases_its_spot() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+
+        let now = Utc::now();
+        let reservation = lot
+            .reserve_spot(
+                Vehicle::new(VehicleType::Motor, "Kia".into(), "RES001".into()),
+                now,
+                now + Duration::minutes(30),
+            )
+            .unwrap();
+
+        let expired = lot
+            .release_expired_reservations(now + Duration::hours(1))
+            .unwrap();
+        assert_eq!(expired, vec![reservation.reservation_id.clone()]);
+        assert_eq!(lot.display_info().unwrap().num_reserved_spots(), 0);
+        assert!(matches!(
+            lot.check_in_reservation(&reservation.reservation_id),
+            Err(ParkingError::ReservationNotPending(
+                ReservationStatus::Expired
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_spot_is_held_on_its_own_floor_for_the_window_only() {
+        let clock = MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()));
+        // Both floors have a spot_3 and nothing else
+        for floor_id in [1, 2] {
+            lot.add_floor(ParkingFloor::new(floor_id)).unwrap();
+            let floor = lot.get_floor_by_id(floor_id).unwrap().unwrap();
+            floor
+                .spots
+                .lock()
+                .unwrap()
+                .retain(|spot_id, _| spot_id == "spot_3");
+        }
+        let car = |plate: &str| Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into());
+
+        let now = lot.now();
+        let soon = lot
+            .reserve_spot(car("RES001"), now, now + Duration::hours(1))
+            .unwrap();
+        let later = lot
+            .reserve_spot(
+                car("RES002"),
+                now + Duration::hours(2),
+                now + Duration::hours(3),
+            )
+            .unwrap();
+        assert_ne!(soon.floor_id, later.floor_id);
+        // Only the reservation that has started holds its spot
+        assert_eq!(lot.display_info().unwrap().num_reserved_spots(), 1);
+        assert_eq!(
+            lot.check_in_reservation(&later.reservation_id).unwrap_err(),
+            ParkingError::ReservationNotStarted
+        );
+
+        let ticket = lot.check_in_reservation(&soon.reservation_id).unwrap();
+        assert_eq!(
+            lot.locate_vehicle("RES001").unwrap().floor_id,
+            soon.floor_id
+        );
+        // A walk-in takes the spot booked for later, then the first car leaves
+        lot.park_vehicle(car("WALK01")).unwrap();
+        assert_eq!(
+            lot.locate_vehicle("WALK01").unwrap().floor_id,
+            later.floor_id
+        );
+        lot.unpark_vehicle(ticket.ticket_id).unwrap();
+
+        clock.advance(Duration::minutes(105));
+        assert_eq!(
+            lot.hold_due_reservations(lot.now()),
+            vec![later.reservation_id.clone()]
+        );
+        let moved = lot.get_reservation(&later.reservation_id).unwrap();
+        assert_eq!(moved.floor_id, soon.floor_id);
+        assert!(lot.park_vehicle(car("WALK02")).is_err());
+        lot.check_in_reservation(&later.reservation_id).unwrap();
+        assert_eq!(
+            lot.locate_vehicle("RES002").unwrap().floor_id,
+            soon.floor_id
+        );
+    }
+}
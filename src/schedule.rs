@@ -0,0 +1,175 @@
+//! Operating hours and scheduled closures. Entries are refused while the lot (or a floor)
+//! is closed; exits are always allowed. Times of day are in UTC.
+
+use chrono::{DateTime, Days, NaiveTime, Utc};
+
+/// A daily period during which the lot is closed, e.g. 02:00-05:00 for cleaning.
+/// `end` earlier than `start` wraps past midnight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DailyClosure {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl DailyClosure {
+    pub fn new(start: NaiveTime, end: NaiveTime) -> Self {
+        Self { start, end }
+    }
+
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClosureScope {
+    Lot,
+    Floor(u32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledClosure {
+    pub scope: ClosureScope,
+    pub from: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub reason: String,
+}
+
+impl ScheduledClosure {
+    pub fn new(
+        scope: ClosureScope,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+        reason: String,
+    ) -> Self {
+        Self {
+            scope,
+            from,
+            until,
+            reason,
+        }
+    }
+
+    fn is_active(&self, at: DateTime<Utc>) -> bool {
+        self.from <= at && at < self.until
+    }
+}
+
+/// A concrete closure interval, as shown in reports.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClosurePeriod {
+    pub scope: ClosureScope,
+    pub from: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OperatingSchedule {
+    daily_closures: Vec<DailyClosure>,
+    closures: Vec<ScheduledClosure>,
+}
+
+impl OperatingSchedule {
+    pub fn add_daily_closure(&mut self, closure: DailyClosure) {
+        self.daily_closures.push(closure);
+    }
+
+    pub fn schedule_closure(&mut self, closure: ScheduledClosure) {
+        self.closures.push(closure);
+    }
+
+    /// Drops one-off closures that ended before `now`.
+    pub fn prune(&mut self, now: DateTime<Utc>) {
+        self.closures.retain(|c| c.until > now);
+    }
+
+    pub fn is_lot_open(&self, at: DateTime<Utc>) -> bool {
+        let time = at.time();
+        !self.daily_closures.iter().any(|d| d.contains(time))
+            && !self
+                .closures
+                .iter()
+                .any(|c| c.scope == ClosureScope::Lot && c.is_active(at))
+    }
+
+    pub fn is_floor_open(&self, floor_id: u32, at: DateTime<Utc>) -> bool {
+        !self
+            .closures
+            .iter()
+            .any(|c| c.scope == ClosureScope::Floor(floor_id) && c.is_active(at))
+    }
+
+    /// Every closure overlapping `from..until`, with daily closures expanded into dated
+    /// periods, ordered by start time.
+    pub fn closure_periods(&self, from: DateTime<Utc>, until: DateTime<Utc>) -> Vec<ClosurePeriod> {
+        let mut periods: Vec<ClosurePeriod> = self
+            .closures
+            .iter()
+            .filter(|c| c.from < until && from < c.until)
+            .map(|c| ClosurePeriod {
+                scope: c.scope,
+                from: c.from,
+                until: c.until,
+                reason: c.reason.clone(),
+            })
+            .collect();
+
+        // Start a day early so a window wrapping past midnight into `from` is included
+        let mut day = from.date_naive() - Days::new(1);
+        while day <= until.date_naive() {
+            for daily in &self.daily_closures {
+                let start = day.and_time(daily.start).and_utc();
+                let end_day = if daily.end >= daily.start {
+                    day
+                } else {
+                    day + Days::new(1)
+                };
+                let end = end_day.and_time(daily.end).and_utc();
+                if start < end && start < until && from < end {
+                    periods.push(ClosurePeriod {
+                        scope: ClosureScope::Lot,
+                        from: start,
+                        until: end,
+                        reason: "Daily closure".to_string(),
+                    });
+                }
+            }
+            day = day + Days::new(1);
+        }
+
+        periods.sort_by_key(|p| p.from);
+        periods
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_overnight_daily_closure_wraps_midnight() {
+        let mut schedule = OperatingSchedule::default();
+        schedule.add_daily_closure(DailyClosure::new(
+            NaiveTime::from_hms_opt(23, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
+        ));
+
+        let at = |h| Utc.with_ymd_and_hms(2024, 3, 1, h, 30, 0).unwrap();
+        assert!(!schedule.is_lot_open(at(23)));
+        assert!(!schedule.is_lot_open(at(2)));
+        assert!(schedule.is_lot_open(at(12)));
+
+        let periods = schedule.closure_periods(at(0), at(12));
+        assert_eq!(periods.len(), 1);
+        assert_eq!(
+            periods[0].until,
+            Utc.with_ymd_and_hms(2024, 3, 1, 5, 0, 0).unwrap()
+        );
+    }
+}
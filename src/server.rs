@@ -0,0 +1,615 @@
+//! HTTP front end for a lot (feature `server`), so the crate can run as a service:
+//!
+//! - `POST /park` with a vehicle
+//! - `POST /unpark/{ticket}`
+//! - `GET /status`
+//! - `GET /floors/{id}`
+//! - `POST /reservations` with a vehicle plus `from` and `until` in RFC 3339
+//! - `POST /payments` with `ticket_id`, `method` and, for cards and prepaid accounts,
+//!   `reference`
+//!
+//! A vehicle is `license_plate` and `vehicle_type`, e.g. `"Motor"`, with optional `model`
+//! and `handicapped_permit`. Requests and responses are JSON in the shapes used by saved
+//! state; failures answer `{"error": "..."}`.
+//!
+//! Routes are served by axum over HTTP/1.1, one request per connection. Headers must
+//! arrive within `HEADER_TIMEOUT` and fit in `MAX_HEADER_BYTES`, bodies are capped at
+//! `MAX_BODY_BYTES`, and a connection is dropped if its request isn't read and answered
+//! within `REQUEST_TIMEOUT`. At most `MAX_CONNECTIONS` are served at once; further ones
+//! wait to be accepted.
+//!
+//! Tickets in paths and payment bodies are the printed ticket codes, checked as at an exit
+//! panel; see `ticket_signing`.
+//!
+//! `serve_until_stopped` runs until its `StopSignal` fires, then stops new entries, lets
+//! requests being served finish within a timeout and shuts the lot down as by
+//! `ParkingLot::shutdown`.
+
+use std::{io, net::TcpListener, path::Path, sync::Arc, time::Duration};
+
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path as UrlPath, State, rejection::BytesRejection},
+    http::{Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use chrono::{DateTime, Utc};
+use hyper::server::conn::http1;
+use hyper_util::{
+    rt::{TokioIo, TokioTimer},
+    service::TowerToHyperService,
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value, json};
+use tokio::sync::{Semaphore, watch};
+
+use crate::{
+    Parkable, ParkingCharge, ParkingLot, Vehicle, VehicleType, error::ParkingError,
+    payment::PaymentMethod, shutdown::ShutdownReport,
+};
+
+/// Largest request body accepted.
+pub const MAX_BODY_BYTES: usize = 64 * 1024;
+/// Largest request line and headers accepted; larger ones are answered with 431.
+pub const MAX_HEADER_BYTES: usize = 16 * 1024;
+/// How long a client has to send its headers.
+pub const HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a connection may take from accept to the end of its response.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Connections served at once.
+pub const MAX_CONNECTIONS: usize = 256;
+
+/// A failed request, answered as `{"error": "..."}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+impl From<ParkingError> for ApiError {
+    fn from(error: ParkingError) -> Self {
+        let status = match error {
+            ParkingError::InvalidTicket | ParkingError::SpotNotFound => StatusCode::NOT_FOUND,
+            ParkingError::ForgedTicket => StatusCode::FORBIDDEN,
+            ParkingError::NoSpotAvailable
+            | ParkingError::LotFull
+            | ParkingError::PlateAlreadyParked(_)
+            | ParkingError::AlreadyPaid
+            | ParkingError::PaymentInProgress
+            | ParkingError::SpotUnderMaintenance
+            | ParkingError::TicketClosed => StatusCode::CONFLICT,
+            ParkingError::ShuttingDown
+            | ParkingError::LotClosed
+            | ParkingError::LotStopped
+            | ParkingError::NoPaymentProcessor => StatusCode::SERVICE_UNAVAILABLE,
+            ParkingError::LockPoisoned => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::UNPROCESSABLE_ENTITY,
+        };
+        Self::new(status, error.to_string())
+    }
+}
+
+impl From<BytesRejection> for ApiError {
+    fn from(rejection: BytesRejection) -> Self {
+        Self::new(rejection.status(), rejection.body_text())
+    }
+}
+
+pub struct ApiServer {
+    lot: Arc<ParkingLot>,
+    /// One permit per connection being served.
+    connections: Arc<Semaphore>,
+}
+
+/// Tells a server started with `ApiServer::serve_until_stopped` to stop. Clones share the
+/// signal.
+#[derive(Debug, Clone)]
+pub struct StopSignal(Arc<watch::Sender<bool>>);
+
+impl Default for StopSignal {
+    fn default() -> Self {
+        Self(Arc::new(watch::Sender::new(false)))
+    }
+}
+
+impl StopSignal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stop(&self) {
+        self.0.send_replace(true);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    async fn stopped(&self) {
+        let _ = self.0.subscribe().wait_for(|stopped| *stopped).await;
+    }
+}
+
+impl ApiServer {
+    pub fn new(lot: Arc<ParkingLot>) -> Self {
+        Self {
+            lot,
+            connections: Arc::new(Semaphore::new(MAX_CONNECTIONS)),
+        }
+    }
+
+    /// The routes, for mounting in a larger axum application. Connection limits and
+    /// timeouts are applied by `serve`, not here.
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/park", post(park))
+            .route("/unpark/{ticket}", post(unpark))
+            .route("/status", get(status))
+            .route("/floors/{id}", get(floor))
+            .route("/reservations", post(reserve))
+            .route("/payments", post(pay))
+            .fallback(no_route)
+            .method_not_allowed_fallback(not_allowed)
+            .layer(DefaultBodyLimit::max(MAX_BODY_BYTES))
+            .with_state(self.lot.clone())
+    }
+
+    /// Serves connections from `listener` until accepting one fails.
+    pub fn serve(self, listener: TcpListener) -> io::Result<()> {
+        listener.set_nonblocking(true)?;
+        runtime()?.block_on(async {
+            let listener = tokio::net::TcpListener::from_std(listener)?;
+            self.accept_until(listener, &StopSignal::new()).await
+        })
+    }
+
+    /// Serves connections from `listener` until `stop` fires. The lot then refuses new
+    /// entries, requests already being served get `timeout` to finish, and the lot is
+    /// shut down and saved to `snapshot_path` if given. Requests still running at the
+    /// timeout count as abandoned in the report.
+    pub fn serve_until_stopped(
+        self,
+        listener: TcpListener,
+        stop: &StopSignal,
+        timeout: Duration,
+        snapshot_path: Option<&Path>,
+    ) -> io::Result<ShutdownReport> {
+        listener.set_nonblocking(true)?;
+        let runtime = runtime()?;
+        let abandoned = runtime.block_on(async {
+            let listener = tokio::net::TcpListener::from_std(listener)?;
+            self.accept_until(listener, stop).await?;
+            self.lot.begin_shutdown();
+            let all = MAX_CONNECTIONS as u32;
+            let _ = tokio::time::timeout(timeout, self.connections.acquire_many(all)).await;
+            io::Result::Ok(MAX_CONNECTIONS - self.connections.available_permits())
+        })?;
+        // Requests past the timeout are left to finish on their own
+        runtime.shutdown_background();
+        let report = self.lot.shutdown(snapshot_path).map_err(io::Error::other)?;
+        Ok(ShutdownReport {
+            abandoned,
+            ..report
+        })
+    }
+
+    /// Accepts connections until `stop` fires, serving each on a task of its own.
+    async fn accept_until(
+        &self,
+        listener: tokio::net::TcpListener,
+        stop: &StopSignal,
+    ) -> io::Result<()> {
+        let router = self.router();
+        loop {
+            let permit = tokio::select! {
+                permit = self.connections.clone().acquire_owned() => permit,
+                () = stop.stopped() => return Ok(()),
+            };
+            let Ok(permit) = permit else {
+                return Ok(());
+            };
+            let stream = tokio::select! {
+                accepted = listener.accept() => accepted?.0,
+                () = stop.stopped() => return Ok(()),
+            };
+            let service = TowerToHyperService::new(router.clone());
+            tokio::spawn(async move {
+                let connection = http1::Builder::new()
+                    .timer(TokioTimer::new())
+                    .header_read_timeout(HEADER_TIMEOUT)
+                    .max_buf_size(MAX_HEADER_BYTES)
+                    .keep_alive(false)
+                    .serve_connection(TokioIo::new(stream), service);
+                let _ = tokio::time::timeout(REQUEST_TIMEOUT, connection).await;
+                drop(permit);
+            });
+        }
+    }
+}
+
+fn runtime() -> io::Result<tokio::runtime::Runtime> {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+}
+
+/// Runs `call` on a blocking thread, as the lot's methods take locks and may wait on
+/// payment processors.
+async fn with_lot<T: Send + 'static>(
+    lot: Arc<ParkingLot>,
+    call: impl FnOnce(&ParkingLot) -> Result<T, ApiError> + Send + 'static,
+) -> Result<T, ApiError> {
+    tokio::task::spawn_blocking(move || call(&lot))
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+}
+
+async fn park(
+    State(lot): State<Arc<ParkingLot>>,
+    body: Result<Bytes, BytesRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let vehicle = parse_body::<VehicleBody>(&body?)?.into_vehicle()?;
+    let ticket = with_lot(lot, move |lot| Ok(lot.park_vehicle(vehicle)?)).await?;
+    Ok((StatusCode::CREATED, Json(to_value(&ticket)?)))
+}
+
+async fn unpark(
+    State(lot): State<Arc<ParkingLot>>,
+    UrlPath(code): UrlPath<String>,
+) -> Result<Json<Value>, ApiError> {
+    let charge = with_lot(lot, move |lot| {
+        let ticket_id = lot.verify_ticket_code(&code)?;
+        Ok(lot.unpark_vehicle(ticket_id)?)
+    })
+    .await?;
+    Ok(Json(charge_to_json(&charge)))
+}
+
+async fn status(State(lot): State<Arc<ParkingLot>>) -> Result<Json<Value>, ApiError> {
+    let board = with_lot(lot, |lot| Ok(lot.display_info()?)).await?;
+    let available: Map<String, Value> = VehicleType::ALL
+        .iter()
+        .map(|vehicle_type| {
+            let count = board.num_available_for(vehicle_type);
+            (format!("{vehicle_type:?}"), count.into())
+        })
+        .collect();
+    Ok(Json(json!({
+        "uid": board.uid(),
+        "floors": board.num_floors(),
+        "closed_floors": board.num_closed_floors(),
+        "parked_vehicles": board.num_parked_vehicles(),
+        "empty_spots": board.num_empty_spots(),
+        "reserved_spots": board.num_reserved_spots(),
+        "out_of_service_spots": board.num_out_of_service_spots(),
+        "available_by_vehicle_type": available,
+    })))
+}
+
+async fn floor(
+    State(lot): State<Arc<ParkingLot>>,
+    UrlPath(floor_id): UrlPath<String>,
+) -> Result<Json<Value>, ApiError> {
+    let floor = match floor_id.parse() {
+        Ok(id) => with_lot(lot, move |lot| Ok(lot.get_floor_by_id(id)?)).await?,
+        Err(_) => None,
+    };
+    let floor = floor.ok_or_else(|| {
+        ApiError::new(StatusCode::NOT_FOUND, format!("Floor {floor_id} not found"))
+    })?;
+    Ok(Json(to_value(&floor)?))
+}
+
+async fn reserve(
+    State(lot): State<Arc<ParkingLot>>,
+    body: Result<Bytes, BytesRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let body: ReservationBody = parse_body(&body?)?;
+    let vehicle = body.vehicle.into_vehicle()?;
+    let reservation = with_lot(lot, move |lot| {
+        Ok(lot.reserve_spot(vehicle, body.from, body.until)?)
+    })
+    .await?;
+    Ok((StatusCode::CREATED, Json(to_value(&reservation)?)))
+}
+
+async fn pay(
+    State(lot): State<Arc<ParkingLot>>,
+    body: Result<Bytes, BytesRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let body: PaymentBody = parse_body(&body?)?;
+    let reference = || {
+        body.reference
+            .clone()
+            .ok_or_else(|| bad_request("Missing field 'reference'"))
+    };
+    let method = match body.method.as_str() {
+        "Cash" => PaymentMethod::Cash,
+        "Card" => PaymentMethod::Card(reference()?),
+        "Prepaid" => PaymentMethod::Prepaid(reference()?),
+        other => return Err(bad_request(format!("Unknown payment method '{other}'"))),
+    };
+    let payment = with_lot(lot, move |lot| {
+        let ticket_id = lot.verify_ticket_code(&body.ticket_id)?;
+        Ok(lot.pay_ticket(&ticket_id, method)?)
+    })
+    .await?;
+    Ok((StatusCode::CREATED, Json(to_value(&payment)?)))
+}
+
+async fn no_route(uri: Uri) -> ApiError {
+    ApiError::new(
+        StatusCode::NOT_FOUND,
+        format!("No route for {}", uri.path()),
+    )
+}
+
+async fn not_allowed(method: Method) -> ApiError {
+    ApiError::new(
+        StatusCode::METHOD_NOT_ALLOWED,
+        format!("{method} not allowed"),
+    )
+}
+
+/// A vehicle in a request body.
+#[derive(Deserialize)]
+struct VehicleBody {
+    vehicle_type: VehicleType,
+    license_plate: String,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    handicapped_permit: Option<bool>,
+}
+
+impl VehicleBody {
+    fn into_vehicle(self) -> Result<Vehicle, ApiError> {
+        let vehicle = Vehicle::try_new(
+            self.vehicle_type,
+            self.model.unwrap_or_default(),
+            &self.license_plate,
+        )?;
+        Ok(if self.handicapped_permit.unwrap_or(false) {
+            vehicle.with_handicapped_permit()
+        } else {
+            vehicle
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct ReservationBody {
+    #[serde(flatten)]
+    vehicle: VehicleBody,
+    from: DateTime<Utc>,
+    until: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct PaymentBody {
+    ticket_id: String,
+    method: String,
+    #[serde(default)]
+    reference: Option<String>,
+}
+
+fn bad_request(message: impl Into<String>) -> ApiError {
+    ApiError::new(StatusCode::BAD_REQUEST, message)
+}
+
+/// Bodies are read as JSON whatever their `Content-Type`.
+fn parse_body<T: DeserializeOwned>(body: &[u8]) -> Result<T, ApiError> {
+    serde_json::from_slice(body).map_err(|e| bad_request(format!("Invalid JSON: {e}")))
+}
+
+fn to_value(value: &impl Serialize) -> Result<Value, ApiError> {
+    serde_json::to_value(value)
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+fn charge_to_json(charge: &ParkingCharge) -> Value {
+    let breakdown: Vec<Value> = charge
+        .breakdown
+        .iter()
+        .map(|line| json!({ "description": line.description, "amount": line.amount }))
+        .collect();
+    json!({
+        "ticket_id": charge.ticket_id,
+        "entry_time": charge.entry_time,
+        "billed_until": charge.billed_until,
+        "total": charge.total,
+        "discount": charge.discount,
+        "fine": charge.fine,
+        "breakdown": breakdown,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ParkingFloor,
+        payment::{CashProcessor, PaymentMethodKind},
+    };
+    use axum::{body::Body, http::Request};
+    use chrono::Duration;
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+        thread,
+    };
+    use tower::ServiceExt;
+
+    async fn call(router: &Router, method: &str, path: &str, body: &str) -> (u16, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(path)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status().as_u16();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_routes_drive_the_lot() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let router = ApiServer::new(Arc::new(lot)).router();
+
+        let car = r#"{"vehicle_type": "Motor", "license_plate": "API 001"}"#;
+        let (status, parked) = call(&router, "POST", "/park", car).await;
+        assert_eq!(status, 201);
+        let ticket_id = parked["ticket_id"].as_str().unwrap();
+        assert_eq!(call(&router, "POST", "/park", car).await.0, 409);
+        assert_eq!(call(&router, "POST", "/park", "{").await.0, 400);
+
+        let (_, status) = call(&router, "GET", "/status", "").await;
+        assert_eq!(status["parked_vehicles"], json!(1));
+        let (_, floor) = call(&router, "GET", "/floors/1?detail=full", "").await;
+        assert_eq!(floor["spots"].as_array().unwrap().len(), 10);
+        assert_eq!(call(&router, "GET", "/floors/9", "").await.0, 404);
+        let (status, error) = call(&router, "DELETE", "/status", "").await;
+        assert_eq!(
+            (status, error["error"].as_str()),
+            (405, Some("DELETE not allowed"))
+        );
+
+        let from = Utc::now() + Duration::hours(1);
+        let reservation = format!(
+            r#"{{"vehicle_type": "Bike", "license_plate": "API 002",
+                "from": "{}", "until": "{}"}}"#,
+            from.to_rfc3339(),
+            (from + Duration::hours(2)).to_rfc3339()
+        );
+        assert_eq!(
+            call(&router, "POST", "/reservations", &reservation).await.0,
+            201
+        );
+
+        let payment = format!(r#"{{"ticket_id": "{ticket_id}", "method": "Card"}}"#);
+        assert_eq!(call(&router, "POST", "/payments", &payment).await.0, 400);
+        let (status, charge) = call(&router, "POST", &format!("/unpark/{ticket_id}"), "").await;
+        assert_eq!(status, 200);
+        assert!(charge.get("total").is_some());
+        let again = call(&router, "POST", &format!("/unpark/{ticket_id}"), "").await;
+        assert_eq!(again.0, 409);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_and_deeply_nested_bodies_are_refused() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let router = ApiServer::new(Arc::new(lot)).router();
+
+        let nested = "[".repeat(MAX_BODY_BYTES - 1);
+        assert_eq!(call(&router, "POST", "/park", &nested).await.0, 400);
+        let oversized = " ".repeat(MAX_BODY_BYTES + 1);
+        assert_eq!(call(&router, "POST", "/park", &oversized).await.0, 413);
+    }
+
+    #[tokio::test]
+    async fn test_signed_lots_refuse_forged_ticket_codes() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_ticket_signing_key("s3cret");
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_payment_processor(PaymentMethodKind::Cash, Box::new(CashProcessor));
+        let lot = Arc::new(lot);
+        let router = ApiServer::new(lot.clone()).router();
+
+        let car = r#"{"vehicle_type": "Motor", "license_plate": "API 004"}"#;
+        let (_, parked) = call(&router, "POST", "/park", car).await;
+        let ticket_id = parked["ticket_id"].as_str().unwrap();
+        let code = lot.ticket_code(&lot.active_ticket(ticket_id).unwrap());
+
+        let pay = |ticket: String| {
+            let body = format!(r#"{{"ticket_id": "{ticket}", "method": "Cash"}}"#);
+            let router = router.clone();
+            async move { call(&router, "POST", "/payments", &body).await.0 }
+        };
+        // A bare ticket id is as good as forged on a lot that signs its tickets
+        assert_eq!(pay(ticket_id.to_string()).await, 403);
+        let unpark = format!("/unpark/{ticket_id}");
+        assert_eq!(call(&router, "POST", &unpark, "").await.0, 403);
+        assert_eq!(pay(code.clone()).await, 201);
+        // Codes in paths may arrive percent-encoded
+        let encoded: String = code.bytes().map(|b| format!("%{b:02X}")).collect();
+        let unpark = format!("/unpark/{encoded}");
+        assert_eq!(call(&router, "POST", &unpark, "").await.0, 200);
+    }
+
+    fn send(addr: std::net::SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        response
+    }
+
+    #[test]
+    fn test_stopping_the_server_drains_and_saves_the_lot() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let lot = Arc::new(lot);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let path = std::env::temp_dir().join(format!("server-stop-{}.json", std::process::id()));
+        let stop = StopSignal::new();
+
+        let serving = {
+            let (lot, stop, path) = (lot.clone(), stop.clone(), path.clone());
+            thread::spawn(move || {
+                ApiServer::new(lot).serve_until_stopped(
+                    listener,
+                    &stop,
+                    std::time::Duration::from_secs(5),
+                    Some(&path),
+                )
+            })
+        };
+        let car = r#"{"vehicle_type": "Motor", "license_plate": "API 003"}"#;
+        let response = send(
+            addr,
+            &format!(
+                "POST /park HTTP/1.1\r\nHost: lot\r\nContent-Length: {}\r\n\r\n{car}",
+                car.len()
+            ),
+        );
+        assert!(response.starts_with("HTTP/1.1 201"));
+        let padding = "x".repeat(MAX_HEADER_BYTES);
+        let response = send(
+            addr,
+            &format!("GET /status HTTP/1.1\r\nHost: lot\r\nX-Padding: {padding}\r\n\r\n"),
+        );
+        assert!(response.starts_with("HTTP/1.1 431"));
+
+        stop.stop();
+        let report = serving.join().unwrap().unwrap();
+        assert!(lot.is_shutting_down());
+        assert_eq!((report.open_tickets, report.abandoned), (1, 0));
+        assert_eq!(report.saved_to.as_deref(), Some(path.as_path()));
+        let restored = ParkingLot::load_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(restored.display_info().unwrap().num_parked_vehicles(), 1);
+    }
+}
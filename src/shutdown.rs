@@ -0,0 +1,200 @@
+//! Graceful shutdown. Once a lot starts draining it refuses new vehicles and
+//! reservations, while exits and payments go on. `shutdown` then flushes queued webhooks,
+//! saves the lot and takes a last occupancy sample, so a stop doesn't have to be a kill.
+//!
+//! A lot run by a `LotHandle` is shut down through the handle, which also lets calls
+//! already queued finish within a timeout.
+//!
+//! `shutdown` waits up to `PAYMENT_DRAIN_TIMEOUT` for payments and exit settlements
+//! already in flight before saving. Draining isn't saved: a lot loaded from its final
+//! snapshot takes vehicles again, so a restart needs no `resume_entries`.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{ParkingLot, analytics::OccupancySample, error::ParkingError};
+
+/// How long `shutdown` waits for payments in flight to settle.
+pub const PAYMENT_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShutdownReport {
+    pub at: DateTime<Utc>,
+    /// Vehicles still parked; their tickets are in the saved snapshot.
+    pub open_tickets: usize,
+    /// Webhook deliveries that failed their last attempt and were still due a retry.
+    pub pending_webhooks: usize,
+    pub final_sample: OccupancySample,
+    pub saved_to: Option<PathBuf>,
+    /// Calls queued on a `LotHandle`, or requests being served by an `ApiServer`, that
+    /// missed the drain timeout.
+    pub abandoned: usize,
+    /// Payments still being charged when `PAYMENT_DRAIN_TIMEOUT` ran out.
+    pub payments_in_flight: usize,
+}
+
+impl ParkingLot {
+    /// Stops taking new vehicles and reservations. Exits, payments and everything else
+    /// keep working.
+    pub fn begin_shutdown(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    /// Takes new vehicles and reservations again after `begin_shutdown`.
+    pub fn resume_entries(&self) {
+        self.draining.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Drains the lot: stops entries, waits for payments in flight, delivers every queued
+    /// webhook without waiting out retry backoff, saves the lot to `snapshot_path` if given
+    /// and records a final occupancy sample.
+    pub fn shutdown(&self, snapshot_path: Option<&Path>) -> Result<ShutdownReport, ParkingError> {
+        self.begin_shutdown();
+        let payments_in_flight = self.wait_for_payments(PAYMENT_DRAIN_TIMEOUT)?;
+        let at = self.now();
+        self.webhooks.flush(at);
+        if let Some(path) = snapshot_path {
+            self.save_to_file(path)?;
+        }
+        Ok(ShutdownReport {
+            at,
+            open_tickets: self.active_tickets.lock()?.len(),
+            pending_webhooks: self.webhooks.pending(),
+            final_sample: self.snapshot(),
+            saved_to: snapshot_path.map(Path::to_path_buf),
+            abandoned: 0,
+            payments_in_flight,
+        })
+    }
+
+    /// Waits until no payment is being charged or `timeout` runs out, and returns how
+    /// many are still in flight.
+    fn wait_for_payments(&self, timeout: Duration) -> Result<usize, ParkingError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let in_flight = self.paying.lock()?.len();
+            if in_flight == 0 || Instant::now() >= deadline {
+                return Ok(in_flight);
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    pub(crate) fn check_not_draining(&self) -> Result<(), ParkingError> {
+        if self.is_shutting_down() {
+            Err(ParkingError::ShuttingDown)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Parkable, ParkingFloor, Vehicle, VehicleType,
+        events::EventKind,
+        webhook::{WebhookEndpoint, WebhookTransport},
+    };
+    use chrono::Duration;
+    use std::sync::{Arc, atomic::AtomicU32};
+
+    /// Fails the first delivery, then accepts everything.
+    struct FlakyTransport(Arc<AtomicU32>);
+
+    impl WebhookTransport for FlakyTransport {
+        fn deliver(
+            &self,
+            _url: &str,
+            _timestamp: i64,
+            _signature: &str,
+            _payload: &str,
+        ) -> Result<(), String> {
+            match self.0.fetch_add(1, Ordering::SeqCst) {
+                0 => Err("timeout".into()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_shutdown_refuses_entries_flushes_webhooks_and_saves() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let attempts = Arc::new(AtomicU32::new(0));
+        lot.webhooks_mut()
+            .set_transport(Box::new(FlakyTransport(attempts.clone())));
+        lot.webhooks_mut().register(WebhookEndpoint::new(
+            "ops".into(),
+            "https://ops.example/hook".into(),
+            "secret".into(),
+            vec![EventKind::VehicleParked],
+        ));
+
+        let car = |plate: &str| Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into());
+        let first = lot.park_vehicle(car("OFF001")).unwrap();
+        lot.park_vehicle(car("OFF002")).unwrap();
+        assert_eq!(lot.deliver_webhooks(), 1);
+        // The first delivery failed and is backing off
+        assert_eq!(lot.webhooks().pending(), 1);
+
+        lot.begin_shutdown();
+        assert_eq!(
+            lot.park_vehicle(car("OFF003")).unwrap_err(),
+            ParkingError::ShuttingDown
+        );
+        let now = lot.now();
+        assert_eq!(
+            lot.reserve_spot(car("OFF004"), now, now + Duration::hours(1))
+                .unwrap_err(),
+            ParkingError::ShuttingDown
+        );
+        lot.unpark_vehicle(first.ticket_id).unwrap();
+
+        let path = std::env::temp_dir().join(format!("shutdown-{}.json", std::process::id()));
+        let report = lot.shutdown(Some(&path)).unwrap();
+        assert_eq!(report.open_tickets, 1);
+        assert_eq!(report.pending_webhooks, 0);
+        assert_eq!(report.final_sample.occupied, 1);
+        assert_eq!(report.saved_to.as_deref(), Some(path.as_path()));
+
+        let restored = ParkingLot::load_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        // A restart from the final snapshot takes vehicles again
+        assert!(!restored.is_shutting_down());
+        restored.park_vehicle(car("OFF003")).unwrap();
+    }
+
+    #[test]
+    fn test_shutdown_waits_for_payments_in_flight() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let lot = Arc::new(lot);
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "OFF005".into());
+        let ticket = lot.park_vehicle(car).unwrap();
+        lot.paying.lock().unwrap().insert(ticket.ticket_id.clone());
+
+        let settling = {
+            let lot = lot.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                lot.paying.lock().unwrap().remove(&ticket.ticket_id);
+            })
+        };
+        let report = lot.shutdown(None).unwrap();
+        settling.join().unwrap();
+        assert_eq!(report.payments_in_flight, 0);
+        assert!(lot.paying.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,76 @@
+//! HMAC-SHA256 signing used for outbound webhook payloads and printed ticket codes.
+//!
+//! Receivers verify a delivery by recomputing `hmac_sha256_hex(secret, body)` and comparing
+//! it with the signature sent alongside the payload.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn mac(key: &[u8], message: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac
+}
+
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    mac(key, message).finalize().into_bytes().into()
+}
+
+pub fn hmac_sha256_hex(key: &str, message: &str) -> String {
+    hmac_sha256(key.as_bytes(), message.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Whether `signature` is the hex `hmac_sha256_hex(key, message)`, compared in constant time.
+pub fn verify_hmac_sha256_hex(key: &str, message: &str, signature: &str) -> bool {
+    let Some(signature) = decode_hex(signature) else {
+        return false;
+    };
+    mac(key.as_bytes(), message.as_bytes())
+        .verify_slice(&signature)
+        .is_ok()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_matches_rfc_4231_vector() {
+        // RFC 4231, test case 2
+        let expected = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+        assert_eq!(
+            hmac_sha256_hex("Jefe", "what do ya want for nothing?"),
+            expected
+        );
+        assert!(verify_hmac_sha256_hex(
+            "Jefe",
+            "what do ya want for nothing?",
+            expected
+        ));
+        assert!(!verify_hmac_sha256_hex(
+            "Jefe",
+            "what do ya want for nothing!",
+            expected
+        ));
+        assert!(!verify_hmac_sha256_hex(
+            "Jefe",
+            "what do ya want for nothing?",
+            "+b"
+        ));
+    }
+}
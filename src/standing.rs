@@ -0,0 +1,349 @@
+//! Standing reservations: recurring bookings such as "a spot near the elevator, every
+//! weekday 08:00-18:00". A series holds nothing by itself. `book_standing_reservations`
+//! turns its upcoming occurrences into ordinary reservations a little ahead of time, so a
+//! spot is only held from then on. An occurrence that overlaps a one-off reservation for
+//! the same vehicle is skipped and recorded as a conflict; the one-off booking wins.
+
+use std::sync::atomic::Ordering;
+
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ParkingLot, RESERVATION_COUNTER, Vehicle,
+    error::ParkingError,
+    reservation::{Reservation, ReservationStatus},
+    tags::SpotTag,
+};
+
+/// Days of the week and a daily window, in UTC. The window can't wrap past midnight.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecurrenceRule {
+    pub days: Vec<Weekday>,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl RecurrenceRule {
+    pub fn new(days: Vec<Weekday>, start: NaiveTime, end: NaiveTime) -> Self {
+        Self { days, start, end }
+    }
+
+    /// Monday to Friday.
+    pub fn weekdays(start: NaiveTime, end: NaiveTime) -> Self {
+        Self::new(
+            vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+            start,
+            end,
+        )
+    }
+
+    pub(crate) fn is_valid(&self) -> bool {
+        !self.days.is_empty() && self.start < self.end
+    }
+
+    /// Occurrence windows that overlap `from..until`, earliest first.
+    pub fn occurrences(
+        &self,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+        if !self.is_valid() {
+            return Vec::new();
+        }
+        from.date_naive()
+            .iter_days()
+            .take_while(|day| *day <= until.date_naive())
+            .filter(|day| self.days.contains(&day.weekday()))
+            .map(|day| {
+                (
+                    day.and_time(self.start).and_utc(),
+                    day.and_time(self.end).and_utc(),
+                )
+            })
+            .filter(|(start, end)| *start < until && from < *end)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SeriesStatus {
+    Active,
+    /// Books nothing new; occurrences already booked are kept.
+    Paused,
+    Cancelled,
+}
+
+/// An occurrence that couldn't be booked.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StandingConflict {
+    pub from: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    /// The one-off reservation it overlapped; `None` when no spot was free.
+    pub reservation_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StandingReservation {
+    pub series_id: String,
+    pub vehicle: Vehicle,
+    pub rule: RecurrenceRule,
+    pub tags: Vec<SpotTag>,
+    pub from: DateTime<Utc>,
+    /// Last day of the series; `None` runs until cancelled.
+    pub until: Option<DateTime<Utc>>,
+    pub status: SeriesStatus,
+    /// Reservations booked for its occurrences, oldest first.
+    pub reservation_ids: Vec<String>,
+    pub conflicts: Vec<StandingConflict>,
+    /// Occurrences starting before this have been booked or recorded as conflicts.
+    pub(crate) booked_through: DateTime<Utc>,
+}
+
+impl ParkingLot {
+    /// Sets up a series for `vehicle` from `from` on, optionally ending at `until`. Spots
+    /// carrying every tag in `tags` are held for its occurrences.
+    pub fn create_standing_reservation(
+        &self,
+        vehicle: Vehicle,
+        rule: RecurrenceRule,
+        tags: Vec<SpotTag>,
+        from: DateTime<Utc>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<StandingReservation, ParkingError> {
+        if !rule.is_valid() {
+            return Err(ParkingError::InvalidRecurrenceRule);
+        }
+        if until.is_some_and(|until| until <= from || until <= self.now()) {
+            return Err(ParkingError::InvalidReservationWindow);
+        }
+        let series = StandingReservation {
+            series_id: format!("SRS_{}", RESERVATION_COUNTER.fetch_add(1, Ordering::SeqCst)),
+            vehicle,
+            rule,
+            tags,
+            from,
+            until,
+            status: SeriesStatus::Active,
+            reservation_ids: Vec::new(),
+            conflicts: Vec::new(),
+            booked_through: from,
+        };
+        self.standing_reservations
+            .lock()?
+            .insert(series.series_id.clone(), series.clone());
+        Ok(series)
+    }
+
+    pub fn standing_reservation(&self, series_id: &str) -> Option<StandingReservation> {
+        self.standing_reservations
+            .lock()
+            .unwrap()
+            .get(series_id)
+            .cloned()
+    }
+
+    pub fn pause_standing_reservation(&self, series_id: &str) -> Result<(), ParkingError> {
+        self.set_series_status(series_id, SeriesStatus::Paused)
+    }
+
+    pub fn resume_standing_reservation(&self, series_id: &str) -> Result<(), ParkingError> {
+        self.set_series_status(series_id, SeriesStatus::Active)
+    }
+
+    /// Ends the series and cancels its booked occurrences that haven't been checked in.
+    pub fn cancel_standing_reservation(&self, series_id: &str) -> Result<(), ParkingError> {
+        self.set_series_status(series_id, SeriesStatus::Cancelled)?;
+        let reservation_ids = self.standing_reservations.lock()?[series_id]
+            .reservation_ids
+            .clone();
+        for reservation_id in reservation_ids {
+            if self
+                .get_reservation(&reservation_id)
+                .is_some_and(|r| r.status == ReservationStatus::Pending)
+            {
+                self.cancel_reservation(&reservation_id)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_series_status(&self, series_id: &str, status: SeriesStatus) -> Result<(), ParkingError> {
+        let mut series = self.standing_reservations.lock()?;
+        let series = series
+            .get_mut(series_id)
+            .ok_or(ParkingError::StandingReservationNotFound)?;
+        if series.status == SeriesStatus::Cancelled {
+            return Err(ParkingError::StandingReservationCancelled);
+        }
+        series.status = status;
+        Ok(())
+    }
+
+    /// Books every active series' occurrences that start within `horizon` from now and
+    /// haven't ended. Meant to run periodically; returns the reservations booked.
+    pub fn book_standing_reservations(&self, horizon: Duration) -> Vec<Reservation> {
+        let now = self.now();
+        let horizon_end = now + horizon;
+        let active: Vec<StandingReservation> = self
+            .standing_reservations
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|s| s.status == SeriesStatus::Active)
+            .cloned()
+            .collect();
+
+        let mut booked = Vec::new();
+        for series in active {
+            let end = series
+                .until
+                .map_or(horizon_end, |until| until.min(horizon_end));
+            let mut reservation_ids = Vec::new();
+            let mut conflicts = Vec::new();
+            for (from, until) in series.rule.occurrences(series.booked_through.max(now), end) {
+                if from < series.booked_through {
+                    continue;
+                }
+                if let Some(one_off) = self.overlapping_one_off(&series.vehicle, from, until) {
+                    conflicts.push(StandingConflict {
+                        from,
+                        until,
+                        reservation_id: Some(one_off),
+                    });
+                    continue;
+                }
+                match self.book_reservation(
+                    series.vehicle.clone(),
+                    from,
+                    until,
+                    &series.tags,
+                    Some(series.series_id.clone()),
+                ) {
+                    Ok(reservation) => {
+                        reservation_ids.push(reservation.reservation_id.clone());
+                        booked.push(reservation);
+                    }
+                    Err(_) => conflicts.push(StandingConflict {
+                        from,
+                        until,
+                        reservation_id: None,
+                    }),
+                }
+            }
+
+            let mut all = self.standing_reservations.lock().unwrap();
+            if let Some(series) = all.get_mut(&series.series_id) {
+                series.reservation_ids.extend(reservation_ids);
+                series.conflicts.extend(conflicts);
+                series.booked_through = series.booked_through.max(end);
+            }
+        }
+        booked
+    }
+
+    /// A pending one-off reservation for the vehicle that overlaps `from..until`.
+    fn overlapping_one_off(
+        &self,
+        vehicle: &Vehicle,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Option<String> {
+        self.reservations
+            .lock()
+            .unwrap()
+            .values()
+            .find(|r| {
+                r.series_id.is_none()
+                    && r.status == ReservationStatus::Pending
+                    && r.vehicle.license_plate == vehicle.license_plate
+                    && r.from < until
+                    && from < r.until
+            })
+            .map(|r| r.reservation_id.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParkingFloor, VehicleType, clock::MockClock};
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_weekday_series_books_ahead_and_skips_one_off_conflicts() {
+        // Sunday 2026-01-04, 20:00
+        let clock = MockClock::new(Utc.with_ymd_and_hms(2026, 1, 4, 20, 0, 0).unwrap());
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "COMMUTE".into());
+        let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let bad = RecurrenceRule::weekdays(at(18, 0), at(8, 0));
+        assert!(matches!(
+            lot.create_standing_reservation(car.clone(), bad, vec![], lot.now(), None),
+            Err(ParkingError::InvalidRecurrenceRule)
+        ));
+
+        // Tuesday is already covered by a one-off booking
+        let tuesday = Utc.with_ymd_and_hms(2026, 1, 6, 9, 0, 0).unwrap();
+        let one_off = lot
+            .reserve_spot(car.clone(), tuesday, tuesday + Duration::hours(2))
+            .unwrap();
+        let (floor_id, spot_id) = lot
+            .spots_with_tags(&[])
+            .into_iter()
+            .find(|(_, id)| *id != one_off.spot_id)
+            .unwrap();
+        lot.tag_spot(floor_id, &spot_id, SpotTag::NearElevator)
+            .unwrap();
+        let series = lot
+            .create_standing_reservation(
+                car,
+                RecurrenceRule::weekdays(at(8, 0), at(18, 0)),
+                vec![SpotTag::NearElevator],
+                lot.now(),
+                None,
+            )
+            .unwrap();
+
+        let booked = lot.book_standing_reservations(Duration::days(2));
+        assert_eq!(booked.len(), 1);
+        assert_eq!(booked[0].spot_id, spot_id);
+        assert_eq!(
+            booked[0].from,
+            Utc.with_ymd_and_hms(2026, 1, 5, 8, 0, 0).unwrap()
+        );
+        let series = lot.standing_reservation(&series.series_id).unwrap();
+        assert_eq!(series.conflicts.len(), 1);
+        assert_eq!(
+            series.conflicts[0].reservation_id,
+            Some(one_off.reservation_id)
+        );
+        // Occurrences already handled aren't booked twice
+        assert!(lot.book_standing_reservations(Duration::days(2)).is_empty());
+
+        lot.pause_standing_reservation(&series.series_id).unwrap();
+        clock.advance(Duration::days(2));
+        lot.release_expired_reservations(lot.now()).unwrap();
+        assert!(lot.book_standing_reservations(Duration::days(2)).is_empty());
+        lot.resume_standing_reservation(&series.series_id).unwrap();
+        assert_eq!(lot.book_standing_reservations(Duration::days(1)).len(), 1);
+
+        lot.cancel_standing_reservation(&series.series_id).unwrap();
+        let series = lot.standing_reservation(&series.series_id).unwrap();
+        assert_eq!(series.status, SeriesStatus::Cancelled);
+        let last = lot.get_reservation(series.reservation_ids.last().unwrap());
+        assert_eq!(last.unwrap().status, ReservationStatus::Cancelled);
+        assert!(matches!(
+            lot.resume_standing_reservation(&series.series_id),
+            Err(ParkingError::StandingReservationCancelled)
+        ));
+    }
+}
@@ -0,0 +1,187 @@
+//! Free-form spot attributes that the fixed `SpotType` can't express ("covered",
+//! "near-elevator", ...). Allocation, reservations and queries can require a set of tags.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ParkingLot, error::ParkingError};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(into = "String", from = "String")]
+pub enum SpotTag {
+    Covered,
+    NearElevator,
+    CameraCovered,
+    ColumnAdjacent,
+    Custom(String),
+}
+
+impl SpotTag {
+    pub fn as_str(&self) -> &str {
+        match self {
+            SpotTag::Covered => "covered",
+            SpotTag::NearElevator => "near-elevator",
+            SpotTag::CameraCovered => "camera-covered",
+            SpotTag::ColumnAdjacent => "column-adjacent",
+            SpotTag::Custom(label) => label,
+        }
+    }
+}
+
+impl From<&str> for SpotTag {
+    fn from(label: &str) -> Self {
+        match label {
+            "covered" => SpotTag::Covered,
+            "near-elevator" => SpotTag::NearElevator,
+            "camera-covered" => SpotTag::CameraCovered,
+            "column-adjacent" => SpotTag::ColumnAdjacent,
+            other => SpotTag::Custom(other.to_string()),
+        }
+    }
+}
+
+impl From<String> for SpotTag {
+    fn from(label: String) -> Self {
+        SpotTag::from(label.as_str())
+    }
+}
+
+impl From<SpotTag> for String {
+    fn from(tag: SpotTag) -> Self {
+        tag.as_str().to_string()
+    }
+}
+
+impl fmt::Display for SpotTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl ParkingLot {
+    pub fn tag_spot(&self, floor_id: u32, spot_id: &str, tag: SpotTag) -> Result<(), ParkingError> {
+        self.with_floor_spot_mut(floor_id, spot_id, |spot| {
+            spot.tags.insert(tag);
+        })?
+        .ok_or(ParkingError::SpotNotFound)
+    }
+
+    pub fn untag_spot(
+        &self,
+        floor_id: u32,
+        spot_id: &str,
+        tag: &SpotTag,
+    ) -> Result<(), ParkingError> {
+        self.with_floor_spot_mut(floor_id, spot_id, |spot| {
+            spot.tags.remove(tag);
+        })?
+        .ok_or(ParkingError::SpotNotFound)
+    }
+
+    /// Floor and spot id of every spot carrying all of `tags`, free or not, sorted. Spot
+    /// ids repeat across floors, so the floor is needed to tell them apart.
+    pub fn spots_with_tags(&self, tags: &[SpotTag]) -> Vec<(u32, String)> {
+        let floors = self.floors.lock().unwrap();
+        let mut spots: Vec<(u32, String)> = floors
+            .values()
+            .flat_map(|floor| {
+                floor
+                    .spots
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|(_, spot)| spot.has_tags(tags))
+                    .map(|(spot_id, _)| (floor.id, spot_id.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        spots.sort();
+        spots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, Vehicle, VehicleType};
+
+    #[test]
+    fn test_allocation_and_reservation_honour_required_tags() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let mut spots = lot.spots_with_tags(&[]);
+        let (_, covered) = spots.pop().unwrap();
+        let (_, also_covered) = spots.pop().unwrap();
+        lot.tag_spot(1, &covered, SpotTag::Covered).unwrap();
+        lot.tag_spot(1, &covered, SpotTag::NearElevator).unwrap();
+        lot.tag_spot(1, &also_covered, "covered".into()).unwrap();
+
+        let ticket = lot
+            .park_vehicle_with_tags(
+                Vehicle::new(VehicleType::Motor, "Kia".into(), "AAA111".into()),
+                &[SpotTag::Covered, SpotTag::NearElevator],
+            )
+            .unwrap();
+        assert_eq!(ticket.spot_id, covered);
+
+        let now = chrono::Utc::now();
+        let reservation = lot
+            .reserve_spot_with_tags(
+                Vehicle::new(VehicleType::Motor, "Kia".into(), "BBB222".into()),
+                now,
+                now + chrono::Duration::hours(1),
+                &[SpotTag::Covered],
+            )
+            .unwrap();
+        assert_eq!(reservation.spot_id, also_covered);
+
+        assert_eq!(
+            lot.park_vehicle_with_tags(
+                Vehicle::new(VehicleType::Motor, "Kia".into(), "CCC333".into()),
+                &[SpotTag::Covered],
+            )
+            .unwrap_err(),
+            ParkingError::NoSpotAvailable
+        );
+        assert!(
+            lot.park_vehicle(Vehicle::new(
+                VehicleType::Motor,
+                "Kia".into(),
+                "CCC333".into()
+            ))
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_tags_stay_on_their_own_floor() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.add_floor(ParkingFloor::new(2)).unwrap();
+        lot.tag_spot(2, "spot_3", SpotTag::Covered).unwrap();
+        assert_eq!(
+            lot.spots_with_tags(&[SpotTag::Covered]),
+            [(2, "spot_3".to_string())]
+        );
+        assert_eq!(lot.spots_with_tags(&[]).len(), 20);
+
+        let ticket = lot
+            .park_vehicle_with_tags(
+                Vehicle::new(VehicleType::Motor, "Kia".into(), "TAG001".into()),
+                &[SpotTag::Covered],
+            )
+            .unwrap();
+        assert_eq!(
+            (ticket.floor_id, ticket.spot_id.as_str()),
+            (Some(2), "spot_3")
+        );
+
+        lot.untag_spot(2, "spot_3", &SpotTag::Covered).unwrap();
+        assert!(lot.spots_with_tags(&[SpotTag::Covered]).is_empty());
+        assert_eq!(
+            lot.tag_spot(3, "spot_3", SpotTag::Covered),
+            Err(ParkingError::SpotNotFound)
+        );
+    }
+}
@@ -0,0 +1,393 @@
+//! Ticket ids. Each lot has an `IdGenerator`; the default hands out random UUIDs, which
+//! don't collide across lots or with tickets saved before a restart.
+//!
+//! Lots that print short ids can use a `TicketIdFormat` instead: a prefix, an optional
+//! issue date and a sequence number, e.g. `PWH-2024-000123`. The sequence is shared by
+//! every lot in the process and moves past restored tickets on `load_from_file`, which
+//! also brings back the lot's format.
+//!
+//! Ids read back from a printed ticket are parsed against the lot's generator, so lookups
+//! accept them in any letter case and, for formats, with or without the sequence padding.
+
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{ParkingLot, TICKET_COUNTER, error::ParkingError};
+
+pub trait IdGenerator: fmt::Debug + Send + Sync {
+    fn next_id(&self, issued_at: DateTime<Utc>) -> String;
+
+    /// `id` written exactly as this generator writes it, if it's one of its ids.
+    fn normalize(&self, _id: &str) -> Option<String> {
+        None
+    }
+
+    /// The sequence number `id` was issued with, for generators that number their ids
+    /// from the shared counter.
+    fn sequence(&self, _id: &str) -> Option<u64> {
+        None
+    }
+
+    /// The generator as saved with the lot. Generators a `SavedIdGenerator` can't
+    /// describe return `None`; a lot loaded without one hands out UUIDs.
+    fn saved(&self) -> Option<SavedIdGenerator> {
+        None
+    }
+}
+
+/// A built-in generator as saved with the lot, see `IdGenerator::saved`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum SavedIdGenerator {
+    Uuid,
+    Format(TicketIdFormat),
+}
+
+impl SavedIdGenerator {
+    /// Rebuilds the generator that was saved.
+    pub fn into_generator(self) -> Box<dyn IdGenerator> {
+        match self {
+            SavedIdGenerator::Uuid => Box::new(UuidIds),
+            SavedIdGenerator::Format(format) => Box::new(format),
+        }
+    }
+}
+
+/// Random (version 4) UUIDs such as `9b2f6c1e-04d7-4a3b-8f5e-2c71d0a9e4b6`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidIds;
+
+/// Tells apart UUIDs generated in the same nanosecond on one thread.
+static UUID_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+impl UuidIds {
+    fn random_u64() -> u64 {
+        // Every `RandomState` is keyed differently, seeded from the OS
+        let mut hasher = RandomState::new().build_hasher();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos());
+        hasher.write_u128(nanos);
+        hasher.write_u64(UUID_COUNTER.fetch_add(1, Ordering::Relaxed));
+        hasher.finish()
+    }
+}
+
+impl IdGenerator for UuidIds {
+    fn next_id(&self, _issued_at: DateTime<Utc>) -> String {
+        let high = (Self::random_u64() & !0xf000) | 0x4000;
+        let low = (Self::random_u64() & !(0b11 << 62)) | (0b10 << 62);
+        format!(
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            high >> 32,
+            (high >> 16) & 0xffff,
+            high & 0xffff,
+            low >> 48,
+            low & 0xffff_ffff_ffff
+        )
+    }
+
+    fn normalize(&self, id: &str) -> Option<String> {
+        let id = id.trim().to_ascii_lowercase();
+        let groups: Vec<&str> = id.split('-').collect();
+        let shaped = groups.iter().map(|g| g.len()).eq([8, 4, 4, 4, 12])
+            && groups
+                .iter()
+                .all(|g| g.bytes().all(|b| b.is_ascii_hexdigit()));
+        shaped.then_some(id)
+    }
+
+    fn saved(&self) -> Option<SavedIdGenerator> {
+        Some(SavedIdGenerator::Uuid)
+    }
+}
+
+/// How much of the issue date goes into an id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DateComponent {
+    /// `2024`
+    Year,
+    /// `202403`
+    YearMonth,
+    /// `20240317`
+    YearMonthDay,
+}
+
+impl DateComponent {
+    fn pattern(self) -> &'static str {
+        match self {
+            DateComponent::Year => "%Y",
+            DateComponent::YearMonth => "%Y%m",
+            DateComponent::YearMonthDay => "%Y%m%d",
+        }
+    }
+
+    fn width(self) -> usize {
+        match self {
+            DateComponent::Year => 4,
+            DateComponent::YearMonth => 6,
+            DateComponent::YearMonthDay => 8,
+        }
+    }
+
+    fn is_valid(self, digits: &str) -> bool {
+        // Pad to a full date so chrono can check the month and day
+        let full = match self {
+            DateComponent::Year => format!("{digits}0101"),
+            DateComponent::YearMonth => format!("{digits}01"),
+            DateComponent::YearMonthDay => digits.to_string(),
+        };
+        NaiveDate::parse_from_str(&full, "%Y%m%d").is_ok()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TicketIdFormat {
+    prefix: String,
+    separator: char,
+    date: Option<DateComponent>,
+    /// Sequence numbers are zero-padded to this many digits.
+    sequence_width: usize,
+}
+
+impl Default for TicketIdFormat {
+    fn default() -> Self {
+        Self::new("TKT")
+    }
+}
+
+/// The parts of a ticket id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedTicketId {
+    /// The date digits, e.g. `2024`.
+    pub date: Option<String>,
+    pub sequence: u64,
+}
+
+impl TicketIdFormat {
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            separator: '_',
+            date: None,
+            sequence_width: 0,
+        }
+    }
+
+    pub fn with_separator(mut self, separator: char) -> Self {
+        self.separator = separator;
+        self
+    }
+
+    pub fn with_date(mut self, date: DateComponent) -> Self {
+        self.date = Some(date);
+        self
+    }
+
+    pub fn with_sequence_width(mut self, width: usize) -> Self {
+        self.sequence_width = width;
+        self
+    }
+
+    pub fn format(&self, issued_at: DateTime<Utc>, sequence: u64) -> String {
+        let mut parts = Vec::new();
+        if !self.prefix.is_empty() {
+            parts.push(self.prefix.clone());
+        }
+        if let Some(date) = self.date {
+            parts.push(issued_at.format(date.pattern()).to_string());
+        }
+        parts.push(format!("{sequence:0width$}", width = self.sequence_width));
+        parts.join(&self.separator.to_string())
+    }
+
+    /// Splits an id in this format into its parts. The prefix is matched ignoring case
+    /// and surrounding whitespace is ignored.
+    pub fn parse(&self, id: &str) -> Result<ParsedTicketId, ParkingError> {
+        let mut rest = id.trim();
+        if !self.prefix.is_empty() {
+            let prefix = rest
+                .get(..self.prefix.len())
+                .filter(|prefix| prefix.eq_ignore_ascii_case(&self.prefix))
+                .ok_or(ParkingError::InvalidTicket)?;
+            rest = rest[prefix.len()..]
+                .strip_prefix(self.separator)
+                .ok_or(ParkingError::InvalidTicket)?;
+        }
+        let date = match self.date {
+            Some(component) => {
+                let digits = rest
+                    .get(..component.width())
+                    .filter(|digits| component.is_valid(digits))
+                    .ok_or(ParkingError::InvalidTicket)?;
+                rest = rest[digits.len()..]
+                    .strip_prefix(self.separator)
+                    .ok_or(ParkingError::InvalidTicket)?;
+                Some(digits.to_string())
+            }
+            None => None,
+        };
+        if rest.is_empty() || !rest.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParkingError::InvalidTicket);
+        }
+        let sequence = rest.parse().map_err(|_| ParkingError::InvalidTicket)?;
+        Ok(ParsedTicketId { date, sequence })
+    }
+
+    /// `id` written exactly as this format writes it, if it parses.
+    pub fn normalize(&self, id: &str) -> Option<String> {
+        let parsed = self.parse(id).ok()?;
+        let mut parts = Vec::new();
+        if !self.prefix.is_empty() {
+            parts.push(self.prefix.clone());
+        }
+        parts.extend(parsed.date);
+        parts.push(format!(
+            "{:0width$}",
+            parsed.sequence,
+            width = self.sequence_width
+        ));
+        Some(parts.join(&self.separator.to_string()))
+    }
+}
+
+impl IdGenerator for TicketIdFormat {
+    fn next_id(&self, issued_at: DateTime<Utc>) -> String {
+        self.format(issued_at, TICKET_COUNTER.fetch_add(1, Ordering::SeqCst))
+    }
+
+    fn normalize(&self, id: &str) -> Option<String> {
+        TicketIdFormat::normalize(self, id)
+    }
+
+    fn sequence(&self, id: &str) -> Option<u64> {
+        self.parse(id).ok().map(|parsed| parsed.sequence)
+    }
+
+    fn saved(&self) -> Option<SavedIdGenerator> {
+        Some(SavedIdGenerator::Format(self.clone()))
+    }
+}
+
+impl ParkingLot {
+    /// Generates the ids of tickets issued from now on. Tickets already issued keep
+    /// their ids.
+    pub fn with_id_generator(mut self, ids: Box<dyn IdGenerator>) -> Self {
+        self.ticket_ids = ids;
+        self
+    }
+
+    /// Numbers tickets issued from now on in `format`.
+    pub fn with_ticket_id_format(self, format: TicketIdFormat) -> Self {
+        self.with_id_generator(Box::new(format))
+    }
+
+    pub(crate) fn generate_ticket_id(&self) -> String {
+        self.ticket_ids.next_id(self.now())
+    }
+
+    /// The id a ticket is stored under for `id` as read off a ticket; ids the generator
+    /// doesn't recognise are taken as they are.
+    pub(crate) fn canonical_ticket_id(&self, id: &str) -> String {
+        self.ticket_ids
+            .normalize(id)
+            .unwrap_or_else(|| id.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, ParkingFloor, Vehicle, VehicleType};
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_formatted_ids_round_trip_through_lookups() {
+        let format = TicketIdFormat::new("PWH")
+            .with_separator('-')
+            .with_date(DateComponent::Year)
+            .with_sequence_width(6);
+        let at = Utc.with_ymd_and_hms(2024, 3, 17, 9, 0, 0).unwrap();
+        assert_eq!(format.format(at, 123), "PWH-2024-000123");
+        assert_eq!(
+            format.parse(" pwh-2024-123 "),
+            Ok(ParsedTicketId {
+                date: Some("2024".into()),
+                sequence: 123
+            })
+        );
+        assert_eq!(
+            format.parse("PWH-2024-12a"),
+            Err(ParkingError::InvalidTicket)
+        );
+        assert_eq!(
+            format.parse("PWH-20x4-000123"),
+            Err(ParkingError::InvalidTicket)
+        );
+        assert_eq!(TicketIdFormat::default().format(at, 7), "TKT_7");
+
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_ticket_id_format(format.clone());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "FMT001".into());
+        let ticket = lot.park_vehicle(car).unwrap();
+        let parsed = format.parse(&ticket.ticket_id).unwrap();
+        assert_eq!(ticket.ticket_id, format.format(lot.now(), parsed.sequence));
+
+        let typed = ticket.ticket_id.to_lowercase().replace("-0", "-");
+        assert!(lot.active_ticket(&typed).is_some());
+        assert_eq!(
+            lot.unpark_vehicle(typed).unwrap().ticket_id,
+            ticket.ticket_id
+        );
+    }
+
+    #[test]
+    fn test_default_ids_are_distinct_uuids() {
+        let now = Utc::now();
+        let ids: std::collections::HashSet<String> =
+            (0..1000).map(|_| UuidIds.next_id(now)).collect();
+        assert_eq!(ids.len(), 1000);
+        let id = ids.iter().next().unwrap();
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "4");
+        assert!("89ab".contains(&id[19..20]));
+        assert_eq!(UuidIds.normalize(&id.to_uppercase()).as_ref(), Some(id));
+        assert_eq!(UuidIds.normalize("TKT_1"), None);
+    }
+
+    #[test]
+    fn test_ids_of_open_tickets_are_never_reissued() {
+        #[derive(Debug)]
+        struct SameId;
+        impl IdGenerator for SameId {
+            fn next_id(&self, _issued_at: DateTime<Utc>) -> String {
+                "SAME".to_string()
+            }
+        }
+
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_id_generator(Box::new(SameId));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let first = Vehicle::new(VehicleType::Motor, "Kia".into(), "DUP001".into());
+        let second = Vehicle::new(VehicleType::Motor, "Kia".into(), "DUP002".into());
+        lot.park_vehicle(first).unwrap();
+
+        assert_eq!(
+            lot.park_vehicle(second).unwrap_err(),
+            ParkingError::DuplicateTicket("SAME".into())
+        );
+        assert_eq!(
+            lot.active_ticket("SAME").unwrap().vehicle.license_plate,
+            "DUP001"
+        );
+    }
+}
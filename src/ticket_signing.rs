@@ -0,0 +1,109 @@
+//! Anti-forgery codes for printed tickets. A lot with a signing key prints each ticket as
+//! `<ticket id>.<signature>`, where the signature is an HMAC-SHA256 over the ticket id,
+//! its entry time and the lot's uid. Exit panels check the code before taking payment or
+//! opening the barrier, so a made-up or altered ticket id is refused with `ForgedTicket`.
+//!
+//! The HTTP API checks codes the same way. Staff calls such as `pay_ticket` and
+//! `unpark_vehicle` take plain ticket ids and aren't checked. Without a key, panels and
+//! the API accept plain ticket ids as before.
+
+use crate::{
+    ParkingLot, ParkingTicket,
+    error::ParkingError,
+    signing::{hmac_sha256_hex, verify_hmac_sha256_hex},
+};
+
+impl ParkingLot {
+    pub fn with_ticket_signing_key(mut self, key: impl Into<String>) -> Self {
+        self.ticket_signing_key = Some(key.into());
+        self
+    }
+
+    /// The code to print on `ticket`: its id, signed if the lot has a signing key.
+    pub fn ticket_code(&self, ticket: &ParkingTicket) -> String {
+        match self.ticket_signature(ticket) {
+            Some(signature) => format!("{}.{}", ticket.ticket_id, signature),
+            None => ticket.ticket_id.clone(),
+        }
+    }
+
+    /// Checks a code presented at a kiosk and returns the ticket id it's for.
+    pub fn verify_ticket_code(&self, code: &str) -> Result<String, ParkingError> {
+        let Some(key) = self.ticket_signing_key.as_deref() else {
+            return Ok(code.to_string());
+        };
+        let (ticket_id, signature) = code.rsplit_once('.').ok_or(ParkingError::ForgedTicket)?;
+        let ticket = self
+            .active_ticket(ticket_id)
+            .ok_or(ParkingError::InvalidTicket)?;
+        if !verify_hmac_sha256_hex(key, &self.signed_payload(&ticket), signature) {
+            return Err(ParkingError::ForgedTicket);
+        }
+        Ok(ticket.ticket_id)
+    }
+
+    fn ticket_signature(&self, ticket: &ParkingTicket) -> Option<String> {
+        let key = self.ticket_signing_key.as_deref()?;
+        Some(hmac_sha256_hex(key, &self.signed_payload(ticket)))
+    }
+
+    fn signed_payload(&self, ticket: &ParkingTicket) -> String {
+        format!(
+            "{}\n{}\n{}",
+            ticket.ticket_id,
+            ticket.entry_time.to_rfc3339(),
+            self.uid
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Parkable, ParkingFloor, Vehicle, VehicleType,
+        panel::ExitPanel,
+        payment::{CashProcessor, PaymentMethod, PaymentMethodKind},
+    };
+
+    #[test]
+    fn test_panels_refuse_forged_and_tampered_codes() {
+        let lot = |uid: &str| {
+            let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), uid.into())
+                .with_ticket_signing_key("s3cret");
+            lot.add_floor(ParkingFloor::new(1)).unwrap();
+            lot.set_payment_processor(PaymentMethodKind::Cash, Box::new(CashProcessor));
+            lot.add_exit_panel(ExitPanel::new("south".into(), "South gate".into()))
+                .unwrap();
+            lot
+        };
+        let (lot, other) = (lot("1"), lot("2"));
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "SIG001".into());
+        let ticket = lot.park_vehicle(car).unwrap();
+        let code = lot.ticket_code(&ticket);
+        assert!(code.starts_with(&format!("{}.", ticket.ticket_id)));
+
+        let last = if code.ends_with('0') { "1" } else { "0" };
+        let tampered = format!("{}{last}", &code[..code.len() - 1]);
+        for forged in [ticket.ticket_id.clone(), tampered] {
+            assert_eq!(
+                lot.pay_at_exit("south", &forged, PaymentMethod::Cash),
+                Err(ParkingError::ForgedTicket)
+            );
+            assert_eq!(
+                lot.unpark_at_exit("south", &forged).unwrap_err(),
+                ParkingError::ForgedTicket
+            );
+        }
+        // Codes are bound to the lot that issued them
+        assert_ne!(other.ticket_code(&ticket), code);
+
+        lot.pay_at_exit("south", &code, PaymentMethod::Cash)
+            .unwrap();
+        lot.unpark_at_exit("south", &code).unwrap();
+        assert_eq!(
+            lot.verify_ticket_code(&code),
+            Err(ParkingError::InvalidTicket)
+        );
+    }
+}
@@ -0,0 +1,441 @@
+//! Everything that happened to one ticket or one spot, merged into a single chronological
+//! timeline for customer support: allocation, sensor updates, charging, payments, staff
+//! overrides and the exit. A complaint like "I was charged twice" can be answered from
+//! one export instead of searching tickets, payments and the audit log separately.
+//!
+//! Spot transitions are stamped with the lot's clock, like tickets and payments, so they
+//! sort among them.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{
+    ParkingLot, ParkingTicket,
+    audit::{AuditAction, AuditEntry},
+    error::ParkingError,
+    journal::{SpotTransition, TransitionCause},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum OperationKind {
+    /// A spot was handed out or held: parking, reservations, leases.
+    Allocation,
+    /// Arrival confirmed or a claim lapsed.
+    Sensor,
+    Charging,
+    Payment,
+    /// Staff or priority actions, maintenance and rollbacks.
+    Override,
+    Exit,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Operation {
+    pub at: DateTime<Utc>,
+    pub kind: OperationKind,
+    pub ticket_id: Option<String>,
+    pub spot_id: Option<String>,
+    pub description: String,
+}
+
+/// The operations on one ticket or spot, oldest first.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OperationLog {
+    /// E.g. `ticket TKT_3` or `spot spot_2 on floor 1`.
+    pub subject: String,
+    pub operations: Vec<Operation>,
+}
+
+impl OperationLog {
+    fn new(subject: String, mut operations: Vec<Operation>) -> Self {
+        operations.sort_by_key(|op| op.at);
+        Self {
+            subject,
+            operations,
+        }
+    }
+
+    pub fn of_kind(&self, kind: OperationKind) -> impl Iterator<Item = &Operation> {
+        self.operations.iter().filter(move |op| op.kind == kind)
+    }
+
+    /// The timeline as a JSON document.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("timelines serialize to JSON")
+    }
+}
+
+impl fmt::Display for OperationLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Operations on {}", self.subject)?;
+        for op in &self.operations {
+            write!(
+                f,
+                "\n{} {:?}: {}",
+                op.at.format("%Y-%m-%d %H:%M:%S"),
+                op.kind,
+                op.description
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl ParkingLot {
+    /// Every operation on the ticket, open or closed.
+    pub fn ticket_operations(&self, ticket_id: &str) -> Result<OperationLog, ParkingError> {
+        let (ticket, total) = self
+            .tickets_ever()?
+            .into_iter()
+            .find(|(ticket, _)| ticket.ticket_id == ticket_id)
+            .ok_or(ParkingError::InvalidTicket)?;
+        let mut operations = self.stay_operations(&ticket, total);
+        let stay_end = ticket.exit_time.unwrap_or_else(|| self.now());
+        let journal = match ticket.floor_id {
+            Some(floor_id) => self.spot_journal(floor_id, &ticket.spot_id)?,
+            None => Vec::new(),
+        };
+        operations.extend(
+            journal
+                .iter()
+                .filter(|t| t.at >= ticket.entry_time && t.at <= stay_end)
+                .filter_map(|t| journal_operation(&ticket.spot_id, t)),
+        );
+        operations.extend(self.audit_log().iter().filter_map(|entry| {
+            let (ticket_ref, spot_ref) = audit_subjects(&entry.action);
+            let during_stay = spot_ref
+                .is_some_and(|spot| spot.is(ticket.floor_id, &ticket.spot_id))
+                && entry.at >= ticket.entry_time
+                && entry.at <= stay_end;
+            (ticket_ref == Some(ticket_id) || during_stay).then(|| audit_operation(entry))
+        }));
+        Ok(OperationLog::new(format!("ticket {ticket_id}"), operations))
+    }
+
+    /// Every operation on the spot: the stays on it and its own state changes.
+    pub fn spot_operations(
+        &self,
+        floor_id: u32,
+        spot_id: &str,
+    ) -> Result<OperationLog, ParkingError> {
+        let journal = self
+            .with_floor_spot_mut(floor_id, spot_id, |spot| spot.transitions())?
+            .ok_or(ParkingError::SpotNotFound)?;
+        let mut operations: Vec<Operation> = self
+            .tickets_ever()?
+            .into_iter()
+            .filter(|(ticket, _)| {
+                (ticket.floor_id == Some(floor_id) && ticket.spot_id == spot_id)
+                    || ticket
+                        .relocations
+                        .iter()
+                        .any(|r| r.from_floor_id == floor_id && r.from_spot_id == spot_id)
+            })
+            .flat_map(|(ticket, total)| self.stay_operations(&ticket, total))
+            .collect();
+        operations.extend(journal.iter().filter_map(|t| journal_operation(spot_id, t)));
+        operations.extend(
+            self.audit_log()
+                .iter()
+                .filter(|entry| {
+                    audit_subjects(&entry.action)
+                        .1
+                        .is_some_and(|spot| spot.is(Some(floor_id), spot_id))
+                })
+                .map(audit_operation),
+        );
+        Ok(OperationLog::new(
+            format!("spot {spot_id} on floor {floor_id}"),
+            operations,
+        ))
+    }
+
+    /// Open tickets, then closed ones with what they were charged.
+    fn tickets_ever(&self) -> Result<Vec<(ParkingTicket, Option<f32>)>, ParkingError> {
+        let mut tickets: Vec<(ParkingTicket, Option<f32>)> = self
+            .active_tickets
+            .lock()?
+            .values()
+            .map(|ticket| (ticket.clone(), None))
+            .collect();
+        tickets.extend(
+            self.ticket_history
+                .completed_tickets()?
+                .into_iter()
+                .map(|completed| (completed.ticket, Some(completed.total))),
+        );
+        Ok(tickets)
+    }
+
+    fn spot_journal(
+        &self,
+        floor_id: u32,
+        spot_id: &str,
+    ) -> Result<Vec<SpotTransition>, ParkingError> {
+        Ok(self
+            .with_floor_spot_mut(floor_id, spot_id, |spot| spot.transitions())?
+            .unwrap_or_default())
+    }
+
+    /// Entry, charging, payment and exit of one stay.
+    fn stay_operations(&self, ticket: &ParkingTicket, total: Option<f32>) -> Vec<Operation> {
+        let op = |at, kind, description: String| Operation {
+            at,
+            kind,
+            ticket_id: Some(ticket.ticket_id.clone()),
+            spot_id: Some(ticket.spot_id.clone()),
+            description,
+        };
+        let mut entry = format!(
+            "{} parked on {}",
+            ticket.vehicle.license_plate, ticket.spot_id
+        );
+        if let Some(entrance) = &ticket.entrance_id {
+            entry.push_str(&format!(" via entrance {entrance}"));
+        }
+        let mut operations = vec![op(ticket.entry_time, OperationKind::Allocation, entry)];
+        for relocation in &ticket.relocations {
+            operations.push(op(
+                relocation.at,
+                OperationKind::Allocation,
+                format!(
+                    "Moved from {} to {} on floor {}: {}",
+                    relocation.from_spot_id,
+                    relocation.spot_id,
+                    relocation.floor_id,
+                    relocation.reason
+                ),
+            ));
+        }
+        if let Some(authorization) = &ticket.pre_authorization {
+            operations.push(op(
+                ticket.entry_time,
+                OperationKind::Payment,
+                format!("Card hold {authorization} placed"),
+            ));
+        }
+
+        for session in self
+            .charging_sessions
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|s| s.ticket_id == ticket.ticket_id)
+        {
+            operations.push(op(
+                session.started_at,
+                OperationKind::Charging,
+                "Charging started".to_string(),
+            ));
+            if let Some(stopped_at) = session.stopped_at {
+                operations.push(op(
+                    stopped_at,
+                    OperationKind::Charging,
+                    format!("Charging stopped after {:.1} kWh", session.energy_kwh),
+                ));
+            }
+        }
+
+        for payment in self.payments_for(&ticket.ticket_id) {
+            operations.push(op(
+                payment.paid_at,
+                OperationKind::Payment,
+                format!(
+                    "Paid ${:.2} by {:?}, transaction {}",
+                    payment.amount,
+                    payment.method.kind(),
+                    payment.transaction_id
+                ),
+            ));
+        }
+
+        if let Some(exit_time) = ticket.exit_time {
+            let mut exit = match total {
+                Some(total) => format!("Left, charged ${total:.2}"),
+                None => "Left".to_string(),
+            };
+            if let Some(exit_panel) = &ticket.exit_id {
+                exit.push_str(&format!(" via exit {exit_panel}"));
+            }
+            operations.push(op(exit_time, OperationKind::Exit, exit));
+        }
+        operations
+    }
+}
+
+/// Spot state changes not already covered by a ticket's entry and exit.
+fn journal_operation(spot_id: &str, transition: &SpotTransition) -> Option<Operation> {
+    let kind = match transition.cause {
+        TransitionCause::Parked | TransitionCause::Unparked | TransitionCause::CheckedIn => {
+            return None;
+        }
+        TransitionCause::ArrivalConfirmed | TransitionCause::ClaimExpired => OperationKind::Sensor,
+        TransitionCause::Reserved
+        | TransitionCause::ReservationCancelled
+        | TransitionCause::ReservationExpired
+        | TransitionCause::Leased
+        | TransitionCause::LeaseEnded
+        | TransitionCause::Relocated => OperationKind::Allocation,
+        TransitionCause::Preempted
+        | TransitionCause::TakenOutOfService
+        | TransitionCause::ReturnedToService
+        | TransitionCause::BatchRolledBack => OperationKind::Override,
+    };
+    Some(Operation {
+        at: transition.at,
+        kind,
+        ticket_id: None,
+        spot_id: Some(spot_id.to_string()),
+        description: format!(
+            "{:?}: {:?} -> {:?}",
+            transition.cause, transition.from, transition.to
+        ),
+    })
+}
+
+/// A spot an audited action refers to, with its floor when the entry records one.
+struct AuditedSpot<'a> {
+    floor_id: Option<u32>,
+    spot_id: &'a str,
+}
+
+impl AuditedSpot<'_> {
+    /// Whether this is `spot_id` on `floor_id`. An unknown floor on either side matches
+    /// any floor.
+    fn is(&self, floor_id: Option<u32>, spot_id: &str) -> bool {
+        self.spot_id == spot_id
+            && (self.floor_id.is_none() || floor_id.is_none() || self.floor_id == floor_id)
+    }
+}
+
+/// The ticket and spot an audited action refers to.
+fn audit_subjects(action: &AuditAction) -> (Option<&str>, Option<AuditedSpot<'_>>) {
+    let spot = |floor_id, spot_id| {
+        Some(AuditedSpot {
+            floor_id,
+            spot_id: spot_id as &str,
+        })
+    };
+    match action {
+        AuditAction::TicketForceClosed { ticket_id } => (Some(ticket_id), None),
+        AuditAction::SpotPreempted { spot_id, .. } => (None, spot(None, spot_id)),
+        AuditAction::ClosedFloorEntered { floor_id, spot_id } => {
+            (None, spot(Some(*floor_id), spot_id))
+        }
+        AuditAction::SpotOutOfService {
+            floor_id, spot_id, ..
+        }
+        | AuditAction::SpotReturnedToService { floor_id, spot_id }
+        | AuditAction::SpotConverted {
+            floor_id, spot_id, ..
+        } => (None, spot(*floor_id, spot_id)),
+        AuditAction::QueueBypassed
+        | AuditAction::FloorAdded { .. }
+        | AuditAction::FloorRemoved { .. }
+        | AuditAction::RatesUpdated => (None, None),
+    }
+}
+
+fn audit_operation(entry: &AuditEntry) -> Operation {
+    let (ticket_id, spot) = audit_subjects(&entry.action);
+    let description = match &entry.action {
+        AuditAction::TicketForceClosed { .. } => "Ticket force-closed".to_string(),
+        AuditAction::SpotPreempted { held_by, .. } => format!("Spot taken from {held_by}"),
+        AuditAction::ClosedFloorEntered { floor_id, .. } => {
+            format!("Parked on closed floor {floor_id}")
+        }
+        AuditAction::SpotOutOfService { reason, .. } => format!("Out of service: {reason}"),
+        AuditAction::SpotReturnedToService { .. } => "Returned to service".to_string(),
+        AuditAction::SpotConverted { from, to, .. } => format!("Converted {from:?} to {to:?}"),
+        AuditAction::QueueBypassed => "Entry queue bypassed".to_string(),
+        AuditAction::FloorAdded { floor_id } => format!("Floor {floor_id} added"),
+        AuditAction::FloorRemoved { floor_id } => format!("Floor {floor_id} removed"),
+        AuditAction::RatesUpdated => "Rates updated".to_string(),
+    };
+    Operation {
+        at: entry.at,
+        kind: OperationKind::Override,
+        ticket_id: ticket_id.map(String::from),
+        spot_id: spot.map(|spot| spot.spot_id.to_string()),
+        description: format!("{description} by {}", entry.actor),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Parkable, ParkingFloor, Vehicle, VehicleType,
+        clock::MockClock,
+        payment::{CashProcessor, PaymentMethod, PaymentMethodKind},
+    };
+
+    #[test]
+    fn test_ticket_timeline_lists_payment_and_exit_in_order() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.set_transit_hold(Some(chrono::Duration::minutes(5)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_payment_processor(PaymentMethodKind::Cash, Box::new(CashProcessor));
+
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "SUP001".into());
+        let ticket = lot.park_vehicle(car).unwrap();
+        lot.confirm_arrival(1, &ticket.spot_id).unwrap();
+        lot.pay_ticket(&ticket.ticket_id, PaymentMethod::Cash)
+            .unwrap();
+        lot.unpark_vehicle(ticket.ticket_id.clone()).unwrap();
+
+        let log = lot.ticket_operations(&ticket.ticket_id).unwrap();
+        let kinds: Vec<OperationKind> = log.operations.iter().map(|op| op.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                OperationKind::Allocation,
+                OperationKind::Sensor,
+                OperationKind::Payment,
+                OperationKind::Exit
+            ]
+        );
+        assert_eq!(log.of_kind(OperationKind::Payment).count(), 1);
+        assert!(log.to_json().contains("\"kind\":\"Payment\""));
+
+        let spot_log = lot.spot_operations(1, &ticket.spot_id).unwrap();
+        assert_eq!(spot_log.operations.len(), 4);
+        assert_eq!(
+            lot.ticket_operations("TKT_missing"),
+            Err(ParkingError::InvalidTicket)
+        );
+    }
+
+    #[test]
+    fn test_spot_timeline_keeps_to_its_floor_and_the_lot_clock() {
+        let clock = MockClock::new(Utc::now() - chrono::Duration::days(3));
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()));
+        lot.set_transit_hold(Some(chrono::Duration::minutes(5)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.add_floor(ParkingFloor::new(2)).unwrap();
+        lot.close_floor(2).unwrap();
+
+        let car = |plate: &str| Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into());
+        let first = lot.park_vehicle(car("SUP002")).unwrap();
+        lot.reopen_floor(2).unwrap();
+        lot.close_floor(1).unwrap();
+        let second = lot.park_vehicle(car("SUP003")).unwrap();
+        assert_eq!((first.floor_id, second.floor_id), (Some(1), Some(2)));
+        assert_eq!(first.spot_id, second.spot_id);
+
+        clock.advance(chrono::Duration::minutes(2));
+        lot.confirm_arrival(2, &second.spot_id).unwrap();
+        let log = lot.spot_operations(2, &second.spot_id).unwrap();
+        assert_eq!(log.subject, format!("spot {} on floor 2", second.spot_id));
+        let tickets: Vec<_> = log.operations.iter().map(|op| &op.ticket_id).collect();
+        assert_eq!(tickets, [&Some(second.ticket_id.clone()), &None]);
+        let sensor = log.of_kind(OperationKind::Sensor).next().unwrap();
+        assert_eq!(sensor.at, lot.now());
+
+        let first_log = lot.ticket_operations(&first.ticket_id).unwrap();
+        assert_eq!(first_log.of_kind(OperationKind::Sensor).count(), 0);
+    }
+}
@@ -0,0 +1,216 @@
+//! Lengths for spot sizes, vehicle dimensions and clearances. A `Length` is kept in whole
+//! millimetres whichever unit it was given in, so a 7 ft clearance and a 2.1336 m one are
+//! the same length and compare exactly.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ParkingLot, error::ParkingError};
+
+const MILLIMETERS_PER_FOOT: f64 = 304.8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LengthUnit {
+    #[default]
+    Meters,
+    Feet,
+}
+
+impl LengthUnit {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            LengthUnit::Meters => "m",
+            LengthUnit::Feet => "ft",
+        }
+    }
+
+    fn millimeters_per_unit(self) -> f64 {
+        match self {
+            LengthUnit::Meters => 1000.0,
+            LengthUnit::Feet => MILLIMETERS_PER_FOOT,
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Length {
+    millimeters: i64,
+}
+
+impl Length {
+    pub fn new(value: f64, unit: LengthUnit) -> Self {
+        Self {
+            millimeters: (value * unit.millimeters_per_unit()).round() as i64,
+        }
+    }
+
+    pub fn meters(value: f64) -> Self {
+        Self::new(value, LengthUnit::Meters)
+    }
+
+    pub fn feet(value: f64) -> Self {
+        Self::new(value, LengthUnit::Feet)
+    }
+
+    pub fn from_millimeters(millimeters: i64) -> Self {
+        Self { millimeters }
+    }
+
+    pub fn millimeters(self) -> i64 {
+        self.millimeters
+    }
+
+    pub fn value_in(self, unit: LengthUnit) -> f64 {
+        self.millimeters as f64 / unit.millimeters_per_unit()
+    }
+
+    /// E.g. `2.13 m` or `7.00 ft`.
+    pub fn format(self, unit: LengthUnit) -> String {
+        format!("{:.2} {}", self.value_in(unit), unit.symbol())
+    }
+
+    /// Reads a number followed by `m` or `ft`, e.g. `2.1 m`, `7ft` or `7 feet`.
+    pub fn parse(text: &str) -> Result<Self, ParkingError> {
+        let invalid = || ParkingError::InvalidLength(text.to_string());
+        let trimmed = text.trim();
+        let split = trimmed
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .ok_or_else(invalid)?;
+        let (value, unit) = trimmed.split_at(split);
+        let value: f64 = value.parse().map_err(|_| invalid())?;
+        let unit = match unit.trim().to_ascii_lowercase().as_str() {
+            "m" | "meter" | "meters" | "metre" | "metres" => LengthUnit::Meters,
+            "ft" | "foot" | "feet" => LengthUnit::Feet,
+            _ => return Err(invalid()),
+        };
+        Ok(Self::new(value, unit))
+    }
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.format(LengthUnit::Meters))
+    }
+}
+
+/// The outer size of a vehicle, or the space a spot offers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Dimensions {
+    #[serde(rename = "length_mm")]
+    pub length: Length,
+    #[serde(rename = "width_mm")]
+    pub width: Length,
+    #[serde(rename = "height_mm")]
+    pub height: Length,
+}
+
+impl Dimensions {
+    pub fn new(length: Length, width: Length, height: Length) -> Self {
+        Self {
+            length,
+            width,
+            height,
+        }
+    }
+
+    /// Whether something this size fits inside `space`.
+    pub fn fits_within(&self, space: &Dimensions) -> bool {
+        self.length <= space.length && self.width <= space.width && self.height <= space.height
+    }
+
+    /// E.g. `5.00 x 2.50 x 2.10 m`.
+    pub fn format(&self, unit: LengthUnit) -> String {
+        format!(
+            "{:.2} x {:.2} x {:.2} {}",
+            self.length.value_in(unit),
+            self.width.value_in(unit),
+            self.height.value_in(unit),
+            unit.symbol()
+        )
+    }
+}
+
+impl ParkingLot {
+    /// The unit floor maps show spot sizes in. Sizes are compared exactly whatever unit
+    /// they were configured in.
+    pub fn set_length_unit(&mut self, unit: LengthUnit) {
+        self.length_unit = unit;
+    }
+
+    pub fn length_unit(&self) -> LengthUnit {
+        self.length_unit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Parkable, ParkingFloor, ParkingLot, ParkingSpot, SpotType, Vehicle, VehicleType,
+        entry_policy::{Capacity, ClearanceGate, EntryPolicies},
+    };
+
+    #[test]
+    fn test_metric_and_imperial_configs_behave_the_same() {
+        assert_eq!(Length::feet(7.0), Length::meters(2.1336));
+        assert_eq!(Length::parse(" 7 ft").unwrap(), Length::feet(7.0));
+        assert_eq!(Length::parse("2.1336m").unwrap(), Length::feet(7.0));
+        for text in ["7 furlongs", "7", "ft"] {
+            assert_eq!(
+                Length::parse(text),
+                Err(ParkingError::InvalidLength(text.into()))
+            );
+        }
+        assert_eq!(Length::feet(7.0).format(LengthUnit::Meters), "2.13 m");
+
+        let van = Dimensions::new(
+            Length::meters(5.2),
+            Length::meters(2.0),
+            Length::meters(2.2),
+        );
+        for unit in [LengthUnit::Meters, LengthUnit::Feet] {
+            let clearance = Length::new(Length::meters(2.1).value_in(unit), unit);
+            let bay = Dimensions::new(
+                Length::new(Length::meters(5.0).value_in(unit), unit),
+                Length::new(Length::meters(2.5).value_in(unit), unit),
+                Length::new(Length::meters(3.0).value_in(unit), unit),
+            );
+
+            let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+            lot.set_entry_policies(
+                EntryPolicies::empty()
+                    .with(Box::new(ClearanceGate::new(clearance)))
+                    .with(Box::new(Capacity)),
+            );
+            lot.set_length_unit(unit);
+            let mut floor = ParkingFloor::new(1);
+            floor.spots.lock().unwrap().clear();
+            floor
+                .add_spot(ParkingSpot::new(true, SpotType::Large).with_dimensions(bay))
+                .unwrap();
+            lot.add_floor(floor).unwrap();
+
+            let tall = Vehicle::new(VehicleType::Motor, "Van".into(), "UNIT01".into())
+                .with_dimensions(van);
+            assert_eq!(
+                lot.park_vehicle(tall).unwrap_err(),
+                ParkingError::ExceedsClearance {
+                    height: Length::meters(2.2),
+                    clearance: Length::meters(2.1),
+                }
+            );
+            // Too long for the only spot, though under the clearance
+            let long = Vehicle::new(VehicleType::Motor, "Van".into(), "UNIT02".into())
+                .with_dimensions(Dimensions {
+                    height: Length::meters(2.0),
+                    ..van
+                });
+            assert_eq!(lot.park_vehicle(long).unwrap_err(), ParkingError::LotFull);
+            assert!(lot.render_floor_map(1).unwrap().contains(&bay.format(unit)));
+        }
+    }
+}
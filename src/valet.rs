@@ -0,0 +1,364 @@
+//! Valet parking. The driver hands the car over at the desk and gets a valet ticket; an
+//! attendant is assigned, parks the car and records the spot, which opens an ordinary
+//! parking ticket billed from that moment. When the driver asks for the car back it joins
+//! a first-come retrieval queue, and the attendant who brings it out closes the stay.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::atomic::Ordering,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ParkingCharge, ParkingLot, User, VALET_COUNTER, Vehicle, batch::Effects,
+    entry_policy::EntryRequest, error::ParkingError, journal::TransitionCause,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValetAttendant {
+    name: String,
+    staff_id: String,
+}
+
+impl ValetAttendant {
+    pub fn new(name: String, staff_id: String) -> Self {
+        Self { name, staff_id }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn staff_id(&self) -> &str {
+        &self.staff_id
+    }
+
+    /// Records that the attendant parked the car on `spot_id` of floor `floor_id`.
+    pub fn mark_parked(
+        &self,
+        lot: &ParkingLot,
+        valet_ticket_id: &str,
+        floor_id: u32,
+        spot_id: &str,
+    ) -> Result<ValetTicket, ParkingError> {
+        lot.valet_parked(self, valet_ticket_id, floor_id, spot_id)
+    }
+
+    /// Hands the car back to the driver, closing its stay.
+    pub fn deliver(
+        &self,
+        lot: &ParkingLot,
+        valet_ticket_id: &str,
+    ) -> Result<ParkingCharge, ParkingError> {
+        lot.valet_delivered(self, valet_ticket_id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ValetStatus {
+    /// Handed over at the desk, not parked yet.
+    CheckedIn,
+    Parked,
+    RetrievalRequested,
+    Delivered,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValetTicket {
+    pub valet_ticket_id: String,
+    pub vehicle: Vehicle,
+    pub customer: String,
+    pub phone: String,
+    /// Staff id of the assigned attendant.
+    pub attendant: Option<String>,
+    pub status: ValetStatus,
+    pub checked_in_at: DateTime<Utc>,
+    /// Parking ticket opened when the car was parked.
+    pub ticket_id: Option<String>,
+    pub floor_id: Option<u32>,
+    pub spot_id: Option<String>,
+    pub retrieval_requested_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+pub(crate) struct ValetDesk {
+    pub(crate) tickets: HashMap<String, ValetTicket>,
+    /// Valet tickets waiting for their car, first come first served.
+    pub(crate) queue: VecDeque<String>,
+    /// How long bringing one car out takes.
+    retrieval_time: Duration,
+}
+
+impl Default for ValetDesk {
+    fn default() -> Self {
+        Self {
+            tickets: HashMap::new(),
+            queue: VecDeque::new(),
+            retrieval_time: Duration::minutes(5),
+        }
+    }
+}
+
+impl ValetDesk {
+    fn ticket_mut(&mut self, valet_ticket_id: &str) -> Result<&mut ValetTicket, ParkingError> {
+        self.tickets
+            .get_mut(valet_ticket_id)
+            .ok_or(ParkingError::ValetTicketNotFound)
+    }
+
+    fn estimated_wait(&self, valet_ticket_id: &str) -> Option<Duration> {
+        let position = self.queue.iter().position(|id| id == valet_ticket_id)?;
+        Some(self.retrieval_time * (position as i32 + 1))
+    }
+}
+
+/// Where a car stands in the retrieval queue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetrievalEstimate {
+    /// 1 for the next car out.
+    pub position: u32,
+    pub estimated_wait: Duration,
+}
+
+impl ParkingLot {
+    /// Time an attendant needs to bring one car out; 5 minutes unless set.
+    pub fn set_valet_retrieval_time(&mut self, retrieval_time: Duration) {
+        self.valet.get_mut().unwrap().retrieval_time = retrieval_time;
+    }
+
+    /// Takes `vehicle` from `user` at the valet desk, if the entry policies admit it.
+    pub fn valet_checkin(
+        &self,
+        vehicle: Vehicle,
+        user: &User,
+    ) -> Result<ValetTicket, ParkingError> {
+        self.check_entry(&EntryRequest {
+            vehicle: &vehicle,
+            zone_id: None,
+            at: self.now(),
+        })?;
+        let ticket = ValetTicket {
+            valet_ticket_id: format!("VLT_{}", VALET_COUNTER.fetch_add(1, Ordering::SeqCst)),
+            vehicle,
+            customer: user.name().to_string(),
+            phone: user.phone().to_string(),
+            attendant: None,
+            status: ValetStatus::CheckedIn,
+            checked_in_at: self.now(),
+            ticket_id: None,
+            floor_id: None,
+            spot_id: None,
+            retrieval_requested_at: None,
+        };
+        self.valet
+            .lock()?
+            .tickets
+            .insert(ticket.valet_ticket_id.clone(), ticket.clone());
+        Ok(ticket)
+    }
+
+    pub fn valet_ticket(&self, valet_ticket_id: &str) -> Option<ValetTicket> {
+        self.valet
+            .lock()
+            .unwrap()
+            .tickets
+            .get(valet_ticket_id)
+            .cloned()
+    }
+
+    /// Gives the car to `attendant`, replacing whoever had it. Delivered cars can't be
+    /// reassigned.
+    pub fn assign_valet(
+        &self,
+        valet_ticket_id: &str,
+        attendant: &ValetAttendant,
+    ) -> Result<(), ParkingError> {
+        let mut desk = self.valet.lock()?;
+        let ticket = desk.ticket_mut(valet_ticket_id)?;
+        if ticket.status == ValetStatus::Delivered {
+            return Err(ParkingError::InvalidValetState(ticket.status));
+        }
+        ticket.attendant = Some(attendant.staff_id.clone());
+        Ok(())
+    }
+
+    /// Queues the car for retrieval and returns its place in the queue.
+    pub fn valet_request_retrieval(
+        &self,
+        valet_ticket_id: &str,
+    ) -> Result<RetrievalEstimate, ParkingError> {
+        let now = self.now();
+        let mut desk = self.valet.lock()?;
+        let ticket = desk.ticket_mut(valet_ticket_id)?;
+        if ticket.status != ValetStatus::Parked {
+            return Err(ParkingError::InvalidValetState(ticket.status));
+        }
+        ticket.status = ValetStatus::RetrievalRequested;
+        ticket.retrieval_requested_at = Some(now);
+        desk.queue.push_back(valet_ticket_id.to_string());
+        Ok(RetrievalEstimate {
+            position: desk.queue.len() as u32,
+            estimated_wait: desk.estimated_wait(valet_ticket_id).unwrap(),
+        })
+    }
+
+    /// Current wait for a car in the retrieval queue.
+    pub fn valet_retrieval_estimate(&self, valet_ticket_id: &str) -> Option<RetrievalEstimate> {
+        let desk = self.valet.lock().unwrap();
+        let position = desk.queue.iter().position(|id| id == valet_ticket_id)?;
+        Some(RetrievalEstimate {
+            position: position as u32 + 1,
+            estimated_wait: desk.estimated_wait(valet_ticket_id)?,
+        })
+    }
+
+    /// Valet tickets waiting for retrieval, next out first.
+    pub fn valet_retrieval_queue(&self) -> Vec<String> {
+        self.valet.lock().unwrap().queue.iter().cloned().collect()
+    }
+
+    fn valet_parked(
+        &self,
+        attendant: &ValetAttendant,
+        valet_ticket_id: &str,
+        floor_id: u32,
+        spot_id: &str,
+    ) -> Result<ValetTicket, ParkingError> {
+        let now = self.now();
+        let vehicle = {
+            let mut desk = self.valet.lock()?;
+            let ticket = desk.ticket_mut(valet_ticket_id)?;
+            check_attendant(ticket, attendant)?;
+            if ticket.status != ValetStatus::CheckedIn {
+                return Err(ParkingError::InvalidValetState(ticket.status));
+            }
+            ticket.vehicle.clone()
+        };
+
+        {
+            let floors = self.floors.lock()?;
+            let floor = floors.get(&floor_id).ok_or(ParkingError::SpotNotFound)?;
+            let mut spots = floor.spots.lock()?;
+            let spot = spots.get_mut(spot_id).ok_or(ParkingError::SpotNotFound)?;
+            if !spot.is_occupied() && !spot.is_available() {
+                return Err(ParkingError::SpotUnavailable);
+            }
+            spot.transition(TransitionCause::Parked, now, |spot| {
+                spot.assign_vehicle(vehicle.clone())
+            })?;
+        }
+        let effects = &mut Effects::immediate();
+        let parking_ticket = self.issue_ticket(vehicle, spot_id.to_string(), effects)?;
+        self.emit_capacity_events(floor_id, effects)?;
+
+        let mut desk = self.valet.lock()?;
+        let ticket = desk.ticket_mut(valet_ticket_id)?;
+        ticket.status = ValetStatus::Parked;
+        ticket.ticket_id = Some(parking_ticket.ticket_id);
+        ticket.floor_id = Some(floor_id);
+        ticket.spot_id = Some(spot_id.to_string());
+        Ok(ticket.clone())
+    }
+
+    fn valet_delivered(
+        &self,
+        attendant: &ValetAttendant,
+        valet_ticket_id: &str,
+    ) -> Result<ParkingCharge, ParkingError> {
+        let ticket_id = {
+            let mut desk = self.valet.lock()?;
+            let ticket = desk.ticket_mut(valet_ticket_id)?;
+            check_attendant(ticket, attendant)?;
+            if ticket.status != ValetStatus::RetrievalRequested {
+                return Err(ParkingError::InvalidValetState(ticket.status));
+            }
+            ticket.ticket_id.clone().unwrap()
+        };
+
+        let charge = self.checkout(ticket_id, None, None, &mut Effects::immediate())?;
+        let mut desk = self.valet.lock()?;
+        desk.ticket_mut(valet_ticket_id)?.status = ValetStatus::Delivered;
+        desk.queue.retain(|id| id != valet_ticket_id);
+        Ok(charge)
+    }
+}
+
+fn check_attendant(ticket: &ValetTicket, attendant: &ValetAttendant) -> Result<(), ParkingError> {
+    if ticket.attendant.as_deref() != Some(attendant.staff_id()) {
+        return Err(ParkingError::ValetNotAssigned);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParkingFloor, VehicleType, clock::MockClock, pricing::FlatHourly};
+
+    #[test]
+    fn test_valet_handoff_from_checkin_to_delivery() {
+        let clock = MockClock::default();
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into())
+            .with_clock(Box::new(clock.clone()))
+            .with_pricing_strategy(Box::new(FlatHourly::new(10.0)));
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.set_valet_retrieval_time(Duration::minutes(4));
+        let user = User::new("Ada".into(), "0800".into());
+        let tunde = ValetAttendant::new("Tunde".into(), "V-1".into());
+        let ngozi = ValetAttendant::new("Ngozi".into(), "V-2".into());
+        let spots = lot.spots_with_tags(&[]);
+        let (floor_a, spot_a) = (spots[0].0, &spots[0].1);
+        let (floor_b, spot_b) = (spots[1].0, &spots[1].1);
+
+        let car = |plate: &str| Vehicle::new(VehicleType::Motor, "Kia".into(), plate.into());
+        let first = lot.valet_checkin(car("VAL001"), &user).unwrap();
+        let second = lot.valet_checkin(car("VAL002"), &user).unwrap();
+        assert!(matches!(
+            tunde.mark_parked(&lot, &first.valet_ticket_id, floor_a, spot_a),
+            Err(ParkingError::ValetNotAssigned)
+        ));
+        lot.assign_valet(&first.valet_ticket_id, &tunde).unwrap();
+        lot.assign_valet(&second.valet_ticket_id, &ngozi).unwrap();
+        let parked = tunde
+            .mark_parked(&lot, &first.valet_ticket_id, floor_a, spot_a)
+            .unwrap();
+        assert_eq!(parked.status, ValetStatus::Parked);
+        assert!(matches!(
+            ngozi.mark_parked(&lot, &second.valet_ticket_id, floor_a, spot_a),
+            Err(ParkingError::SpotOccupied)
+        ));
+        ngozi
+            .mark_parked(&lot, &second.valet_ticket_id, floor_b, spot_b)
+            .unwrap();
+        let located = lot.locate_vehicle("VAL002").unwrap();
+        assert_eq!((located.floor_id, &located.spot_id), (floor_b, spot_b));
+
+        clock.advance(Duration::hours(2));
+        let estimate = lot.valet_request_retrieval(&first.valet_ticket_id).unwrap();
+        assert_eq!(estimate.estimated_wait, Duration::minutes(4));
+        let estimate = lot
+            .valet_request_retrieval(&second.valet_ticket_id)
+            .unwrap();
+        assert_eq!(
+            (estimate.position, estimate.estimated_wait),
+            (2, Duration::minutes(8))
+        );
+
+        let charge = tunde.deliver(&lot, &first.valet_ticket_id).unwrap();
+        assert_eq!(charge.total, 20.0);
+        assert_eq!(
+            lot.valet_retrieval_estimate(&second.valet_ticket_id),
+            Some(RetrievalEstimate {
+                position: 1,
+                estimated_wait: Duration::minutes(4),
+            })
+        );
+        assert_eq!(
+            lot.valet_ticket(&first.valet_ticket_id).unwrap().status,
+            ValetStatus::Delivered
+        );
+        assert!(lot.locate_vehicle("VAL001").is_none());
+    }
+}
@@ -0,0 +1,266 @@
+//! Read-only views of the lot for dashboards. `ParkingLot::publish_view` copies the live
+//! state into an immutable `LotView`, and dashboard threads read the latest copy through a
+//! `LotViewReader` without touching the lot's own locks, so a slow screen can never hold up
+//! a gate. `refresh_view_every` republishes on a timer.
+//!
+//! The reader's slot is locked only long enough to swap or clone a pointer, never while a
+//! view is built or read.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, Weak, mpsc},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{ParkingFloor, ParkingLot, SpotStatus, SpotType, error::ParkingError};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpotView {
+    pub spot_id: String,
+    pub spot_type: SpotType,
+    pub status: SpotStatus,
+    /// Plate of the vehicle parked on (or driving to) the spot.
+    pub license_plate: Option<String>,
+    /// Held for a lease holder, so not free to other drivers even while empty.
+    pub leased: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloorView {
+    pub floor_id: u32,
+    pub closed: bool,
+    /// Sorted by spot id.
+    pub spots: Vec<SpotView>,
+}
+
+impl FloorView {
+    /// Spots any driver could take now; empty leased spots aren't counted.
+    pub fn free_spots(&self) -> usize {
+        self.count(|spot| spot.status == SpotStatus::Free && !spot.leased)
+    }
+
+    pub fn occupied_spots(&self) -> usize {
+        self.count(|spot| matches!(spot.status, SpotStatus::Occupied | SpotStatus::Claimed(_)))
+    }
+
+    fn count(&self, matches: impl Fn(&SpotView) -> bool) -> usize {
+        self.spots.iter().filter(|spot| matches(spot)).count()
+    }
+}
+
+/// The lot as it was at `generated_at`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LotView {
+    /// Counts up with each publish, so a dashboard can skip redrawing an unchanged view.
+    pub version: u64,
+    pub generated_at: DateTime<Utc>,
+    pub floors: BTreeMap<u32, FloorView>,
+    pub active_tickets: usize,
+    pub evacuating: bool,
+}
+
+impl LotView {
+    pub fn free_spots(&self) -> usize {
+        self.floors.values().map(FloorView::free_spots).sum()
+    }
+
+    pub fn occupied_spots(&self) -> usize {
+        self.floors.values().map(FloorView::occupied_spots).sum()
+    }
+
+    /// Occupied share of all spots, 0.0 for a lot without spots.
+    pub fn occupancy_ratio(&self) -> f32 {
+        let total: usize = self.floors.values().map(|floor| floor.spots.len()).sum();
+        if total == 0 {
+            return 0.0;
+        }
+        self.occupied_spots() as f32 / total as f32
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ViewSlot {
+    latest: Mutex<Option<Arc<LotView>>>,
+}
+
+/// A cloneable handle to the lot's latest published view.
+#[derive(Debug, Clone)]
+pub struct LotViewReader {
+    slot: Arc<ViewSlot>,
+}
+
+impl LotViewReader {
+    /// The latest view, or `None` before the first publish.
+    pub fn latest(&self) -> Result<Option<Arc<LotView>>, ParkingError> {
+        Ok(self.slot.latest.lock()?.clone())
+    }
+}
+
+/// Republishes the view until stopped or dropped, or until the lot is dropped.
+#[derive(Debug)]
+pub struct ViewRefresh {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ViewRefresh {
+    /// Stops refreshing and waits for the refresh thread to finish.
+    pub fn stop(mut self) {
+        self.finish();
+    }
+
+    fn finish(&mut self) {
+        // Closing the channel wakes the thread
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ViewRefresh {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+impl ParkingLot {
+    /// Copies the live state into a new view for readers. The floor map is locked only to
+    /// list the floors; each floor is then locked in turn while it's copied.
+    pub fn publish_view(&self) -> Result<Arc<LotView>, ParkingError> {
+        let closed_floors = self.closed_floors.lock()?.clone();
+        let floors: Vec<ParkingFloor> = self.floors.lock()?.values().cloned().collect();
+        let floors = floors
+            .iter()
+            .map(|floor| {
+                let spots = floor.spots.lock()?;
+                let mut spots: Vec<SpotView> = spots
+                    .values()
+                    .map(|spot| SpotView {
+                        spot_id: spot.id.clone(),
+                        spot_type: spot.spot_type,
+                        status: spot.status.clone(),
+                        license_plate: spot.vehicle.as_ref().map(|v| v.license_plate.clone()),
+                        leased: spot.is_leased(),
+                    })
+                    .collect();
+                spots.sort_by(|a, b| a.spot_id.cmp(&b.spot_id));
+                let view = FloorView {
+                    floor_id: floor.id,
+                    closed: closed_floors.contains(&floor.id),
+                    spots,
+                };
+                Ok((floor.id, view))
+            })
+            .collect::<Result<_, ParkingError>>()?;
+        let active_tickets = self.active_tickets.lock()?.len();
+        let evacuating = self.active_evacuation().is_some();
+
+        let mut latest = self.view.latest.lock()?;
+        let view = Arc::new(LotView {
+            version: latest.as_ref().map_or(1, |view| view.version + 1),
+            generated_at: self.now(),
+            floors,
+            active_tickets,
+            evacuating,
+        });
+        *latest = Some(view.clone());
+        Ok(view)
+    }
+
+    pub fn view_reader(&self) -> LotViewReader {
+        LotViewReader {
+            slot: self.view.clone(),
+        }
+    }
+
+    /// Publishes a view now and then every `interval` on a thread of its own.
+    pub fn refresh_view_every(self: &Arc<Self>, interval: Duration) -> ViewRefresh {
+        let lot: Weak<ParkingLot> = Arc::downgrade(self);
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || {
+            // A lot whose state is poisoned can't be viewed any more
+            while let Some(Ok(_)) = lot.upgrade().map(|lot| lot.publish_view()) {
+                if stopped.recv_timeout(interval) != Err(mpsc::RecvTimeoutError::Timeout) {
+                    break;
+                }
+            }
+        });
+        ViewRefresh {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parkable, Vehicle, VehicleType};
+
+    #[test]
+    fn test_readers_see_published_views_only() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let reader = lot.view_reader();
+        assert!(reader.latest().unwrap().is_none());
+
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "VIEW01".into());
+        lot.park_vehicle(car).unwrap();
+        let view = lot.publish_view().unwrap();
+        assert_eq!(reader.latest().unwrap(), Some(view.clone()));
+        assert_eq!((view.version, view.active_tickets), (1, 1));
+        assert_eq!((view.free_spots(), view.occupied_spots()), (9, 1));
+        let floor = &view.floors[&1];
+        assert!(
+            floor
+                .spots
+                .iter()
+                .any(|spot| spot.license_plate.as_deref() == Some("VIEW01"))
+        );
+
+        // Later changes wait for the next publish
+        let car = Vehicle::new(VehicleType::Motor, "Kia".into(), "VIEW02".into());
+        lot.park_vehicle(car).unwrap();
+        assert_eq!(reader.latest().unwrap().unwrap().occupied_spots(), 1);
+
+        let lot = Arc::new(lot);
+        let refresh = lot.refresh_view_every(Duration::from_secs(60));
+        // The first refresh publishes at once
+        while reader.latest().unwrap().unwrap().version < 2 {
+            thread::yield_now();
+        }
+        refresh.stop();
+        let view = reader.latest().unwrap().unwrap();
+        assert_eq!((view.version, view.occupied_spots()), (2, 2));
+        assert_eq!(view.occupancy_ratio(), 0.2);
+    }
+
+    #[test]
+    fn test_empty_leased_spots_are_not_free() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        let from = lot.now();
+        lot.lease_spots(
+            "ShareCo".into(),
+            &[(1, "spot_0")],
+            from,
+            from + chrono::Duration::days(1),
+            20.0,
+        )
+        .unwrap();
+
+        let view = lot.publish_view().unwrap();
+        assert_eq!((view.free_spots(), view.occupied_spots()), (9, 0));
+        let leased: Vec<&SpotView> = view.floors[&1]
+            .spots
+            .iter()
+            .filter(|spot| spot.leased)
+            .collect();
+        assert_eq!(leased.len(), 1);
+        assert_eq!(leased[0].status, SpotStatus::Free);
+    }
+}
@@ -0,0 +1,419 @@
+//! Outbound webhooks: signed event payloads delivered to registered endpoints, with
+//! exponential-backoff retries and a delivery log for debugging integrations.
+//!
+//! Raising an event only queues its deliveries, so parking never waits on an endpoint.
+//! They are sent, and failed ones retried, when the owner calls
+//! [`WebhookDispatcher::process_due`] (e.g. from a periodic task). The transport is called
+//! with no lock held. Only the most recent finished deliveries are kept in the log.
+//!
+//! Each attempt is signed over its own timestamp and the payload (see [`sign`]), so
+//! receivers can refuse stale or replayed deliveries. Nothing is queued until a transport
+//! is set, and once `MAX_QUEUE_LEN` deliveries are waiting the oldest are dropped.
+
+use std::{collections::VecDeque, fmt, sync::Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    events::{EventKind, ParkingEvent},
+    signing::hmac_sha256_hex,
+};
+
+/// Sends a signed payload to an endpoint URL. Implemented by the host application
+/// on top of whatever HTTP client it uses.
+pub trait WebhookTransport: Send + Sync {
+    /// `signature` is [`sign`] of `timestamp` (Unix seconds) and `payload`; both are sent
+    /// along so the receiver can check the signature and how old the attempt is.
+    fn deliver(
+        &self,
+        url: &str,
+        timestamp: i64,
+        signature: &str,
+        payload: &str,
+    ) -> Result<(), String>;
+}
+
+/// Signature of a delivery attempt made at `timestamp`: the hex HMAC-SHA256 of
+/// `"{timestamp}.{payload}"` keyed with the endpoint's secret.
+pub fn sign(secret: &str, timestamp: i64, payload: &str) -> String {
+    hmac_sha256_hex(secret, &format!("{timestamp}.{payload}"))
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookEndpoint {
+    pub id: String,
+    pub url: String,
+    pub secret: String,
+    /// Event kinds this endpoint receives; empty means all events.
+    pub event_kinds: Vec<EventKind>,
+}
+
+impl WebhookEndpoint {
+    pub fn new(id: String, url: String, secret: String, event_kinds: Vec<EventKind>) -> Self {
+        Self {
+            id,
+            url,
+            secret,
+            event_kinds,
+        }
+    }
+
+    fn accepts(&self, kind: EventKind) -> bool {
+        self.event_kinds.is_empty() || self.event_kinds.contains(&kind)
+    }
+}
+
+/// Finished deliveries kept for `WebhookDispatcher::deliveries`.
+const DELIVERY_LOG_LEN: usize = 100;
+
+/// Deliveries kept waiting for an attempt; older ones are dropped past this.
+pub const MAX_QUEUE_LEN: usize = 1000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled for every further attempt.
+    pub base_delay: Duration,
+    /// Longest wait between two attempts, however many have failed.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::seconds(30),
+            max_delay: Duration::hours(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait after the `attempts`th failed attempt.
+    fn backoff(&self, attempts: u32) -> Duration {
+        2i32.checked_pow(attempts.saturating_sub(1))
+            .and_then(|factor| self.base_delay.checked_mul(factor))
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookDelivery {
+    pub endpoint_id: String,
+    pub event_kind: EventKind,
+    pub payload: String,
+    /// Signature of the latest attempt, made at `signed_at`.
+    pub signature: String,
+    pub signed_at: DateTime<Utc>,
+    pub attempts: u32,
+    pub status: DeliveryStatus,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+pub struct WebhookDispatcher {
+    endpoints: Vec<WebhookEndpoint>,
+    transport: Option<Box<dyn WebhookTransport>>,
+    retry_policy: RetryPolicy,
+    /// Deliveries waiting for an attempt, oldest first.
+    queue: Mutex<Vec<WebhookDelivery>>,
+    /// Delivered and failed deliveries, the last `DELIVERY_LOG_LEN` of them.
+    finished: Mutex<VecDeque<WebhookDelivery>>,
+}
+
+impl fmt::Debug for WebhookDispatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebhookDispatcher")
+            .field("endpoints", &self.endpoints)
+            .field("has_transport", &self.transport.is_some())
+            .field("retry_policy", &self.retry_policy)
+            .finish()
+    }
+}
+
+impl WebhookDispatcher {
+    pub fn register(&mut self, endpoint: WebhookEndpoint) {
+        self.endpoints.retain(|e| e.id != endpoint.id);
+        self.endpoints.push(endpoint);
+    }
+
+    pub fn unregister(&mut self, endpoint_id: &str) {
+        self.endpoints.retain(|e| e.id != endpoint_id);
+    }
+
+    pub fn endpoints(&self) -> &[WebhookEndpoint] {
+        &self.endpoints
+    }
+
+    pub fn set_transport(&mut self, transport: Box<dyn WebhookTransport>) {
+        self.transport = Some(transport);
+    }
+
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    /// Queues a signed delivery of `event` for every endpoint subscribed to its kind. Does
+    /// nothing until a transport is set. Past `MAX_QUEUE_LEN` waiting deliveries, the
+    /// oldest are logged as failed and dropped.
+    pub fn enqueue(&self, event: &ParkingEvent, now: DateTime<Utc>) {
+        if self.transport.is_none() {
+            return;
+        }
+        let payload = event.to_json();
+        let mut queue = self.queue.lock().unwrap();
+        for endpoint in self.endpoints.iter().filter(|e| e.accepts(event.kind())) {
+            queue.push(WebhookDelivery {
+                endpoint_id: endpoint.id.clone(),
+                event_kind: event.kind(),
+                signature: sign(&endpoint.secret, now.timestamp(), &payload),
+                signed_at: now,
+                payload: payload.clone(),
+                attempts: 0,
+                status: DeliveryStatus::Pending,
+                last_error: None,
+                next_attempt_at: now,
+            });
+        }
+        let excess = queue.len().saturating_sub(MAX_QUEUE_LEN);
+        if excess > 0 {
+            let dropped: Vec<WebhookDelivery> = queue
+                .drain(..excess)
+                .map(|mut delivery| {
+                    delivery.status = DeliveryStatus::Failed;
+                    delivery.last_error = Some("Dropped: delivery queue full".to_string());
+                    delivery
+                })
+                .collect();
+            drop(queue);
+            self.log_finished(dropped);
+        }
+    }
+
+    /// Attempts every pending delivery that is due at `now`. Returns how many succeeded.
+    pub fn process_due(&self, now: DateTime<Utc>) -> usize {
+        self.attempt_pending(now, |delivery| delivery.next_attempt_at <= now)
+    }
+
+    /// Attempts every pending delivery now, without waiting out retry backoff. Returns how
+    /// many succeeded.
+    pub fn flush(&self, now: DateTime<Utc>) -> usize {
+        self.attempt_pending(now, |_| true)
+    }
+
+    /// Deliveries still waiting for an attempt.
+    pub fn pending(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    fn attempt_pending(
+        &self,
+        now: DateTime<Utc>,
+        ready: impl Fn(&WebhookDelivery) -> bool,
+    ) -> usize {
+        let Some(transport) = &self.transport else {
+            return 0;
+        };
+
+        // Take the due deliveries out of the queue so the transport runs without the lock
+        let due: Vec<WebhookDelivery> = {
+            let mut queue = self.queue.lock().unwrap();
+            let (due, waiting) = std::mem::take(&mut *queue).into_iter().partition(&ready);
+            *queue = waiting;
+            due
+        };
+
+        let mut delivered = 0;
+        let mut retries = Vec::new();
+        let mut finished = Vec::new();
+        for mut delivery in due {
+            match self.endpoints.iter().find(|e| e.id == delivery.endpoint_id) {
+                None => {
+                    delivery.status = DeliveryStatus::Failed;
+                    delivery.last_error = Some("Endpoint no longer registered".to_string());
+                }
+                Some(endpoint) => {
+                    delivery.attempts += 1;
+                    delivery.signature = sign(&endpoint.secret, now.timestamp(), &delivery.payload);
+                    delivery.signed_at = now;
+                    match transport.deliver(
+                        &endpoint.url,
+                        now.timestamp(),
+                        &delivery.signature,
+                        &delivery.payload,
+                    ) {
+                        Ok(()) => {
+                            delivery.status = DeliveryStatus::Delivered;
+                            delivery.last_error = None;
+                            delivered += 1;
+                        }
+                        Err(e) => {
+                            delivery.last_error = Some(e);
+                            if delivery.attempts >= self.retry_policy.max_attempts {
+                                delivery.status = DeliveryStatus::Failed;
+                            } else {
+                                delivery.next_attempt_at =
+                                    now + self.retry_policy.backoff(delivery.attempts);
+                            }
+                        }
+                    }
+                }
+            }
+            match delivery.status {
+                DeliveryStatus::Pending => retries.push(delivery),
+                _ => finished.push(delivery),
+            }
+        }
+
+        self.queue.lock().unwrap().extend(retries);
+        self.log_finished(finished);
+        delivered
+    }
+
+    fn log_finished(&self, finished: Vec<WebhookDelivery>) {
+        let mut log = self.finished.lock().unwrap();
+        log.extend(finished);
+        let excess = log.len().saturating_sub(DELIVERY_LOG_LEN);
+        log.drain(..excess);
+    }
+
+    /// The most recent finished deliveries, oldest first, then those still queued.
+    pub fn deliveries(&self) -> Vec<WebhookDelivery> {
+        let mut deliveries: Vec<WebhookDelivery> =
+            self.finished.lock().unwrap().iter().cloned().collect();
+        deliveries.extend(self.queue.lock().unwrap().iter().cloned());
+        deliveries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, atomic::AtomicU32, atomic::Ordering};
+
+    struct FlakyTransport {
+        failures_left: Arc<AtomicU32>,
+    }
+
+    impl WebhookTransport for FlakyTransport {
+        fn deliver(
+            &self,
+            _url: &str,
+            _timestamp: i64,
+            _signature: &str,
+            _payload: &str,
+        ) -> Result<(), String> {
+            if self.failures_left.load(Ordering::SeqCst) > 0 {
+                self.failures_left.fetch_sub(1, Ordering::SeqCst);
+                return Err("503".to_string());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_failed_delivery_is_retried_after_backoff() {
+        let mut dispatcher = WebhookDispatcher::default();
+        dispatcher.register(WebhookEndpoint::new(
+            "ops".into(),
+            "https://example.test/hook".into(),
+            "secret".into(),
+            vec![EventKind::VehicleParked],
+        ));
+        dispatcher.set_transport(Box::new(FlakyTransport {
+            failures_left: Arc::new(AtomicU32::new(1)),
+        }));
+
+        let now = Utc::now();
+        let event = ParkingEvent::VehicleParked {
+            ticket_id: "TKT_1".into(),
+            license_plate: "ABC123".into(),
+            spot_id: "spot_1".into(),
+            at: now,
+        };
+        dispatcher.enqueue(&event, now);
+
+        assert_eq!(dispatcher.process_due(now), 0);
+        assert_eq!(dispatcher.process_due(now + Duration::seconds(10)), 0);
+        assert_eq!(dispatcher.process_due(now + Duration::seconds(30)), 1);
+
+        let delivery = &dispatcher.deliveries()[0];
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+        assert_eq!(delivery.attempts, 2);
+        // The retry is signed over its own time, not the first attempt's
+        let retried_at = now + Duration::seconds(30);
+        assert_eq!(delivery.signed_at, retried_at);
+        assert_eq!(
+            delivery.signature,
+            sign("secret", retried_at.timestamp(), &event.to_json())
+        );
+        assert_ne!(
+            delivery.signature,
+            sign("secret", now.timestamp(), &event.to_json())
+        );
+    }
+
+    #[test]
+    fn test_backoff_is_capped_and_finished_deliveries_are_pruned() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::seconds(30));
+        assert_eq!(policy.backoff(3), Duration::minutes(2));
+        assert_eq!(policy.backoff(40), policy.max_delay);
+
+        let mut dispatcher = WebhookDispatcher::default();
+        dispatcher.register(WebhookEndpoint::new(
+            "ops".into(),
+            "https://example.test/hook".into(),
+            "secret".into(),
+            Vec::new(),
+        ));
+        dispatcher.set_transport(Box::new(FlakyTransport {
+            failures_left: Arc::new(AtomicU32::new(0)),
+        }));
+        let now = Utc::now();
+        for _ in 0..DELIVERY_LOG_LEN + 20 {
+            dispatcher.enqueue(&ParkingEvent::LotFull { at: now }, now);
+        }
+
+        assert_eq!(dispatcher.process_due(now), DELIVERY_LOG_LEN + 20);
+        assert_eq!(dispatcher.pending(), 0);
+        assert_eq!(dispatcher.deliveries().len(), DELIVERY_LOG_LEN);
+    }
+
+    #[test]
+    fn test_queue_needs_a_transport_and_drops_its_oldest_deliveries_when_full() {
+        let mut dispatcher = WebhookDispatcher::default();
+        dispatcher.register(WebhookEndpoint::new(
+            "ops".into(),
+            "https://example.test/hook".into(),
+            "secret".into(),
+            Vec::new(),
+        ));
+        let now = Utc::now();
+        dispatcher.enqueue(&ParkingEvent::LotFull { at: now }, now);
+        assert_eq!(dispatcher.pending(), 0);
+
+        dispatcher.set_transport(Box::new(FlakyTransport {
+            failures_left: Arc::new(AtomicU32::new(u32::MAX)),
+        }));
+        for _ in 0..MAX_QUEUE_LEN + 5 {
+            dispatcher.enqueue(&ParkingEvent::LotFull { at: now }, now);
+        }
+        assert_eq!(dispatcher.pending(), MAX_QUEUE_LEN);
+        let deliveries = dispatcher.deliveries();
+        assert_eq!(deliveries.len(), MAX_QUEUE_LEN + 5);
+        assert!(
+            deliveries[..5]
+                .iter()
+                .all(|d| d.status == DeliveryStatus::Failed)
+        );
+    }
+}
@@ -0,0 +1,241 @@
+//! Non-parkable zones (fire lanes, loading docks). Zones live next to a floor's spots but
+//! are never part of the allocatable inventory; blocking one is recorded as an incident.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{ParkingError, ParkingLot};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum ZoneKind {
+    FireLane,
+    LoadingDock,
+    Other(String),
+}
+
+impl ZoneKind {
+    pub fn label(&self) -> &str {
+        match self {
+            ZoneKind::FireLane => "FIRE LANE",
+            ZoneKind::LoadingDock => "LOADING DOCK",
+            ZoneKind::Other(label) => label,
+        }
+    }
+}
+
+/// Saved as `FireLane`, `LoadingDock` or `Other:<label>`.
+impl From<ZoneKind> for String {
+    fn from(kind: ZoneKind) -> Self {
+        match kind {
+            ZoneKind::FireLane => "FireLane".to_string(),
+            ZoneKind::LoadingDock => "LoadingDock".to_string(),
+            ZoneKind::Other(label) => format!("Other:{label}"),
+        }
+    }
+}
+
+impl TryFrom<String> for ZoneKind {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        match name.as_str() {
+            "FireLane" => Ok(ZoneKind::FireLane),
+            "LoadingDock" => Ok(ZoneKind::LoadingDock),
+            other => other
+                .strip_prefix("Other:")
+                .map(|label| ZoneKind::Other(label.to_string()))
+                .ok_or_else(|| format!("Unknown zone kind '{other}'")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NoParkingZone {
+    pub id: String,
+    pub floor_id: u32,
+    pub kind: ZoneKind,
+}
+
+impl NoParkingZone {
+    pub fn new(id: String, floor_id: u32, kind: ZoneKind) -> Self {
+        Self { id, floor_id, kind }
+    }
+}
+
+/// A vehicle found blocking a no-parking zone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ZoneIncident {
+    pub zone_id: String,
+    pub license_plate: String,
+    pub reported_at: DateTime<Utc>,
+    pub note: String,
+}
+
+impl ParkingLot {
+    pub fn add_no_parking_zone(&self, zone: NoParkingZone) -> Result<(), ParkingError> {
+        {
+            let floors = self.floors.lock()?;
+            let floor = floors
+                .get(&zone.floor_id)
+                .ok_or(ParkingError::FloorNotFound)?;
+            if floor.spots.lock()?.contains_key(&zone.id) {
+                return Err(ParkingError::ZoneClashesWithSpot(zone.id));
+            }
+        }
+        let mut zones = self.no_parking_zones.lock()?;
+        if zones.iter().any(|z| z.id == zone.id) {
+            return Err(ParkingError::DuplicateZone(zone.id));
+        }
+        zones.push(zone);
+        Ok(())
+    }
+
+    pub fn remove_no_parking_zone(&self, zone_id: &str) -> Option<NoParkingZone> {
+        let mut zones = self.no_parking_zones.lock().unwrap();
+        let index = zones.iter().position(|z| z.id == zone_id)?;
+        Some(zones.remove(index))
+    }
+
+    pub fn no_parking_zones(&self, floor_id: u32) -> Vec<NoParkingZone> {
+        self.no_parking_zones
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|z| z.floor_id == floor_id)
+            .cloned()
+            .collect()
+    }
+
+    pub fn report_blocked_zone(
+        &self,
+        zone_id: &str,
+        license_plate: String,
+        note: String,
+    ) -> Result<ZoneIncident, ParkingError> {
+        if !self
+            .no_parking_zones
+            .lock()?
+            .iter()
+            .any(|z| z.id == zone_id)
+        {
+            return Err(ParkingError::NoParkingZoneNotFound);
+        }
+        let incident = ZoneIncident {
+            zone_id: zone_id.to_string(),
+            license_plate,
+            reported_at: self.now(),
+            note,
+        };
+        self.zone_incidents.lock()?.push(incident.clone());
+        Ok(incident)
+    }
+
+    pub fn zone_incidents(&self, zone_id: &str) -> Vec<ZoneIncident> {
+        self.zone_incidents
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|i| i.zone_id == zone_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Plain-text map of a floor: one line per spot, followed by its no-parking zones.
+    pub fn render_floor_map(&self, floor_id: u32) -> Result<String, ParkingError> {
+        let mut map = format!("Floor {}\n", floor_id);
+        {
+            let floors = self.floors.lock()?;
+            let floor = floors.get(&floor_id).ok_or(ParkingError::FloorNotFound)?;
+            let spots = floor.spots.lock()?;
+            let mut spot_ids: Vec<&String> = spots.keys().collect();
+            spot_ids.sort();
+            for spot_id in spot_ids {
+                let spot = &spots[spot_id];
+                let state = if spot.is_occupied() {
+                    "occupied"
+                } else if spot.is_reserved() {
+                    "reserved"
+                } else {
+                    "free"
+                };
+                let size = spot
+                    .dimensions
+                    .map(|d| format!(" {}", d.format(self.length_unit)))
+                    .unwrap_or_default();
+                map.push_str(&format!(
+                    "  [{:?}] {} {}{}\n",
+                    spot.spot_type, spot_id, state, size
+                ));
+            }
+        }
+        for zone in self.no_parking_zones(floor_id) {
+            map.push_str(&format!(
+                "  [{}] {} no parking\n",
+                zone.kind.label(),
+                zone.id
+            ));
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParkingFloor;
+
+    #[test]
+    fn test_zone_appears_on_map_and_records_incidents() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        lot.add_floor(ParkingFloor::new(1)).unwrap();
+        lot.add_no_parking_zone(NoParkingZone::new("fire_1".into(), 1, ZoneKind::FireLane))
+            .unwrap();
+
+        assert!(
+            lot.render_floor_map(1)
+                .unwrap()
+                .contains("[FIRE LANE] fire_1")
+        );
+        assert!(
+            lot.report_blocked_zone("fire_1", "ABC123".into(), "Blocking exit".into())
+                .is_ok()
+        );
+        assert_eq!(lot.zone_incidents("fire_1").len(), 1);
+        assert_eq!(
+            lot.report_blocked_zone("nope", "ABC123".into(), String::new()),
+            Err(ParkingError::NoParkingZoneNotFound)
+        );
+    }
+
+    #[test]
+    fn test_zones_refuse_unknown_floors_and_taken_ids() {
+        let mut lot = ParkingLot::new("Hub".into(), "Lagos".into(), "1".into());
+        let mut floor = ParkingFloor::new(1);
+        let mut spot = crate::ParkingSpot::new(true, crate::SpotType::Regular);
+        spot.id = "bay_1".into();
+        floor.add_spot(spot).unwrap();
+        lot.add_floor(floor).unwrap();
+        lot.add_no_parking_zone(NoParkingZone::new(
+            "dock_1".into(),
+            1,
+            ZoneKind::LoadingDock,
+        ))
+        .unwrap();
+
+        let zone = |id: &str, floor_id| NoParkingZone::new(id.into(), floor_id, ZoneKind::FireLane);
+        assert_eq!(
+            lot.add_no_parking_zone(zone("fire_1", 2)),
+            Err(ParkingError::FloorNotFound)
+        );
+        assert_eq!(
+            lot.add_no_parking_zone(zone("bay_1", 1)),
+            Err(ParkingError::ZoneClashesWithSpot("bay_1".into()))
+        );
+        assert_eq!(
+            lot.add_no_parking_zone(zone("dock_1", 1)),
+            Err(ParkingError::DuplicateZone("dock_1".into()))
+        );
+        assert_eq!(lot.render_floor_map(2), Err(ParkingError::FloorNotFound));
+    }
+}
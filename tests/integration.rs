@@ -0,0 +1,351 @@
+//! End-to-end scenarios driven through the public API.
+//!
+//! `Harness` wires a lot together with:
+//! - a mock clock the scenario moves by hand;
+//! - an event capture (a webhook transport that records every delivered payload, and can
+//!   be switched off to simulate an outage);
+//! - a card payment gateway that can likewise be switched off;
+//! - in-memory storage the lot is saved to and restored from on `restart`.
+//!
+//! The rush hour, waitlist and payment outage scenarios can also be run and watched as
+//! examples, e.g. `cargo run --example lot_full_with_waitlist`.
+
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, Ordering},
+};
+
+use chrono::{Duration, TimeZone, Utc};
+use parking_lot::{
+    Parkable, ParkingFloor, ParkingLot, Vehicle, VehicleType,
+    admission::AdmissionPolicy,
+    clock::MockClock,
+    error::ParkingError,
+    events::EventKind,
+    payment::{PaymentMethod, PaymentMethodKind, PaymentProcessor},
+    pricing::FlatHourly,
+    webhook::{DeliveryStatus, RetryPolicy, WebhookEndpoint, WebhookTransport},
+};
+
+#[derive(Clone, Default)]
+struct EventCapture {
+    payloads: Arc<Mutex<Vec<String>>>,
+    offline: Arc<AtomicBool>,
+}
+
+impl EventCapture {
+    fn count(&self, kind: EventKind) -> usize {
+        let needle = format!("\"event\":\"{}\"", kind.as_str());
+        self.payloads
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| p.contains(&needle))
+            .count()
+    }
+
+    fn set_offline(&self, offline: bool) {
+        self.offline.store(offline, Ordering::SeqCst);
+    }
+}
+
+impl WebhookTransport for EventCapture {
+    fn deliver(
+        &self,
+        _url: &str,
+        _timestamp: i64,
+        _signature: &str,
+        payload: &str,
+    ) -> Result<(), String> {
+        if self.offline.load(Ordering::SeqCst) {
+            return Err("connection refused".to_string());
+        }
+        self.payloads.lock().unwrap().push(payload.to_string());
+        Ok(())
+    }
+}
+
+/// Takes card payments, recording each amount, unless switched off.
+#[derive(Debug, Clone, Default)]
+struct PaymentGateway {
+    charges: Arc<Mutex<Vec<f32>>>,
+    offline: Arc<AtomicBool>,
+}
+
+impl PaymentGateway {
+    fn charges(&self) -> Vec<f32> {
+        self.charges.lock().unwrap().clone()
+    }
+
+    fn set_offline(&self, offline: bool) {
+        self.offline.store(offline, Ordering::SeqCst);
+    }
+}
+
+impl PaymentProcessor for PaymentGateway {
+    fn charge(&self, _method: &PaymentMethod, amount: f32) -> Result<String, String> {
+        if self.offline.load(Ordering::SeqCst) {
+            return Err("gateway timeout".to_string());
+        }
+        let mut charges = self.charges.lock().unwrap();
+        charges.push(amount);
+        Ok(format!("TXN{}", charges.len()))
+    }
+}
+
+/// Keeps the last saved snapshot in memory.
+#[derive(Clone, Default)]
+struct MemoryStorage {
+    snapshot: Arc<Mutex<Option<String>>>,
+}
+
+impl MemoryStorage {
+    fn save(&self, lot: &ParkingLot) {
+        *self.snapshot.lock().unwrap() = Some(lot.snapshot_text().unwrap());
+    }
+
+    fn load(&self) -> ParkingLot {
+        let snapshot = self.snapshot.lock().unwrap();
+        ParkingLot::from_snapshot_text(snapshot.as_deref().expect("nothing saved")).unwrap()
+    }
+}
+
+struct Harness {
+    lot: ParkingLot,
+    clock: MockClock,
+    events: EventCapture,
+    payments: PaymentGateway,
+    storage: MemoryStorage,
+}
+
+impl Harness {
+    fn new(num_floors: u32) -> Self {
+        let mut lot = ParkingLot::new("Harness Lot".into(), "Test Street".into(), "it".into());
+        for id in 1..=num_floors {
+            lot.add_floor(ParkingFloor::new(id)).unwrap();
+        }
+        lot.webhooks_mut().register(WebhookEndpoint::new(
+            "capture".into(),
+            "memory://capture".into(),
+            "secret".into(),
+            Vec::new(),
+        ));
+
+        let mut harness = Self {
+            lot,
+            clock: MockClock::new(Utc.with_ymd_and_hms(2024, 3, 4, 8, 0, 0).unwrap()),
+            events: EventCapture::default(),
+            payments: PaymentGateway::default(),
+            storage: MemoryStorage::default(),
+        };
+        harness.wire();
+        harness
+    }
+
+    /// Hands the lot the harness's clock, transport, gateway and rates, which are supplied
+    /// by code rather than saved with it.
+    fn wire(&mut self) {
+        let lot = &mut self.lot;
+        lot.set_clock(Box::new(self.clock.clone()));
+        lot.set_pricing_strategy(Box::new(FlatHourly::new(3.0)));
+        lot.set_payment_processor(PaymentMethodKind::Card, Box::new(self.payments.clone()));
+        lot.webhooks_mut()
+            .set_transport(Box::new(self.events.clone()));
+        lot.webhooks_mut().set_retry_policy(RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::zero(),
+            ..RetryPolicy::default()
+        });
+    }
+
+    /// Saves the lot, then carries on with one loaded from the saved state.
+    fn restart(&mut self) {
+        self.storage.save(&self.lot);
+        self.lot = self.storage.load();
+        self.wire();
+    }
+
+    fn car(n: usize) -> Vehicle {
+        Vehicle::new(VehicleType::Motor, "Corolla".into(), format!("CAR{n:04}"))
+    }
+
+    fn card() -> PaymentMethod {
+        PaymentMethod::Card("tok_visa".into())
+    }
+}
+
+#[test]
+fn scenario_rush_hour() {
+    let harness = Harness::new(3);
+
+    let tickets: Vec<_> = (0..25)
+        .map(|n| harness.lot.park_vehicle(Harness::car(n)).unwrap())
+        .collect();
+    assert_eq!(
+        harness.lot.display_info().unwrap().num_parked_vehicles(),
+        25
+    );
+
+    harness.clock.advance(Duration::minutes(90));
+    for ticket in tickets.iter().take(10) {
+        let charge = harness
+            .lot
+            .unpark_vehicle(ticket.ticket_id.clone())
+            .unwrap();
+        assert_eq!(charge.total, 3.0);
+    }
+
+    assert_eq!(
+        harness.lot.display_info().unwrap().num_parked_vehicles(),
+        15
+    );
+    harness.lot.deliver_webhooks();
+    assert_eq!(harness.lot.webhooks().pending(), 0);
+    assert_eq!(harness.events.count(EventKind::VehicleParked), 25);
+    assert_eq!(harness.events.count(EventKind::VehicleUnparked), 10);
+}
+
+#[test]
+fn scenario_lot_full_with_waitlist() {
+    let mut harness = Harness::new(1);
+    harness.lot.set_admission_policy(AdmissionPolicy {
+        one_in_one_out_at: 2,
+    });
+    for n in 0..12 {
+        assert_eq!(
+            harness.lot.join_entry_queue(Harness::car(n)).unwrap(),
+            n + 1
+        );
+    }
+
+    // The queue runs freely until two spots remain, then waits for exits
+    let mut tickets = Vec::new();
+    while let Some(ticket) = harness.lot.admit_next().unwrap() {
+        tickets.push(ticket);
+    }
+    assert_eq!(tickets.len(), 8);
+    assert_eq!(harness.lot.entry_queue_length().unwrap(), 4);
+    assert_eq!(harness.lot.display_info().unwrap().num_empty_spots(), 2);
+    assert_eq!(harness.lot.estimated_entry_wait().unwrap(), None);
+
+    // Each exit lets exactly one waiting car in
+    for (n, ticket) in tickets.iter().take(4).enumerate() {
+        harness.clock.advance(Duration::minutes(10));
+        harness
+            .lot
+            .unpark_vehicle(ticket.ticket_id.clone())
+            .unwrap();
+        let admitted = harness.lot.admit_next().unwrap().unwrap();
+        assert_eq!(
+            admitted.vehicle.license_plate(),
+            Harness::car(8 + n).license_plate()
+        );
+        assert!(harness.lot.admit_next().unwrap().is_none());
+        // Two cars still wait, one exit every ten minutes
+        if n == 1 {
+            assert_eq!(
+                harness.lot.estimated_entry_wait().unwrap(),
+                Some(Duration::minutes(20))
+            );
+        }
+    }
+
+    assert_eq!(harness.lot.entry_queue_length().unwrap(), 0);
+    assert_eq!(harness.lot.display_info().unwrap().num_parked_vehicles(), 8);
+    harness.lot.deliver_webhooks();
+    assert_eq!(harness.events.count(EventKind::VehicleParked), 12);
+}
+
+#[test]
+fn scenario_event_delivery_outage() {
+    let harness = Harness::new(1);
+
+    harness.events.set_offline(true);
+    let ticket = harness.lot.park_vehicle(Harness::car(1)).unwrap();
+    assert_eq!(harness.lot.deliver_webhooks(), 0);
+    assert_eq!(harness.events.count(EventKind::VehicleParked), 0);
+
+    // Parking keeps working while the integration is down
+    harness.lot.unpark_vehicle(ticket.ticket_id).unwrap();
+
+    harness.events.set_offline(false);
+    assert_eq!(harness.lot.deliver_webhooks(), 2);
+    assert!(
+        harness
+            .lot
+            .webhooks()
+            .deliveries()
+            .iter()
+            .all(|d| d.status == DeliveryStatus::Delivered)
+    );
+}
+
+#[test]
+fn scenario_payment_outage() {
+    let mut harness = Harness::new(1);
+    harness.lot.set_payment_required_before_exit(true);
+    let ticket = harness.lot.park_vehicle(Harness::car(1)).unwrap();
+    harness.clock.advance(Duration::hours(2));
+
+    harness.payments.set_offline(true);
+    assert!(matches!(
+        harness.lot.pay_ticket(&ticket.ticket_id, Harness::card()),
+        Err(ParkingError::PaymentFailed(_))
+    ));
+    // Without a payment the barrier stays down
+    assert_eq!(
+        harness
+            .lot
+            .unpark_vehicle(ticket.ticket_id.clone())
+            .unwrap_err(),
+        ParkingError::PaymentRequired
+    );
+
+    harness.payments.set_offline(false);
+    let payment = harness
+        .lot
+        .pay_ticket(&ticket.ticket_id, Harness::card())
+        .unwrap();
+    assert_eq!(payment.amount, 6.0);
+    assert_eq!(harness.payments.charges(), vec![6.0]);
+    let charge = harness.lot.unpark_vehicle(ticket.ticket_id).unwrap();
+    assert_eq!(charge.amount_due(), 0.0);
+    assert_eq!(harness.lot.display_info().unwrap().num_parked_vehicles(), 0);
+}
+
+#[test]
+fn scenario_restart_keeps_parked_vehicles() {
+    let mut harness = Harness::new(2);
+    let tickets: Vec<_> = (0..12)
+        .map(|n| harness.lot.park_vehicle(Harness::car(n)).unwrap())
+        .collect();
+    harness.clock.advance(Duration::minutes(30));
+    harness
+        .lot
+        .unpark_vehicle(tickets[0].ticket_id.clone())
+        .unwrap();
+
+    harness.restart();
+
+    assert_eq!(
+        harness.lot.display_info().unwrap().num_parked_vehicles(),
+        11
+    );
+    assert!(matches!(
+        harness.lot.park_vehicle(Harness::car(1)),
+        Err(ParkingError::PlateAlreadyParked(_))
+    ));
+    harness.clock.advance(Duration::minutes(150));
+    let charge = harness
+        .lot
+        .unpark_vehicle(tickets[1].ticket_id.clone())
+        .unwrap();
+    assert_eq!(charge.total, 9.0);
+    assert_eq!(
+        harness
+            .lot
+            .unpark_vehicle(tickets[0].ticket_id.clone())
+            .unwrap_err(),
+        ParkingError::TicketClosed
+    );
+}